@@ -0,0 +1,246 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Captions are split so that no cue is longer than this
+const MAX_CUE_DURATION_MS: f32 = 3000.0;
+/// Captions are split so that no cue has more characters than this
+const MAX_CUE_CHARS: usize = 42;
+
+/// When a phoneme is pronounced in a clip
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhonemeTiming {
+    pub phoneme: String,
+    pub start_ms: f32,
+    pub end_ms: f32,
+}
+
+/// A format for exporting the phoneme alignment of a clip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignmentFormat {
+    /// A Praat TextGrid with a single `phonemes` interval tier
+    TextGrid,
+    /// SubRip captions, with phonemes grouped into cues at word boundaries
+    Srt,
+    /// WebVTT captions, with phonemes grouped into cues at word boundaries
+    Vtt,
+}
+
+impl FromStr for AlignmentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "textgrid" => Ok(Self::TextGrid),
+            "srt" => Ok(Self::Srt),
+            "vtt" => Ok(Self::Vtt),
+            _ => Err(format!(
+                "Unknown alignment format `{}`. Supported formats are `textgrid`, `srt` and `vtt`",
+                s
+            )),
+        }
+    }
+}
+
+pub(crate) fn export(timings: &[PhonemeTiming], duration_ms: f32, format: AlignmentFormat) -> String {
+    match format {
+        AlignmentFormat::TextGrid => to_text_grid(timings, duration_ms),
+        AlignmentFormat::Srt => to_captions(timings, false),
+        AlignmentFormat::Vtt => to_captions(timings, true),
+    }
+}
+
+fn to_text_grid(timings: &[PhonemeTiming], duration_ms: f32) -> String {
+    let xmax = timings
+        .iter()
+        .map(|timing| timing.end_ms)
+        .fold(duration_ms, f32::max)
+        / 1000.0;
+    // Praat expects the intervals to cover the whole tier, so fill the gaps with empty ones
+    let mut intervals: Vec<(f32, f32, &str)> = Vec::with_capacity(timings.len());
+    let mut position = 0f32;
+    for timing in timings {
+        let start = timing.start_ms / 1000.0;
+        if start > position {
+            intervals.push((position, start, ""));
+        }
+        let start = start.max(position);
+        let end = (timing.end_ms / 1000.0).max(start);
+        intervals.push((start, end, &timing.phoneme));
+        position = end;
+    }
+    if position < xmax || intervals.is_empty() {
+        intervals.push((position, xmax, ""));
+    }
+    let mut out = String::new();
+    out.push_str("File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n\n");
+    writeln!(out, "xmin = 0\nxmax = {}\ntiers? <exists>\nsize = 1\nitem []:", xmax).unwrap();
+    out.push_str("    item [1]:\n        class = \"IntervalTier\"\n        name = \"phonemes\"\n");
+    writeln!(
+        out,
+        "        xmin = 0\n        xmax = {}\n        intervals: size = {}",
+        xmax,
+        intervals.len()
+    )
+    .unwrap();
+    for (i, (start, end, text)) in intervals.into_iter().enumerate() {
+        writeln!(
+            out,
+            "        intervals [{}]:\n            xmin = {}\n            xmax = {}\n            text = \"{}\"",
+            i + 1,
+            start,
+            end,
+            text.replace('"', "\"\"")
+        )
+        .unwrap();
+    }
+    out
+}
+
+struct Cue {
+    start_ms: f32,
+    end_ms: f32,
+    text: String,
+}
+
+/// Group the phonemes into words at spaces, then the words into cues
+fn group_into_cues(timings: &[PhonemeTiming]) -> Vec<Cue> {
+    let words = timings
+        .split(|timing| timing.phoneme.trim().is_empty())
+        .filter(|word| !word.is_empty());
+    let mut cues: Vec<Cue> = Vec::new();
+    for word in words {
+        let text: String = word.iter().map(|timing| timing.phoneme.as_str()).collect();
+        let start_ms = word[0].start_ms;
+        let end_ms = word[word.len() - 1].end_ms;
+        match cues.last_mut() {
+            Some(cue)
+                if cue.text.chars().count() + 1 + text.chars().count() <= MAX_CUE_CHARS
+                    && end_ms - cue.start_ms <= MAX_CUE_DURATION_MS =>
+            {
+                cue.text.push(' ');
+                cue.text.push_str(&text);
+                cue.end_ms = end_ms;
+            }
+            _ => cues.push(Cue {
+                start_ms,
+                end_ms,
+                text,
+            }),
+        }
+    }
+    cues
+}
+
+fn to_captions(timings: &[PhonemeTiming], webvtt: bool) -> String {
+    let mut out = String::new();
+    if webvtt {
+        out.push_str("WEBVTT\n\n");
+    }
+    let separator = if webvtt { '.' } else { ',' };
+    for (i, cue) in group_into_cues(timings).into_iter().enumerate() {
+        if !webvtt {
+            writeln!(out, "{}", i + 1).unwrap();
+        }
+        writeln!(
+            out,
+            "{} --> {}\n{}\n",
+            format_timestamp(cue.start_ms, separator),
+            format_timestamp(cue.end_ms, separator),
+            cue.text
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// `HH:MM:SS,mmm` (SRT) or `HH:MM:SS.mmm` (WebVTT)
+fn format_timestamp(time_ms: f32, separator: char) -> String {
+    let total_ms = time_ms.max(0.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        total_ms / 3_600_000,
+        total_ms / 60_000 % 60,
+        total_ms / 1000 % 60,
+        separator,
+        total_ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timings(phonemes: &[&str], phoneme_ms: f32) -> Vec<PhonemeTiming> {
+        phonemes
+            .iter()
+            .enumerate()
+            .map(|(i, phoneme)| PhonemeTiming {
+                phoneme: phoneme.to_string(),
+                start_ms: i as f32 * phoneme_ms,
+                end_ms: (i + 1) as f32 * phoneme_ms,
+            })
+            .collect()
+    }
+
+    fn parse_timestamp(timestamp: &str) -> f32 {
+        let (hms, ms) = timestamp.split_at(timestamp.len() - 4);
+        let seconds = hms
+            .split(':')
+            .fold(0u64, |acc, part| acc * 60 + part.parse::<u64>().unwrap());
+        (seconds * 1000 + ms[1..].parse::<u64>().unwrap()) as f32
+    }
+
+    #[test]
+    fn test_timestamps_round_trip() {
+        for time_ms in [0.0, 999.0, 61_001.0, 3_723_456.0] {
+            assert_eq!(format_timestamp(time_ms, ','), format_timestamp(time_ms, '.').replace('.', ","));
+            assert_eq!(parse_timestamp(&format_timestamp(time_ms, ',')), time_ms);
+        }
+        assert_eq!(format_timestamp(3_723_456.0, '.'), "01:02:03.456");
+    }
+
+    #[test]
+    fn test_text_grid() {
+        let mut timings = timings(&["h", "\""], 100.0);
+        timings[1].start_ms = 150.0;
+        timings[1].end_ms = 250.0;
+        let text_grid = export(&timings, 300.0, AlignmentFormat::TextGrid);
+        assert!(text_grid.starts_with("File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n"));
+        assert!(text_grid.contains("name = \"phonemes\"\n        xmin = 0\n        xmax = 0.3\n        intervals: size = 4\n"));
+        assert!(text_grid.contains(
+            "intervals [2]:\n            xmin = 0.1\n            xmax = 0.15\n            text = \"\"\n"
+        ));
+        assert!(text_grid.contains(
+            "intervals [3]:\n            xmin = 0.15\n            xmax = 0.25\n            text = \"\"\"\"\n"
+        ));
+        assert!(text_grid.ends_with("            xmin = 0.25\n            xmax = 0.3\n            text = \"\"\n"));
+    }
+
+    #[test]
+    fn test_captions() {
+        let timings = timings(&["h", "ə", " ", "l", "oʊ"], 100.0);
+        assert_eq!(
+            export(&timings, 500.0, AlignmentFormat::Srt),
+            "1\n00:00:00,000 --> 00:00:00,500\nhə loʊ\n\n"
+        );
+        assert_eq!(
+            export(&timings, 500.0, AlignmentFormat::Vtt),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:00.500\nhə loʊ\n\n"
+        );
+    }
+
+    #[test]
+    fn test_long_captions_are_split() {
+        let words: Vec<&str> = std::iter::repeat_n(["a", " "], 40).flatten().collect();
+        let srt = export(&timings(&words, 100.0), 8000.0, AlignmentFormat::Srt);
+        assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:02,900\n"));
+        assert!(srt.contains("\n\n2\n00:00:03,000 --> "));
+    }
+
+    #[test]
+    fn test_parse_alignment_format() {
+        assert_eq!("TextGrid".parse(), Ok(AlignmentFormat::TextGrid));
+        assert_eq!("vtt".parse(), Ok(AlignmentFormat::Vtt));
+        assert!("json".parse::<AlignmentFormat>().is_err());
+    }
+}
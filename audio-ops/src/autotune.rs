@@ -0,0 +1,392 @@
+#[cfg(feature = "autotune")]
+use crate::AudioError;
+
+/// A musical scale that pitches are snapped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scale {
+    /// All twelve semitones
+    Chromatic,
+    /// The major scale of the given root, as a pitch class (0 is C, 1 is C#, ..., 11 is B)
+    Major(u8),
+    /// The natural minor scale of the given root, as a pitch class (0 is C, ..., 11 is B)
+    Minor(u8),
+}
+
+impl Scale {
+    fn root(self) -> u8 {
+        match self {
+            Self::Chromatic => 0,
+            Self::Major(root) | Self::Minor(root) => root,
+        }
+    }
+    fn contains(self, pitch_class: i32) -> bool {
+        let degree = (pitch_class - self.root() as i32).rem_euclid(12);
+        match self {
+            Self::Chromatic => true,
+            Self::Major(_) => [0, 2, 4, 5, 7, 9, 11].contains(&degree),
+            Self::Minor(_) => [0, 2, 3, 5, 7, 8, 10].contains(&degree),
+        }
+    }
+    /// The frequency of the note of the scale nearest to `pitch_hz` (with A4 at 440 Hz)
+    pub fn nearest_note_hz(self, pitch_hz: f32) -> f32 {
+        let midi = 69.0 + 12.0 * (pitch_hz / 440.0).log2();
+        let nearest = midi.round() as i32;
+        let note = (nearest - 6..=nearest + 6)
+            .filter(|note| self.contains(note.rem_euclid(12)))
+            .min_by(|a, b| {
+                (*a as f32 - midi)
+                    .abs()
+                    .total_cmp(&(*b as f32 - midi).abs())
+            })
+            .unwrap_or(nearest);
+        440.0 * 2f32.powf((note - 69) as f32 / 12.0)
+    }
+}
+
+/// Settings of the autotune effect, which retunes speech toward the notes of a scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutotuneConfig {
+    pub scale: Scale,
+    /// How far the pitch is moved toward the nearest note, from 0 (unchanged) to 1 (snapped)
+    pub strength: f32,
+}
+
+#[cfg(feature = "autotune")]
+impl AutotuneConfig {
+    pub(crate) fn validate(&self) -> Result<(), AudioError> {
+        if !(0.0..=1.0).contains(&self.strength) {
+            return Err(AudioError::new(format!(
+                "Invalid autotune strength `{}`. Expected a value between 0 and 1",
+                self.strength
+            )));
+        }
+        if self.scale.root() > 11 {
+            return Err(AudioError::new(format!(
+                "Invalid scale root `{}`. Expected a pitch class between 0 and 11",
+                self.scale.root()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "autotune")]
+pub use shifter::Autotune;
+
+#[cfg(feature = "autotune")]
+mod shifter {
+    use super::AutotuneConfig;
+    use crate::AudioError;
+    use std::f32::consts::PI;
+
+    /// Range of voice pitches that are detected
+    const MIN_PITCH_HZ: f32 = 70.0;
+    const MAX_PITCH_HZ: f32 = 600.0;
+    /// Length of the window the pitch is detected in. Holds at least two periods of the lowest pitch.
+    const ANALYSIS_MS: f32 = 40.0;
+    /// How often the pitch is detected
+    const HOP_MS: f32 = 10.0;
+    /// How far (each way) the read position may drift from the input before jumping back
+    const MAX_DELAY_MS: f32 = 15.0;
+    /// Length of the crossfade after a jump of the read position
+    const CROSSFADE_MS: f32 = 5.0;
+    /// Time constant of the changes of the pitch ratio, so that corrections don't click
+    const SMOOTHING_MS: f32 = 20.0;
+    /// Windows whose normalized difference (YIN) never dips below this are unvoiced
+    const VOICING_THRESHOLD: f32 = 0.2;
+    const SILENCE_RMS: f32 = 1e-3;
+
+    /// Retunes the pitch of speech toward the nearest note of a scale.
+    ///
+    /// The pitch is detected every few milliseconds with the YIN algorithm, and shifted by
+    /// reading the input at a varying rate. When the read position drifts too far from the
+    /// input, it jumps back by a whole number of pitch periods and crossfades, so that the
+    /// waveform stays in phase. Unvoiced and silent parts are left unchanged.
+    ///
+    /// Like the other stages, it keeps its state between calls to [`Autotune::process`].
+    /// The output lags behind the input by a few tens of milliseconds, and the rest of it is
+    /// returned by [`Autotune::finish`], so the output is as long as the input.
+    pub struct Autotune {
+        config: AutotuneConfig,
+        sample_rate: f32,
+        num_channels: usize,
+        /// Interleaved input, starting at frame `offset`
+        input: Vec<f32>,
+        offset: usize,
+        received_frames: usize,
+        emitted_frames: usize,
+        analysis_len: usize,
+        hop: usize,
+        min_lag: usize,
+        max_lag: usize,
+        max_delay: f32,
+        crossfade_len: usize,
+        smoothing: f32,
+        target_ratio: f32,
+        ratio: f32,
+        /// The last detected pitch period, in frames
+        period: f32,
+        /// How far (in frames) the read position is behind the input
+        delay: f32,
+        /// The delay being faded out after a jump, and the number of frames left in the fade
+        fading: Option<(f32, usize)>,
+        mix: Vec<f32>,
+        difference: Vec<f32>,
+    }
+
+    impl Autotune {
+        pub fn new(
+            config: AutotuneConfig,
+            sample_rate: usize,
+            num_channels: usize,
+        ) -> Result<Self, AudioError> {
+            config.validate()?;
+            let frames = |time_ms: f32| (time_ms * sample_rate as f32 / 1000.0).round() as usize;
+            let max_delay = frames(MAX_DELAY_MS).max(1) as f32;
+            Ok(Self {
+                config,
+                sample_rate: sample_rate as f32,
+                num_channels: num_channels.max(1),
+                input: Vec::new(),
+                offset: 0,
+                received_frames: 0,
+                emitted_frames: 0,
+                analysis_len: frames(ANALYSIS_MS).max(4),
+                hop: frames(HOP_MS).max(1),
+                min_lag: (sample_rate as f32 / MAX_PITCH_HZ).floor().max(2.0) as usize,
+                max_lag: (sample_rate as f32 / MIN_PITCH_HZ).ceil().max(3.0) as usize,
+                max_delay,
+                crossfade_len: frames(CROSSFADE_MS).max(1),
+                smoothing: (-1000.0 / (SMOOTHING_MS * sample_rate as f32)).exp(),
+                target_ratio: 1.0,
+                ratio: 1.0,
+                period: max_delay,
+                delay: 0.0,
+                fading: None,
+                mix: Vec::new(),
+                difference: Vec::new(),
+            })
+        }
+        /// Process interleaved samples, appending the output that is ready so far to `out`
+        pub fn process(&mut self, samples: &[f32], out: &mut Vec<f32>) {
+            self.input.extend_from_slice(samples);
+            self.received_frames += samples.len() / self.num_channels;
+            // Room for the read position to be ahead of the input, and for the analysis window
+            let lookahead = self.max_delay as usize * 2 + self.analysis_len;
+            while self.emitted_frames + lookahead <= self.received_frames {
+                self.emit_frame(out);
+            }
+            let keep_from = self.emitted_frames.saturating_sub(lookahead);
+            if keep_from > self.offset + lookahead {
+                self.input
+                    .drain(..(keep_from - self.offset) * self.num_channels);
+                self.offset = keep_from;
+            }
+        }
+        /// Append the rest of the output to `out`
+        pub fn finish(&mut self, out: &mut Vec<f32>) {
+            while self.emitted_frames < self.received_frames {
+                self.emit_frame(out);
+            }
+        }
+        fn emit_frame(&mut self, out: &mut Vec<f32>) {
+            let frame = self.emitted_frames;
+            if frame.is_multiple_of(self.hop) {
+                self.target_ratio = self.detect_ratio(frame);
+            }
+            self.ratio += (self.target_ratio - self.ratio) * (1.0 - self.smoothing);
+            // Reading at `ratio` times the input rate changes the delay by `1 - ratio` per frame
+            let drift = 1.0 - self.ratio;
+            self.delay += drift;
+            if let Some((ref mut old_delay, _)) = self.fading {
+                *old_delay += drift;
+            } else if self.delay.abs() > self.max_delay {
+                let jump = self.period * (self.max_delay / self.period).ceil();
+                let old_delay = self.delay;
+                self.delay -= jump * self.delay.signum();
+                self.fading = Some((old_delay, self.crossfade_len));
+            }
+            let position = frame as f32 - self.delay;
+            for channel in 0..self.num_channels {
+                let mut sample = self.read(position, channel);
+                if let Some((old_delay, remaining)) = self.fading {
+                    let fade = (remaining as f32 / self.crossfade_len as f32 * PI / 2.0)
+                        .sin()
+                        .powi(2);
+                    let old_sample = self.read(frame as f32 - old_delay, channel);
+                    sample = sample * (1.0 - fade) + old_sample * fade;
+                }
+                out.push(sample);
+            }
+            if let Some((_, ref mut remaining)) = self.fading {
+                *remaining -= 1;
+                if *remaining == 0 {
+                    self.fading = None;
+                }
+            }
+            self.emitted_frames += 1;
+        }
+        /// The input at a fractional frame position, with silence outside of it
+        fn read(&self, position: f32, channel: usize) -> f32 {
+            let index = position.floor();
+            let frac = position - index;
+            let sample = |index: f32| {
+                if index < self.offset as f32 || index >= self.received_frames as f32 {
+                    return 0.0;
+                }
+                self.input[(index as usize - self.offset) * self.num_channels + channel]
+            };
+            sample(index) * (1.0 - frac) + sample(index + 1.0) * frac
+        }
+        /// The pitch ratio that moves the pitch around `frame` toward the nearest note
+        fn detect_ratio(&mut self, frame: usize) -> f32 {
+            let start = frame as f32 - (self.analysis_len / 2) as f32;
+            let mix: Vec<f32> = (0..self.analysis_len)
+                .map(|i| {
+                    (0..self.num_channels)
+                        .map(|channel| self.read(start + i as f32, channel))
+                        .sum::<f32>()
+                        / self.num_channels as f32
+                })
+                .collect();
+            self.mix = mix;
+            let rms = (self.mix.iter().map(|x| x * x).sum::<f32>() / self.mix.len() as f32).sqrt();
+            if rms < SILENCE_RMS {
+                return 1.0;
+            }
+            let Some(period) = self.detect_period() else {
+                return 1.0;
+            };
+            self.period = period;
+            let pitch_hz = self.sample_rate / period;
+            let note_hz = self.config.scale.nearest_note_hz(pitch_hz);
+            (note_hz / pitch_hz).powf(self.config.strength)
+        }
+        /// The pitch period (in frames) of `self.mix` using YIN, or `None` if it is unvoiced
+        fn detect_period(&mut self) -> Option<f32> {
+            let max_lag = self.max_lag.min(self.mix.len() / 2);
+            let len = self.mix.len() - max_lag;
+            let x = &self.mix;
+            self.difference.clear();
+            self.difference.push(1.0);
+            let mut running_sum = 0f32;
+            for lag in 1..=max_lag {
+                let difference: f32 = (0..len).map(|j| (x[j] - x[j + lag]).powi(2)).sum();
+                running_sum += difference;
+                // Cumulative mean normalized difference
+                self.difference.push(if running_sum > 0.0 {
+                    difference * lag as f32 / running_sum
+                } else {
+                    1.0
+                });
+            }
+            let d = &self.difference;
+            let mut lag = (self.min_lag..=max_lag).find(|lag| d[*lag] < VOICING_THRESHOLD)?;
+            while lag < max_lag && d[lag + 1] < d[lag] {
+                lag += 1;
+            }
+            if lag == max_lag {
+                return Some(lag as f32);
+            }
+            // Refine the minimum with a parabola through its neighbours
+            let (a, b, c) = (d[lag - 1], d[lag], d[lag + 1]);
+            let curvature = a - 2.0 * b + c;
+            let shift = if curvature.abs() > f32::EPSILON {
+                0.5 * (a - c) / curvature
+            } else {
+                0.0
+            };
+            Some(lag as f32 + shift)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_note() {
+        // 229 Hz is between A3 (220 Hz) and A#3 (233.08 Hz), closer to A#3
+        assert!((Scale::Chromatic.nearest_note_hz(229.0) - 233.08).abs() < 0.01);
+        // A#3 isn't in C major
+        assert!((Scale::Major(0).nearest_note_hz(229.0) - 220.0).abs() < 0.01);
+        // but it is in F minor (as B flat)
+        assert!((Scale::Minor(5).nearest_note_hz(229.0) - 233.08).abs() < 0.01);
+        assert!((Scale::Chromatic.nearest_note_hz(440.0) - 440.0).abs() < 0.01);
+    }
+
+    #[cfg(feature = "autotune")]
+    mod shifter {
+        use super::super::*;
+        use std::f32::consts::PI;
+
+        const SAMPLE_RATE: usize = 22050;
+
+        fn tone(freq: f32, num_frames: usize) -> Vec<f32> {
+            (0..num_frames)
+                .map(|i| 0.5 * (2.0 * PI * freq * i as f32 / SAMPLE_RATE as f32).sin())
+                .collect()
+        }
+
+        /// Frequency of a tone, from the interpolated positions of its rising zero crossings
+        fn frequency(samples: &[f32]) -> f32 {
+            let crossings: Vec<f32> = samples
+                .windows(2)
+                .enumerate()
+                .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+                .map(|(i, pair)| i as f32 + pair[0] / (pair[0] - pair[1]))
+                .collect();
+            let periods = (crossings.len() - 1) as f32;
+            periods * SAMPLE_RATE as f32 / (crossings[crossings.len() - 1] - crossings[0])
+        }
+
+        fn autotune(samples: &[f32], scale: Scale, strength: f32, chunk_len: usize) -> Vec<f32> {
+            let config = AutotuneConfig { scale, strength };
+            let mut autotune = Autotune::new(config, SAMPLE_RATE, 1).unwrap();
+            let mut out = Vec::new();
+            for chunk in samples.chunks(chunk_len) {
+                autotune.process(chunk, &mut out);
+            }
+            autotune.finish(&mut out);
+            out
+        }
+
+        #[test]
+        fn test_steady_tone_snaps_to_the_nearest_note() {
+            let samples = tone(229.0, SAMPLE_RATE * 3 / 2);
+            // Skip the start, while the correction ramps up
+            let settled = SAMPLE_RATE / 4..SAMPLE_RATE * 5 / 4;
+            let out = autotune(&samples, Scale::Chromatic, 1.0, 1000);
+            assert_eq!(out.len(), samples.len());
+            assert!((frequency(&out[settled.clone()]) - 233.08).abs() < 233.08 * 0.005);
+            let out = autotune(&samples, Scale::Major(0), 1.0, 1000);
+            assert!((frequency(&out[settled.clone()]) - 220.0).abs() < 220.0 * 0.005);
+            // Half of the way from 229 Hz to 220 Hz, in cents
+            let halfway = (229f32 * 220.0).sqrt();
+            let out = autotune(&samples, Scale::Major(0), 0.5, 1000);
+            assert!((frequency(&out[settled]) - halfway).abs() < halfway * 0.005);
+        }
+
+        #[test]
+        fn test_zero_strength_leaves_the_audio_unchanged() {
+            let samples = tone(229.0, SAMPLE_RATE / 2);
+            assert_eq!(autotune(&samples, Scale::Chromatic, 0.0, 357), samples);
+        }
+
+        #[test]
+        fn test_invalid_config() {
+            let config = |scale, strength| AutotuneConfig { scale, strength };
+            assert!(config(Scale::Major(3), 0.5).validate().is_ok());
+            assert!(config(Scale::Major(12), 0.5).validate().is_err());
+            assert!(config(Scale::Chromatic, 1.5).validate().is_err());
+            assert!(config(Scale::Chromatic, f32::NAN).validate().is_err());
+        }
+
+        #[test]
+        fn test_silence_is_unchanged() {
+            let samples = vec![0f32; SAMPLE_RATE / 2];
+            assert_eq!(autotune(&samples, Scale::Chromatic, 1.0, 512), samples);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::AudioError;
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+/// A second order IIR filter (RBJ audio EQ cookbook) that keeps its state per channel,
+/// so consecutive chunks of a stream can be filtered without discontinuities.
+#[derive(Debug, Clone)]
+pub struct BiquadFilter {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    // (x[n-1], x[n-2], y[n-1], y[n-2]) for each channel
+    state: Vec<[f32; 4]>,
+}
+
+impl BiquadFilter {
+    /// A Butterworth high-pass filter with the given cutoff frequency.
+    /// Returns an error unless the cutoff is between zero and the Nyquist frequency.
+    pub fn highpass(
+        sample_rate: usize,
+        cutoff_hz: f32,
+        num_channels: usize,
+    ) -> Result<Self, AudioError> {
+        let nyquist_hz = sample_rate as f32 / 2.0;
+        if !(cutoff_hz > 0.0 && cutoff_hz < nyquist_hz) {
+            return Err(AudioError::new(format!(
+                "High-pass cutoff must be between 0 and {} Hz, got {} Hz",
+                nyquist_hz, cutoff_hz
+            )));
+        }
+        let omega = 2.0 * PI * cutoff_hz / sample_rate as f32;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2.0 * FRAC_1_SQRT_2);
+        let a0 = 1.0 + alpha;
+        Ok(Self {
+            b0: (1.0 + cos) / 2.0 / a0,
+            b1: -(1.0 + cos) / a0,
+            b2: (1.0 + cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            state: vec![[0.0; 4]; num_channels.max(1)],
+        })
+    }
+    /// A band-pass filter with unity gain at `center_hz`, whose bandwidth is `center_hz / q`
+    pub fn bandpass(sample_rate: usize, center_hz: f32, q: f32, num_channels: usize) -> Self {
+        let omega = 2.0 * PI * center_hz / sample_rate as f32;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: alpha / a0,
+            b1: 0.0,
+            b2: -alpha / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            state: vec![[0.0; 4]; num_channels.max(1)],
+        }
+    }
+    /// Filter interleaved samples in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let num_channels = self.state.len();
+        for frame in samples.chunks_mut(num_channels) {
+            for (sample, [x1, x2, y1, y2]) in frame.iter_mut().zip(self.state.iter_mut()) {
+                let x0 = *sample;
+                let y0 = self.b0 * x0 + self.b1 * *x1 + self.b2 * *x2 - self.a1 * *y1 - self.a2 * *y2;
+                *x2 = *x1;
+                *x1 = x0;
+                *y2 = *y1;
+                *y1 = y0;
+                *sample = y0;
+            }
+        }
+    }
+    /// Clear the filter history
+    pub fn reset(&mut self) {
+        self.state.iter_mut().for_each(|s| *s = [0.0; 4]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gain_at(freq: f32, cutoff: f32) -> f32 {
+        let sample_rate = 16000;
+        let mut filter = BiquadFilter::highpass(sample_rate, cutoff, 1).unwrap();
+        let mut samples: Vec<f32> = (0..sample_rate)
+            .map(|i| (2.0 * PI * freq * i as f32 / sample_rate as f32).sin())
+            .collect();
+        filter.process(&mut samples);
+        // Skip the transient at the start
+        let settled = &samples[sample_rate / 2..];
+        let rms = (settled.iter().map(|s| s * s).sum::<f32>() / settled.len() as f32).sqrt();
+        rms * 2f32.sqrt()
+    }
+
+    #[test]
+    fn test_highpass_frequency_response() {
+        let cutoff = 80.0;
+        assert!((gain_at(cutoff, cutoff) - FRAC_1_SQRT_2).abs() < 0.05);
+        assert!(gain_at(cutoff / 4.0, cutoff) < 0.1);
+        assert!(gain_at(1000.0, cutoff) > 0.98);
+    }
+
+    #[test]
+    fn test_highpass_state_carries_across_chunks() {
+        let samples: Vec<f32> = (0..400).map(|i| (i as f32 * 0.3).sin()).collect();
+        let mut whole = samples.clone();
+        BiquadFilter::highpass(16000, 100.0, 2)
+            .unwrap()
+            .process(&mut whole);
+        let mut chunked = samples;
+        let mut filter = BiquadFilter::highpass(16000, 100.0, 2).unwrap();
+        for chunk in chunked.chunks_mut(64) {
+            filter.process(chunk);
+        }
+        assert_eq!(whole, chunked);
+    }
+
+    #[test]
+    fn test_highpass_rejects_cutoff_out_of_range() {
+        for cutoff in [0.0, -50.0, 8000.0, 12000.0, f32::NAN] {
+            assert!(BiquadFilter::highpass(16000, cutoff, 1).is_err(), "{}", cutoff);
+        }
+        assert!(BiquadFilter::highpass(16000, 7999.0, 1).is_ok());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::BiquadFilter;
+
+/// Bandwidth of the sibilance band relative to its center frequency
+const BAND_Q: f32 = 2.0;
+/// How much the band is compressed above the threshold
+const RATIO: f32 = 4.0;
+const ATTACK_MS: f32 = 1.0;
+const RELEASE_MS: f32 = 60.0;
+
+/// A band-limited compressor that tames harsh sibilance ("s" and "sh" sounds).
+///
+/// The band around the center frequency is split off with a band-pass filter and turned down
+/// while its envelope is above the threshold, leaving the rest of the spectrum untouched.
+/// Like [`BiquadFilter`], it keeps its state per channel between calls to [`DeEsser::process`].
+#[derive(Debug, Clone)]
+pub struct DeEsser {
+    band: BiquadFilter,
+    threshold: f32,
+    attack: f32,
+    release: f32,
+    envelopes: Vec<f32>,
+    scratch: Vec<f32>,
+}
+
+impl DeEsser {
+    /// `frequency_hz` is the center of the sibilance band (around 6500 Hz for most voices).
+    /// It is kept below the Nyquist frequency of `sample_rate`.
+    pub fn new(sample_rate: usize, frequency_hz: f32, threshold_db: f32, num_channels: usize) -> Self {
+        let frequency_hz = frequency_hz.min(sample_rate as f32 * 0.45);
+        let time_constant = |time_ms: f32| (-1000.0 / (time_ms * sample_rate as f32)).exp();
+        Self {
+            band: BiquadFilter::bandpass(sample_rate, frequency_hz, BAND_Q, num_channels),
+            threshold: 10f32.powf(threshold_db / 20.0),
+            attack: time_constant(ATTACK_MS),
+            release: time_constant(RELEASE_MS),
+            envelopes: vec![0.0; num_channels.max(1)],
+            scratch: Vec::new(),
+        }
+    }
+    /// Process interleaved samples in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        self.scratch.clear();
+        self.scratch.extend_from_slice(samples);
+        self.band.process(&mut self.scratch);
+        let num_channels = self.envelopes.len();
+        for (frame, band_frame) in samples
+            .chunks_mut(num_channels)
+            .zip(self.scratch.chunks(num_channels))
+        {
+            for ((sample, band), envelope) in frame
+                .iter_mut()
+                .zip(band_frame)
+                .zip(self.envelopes.iter_mut())
+            {
+                let level = band.abs();
+                let coeff = if level > *envelope { self.attack } else { self.release };
+                *envelope = coeff * *envelope + (1.0 - coeff) * level;
+                if *envelope > self.threshold {
+                    let gain = (self.threshold / *envelope).powf(1.0 - 1.0 / RATIO);
+                    *sample -= band * (1.0 - gain);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: usize = 22050;
+
+    fn tone(freq: f32, amplitude: f32) -> impl Fn(usize) -> f32 {
+        move |i| amplitude * (2.0 * PI * freq * i as f32 / SAMPLE_RATE as f32).sin()
+    }
+
+    /// Amplitude of the `freq` component of `samples` (Goertzel algorithm)
+    fn amplitude_at(samples: &[f32], freq: f32) -> f32 {
+        let coeff = 2.0 * (2.0 * PI * freq / SAMPLE_RATE as f32).cos();
+        let (mut s1, mut s2) = (0f32, 0f32);
+        for sample in samples {
+            let s0 = sample + coeff * s1 - s2;
+            s2 = s1;
+            s1 = s0;
+        }
+        let power = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+        2.0 * power.sqrt() / samples.len() as f32
+    }
+
+    #[test]
+    fn test_sibilance_is_reduced() {
+        let (low, high) = (tone(300.0, 0.3), tone(6500.0, 0.3));
+        let mut samples: Vec<f32> = (0..SAMPLE_RATE).map(|i| low(i) + high(i)).collect();
+        DeEsser::new(SAMPLE_RATE, 6500.0, -30.0, 1).process(&mut samples);
+        // Skip the attack at the start
+        let settled = &samples[SAMPLE_RATE / 2..];
+        assert!(amplitude_at(settled, 6500.0) < 0.3 * 0.5);
+        assert!((amplitude_at(settled, 300.0) - 0.3).abs() < 0.3 * 0.05);
+    }
+
+    #[test]
+    fn test_quiet_sibilance_is_untouched() {
+        let high = tone(6500.0, 0.01);
+        let samples: Vec<f32> = (0..SAMPLE_RATE / 10).map(high).collect();
+        let mut processed = samples.clone();
+        DeEsser::new(SAMPLE_RATE, 6500.0, -30.0, 1).process(&mut processed);
+        assert_eq!(processed, samples);
+    }
+
+    #[test]
+    fn test_state_carries_across_chunks() {
+        let high = tone(6000.0, 0.5);
+        let samples: Vec<f32> = (0..2000).map(high).collect();
+        let mut whole = samples.clone();
+        DeEsser::new(SAMPLE_RATE, 6500.0, -20.0, 2).process(&mut whole);
+        let mut chunked = samples;
+        let mut de_esser = DeEsser::new(SAMPLE_RATE, 6500.0, -20.0, 2);
+        for chunk in chunked.chunks_mut(64) {
+            de_esser.process(chunk);
+        }
+        assert_eq!(whole, chunked);
+    }
+}
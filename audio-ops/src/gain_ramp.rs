@@ -0,0 +1,76 @@
+const PI: f32 = std::f32::consts::PI;
+
+/// A fade-in over the first frames of a stream of audio.
+///
+/// It follows the same quarter-sine curve as [`AudioSamples::fade_in`](crate::AudioSamples::fade_in),
+/// but counts the frames it has seen, so the ramp stays continuous when the stream arrives
+/// in chunks. Frames past the end of the ramp are left untouched.
+#[derive(Debug, Clone)]
+pub struct GainRamp {
+    ramp_frames: usize,
+    elapsed_frames: usize,
+    num_channels: usize,
+}
+
+impl GainRamp {
+    pub fn new(ramp_frames: usize, num_channels: usize) -> Self {
+        Self {
+            ramp_frames,
+            elapsed_frames: 0,
+            num_channels: num_channels.max(1),
+        }
+    }
+    /// A ramp over the first `duration_ms` of audio at `sample_rate`
+    pub fn from_duration_ms(duration_ms: u32, sample_rate: usize, num_channels: usize) -> Self {
+        Self::new(duration_ms as usize * sample_rate / 1000, num_channels)
+    }
+    /// Whether the ramp is over, and leaves the remaining frames as they are
+    pub fn is_finished(&self) -> bool {
+        self.elapsed_frames >= self.ramp_frames
+    }
+    /// Process interleaved samples in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for frame in samples.chunks_mut(self.num_channels) {
+            if self.is_finished() {
+                return;
+            }
+            let gain = (self.elapsed_frames as f32 / self.ramp_frames as f32 * PI / 2.0).sin();
+            frame.iter_mut().for_each(|sample| *sample *= gain);
+            self.elapsed_frames += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AudioSamples;
+
+    #[test]
+    fn test_chunked_ramp_matches_fade_in() {
+        let samples: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.05).sin()).collect();
+        let mut faded = AudioSamples::from(samples.clone());
+        faded.fade_in(220);
+        let mut ramp = GainRamp::from_duration_ms(10, 22050, 1);
+        let mut streamed = Vec::new();
+        for chunk in samples.chunks(97) {
+            let mut chunk = chunk.to_vec();
+            ramp.process(&mut chunk);
+            streamed.extend(chunk);
+        }
+        assert!(ramp.is_finished());
+        for (streamed, faded) in streamed.iter().zip(faded.as_slice()) {
+            assert!((streamed - faded).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_ramp_applies_per_frame() {
+        let mut ramp = GainRamp::new(2, 2);
+        let mut samples = vec![1.0; 6];
+        ramp.process(&mut samples);
+        assert_eq!(samples[..2], [0.0, 0.0]);
+        assert_eq!(samples[2], samples[3]);
+        assert_eq!(samples[4..], [1.0, 1.0]);
+    }
+}
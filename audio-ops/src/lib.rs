@@ -1,6 +1,29 @@
+mod alignment;
+mod autotune;
+mod biquad;
+mod de_esser;
+mod gain_ramp;
+mod resampler;
 mod samples;
+mod viseme;
 mod wave_writer;
 pub(crate) mod hanning_window;
+pub(crate) mod wsola;
 
-pub use samples::{Audio, AudioInfo, AudioSamples};
-pub use wave_writer::{write_wave_samples_to_buffer, write_wave_samples_to_file, WaveWriterError};
+pub use alignment::{AlignmentFormat, PhonemeTiming};
+#[cfg(feature = "autotune")]
+pub use autotune::Autotune;
+pub use autotune::{AutotuneConfig, Scale};
+pub use biquad::BiquadFilter;
+pub use de_esser::DeEsser;
+pub use gain_ramp::GainRamp;
+pub use resampler::Resampler;
+pub use samples::{
+    Audio, AudioError, AudioInfo, AudioSamples, ClippingStats, SampleConversionFn, SampleConverter,
+    SentenceInfo,
+};
+pub use viseme::{VisemeSet, VisemeTiming};
+pub use wave_writer::{
+    repair_wav, supported_output_formats, write_wave_samples_to_buffer, write_wave_samples_to_file,
+    StreamingWaveWriter, WaveWriterError,
+};
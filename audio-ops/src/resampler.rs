@@ -0,0 +1,178 @@
+use std::f64::consts::PI;
+
+/// Number of input frames on each side of an output frame used for interpolation,
+/// before widening the kernel for downsampling
+const HALF_TAPS: usize = 16;
+
+/// A band-limited (windowed sinc) sample rate converter for interleaved samples.
+///
+/// The resampler keeps the input frames it still needs between calls to
+/// [`Resampler::process`], so a stream can be resampled chunk by chunk with the
+/// same result as resampling it in one go.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    from_rate: u64,
+    to_rate: u64,
+    num_channels: usize,
+    /// Low-pass cutoff relative to the input Nyquist frequency
+    cutoff: f64,
+    half_width: usize,
+    /// Buffered input frames, interleaved. The first frame has index `buffer_start`.
+    buffer: Vec<f32>,
+    buffer_start: i64,
+    num_input_frames: u64,
+    next_output_frame: u64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: usize, to_rate: usize, num_channels: usize) -> Self {
+        let num_channels = num_channels.max(1);
+        let cutoff = (to_rate as f64 / from_rate as f64).min(1.0);
+        let half_width = (HALF_TAPS as f64 / cutoff).ceil() as usize;
+        Self {
+            from_rate: from_rate as u64,
+            to_rate: to_rate as u64,
+            num_channels,
+            cutoff,
+            half_width,
+            // Frames before the start of the stream are silent
+            buffer: vec![0.0; half_width * num_channels],
+            buffer_start: -(half_width as i64),
+            num_input_frames: 0,
+            next_output_frame: 0,
+        }
+    }
+    /// Resample the given input, appending the output frames that can be computed so far to `out`.
+    /// The input must consist of whole frames.
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        self.buffer.extend_from_slice(input);
+        self.num_input_frames += (input.len() / self.num_channels) as u64;
+        self.drain(out, false);
+    }
+    /// Append the remaining output frames to `out`, treating the stream as ended
+    pub fn finish(&mut self, out: &mut Vec<f32>) {
+        self.buffer
+            .extend(std::iter::repeat_n(0.0, self.half_width * self.num_channels));
+        self.drain(out, true);
+    }
+    /// Number of output frames for the given number of input frames
+    pub fn output_len(&self, num_input_frames: usize) -> usize {
+        (num_input_frames as u64 * self.to_rate).div_ceil(self.from_rate) as usize
+    }
+    fn drain(&mut self, out: &mut Vec<f32>, finished: bool) {
+        let buffered_end = self.buffer_start + (self.buffer.len() / self.num_channels) as i64;
+        let total_output = self.output_len(self.num_input_frames as usize) as u64;
+        loop {
+            let position = self.next_output_frame * self.from_rate;
+            let center = (position / self.to_rate) as i64;
+            let needed_end = center + self.half_width as i64 + 1;
+            if needed_end > buffered_end || (finished && self.next_output_frame >= total_output) {
+                break;
+            }
+            let fraction = (position % self.to_rate) as f64 / self.to_rate as f64;
+            self.interpolate(center, fraction, out);
+            self.next_output_frame += 1;
+        }
+        // Drop the frames no later output frame needs
+        let next_center = (self.next_output_frame * self.from_rate / self.to_rate) as i64;
+        let keep_from = (next_center - self.half_width as i64 + 1).max(self.buffer_start);
+        let num_dropped = (keep_from - self.buffer_start) as usize;
+        self.buffer.drain(..num_dropped * self.num_channels);
+        self.buffer_start = keep_from;
+    }
+    fn interpolate(&self, center: i64, fraction: f64, out: &mut Vec<f32>) {
+        let half_width = self.half_width as i64;
+        let frame_start = out.len();
+        out.resize(frame_start + self.num_channels, 0.0);
+        for k in (center - half_width + 1)..=(center + half_width) {
+            let weight = self.kernel((k - center) as f64 - fraction) as f32;
+            let offset = (k - self.buffer_start) as usize * self.num_channels;
+            for (acc, sample) in out[frame_start..]
+                .iter_mut()
+                .zip(&self.buffer[offset..offset + self.num_channels])
+            {
+                *acc += weight * sample;
+            }
+        }
+    }
+    fn kernel(&self, x: f64) -> f64 {
+        let half_width = self.half_width as f64;
+        if x.abs() >= half_width {
+            return 0.0;
+        }
+        let scaled = x * self.cutoff;
+        let sinc = if scaled == 0.0 {
+            1.0
+        } else {
+            (PI * scaled).sin() / (PI * scaled)
+        };
+        // Blackman window
+        let w = 0.5 + 0.5 * (x / half_width);
+        let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+        self.cutoff * sinc * window
+    }
+}
+
+/// Resample a whole clip of interleaved samples
+pub(crate) fn resample(
+    samples: &[f32],
+    from_rate: usize,
+    to_rate: usize,
+    num_channels: usize,
+) -> Vec<f32> {
+    let mut resampler = Resampler::new(from_rate, to_rate, num_channels);
+    let mut out = Vec::with_capacity(
+        resampler.output_len(samples.len() / num_channels.max(1)) * num_channels.max(1),
+    );
+    resampler.process(samples, &mut out);
+    resampler.finish(&mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, sample_rate: usize, num_frames: usize) -> Vec<f32> {
+        (0..num_frames)
+            .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_resample_length_and_signal() {
+        let input = sine(440.0, 22050, 22050);
+        let output = resample(&input, 22050, 48000, 1);
+        assert_eq!(output.len(), 48000);
+        let expected = sine(440.0, 48000, 48000);
+        // Skip the edges, where the kernel reaches past the signal
+        let max_error = output[1000..47000]
+            .iter()
+            .zip(&expected[1000..47000])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0f32, f32::max);
+        assert!(max_error < 0.01, "max error {}", max_error);
+    }
+
+    #[test]
+    fn test_downsampling_removes_frequencies_above_nyquist() {
+        let input = sine(7000.0, 22050, 22050);
+        let output = resample(&input, 22050, 8000, 1);
+        assert_eq!(output.len(), 8000);
+        let peak = output[500..7500].iter().map(|s| s.abs()).fold(0f32, f32::max);
+        assert!(peak < 0.05, "peak {}", peak);
+    }
+
+    #[test]
+    fn test_chunked_resampling_matches_whole() {
+        let input: Vec<f32> = (0..3000).map(|i| ((i as f32) * 0.05).sin()).collect();
+        let whole = resample(&input, 16000, 22050, 2);
+        let mut resampler = Resampler::new(16000, 22050, 2);
+        let mut chunked = Vec::new();
+        for chunk in input.chunks(314) {
+            resampler.process(chunk, &mut chunked);
+        }
+        resampler.finish(&mut chunked);
+        assert_eq!(whole, chunked);
+    }
+}
@@ -1,11 +1,118 @@
 use crate::hanning_window;
+use crate::{AlignmentFormat, PhonemeTiming, VisemeSet, VisemeTiming};
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 const PI: f32 = std::f32::consts::PI;
 const I16MIN_F32: f32 = i16::MIN as f32;
 const I16MAX_F32: f32 = i16::MAX as f32;
 const MAX_WAV_VALUE_I16: f32 = 32767.0;
 
+#[derive(Debug)]
+pub struct AudioError(String);
+
+impl AudioError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A replacement for the final conversion of float samples to 16-bit PCM, e.g. for a specific
+/// rounding or dither, in place of [`AudioSamples::to_i16_vec`].
+///
+/// The default conversion scales each clip so that its peak reaches full scale, then rounds
+/// the samples to the nearest value, without dither. [`SampleConverter::rounding`] converts at
+/// a fixed scale instead, optionally with dither. A converter is called with the samples of
+/// each clip (or chunk, when streaming) in order, so it can keep state such as a noise-shaping
+/// filter between calls, and must return one value per sample.
+///
+/// Converters hash by what they do: the rounding ones by their dither, while all the
+/// converters made from a function hash the same, since functions can't be told apart.
+#[derive(Clone)]
+pub struct SampleConverter {
+    convert: Arc<Mutex<SampleConversionFn>>,
+    kind: ConversionKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ConversionKind {
+    Rounding { dither: bool },
+    Custom,
+}
+
+/// A function converting float samples to 16-bit PCM, see [`SampleConverter`]
+pub type SampleConversionFn = dyn FnMut(&[f32]) -> Vec<i16> + Send;
+
+impl SampleConverter {
+    pub fn new(convert: impl FnMut(&[f32]) -> Vec<i16> + Send + 'static) -> Self {
+        Self {
+            convert: Arc::new(Mutex::new(convert)),
+            kind: ConversionKind::Custom,
+        }
+    }
+    /// Round each sample to the nearest 16-bit value, with full scale at ±1.0 and samples
+    /// beyond it clipped. With `dither`, triangular dither of ±1 step is added before rounding,
+    /// which turns the distortion of quiet passages into a low, even noise floor.
+    pub fn rounding(dither: bool) -> Self {
+        // A xorshift generator is plenty for dither, and keeps the output reproducible
+        let mut state = 0x9e3779b9u32;
+        let mut uniform = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32
+        };
+        let convert = Self::new(move |samples| {
+            Vec::from_iter(samples.iter().map(|sample| {
+                let noise = if dither { uniform() - uniform() } else { 0.0 };
+                round_to_i16(sample * MAX_WAV_VALUE_I16 + noise)
+            }))
+        });
+        Self {
+            kind: ConversionKind::Rounding { dither },
+            ..convert
+        }
+    }
+    pub fn convert(&self, samples: &[f32]) -> Result<Vec<i16>, AudioError> {
+        let converted = (self.convert.lock().unwrap())(samples);
+        if converted.len() != samples.len() {
+            return Err(AudioError::new(format!(
+                "The sample converter returned {} samples for {} input samples",
+                converted.len(),
+                samples.len()
+            )));
+        }
+        Ok(converted)
+    }
+}
+
+/// `value` rounded to the nearest 16-bit value, clipping what is beyond the range
+fn round_to_i16(value: f32) -> i16 {
+    value.round().clamp(I16MIN_F32, I16MAX_F32) as i16
+}
+
+impl fmt::Debug for SampleConverter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SampleConverter").field(&self.kind).finish()
+    }
+}
+
+impl Hash for SampleConverter {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AudioInfo {
     pub sample_rate: usize,
@@ -67,15 +174,32 @@ impl AudioSamples {
             .max(min_audio_value.abs())
             .max(f32::EPSILON);
         let audio_scale = MAX_WAV_VALUE_I16 / abs_max;
-        Vec::from_iter(
-            self.0
-                .iter()
-                .map(|f| (f * audio_scale).clamp(I16MIN_F32, I16MAX_F32) as i16),
-        )
+        Vec::from_iter(self.0.iter().map(|f| round_to_i16(f * audio_scale)))
+    }
+    /// The samples as 16-bit PCM at a fixed scale, with full scale at ±1.0 and samples
+    /// beyond it clipped, like [`SampleConverter::rounding`] without dither
+    pub fn to_i16_vec_at_fixed_scale(&self) -> Vec<i16> {
+        Vec::from_iter(self.0.iter().map(|f| round_to_i16(f * MAX_WAV_VALUE_I16)))
+    }
+    /// [`Self::to_i16_vec`], or the samples converted with `converter` if one is given
+    pub fn to_i16_vec_with(&self, converter: Option<&SampleConverter>) -> Result<Vec<i16>, AudioError> {
+        match converter {
+            Some(converter) => converter.convert(self.as_slice()),
+            None => Ok(self.to_i16_vec()),
+        }
     }
     pub fn as_wave_bytes(&self) -> Vec<u8> {
         Vec::from_iter(self.to_i16_vec().into_iter().flat_map(|i| i.to_le_bytes()))
     }
+    /// Like [`Self::as_wave_bytes`], converting the samples with `converter` if one is given
+    pub fn as_wave_bytes_with(&self, converter: Option<&SampleConverter>) -> Result<Vec<u8>, AudioError> {
+        let samples = self.to_i16_vec_with(converter)?;
+        Ok(Vec::from_iter(samples.into_iter().flat_map(|i| i.to_le_bytes())))
+    }
+    /// Samples encoded as 8-bit unsigned PCM
+    pub fn to_u8_vec(&self) -> Vec<u8> {
+        Vec::from_iter(self.to_i16_vec().into_iter().map(crate::wave_writer::i16_to_u8))
+    }
     pub fn merge(&mut self, mut other: Self) {
         self.0.append(other.0.as_mut());
     }
@@ -205,12 +329,57 @@ impl IntoIterator for AudioSamples {
     }
 }
 
+/// The sentence an [`Audio`] clip was synthesized from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentenceInfo {
+    /// Position of the sentence in the input text
+    pub index: usize,
+    pub text: String,
+}
+
+/// How many samples of a clip are at or beyond full scale (`|sample| >= 1.0`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClippingStats {
+    pub clipped_samples: usize,
+    pub total_samples: usize,
+    /// Largest absolute sample value
+    pub peak: f32,
+}
+
+impl ClippingStats {
+    /// Percentage of the samples that are clipped, between 0 and 100
+    pub fn clipped_percentage(&self) -> f32 {
+        if self.total_samples == 0 {
+            0.0
+        } else {
+            self.clipped_samples as f32 * 100.0 / self.total_samples as f32
+        }
+    }
+    pub fn has_clipping(&self) -> bool {
+        self.clipped_samples > 0
+    }
+}
+
+/// A clip of speech along with what is known about it.
+///
+/// Build it with [`Audio::new`] or [`Audio::with_info`], then set the optional fields.
 #[derive(Debug, Clone)]
 #[must_use]
+#[non_exhaustive]
 pub struct Audio {
     pub samples: AudioSamples,
     pub info: AudioInfo,
     pub inference_ms: Option<f32>,
+    pub sentence: Option<SentenceInfo>,
+    /// When each phoneme is pronounced, for models that report phoneme durations
+    pub phoneme_timings: Option<Vec<PhonemeTiming>>,
+    /// Set when the model output is implausibly short for its input,
+    /// which usually means that the end of the speech was cut off
+    pub truncation_suspected: bool,
+    /// Convert the samples to PCM at a fixed scale (see
+    /// [`AudioSamples::to_i16_vec_at_fixed_scale`]) instead of scaling the clip to its peak,
+    /// when no converter is given. Set on speech with a gain, so that its level is kept.
+    pub fixed_scale: bool,
 }
 
 impl Audio {
@@ -218,6 +387,10 @@ impl Audio {
         Self {
             samples,
             inference_ms,
+            sentence: None,
+            phoneme_timings: None,
+            truncation_suspected: false,
+            fixed_scale: false,
             info: AudioInfo {
                 sample_rate,
                 num_channels: 1,
@@ -226,12 +399,97 @@ impl Audio {
         }
     }
 
+    /// Audio in the given format, without any of the optional fields
+    pub fn with_info(samples: AudioSamples, info: AudioInfo, inference_ms: Option<f32>) -> Self {
+        Self {
+            samples,
+            info,
+            inference_ms,
+            sentence: None,
+            phoneme_timings: None,
+            truncation_suspected: false,
+            fixed_scale: false,
+        }
+    }
+    /// Silent audio of the given duration in the given format
+    pub fn silence(info: AudioInfo, duration_ms: u32) -> Self {
+        let num_frames = (duration_ms as usize * info.sample_rate) / 1000;
+        Self {
+            samples: vec![0f32; num_frames * info.num_channels.max(1)].into(),
+            info,
+            inference_ms: None,
+            sentence: None,
+            phoneme_timings: None,
+            truncation_suspected: false,
+            fixed_scale: false,
+        }
+    }
+
     pub fn into_vec(self) -> Vec<f32> {
         self.samples.into_vec()
     }
 
+    /// Samples encoded according to `info.sample_width`:
+    /// 16-bit little-endian PCM, or 8-bit unsigned PCM if the sample width is 1.
+    /// See [`Audio::fixed_scale`] for their scale.
     pub fn as_wave_bytes(&self) -> Vec<u8> {
-        self.samples.as_wave_bytes()
+        self.encode(self.to_i16_vec())
+    }
+
+    /// Like [`Audio::as_wave_bytes`], converting the samples with `converter` if one is given
+    pub fn as_wave_bytes_with(&self, converter: Option<&SampleConverter>) -> Result<Vec<u8>, AudioError> {
+        Ok(self.encode(self.to_i16_vec_with(converter)?))
+    }
+
+    /// The samples as 16-bit PCM, at the scale given by [`Audio::fixed_scale`]
+    fn to_i16_vec(&self) -> Vec<i16> {
+        match self.fixed_scale {
+            true => self.samples.to_i16_vec_at_fixed_scale(),
+            false => self.samples.to_i16_vec(),
+        }
+    }
+
+    fn to_i16_vec_with(&self, converter: Option<&SampleConverter>) -> Result<Vec<i16>, AudioError> {
+        match converter {
+            Some(converter) => converter.convert(self.samples.as_slice()),
+            None => Ok(self.to_i16_vec()),
+        }
+    }
+
+    fn encode(&self, samples: Vec<i16>) -> Vec<u8> {
+        if self.info.sample_width == 1 {
+            Vec::from_iter(samples.into_iter().map(crate::wave_writer::i16_to_u8))
+        } else {
+            Vec::from_iter(samples.into_iter().flat_map(|i| i.to_le_bytes()))
+        }
+    }
+
+    /// Change the bit depth used by [`Audio::as_wave_bytes`] and [`Audio::save_to_file`].
+    /// Supported values are 8 (unsigned PCM) and 16.
+    pub fn with_bit_depth(mut self, bit_depth: u16) -> Result<Audio, AudioError> {
+        self.info.sample_width = match bit_depth {
+            8 => 1,
+            16 => 2,
+            _ => {
+                return Err(AudioError::new(format!(
+                    "Unsupported bit depth `{}`. Supported bit depths are 8 and 16",
+                    bit_depth
+                )))
+            }
+        };
+        Ok(self)
+    }
+
+    /// Append silence so that the number of frames is a multiple of `block_frames`,
+    /// for hardware and DSP paths that consume audio in fixed-size blocks
+    pub fn pad_to_block_size(mut self, block_frames: usize) -> Result<Audio, AudioError> {
+        if block_frames == 0 {
+            return Err(AudioError::new("Block size must be greater than zero"));
+        }
+        let num_frames = self.num_frames().next_multiple_of(block_frames);
+        let num_samples = num_frames * self.info.num_channels.max(1);
+        self.samples.as_mut_vec().resize(num_samples, 0.0);
+        Ok(self)
     }
 
     pub fn len(&self) -> usize {
@@ -242,8 +500,224 @@ impl Audio {
         self.samples.is_empty()
     }
 
+    /// Number of frames, i.e. samples per channel
+    pub fn num_frames(&self) -> usize {
+        self.len() / self.info.num_channels.max(1)
+    }
+
+    /// How long the audio plays, from its number of frames. Silence in the samples, such as
+    /// appended pauses or block padding, counts toward it.
     pub fn duration_ms(&self) -> f32 {
-        (self.len() as f32 / self.info.sample_rate as f32) * 1000.0f32
+        (self.num_frames() as f32 / self.info.sample_rate as f32) * 1000.0f32
+    }
+
+    /// Return the part of this audio between `start_ms` and `end_ms`.
+    ///
+    /// Both bounds are clamped to the duration of the audio, and are rounded to
+    /// the nearest frame so that channels stay interleaved correctly.
+    pub fn slice(&self, start_ms: f32, end_ms: f32) -> Result<Audio, AudioError> {
+        if !(start_ms.is_finite() && end_ms.is_finite()) || start_ms < 0.0 || end_ms < 0.0 {
+            return Err(AudioError::new(format!(
+                "Invalid slice bounds `{}..{}`. Bounds must be non-negative numbers",
+                start_ms, end_ms
+            )));
+        }
+        if start_ms > end_ms {
+            return Err(AudioError::new(format!(
+                "Slice start `{}` is after slice end `{}`",
+                start_ms, end_ms
+            )));
+        }
+        let num_frames = self.num_frames();
+        let ms_to_frame = |ms: f32| {
+            ((ms * self.info.sample_rate as f32 / 1000.0).round() as usize).min(num_frames)
+        };
+        Ok(self.slice_frames(ms_to_frame(start_ms), ms_to_frame(end_ms)))
+    }
+
+    /// The frames `start..end`, which must be in bounds
+    fn slice_frames(&self, start: usize, end: usize) -> Audio {
+        let num_channels = self.info.num_channels.max(1);
+        let (start, end) = (start * num_channels, end * num_channels);
+        Audio {
+            samples: self.samples.as_slice()[start..end].to_vec().into(),
+            info: self.info.clone(),
+            inference_ms: None,
+            sentence: self.sentence.clone(),
+            phoneme_timings: self.phoneme_timings.as_ref().map(|timings| {
+                let start_ms = start as f32 * 1000.0 / (num_channels * self.info.sample_rate) as f32;
+                let end_ms = end as f32 * 1000.0 / (num_channels * self.info.sample_rate) as f32;
+                timings
+                    .iter()
+                    .filter(|timing| timing.end_ms > start_ms && timing.start_ms < end_ms)
+                    .map(|timing| PhonemeTiming {
+                        phoneme: timing.phoneme.clone(),
+                        start_ms: timing.start_ms.max(start_ms) - start_ms,
+                        end_ms: timing.end_ms.min(end_ms) - start_ms,
+                    })
+                    .collect()
+            }),
+            truncation_suspected: self.truncation_suspected,
+            fixed_scale: self.fixed_scale,
+        }
+    }
+
+    /// Split the audio into segments at its pauses: the runs of at least `min_silence_ms`
+    /// milliseconds whose samples are all quieter than `threshold_dbfs` (e.g. `-50`).
+    ///
+    /// Each pause is split in its middle, so that both segments keep half of it. Silence at
+    /// the start and end of the audio stays in the first and last segment. Without any pause,
+    /// the whole audio is the only segment.
+    pub fn split_at_silence(&self, min_silence_ms: u32, threshold_dbfs: f32) -> Vec<Audio> {
+        let num_channels = self.info.num_channels.max(1);
+        let threshold = 10f32.powf(threshold_dbfs / 20.0);
+        let min_silence_frames =
+            (min_silence_ms as usize * self.info.sample_rate / 1000).max(1);
+        let mut split_points = Vec::new();
+        // The first frame of the current run of silence, once a frame was heard
+        let mut silence_start = None;
+        let mut heard = false;
+        for (i, frame) in self.samples.as_slice().chunks_exact(num_channels).enumerate() {
+            if frame.iter().all(|sample| sample.abs() < threshold) {
+                if heard {
+                    silence_start.get_or_insert(i);
+                }
+                continue;
+            }
+            if let Some(start) = silence_start.take() {
+                if i - start >= min_silence_frames {
+                    split_points.push(start + (i - start) / 2);
+                }
+            }
+            heard = true;
+        }
+        let mut segments = Vec::with_capacity(split_points.len() + 1);
+        let mut start = 0;
+        for end in split_points.into_iter().chain([self.num_frames()]) {
+            segments.push(self.slice_frames(start, end));
+            start = end;
+        }
+        segments
+    }
+
+    /// Change the duration of the audio by `factor` while keeping its pitch.
+    ///
+    /// A factor of `2.0` doubles the duration and `0.5` halves it.
+    /// Uses WSOLA (waveform similarity overlap-add) on the raw samples.
+    pub fn time_stretch(&self, factor: f32) -> Result<Audio, AudioError> {
+        if !factor.is_finite() || !(0.25..=4.0).contains(&factor) {
+            return Err(AudioError::new(format!(
+                "Invalid time stretch factor `{}`. Expected a value between 0.25 and 4.0",
+                factor
+            )));
+        }
+        let samples = crate::wsola::time_stretch(
+            self.samples.as_slice(),
+            self.info.sample_rate,
+            self.info.num_channels,
+            factor,
+        );
+        Ok(Audio {
+            samples: samples.into(),
+            info: self.info.clone(),
+            inference_ms: self.inference_ms,
+            sentence: self.sentence.clone(),
+            phoneme_timings: self.phoneme_timings.as_ref().map(|timings| {
+                timings
+                    .iter()
+                    .map(|timing| PhonemeTiming {
+                        phoneme: timing.phoneme.clone(),
+                        start_ms: timing.start_ms * factor,
+                        end_ms: timing.end_ms * factor,
+                    })
+                    .collect()
+            }),
+            truncation_suspected: self.truncation_suspected,
+            fixed_scale: self.fixed_scale,
+        })
+    }
+
+    /// The loudness of the speech in the audio, in dBFS: the RMS level of its 20 ms blocks
+    /// that are louder than -60 dBFS, so that pauses don't lower it. A full-scale sine wave
+    /// is at -3 dBFS. `None` if the audio is silent.
+    pub fn loudness_db(&self) -> Option<f32> {
+        const BLOCK_MS: usize = 20;
+        const GATE_DB: f32 = -60.0;
+        let block_len = (self.info.sample_rate * BLOCK_MS / 1000).max(1) * self.info.num_channels.max(1);
+        let gate = 10f32.powf(GATE_DB / 10.0);
+        let (sum, len) = self
+            .samples
+            .as_slice()
+            .chunks(block_len)
+            .map(|block| (block.iter().map(|s| s * s).sum::<f32>(), block.len()))
+            .filter(|(sum, len)| sum / *len as f32 > gate)
+            .fold((0f32, 0usize), |(total, total_len), (sum, len)| (total + sum, total_len + len));
+        (len > 0).then(|| 10.0 * (sum / len as f32).log10())
+    }
+
+    /// Count the float samples at or beyond full scale (`|sample| >= 1.0`). A fixed-scale
+    /// conversion (see [`Audio::fixed_scale`] and [`SampleConverter::rounding`]) clips them,
+    /// while the default conversion of [`Audio::as_wave_bytes`] scales the clip to its peak
+    /// and clips nothing.
+    pub fn clipping_stats(&self) -> ClippingStats {
+        let (clipped_samples, peak) = self
+            .samples
+            .as_slice()
+            .iter()
+            .fold((0, 0f32), |(clipped, peak), sample| {
+                let magnitude = sample.abs();
+                (clipped + usize::from(magnitude >= 1.0), peak.max(magnitude))
+            });
+        ClippingStats {
+            clipped_samples,
+            total_samples: self.len(),
+            peak,
+        }
+    }
+
+    /// Convert the audio to the given sample rate
+    pub fn resample(&self, sample_rate: usize) -> Result<Audio, AudioError> {
+        if sample_rate == 0 {
+            return Err(AudioError::new("Sample rate must be greater than zero"));
+        }
+        let samples = crate::resampler::resample(
+            self.samples.as_slice(),
+            self.info.sample_rate,
+            sample_rate,
+            self.info.num_channels,
+        );
+        Ok(Audio {
+            samples: samples.into(),
+            info: AudioInfo {
+                sample_rate,
+                ..self.info.clone()
+            },
+            inference_ms: self.inference_ms,
+            sentence: self.sentence.clone(),
+            phoneme_timings: self.phoneme_timings.clone(),
+            truncation_suspected: self.truncation_suspected,
+            fixed_scale: self.fixed_scale,
+        })
+    }
+
+    /// Export the phoneme alignment of the clip as a Praat TextGrid, or as SRT/WebVTT captions
+    pub fn export_alignment(&self, format: AlignmentFormat) -> Result<String, AudioError> {
+        match self.phoneme_timings {
+            Some(ref timings) => Ok(crate::alignment::export(timings, self.duration_ms(), format)),
+            None => Err(AudioError::new(
+                "No phoneme timings are available for this audio",
+            )),
+        }
+    }
+
+    /// When each viseme (mouth shape) of the given set is shown, for lip-syncing an avatar
+    pub fn visemes(&self, viseme_set: VisemeSet) -> Result<Vec<VisemeTiming>, AudioError> {
+        match self.phoneme_timings {
+            Some(ref timings) => Ok(crate::viseme::to_visemes(timings, viseme_set)),
+            None => Err(AudioError::new(
+                "No phoneme timings are available for this audio",
+            )),
+        }
     }
 
     pub fn inference_ms(&self) -> Option<f32> {
@@ -260,9 +734,21 @@ impl Audio {
     }
 
     pub fn save_to_file(&self, filename: &Path) -> Result<(), crate::WaveWriterError> {
+        self.save_to_file_with(filename, None)
+    }
+
+    /// Like [`Audio::save_to_file`], converting the samples with `converter` if one is given
+    pub fn save_to_file_with(
+        &self,
+        filename: &Path,
+        converter: Option<&SampleConverter>,
+    ) -> Result<(), crate::WaveWriterError> {
+        let samples = self
+            .to_i16_vec_with(converter)
+            .map_err(|e| crate::WaveWriterError(e.to_string()))?;
         crate::write_wave_samples_to_file(
             filename,
-            self.samples.to_i16_vec().iter(),
+            samples.iter(),
             self.info.sample_rate as u32,
             self.info.num_channels as u32,
             self.info.sample_width as u32,
@@ -283,6 +769,116 @@ impl IntoIterator for Audio {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_8_bit_wave_bytes() {
+        let audio = Audio::new(vec![-1.0, 0.0, 0.5, 1.0].into(), 16000, None);
+        assert_eq!(audio.as_wave_bytes().len(), 8);
+        let audio = audio.with_bit_depth(8).unwrap();
+        assert_eq!(audio.info.sample_width, 1);
+        assert_eq!(audio.as_wave_bytes(), vec![0, 128, 192, 255]);
+        assert!(audio.with_bit_depth(12).is_err());
+    }
+
+    #[test]
+    fn test_custom_sample_converter() {
+        let audio = Audio::new(vec![-0.5, 0.0, 0.25].into(), 16000, None);
+        let rounding = SampleConverter::new(|samples| {
+            samples.iter().map(|s| (s * 32767.0).round() as i16).collect()
+        });
+        let bytes = audio.as_wave_bytes_with(Some(&rounding)).unwrap();
+        assert_eq!(bytes[..2], (-16384i16).to_le_bytes());
+        assert_eq!(bytes[4..], 8192i16.to_le_bytes());
+        assert_eq!(audio.as_wave_bytes_with(None).unwrap(), audio.as_wave_bytes());
+        let broken = SampleConverter::new(|_| Vec::new());
+        assert!(audio.as_wave_bytes_with(Some(&broken)).is_err());
+    }
+
+    #[test]
+    fn test_converters_hash_by_kind() {
+        let hash = |converter: &SampleConverter| {
+            let mut state = std::collections::hash_map::DefaultHasher::new();
+            converter.hash(&mut state);
+            state.finish()
+        };
+        let rounding = SampleConverter::rounding(false);
+        assert_eq!(hash(&rounding), hash(&SampleConverter::rounding(false)));
+        assert_ne!(hash(&rounding), hash(&SampleConverter::rounding(true)));
+        assert_ne!(hash(&rounding), hash(&SampleConverter::new(|_| Vec::new())));
+    }
+
+    #[test]
+    fn test_fixed_scale_keeps_the_level() {
+        let mut audio = Audio::new(vec![0.25, -0.25, 2.0].into(), 16000, None);
+        let level = |bytes: Vec<u8>| i16::from_le_bytes([bytes[0], bytes[1]]);
+        assert_eq!(level(audio.as_wave_bytes()), 4096);
+        audio.fixed_scale = true;
+        assert_eq!(level(audio.as_wave_bytes()), 8192);
+        assert_eq!(audio.as_wave_bytes()[4..], i16::MAX.to_le_bytes());
+        assert_eq!(audio.as_wave_bytes_with(None).unwrap(), audio.as_wave_bytes());
+        assert_eq!(level(audio.slice(0.0, 1.0).unwrap().as_wave_bytes()), 8192);
+    }
+
+    #[test]
+    fn test_conversions_round() {
+        let samples: AudioSamples = vec![1.0, 0.50002, -0.25].into();
+        assert_eq!(samples.to_i16_vec(), [32767, 16384, -8192]);
+        let rounding = SampleConverter::rounding(false);
+        assert_eq!(rounding.convert(&[0.50002, -0.25, 2.0]).unwrap(), [16384, -8192, 32767]);
+    }
+
+    #[test]
+    fn test_dither_stays_within_a_step() {
+        let samples = Vec::from_iter((0..1000).map(|i| i as f32 / 1000.0 * 0.001));
+        let rounded = SampleConverter::rounding(false).convert(&samples).unwrap();
+        let dithered = SampleConverter::rounding(true).convert(&samples).unwrap();
+        assert_ne!(dithered, rounded);
+        assert!(dithered.iter().zip(&rounded).all(|(a, b)| (a - b).abs() <= 1));
+        let error: f32 = samples
+            .iter()
+            .zip(&dithered)
+            .map(|(sample, value)| *value as f32 - sample * MAX_WAV_VALUE_I16)
+            .sum();
+        assert!((error / samples.len() as f32).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_resample() {
+        let audio = Audio::new(vec![0.25; 1600].into(), 16000, None);
+        let resampled = audio.resample(24000).unwrap();
+        assert_eq!(resampled.info.sample_rate, 24000);
+        assert_eq!(resampled.num_frames(), 2400);
+        assert!((resampled.duration_ms() - audio.duration_ms()).abs() < f32::EPSILON);
+        assert!(audio.resample(0).is_err());
+    }
+
+    #[test]
+    fn test_clipping_stats() {
+        let samples = vec![0.5, -1.0, 1.7, 0.2, -2.5, 0.0, 0.99, 1.0];
+        let stats = Audio::new(samples.into(), 16000, None).clipping_stats();
+        assert_eq!(stats.clipped_samples, 4);
+        assert_eq!(stats.total_samples, 8);
+        assert_eq!(stats.peak, 2.5);
+        assert_eq!(stats.clipped_percentage(), 50.0);
+        assert!(stats.has_clipping());
+        let silence = Audio::new(vec![0.0; 4].into(), 16000, None).clipping_stats();
+        assert!(!silence.has_clipping());
+        assert_eq!(silence.clipped_percentage(), 0.0);
+    }
+
+    #[test]
+    fn test_loudness_ignores_silence() {
+        let sine: Vec<f32> = (0..16000)
+            .map(|i| 0.5 * (i as f32 * 440.0 * std::f32::consts::TAU / 16000.0).sin())
+            .collect();
+        let loudness = Audio::new(sine.clone().into(), 16000, None).loudness_db().unwrap();
+        assert!((loudness - (-9.03)).abs() < 0.05);
+        let mut with_pause = sine;
+        with_pause.extend(vec![0.0; 16000]);
+        let loudness_with_pause = Audio::new(with_pause.into(), 16000, None).loudness_db().unwrap();
+        assert!((loudness_with_pause - loudness).abs() < 0.05);
+        assert_eq!(Audio::new(vec![0.0; 320].into(), 16000, None).loudness_db(), None);
+    }
+
     #[test]
     fn test_fade_in() {
         let data = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
@@ -340,6 +936,149 @@ mod tests {
         );
     }
 
+    fn sine(freq: f32, sample_rate: usize, num_frames: usize) -> Vec<f32> {
+        (0..num_frames)
+            .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    fn zero_crossing_freq(samples: &[f32], sample_rate: usize) -> f32 {
+        let crossings = samples
+            .windows(2)
+            .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+            .count();
+        crossings as f32 * sample_rate as f32 / samples.len() as f32
+    }
+
+    #[test]
+    fn test_time_stretch() {
+        let sample_rate = 16000;
+        let audio = Audio::new(sine(220.0, sample_rate, sample_rate).into(), sample_rate, None);
+        for factor in [0.5, 1.5, 2.0] {
+            let stretched = audio.time_stretch(factor).unwrap();
+            let expected = audio.duration_ms() * factor;
+            assert!((stretched.duration_ms() - expected).abs() <= 1.0);
+            // Skip the edges where the overlap-add has no neighbour
+            let frames = stretched.samples.as_slice();
+            let middle = &frames[frames.len() / 10..frames.len() * 9 / 10];
+            let freq = zero_crossing_freq(middle, sample_rate);
+            assert!((freq - 220.0).abs() < 220.0 * 0.03, "pitch drifted to {}", freq);
+        }
+    }
+
+    #[test]
+    fn test_time_stretch_rejects_invalid_factor() {
+        let audio = Audio::new(vec![0.0; 1600].into(), 16000, None);
+        assert!(audio.time_stretch(0.0).is_err());
+        assert!(audio.time_stretch(f32::NAN).is_err());
+        assert!(audio.time_stretch(10.0).is_err());
+    }
+
+    #[test]
+    fn test_silence() {
+        let info = AudioInfo {
+            sample_rate: 22050,
+            num_channels: 2,
+            sample_width: 2,
+        };
+        let silence = Audio::silence(info, 200);
+        assert_eq!(silence.num_frames(), 4410);
+        assert_eq!(silence.len(), 8820);
+        assert_eq!(silence.duration_ms(), 200.0);
+        assert!(silence.samples.as_slice().iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_pad_to_block_size() {
+        let mut audio = Audio::new(vec![0.5; 2 * 1000].into(), 16000, None);
+        audio.info.num_channels = 2;
+        for (block_frames, num_frames) in [(1, 1000), (256, 1024), (1000, 1000), (4096, 4096)] {
+            let padded = audio.clone().pad_to_block_size(block_frames).unwrap();
+            assert_eq!(padded.num_frames(), num_frames);
+            assert_eq!(padded.len(), num_frames * 2);
+            assert_eq!(padded.duration_ms(), num_frames as f32 / 16.0);
+            assert_eq!(padded.samples.as_slice()[..2000], audio.samples.as_slice()[..]);
+            assert!(padded.samples.as_slice()[2000..].iter().all(|s| *s == 0.0));
+        }
+        assert!(audio.pad_to_block_size(0).is_err());
+    }
+
+    #[test]
+    fn test_slice() {
+        let audio = Audio::new(Vec::from_iter((0..1000).map(|i| i as f32)).into(), 1000, None);
+        let clip = audio.slice(100.0, 250.0).unwrap();
+        assert_eq!(clip.len(), 150);
+        assert_eq!(clip.samples.as_slice()[0], 100.0);
+        assert_eq!(clip.duration_ms(), 150.0);
+    }
+
+    #[test]
+    fn test_duration_counts_frames() {
+        let mut audio = Audio::new(vec![0.5; 1600].into(), 16000, None);
+        // Mono audio keeps the duration of its samples
+        assert_eq!(audio.duration_ms(), 100.0);
+        audio.info.num_channels = 2;
+        assert_eq!(audio.duration_ms(), 50.0);
+        let padded = audio.pad_to_block_size(1000).unwrap();
+        assert_eq!(padded.duration_ms(), 1000.0 / 16.0);
+    }
+
+    #[test]
+    fn test_slice_clamps_to_bounds() {
+        let audio = Audio::new(vec![0.5; 1000].into(), 1000, None);
+        assert_eq!(audio.slice(900.0, 5000.0).unwrap().len(), 100);
+        assert!(audio.slice(2000.0, 3000.0).unwrap().is_empty());
+        assert!(audio.slice(300.0, 200.0).is_err());
+    }
+
+    #[test]
+    fn test_slice_keeps_channels_interleaved() {
+        let mut audio = Audio::new(
+            Vec::from_iter((0..2000).map(|i| (i % 2) as f32)).into(),
+            1000,
+            None,
+        );
+        audio.info.num_channels = 2;
+        let clip = audio.slice(10.0, 20.0).unwrap();
+        assert_eq!(clip.len(), 20);
+        assert_eq!(clip.samples.as_slice()[0], 0.0);
+        assert_eq!(clip.samples.as_slice()[1], 1.0);
+    }
+
+    #[test]
+    fn test_split_at_silence() {
+        let tone = |ms: usize| vec![0.5f32; ms];
+        let silence = |ms: usize| vec![0.001f32; ms];
+        // 1 kHz, so a sample lasts 1 ms
+        let samples = [silence(50), tone(100), silence(300), tone(100), silence(100), tone(100), silence(50)].concat();
+        let audio = Audio::new(samples.into(), 1000, None);
+
+        let segments = audio.split_at_silence(200, -40.0);
+        let durations: Vec<_> = segments.iter().map(Audio::duration_ms).collect();
+        // Only the 300 ms pause is long enough, and it's split in its middle
+        assert_eq!(durations, [50.0 + 100.0 + 150.0, 150.0 + 100.0 + 100.0 + 100.0 + 50.0]);
+        assert_eq!(segments.iter().map(Audio::len).sum::<usize>(), audio.len());
+
+        let segments = audio.split_at_silence(100, -40.0);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[1].duration_ms(), 150.0 + 100.0 + 50.0);
+        // The pauses aren't quiet enough for a lower threshold
+        assert_eq!(audio.split_at_silence(100, -70.0).len(), 1);
+    }
+
+    #[test]
+    fn test_split_at_silence_keeps_channels_interleaved() {
+        let mut samples = vec![0.5f32; 200];
+        samples.extend(vec![0.0; 400]);
+        samples.extend(Vec::from_iter((0..200).map(|i| (i % 2) as f32 * 0.5)));
+        let mut audio = Audio::new(samples.into(), 1000, None);
+        audio.info.num_channels = 2;
+        let segments = audio.split_at_silence(100, -40.0);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].num_frames(), 200);
+        assert_eq!(segments[1].samples.as_slice()[200..202], [0.0, 0.5]);
+    }
+
     #[test]
     fn test_strip_silence() {
         let data = vec![0.0, 0.1, 2.2, 0.0, 0.5, 0.0, 0.7, 0.0];
@@ -0,0 +1,192 @@
+use crate::PhonemeTiming;
+use std::str::FromStr;
+
+/// Marks that modify the preceding phoneme without changing the mouth shape
+const MODIFIERS: &[char] = &['ˈ', 'ˌ', 'ː', 'ˑ', '\u{0303}', '\u{0329}', '\u{032F}'];
+
+/// A set of mouth shapes that phonemes are mapped to for lip-sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisemeSet {
+    /// The 15 visemes of the Oculus Lipsync SDK (`sil`, `PP`, `FF`, `TH`, `DD`, `kk`, `CH`,
+    /// `SS`, `nn`, `RR`, `aa`, `E`, `ih`, `oh` and `ou`)
+    Oculus,
+    /// The 10 mouth shapes of Preston Blair's animation guide (`AI`, `E`, `O`, `U`, `etc`,
+    /// `FV`, `L`, `MBP`, `WQ` and `rest`)
+    PrestonBlair,
+}
+
+impl FromStr for VisemeSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "oculus" => Ok(Self::Oculus),
+            "preston_blair" | "prestonblair" => Ok(Self::PrestonBlair),
+            _ => Err(format!(
+                "Unknown viseme set `{}`. Supported sets are `oculus` and `preston_blair`",
+                s
+            )),
+        }
+    }
+}
+
+impl VisemeSet {
+    /// The viseme for silence, pauses and anything that isn't a phoneme
+    pub fn silence(self) -> &'static str {
+        match self {
+            Self::Oculus => "sil",
+            Self::PrestonBlair => "rest",
+        }
+    }
+    /// The viseme of an IPA phoneme, as produced by the phonemizer (e.g. `ˈaɪ` or `tʃ`).
+    /// Diphthongs take the shape of their first vowel.
+    pub fn viseme(self, phoneme: &str) -> &'static str {
+        let mut chars = phoneme.chars().filter(|c| !MODIFIERS.contains(c));
+        let Some(first) = chars.next() else {
+            return self.silence();
+        };
+        // Affricates are written as a stop followed by a fricative
+        let first = match (first, chars.next()) {
+            ('t', Some('ʃ')) | ('d', Some('ʒ')) => 'ʃ',
+            ('t', Some('s')) | ('d', Some('z')) => 's',
+            _ => first,
+        };
+        match self {
+            Self::Oculus => oculus_viseme(first),
+            Self::PrestonBlair => preston_blair_viseme(first),
+        }
+    }
+}
+
+fn oculus_viseme(c: char) -> &'static str {
+    match c {
+        'p' | 'b' | 'm' => "PP",
+        'f' | 'v' | 'ɱ' => "FF",
+        'θ' | 'ð' => "TH",
+        't' | 'd' | 'ɾ' => "DD",
+        'k' | 'g' | 'ɡ' | 'ŋ' | 'x' | 'ɣ' | 'q' | 'h' | 'ɦ' | 'ʔ' => "kk",
+        'ʃ' | 'ʒ' | 'ç' | 'ɕ' | 'ʑ' => "CH",
+        's' | 'z' => "SS",
+        'n' | 'ɲ' | 'l' | 'ɫ' | 'ʎ' => "nn",
+        'r' | 'ɹ' | 'ɻ' | 'ʁ' | 'ʀ' | 'ɚ' | 'ɝ' => "RR",
+        'a' | 'ɑ' | 'ɐ' | 'æ' | 'ʌ' => "aa",
+        'e' | 'ɛ' | 'ə' | 'ɜ' => "E",
+        'i' | 'ɪ' | 'y' | 'ʏ' | 'j' | 'ɨ' => "ih",
+        'o' | 'ɔ' | 'ɒ' | 'ø' | 'œ' => "oh",
+        'u' | 'ʊ' | 'ɯ' | 'w' => "ou",
+        _ => "sil",
+    }
+}
+
+fn preston_blair_viseme(c: char) -> &'static str {
+    match c {
+        'p' | 'b' | 'm' => "MBP",
+        'f' | 'v' | 'ɱ' => "FV",
+        'l' | 'ɫ' | 'ʎ' => "L",
+        'w' => "WQ",
+        'a' | 'ɑ' | 'ɐ' | 'æ' | 'ʌ' | 'ə' | 'ɪ' => "AI",
+        'e' | 'ɛ' | 'ɜ' | 'ɚ' | 'ɝ' | 'i' | 'y' | 'ʏ' | 'ɨ' => "E",
+        'o' | 'ɔ' | 'ɒ' | 'ø' | 'œ' => "O",
+        'u' | 'ʊ' | 'ɯ' => "U",
+        c if c.is_alphabetic() => "etc",
+        _ => "rest",
+    }
+}
+
+/// When a viseme is shown in a clip
+#[derive(Debug, Clone, PartialEq)]
+pub struct VisemeTiming {
+    pub viseme: &'static str,
+    pub start_ms: f32,
+    pub end_ms: f32,
+}
+
+/// Map the phonemes to visemes, merging consecutive phonemes with the same viseme
+pub(crate) fn to_visemes(timings: &[PhonemeTiming], viseme_set: VisemeSet) -> Vec<VisemeTiming> {
+    let mut visemes: Vec<VisemeTiming> = Vec::with_capacity(timings.len());
+    for timing in timings {
+        let viseme = viseme_set.viseme(&timing.phoneme);
+        match visemes.last_mut() {
+            Some(last) if last.viseme == viseme => last.end_ms = timing.end_ms,
+            _ => visemes.push(VisemeTiming {
+                viseme,
+                start_ms: timing.start_ms,
+                end_ms: timing.end_ms,
+            }),
+        }
+    }
+    visemes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timings(phonemes: &[&str], phoneme_ms: f32) -> Vec<PhonemeTiming> {
+        phonemes
+            .iter()
+            .enumerate()
+            .map(|(i, phoneme)| PhonemeTiming {
+                phoneme: phoneme.to_string(),
+                start_ms: i as f32 * phoneme_ms,
+                end_ms: (i + 1) as f32 * phoneme_ms,
+            })
+            .collect()
+    }
+
+    fn timeline(visemes: &[VisemeTiming]) -> Vec<(&str, f32, f32)> {
+        visemes
+            .iter()
+            .map(|timing| (timing.viseme, timing.start_ms, timing.end_ms))
+            .collect()
+    }
+
+    #[test]
+    fn test_phonemes_map_to_viseme_timeline() {
+        // "hello much" (həlˈoʊ mˈʌtʃ)
+        let timings = timings(&["h", "ə", "l", "ˈoʊ", " ", "m", "ˈʌ", "tʃ"], 100.0);
+        assert_eq!(
+            timeline(&to_visemes(&timings, VisemeSet::Oculus)),
+            [
+                ("kk", 0.0, 100.0),
+                ("E", 100.0, 200.0),
+                ("nn", 200.0, 300.0),
+                ("oh", 300.0, 400.0),
+                ("sil", 400.0, 500.0),
+                ("PP", 500.0, 600.0),
+                ("aa", 600.0, 700.0),
+                ("CH", 700.0, 800.0),
+            ]
+        );
+        assert_eq!(
+            timeline(&to_visemes(&timings, VisemeSet::PrestonBlair)),
+            [
+                ("etc", 0.0, 100.0),
+                ("AI", 100.0, 200.0),
+                ("L", 200.0, 300.0),
+                ("O", 300.0, 400.0),
+                ("rest", 400.0, 500.0),
+                ("MBP", 500.0, 600.0),
+                ("AI", 600.0, 700.0),
+                ("etc", 700.0, 800.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_consecutive_visemes_are_merged() {
+        // "jump" (dʒˈʌmp): the lips stay closed for both `m` and `p`
+        let timings = timings(&["dʒ", "ˈʌ", "m", "p"], 50.0);
+        assert_eq!(
+            timeline(&to_visemes(&timings, VisemeSet::Oculus)),
+            [("CH", 0.0, 50.0), ("aa", 50.0, 100.0), ("PP", 100.0, 200.0)]
+        );
+    }
+
+    #[test]
+    fn test_parse_viseme_set() {
+        assert_eq!("Oculus".parse(), Ok(VisemeSet::Oculus));
+        assert_eq!("preston_blair".parse(), Ok(VisemeSet::PrestonBlair));
+        assert!("disney".parse::<VisemeSet>().is_err());
+    }
+}
@@ -2,10 +2,11 @@ use riff_wave::WaveWriter;
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::{BufReader, BufWriter, SeekFrom};
 use std::path::Path;
 
 #[derive(Debug)]
-pub struct WaveWriterError(String);
+pub struct WaveWriterError(pub(crate) String);
 
 impl std::error::Error for WaveWriterError {}
 
@@ -15,6 +16,12 @@ impl fmt::Display for WaveWriterError {
     }
 }
 
+/// Convert a signed 16-bit sample to the unsigned (offset binary) encoding of 8-bit wave files
+pub(crate) fn i16_to_u8(sample: i16) -> u8 {
+    ((sample >> 8) + 128) as u8
+}
+
+/// Write a wave file of 16-bit samples, or of 8-bit unsigned samples if `sample_width` is 1
 pub fn write_wave_samples_to_buffer<'a, I, B>(
     buf: B,
     samples: I,
@@ -37,7 +44,13 @@ where
         ));
     };
     let any_fail = samples
-        .map(|i| wave_writer.write_sample_i16(*i))
+        .map(|i| {
+            if sample_width == 1 {
+                wave_writer.write_sample_u8(i16_to_u8(*i))
+            } else {
+                wave_writer.write_sample_i16(*i)
+            }
+        })
         .any(|r| r.is_err());
     if any_fail {
         return Err(WaveWriterError("Failed to write wave samples".to_string()));
@@ -85,3 +98,392 @@ where
         ))),
     }
 }
+
+/// Names of the output formats whose encoders are available in this build
+pub fn supported_output_formats() -> Vec<String> {
+    // Raw 16-bit little-endian PCM, as produced by `AudioSamples::as_wave_bytes`, and wave
+    // files are always compiled in. An encoder behind a cargo feature is added with
+    // `if cfg!(feature = "...")`, so that the list follows the features of the build.
+    let formats = vec!["pcm", "wav"];
+    formats.into_iter().map(String::from).collect()
+}
+
+/// Size of the canonical PCM wave header in bytes
+const WAVE_HEADER_SIZE: u32 = 44;
+
+/// Incrementally writes wave samples, keeping the header valid up to the last flush.
+///
+/// Samples are buffered in memory and written out when [`StreamingWaveWriter::flush`] is called,
+/// when the optional flush interval is reached, or when the writer is dropped. Each flush patches
+/// the header sizes so that an interrupted recording is still playable up to the last flushed chunk.
+pub struct StreamingWaveWriter<W: Write + Seek> {
+    writer: W,
+    buffer: Vec<u8>,
+    data_size: u32,
+    sample_width: u32,
+    block_align: u32,
+    flush_interval: Option<usize>,
+}
+
+impl StreamingWaveWriter<BufWriter<File>> {
+    pub fn create(
+        filename: &Path,
+        sample_rate: u32,
+        num_channels: u32,
+        sample_width: u32,
+    ) -> Result<Self, WaveWriterError> {
+        match File::create(filename) {
+            Ok(file) => Self::new(BufWriter::new(file), sample_rate, num_channels, sample_width),
+            Err(e) => Err(WaveWriterError(format!(
+                "Failed to create file `{}` for writing. Error: {}",
+                filename.display(),
+                e
+            ))),
+        }
+    }
+}
+
+impl<W: Write + Seek> StreamingWaveWriter<W> {
+    pub fn new(
+        mut writer: W,
+        sample_rate: u32,
+        num_channels: u32,
+        sample_width: u32,
+    ) -> Result<Self, WaveWriterError> {
+        if !matches!(sample_width, 1 | 2) || num_channels == 0 || sample_rate == 0 {
+            return Err(WaveWriterError(format!(
+                "Unsupported wave format. sample rate: {}, channels: {}, sample width: {}",
+                sample_rate, num_channels, sample_width
+            )));
+        }
+        let block_align = num_channels * sample_width;
+        let mut header = Vec::with_capacity(WAVE_HEADER_SIZE as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(WAVE_HEADER_SIZE - 8).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&(num_channels as u16).to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * block_align).to_le_bytes());
+        header.extend_from_slice(&(block_align as u16).to_le_bytes());
+        header.extend_from_slice(&((sample_width * 8) as u16).to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        if let Err(e) = writer.write_all(&header) {
+            return Err(WaveWriterError(format!(
+                "Failed to write wave header. Error: {}",
+                e
+            )));
+        }
+        Ok(Self {
+            writer,
+            buffer: Vec::new(),
+            data_size: 0,
+            sample_width,
+            block_align,
+            flush_interval: None,
+        })
+    }
+    /// Flush automatically whenever at least `num_frames` frames are buffered
+    pub fn with_flush_interval(mut self, num_frames: usize) -> Self {
+        self.flush_interval = Some(num_frames * self.block_align as usize);
+        self
+    }
+    pub fn write_samples<'a, I>(&mut self, samples: I) -> Result<(), WaveWriterError>
+    where
+        I: Iterator<Item = &'a i16>,
+    {
+        if self.sample_width == 1 {
+            self.buffer.extend(samples.map(|sample| i16_to_u8(*sample)));
+        } else {
+            for sample in samples {
+                self.buffer.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+        match self.flush_interval {
+            Some(interval) if self.buffer.len() >= interval => self.flush(),
+            _ => Ok(()),
+        }
+    }
+    /// Write the buffered samples and update the header to cover them
+    pub fn flush(&mut self) -> Result<(), WaveWriterError> {
+        // Only write whole frames so the file never ends mid-frame
+        let num_bytes = self.buffer.len() - self.buffer.len() % self.block_align as usize;
+        let Some(data_size) = u32::try_from(num_bytes)
+            .ok()
+            .and_then(|n| self.data_size.checked_add(n))
+            .filter(|size| size.checked_add(WAVE_HEADER_SIZE).is_some())
+        else {
+            return Err(WaveWriterError(
+                "Wave file exceeds the maximum size of 4 GiB".to_string(),
+            ));
+        };
+        let result = self
+            .writer
+            .write_all(&self.buffer[..num_bytes])
+            .and_then(|_| self.writer.seek(SeekFrom::Start(4)))
+            .and_then(|_| {
+                self.writer
+                    .write_all(&(data_size + WAVE_HEADER_SIZE - 8).to_le_bytes())
+            })
+            .and_then(|_| self.writer.seek(SeekFrom::Start(40)))
+            .and_then(|_| self.writer.write_all(&data_size.to_le_bytes()))
+            .and_then(|_| self.writer.seek(SeekFrom::End(0)))
+            .and_then(|_| self.writer.flush());
+        if let Err(e) = result {
+            return Err(WaveWriterError(format!(
+                "Failed to flush wave samples. Error: {}",
+                e
+            )));
+        }
+        self.buffer.drain(..num_bytes);
+        self.data_size = data_size;
+        Ok(())
+    }
+    /// Flush the remaining samples. Dropping the writer does the same but ignores errors.
+    pub fn finalize(mut self) -> Result<(), WaveWriterError> {
+        self.flush()
+    }
+    /// Number of sample bytes written to the underlying writer so far
+    pub fn flushed_data_size(&self) -> u32 {
+        self.data_size
+    }
+}
+
+impl<W: Write + Seek> Drop for StreamingWaveWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Fix the header of a wave file whose recording was interrupted, e.g. one written by a
+/// [`StreamingWaveWriter`] that crashed before its last flush.
+///
+/// The data chunk is taken to run to the end of the file, as it does in streamed files.
+/// A partial frame at the end is cut off, and the RIFF and data sizes are rewritten to
+/// cover the rest. Files with another chunk (e.g. `LIST`) right after the declared end of
+/// the data are complete and left alone. Returns whether the file had to be changed.
+pub fn repair_wav(filename: &Path) -> Result<bool, WaveWriterError> {
+    let io_error = |e: std::io::Error| {
+        WaveWriterError(format!(
+            "Failed to repair wave file `{}`. Error: {}",
+            filename.display(),
+            e
+        ))
+    };
+    let invalid = |reason: &str| {
+        WaveWriterError(format!(
+            "Can't repair `{}`: {}",
+            filename.display(),
+            reason
+        ))
+    };
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(filename)
+        .map_err(io_error)?;
+    let file_len = file.metadata().map_err(io_error)?.len() as usize;
+    // Only the chunk headers are read, the samples are skipped over
+    let mut reader = BufReader::new(&file);
+    let mut header = [0u8; 12];
+    if file_len < 12 {
+        return Err(invalid("not a wave file"));
+    }
+    reader.read_exact(&mut header).map_err(io_error)?;
+    if &header[..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(invalid("not a wave file"));
+    }
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes[..4].try_into().unwrap());
+    let riff_size_on_disk = read_u32(&header[4..]);
+    // Walk the chunks up to the data chunk, taking the block align from the format chunk
+    let mut offset = 12;
+    let mut block_align = None;
+    let (data_offset, data_size_on_disk) = loop {
+        if offset + 8 > file_len {
+            return Err(invalid("no data chunk"));
+        }
+        let mut chunk_header = [0u8; 8];
+        reader.read_exact(&mut chunk_header).map_err(io_error)?;
+        let chunk_size = read_u32(&chunk_header[4..]) as usize;
+        // Chunks are padded to an even size
+        let mut skip = chunk_size + chunk_size % 2;
+        match &chunk_header[..4] {
+            b"data" => break (offset + 8, chunk_size as u32),
+            b"fmt " if chunk_size >= 16 && offset + 24 <= file_len => {
+                let mut format = [0u8; 16];
+                reader.read_exact(&mut format).map_err(io_error)?;
+                block_align = Some(u16::from_le_bytes([format[12], format[13]]));
+                skip -= 16;
+            }
+            _ => {}
+        }
+        reader.seek_relative(skip as i64).map_err(io_error)?;
+        offset += 8 + chunk_size + chunk_size % 2;
+    };
+    // A complete chunk after the data means the sizes are right
+    let data_end = data_offset + data_size_on_disk as usize + data_size_on_disk as usize % 2;
+    if data_end + 8 <= file_len {
+        let mut chunk_header = [0u8; 8];
+        reader
+            .seek_relative((data_end - data_offset) as i64)
+            .and_then(|_| reader.read_exact(&mut chunk_header))
+            .map_err(io_error)?;
+        let is_chunk_id = chunk_header[..4].iter().all(|c| c.is_ascii_graphic() || *c == b' ');
+        let chunk_end = data_end + 8 + read_u32(&chunk_header[4..]) as usize;
+        if is_chunk_id && chunk_end <= file_len {
+            return Ok(false);
+        }
+    }
+    drop(reader);
+    let Some(block_align) = block_align.filter(|block_align| *block_align > 0) else {
+        return Err(invalid("no valid format chunk before the data chunk"));
+    };
+    let available = file_len - data_offset;
+    let data_size = available - available % block_align as usize;
+    let Some(data_size) = u32::try_from(data_size)
+        .ok()
+        .filter(|size| size.checked_add(data_offset as u32).is_some())
+    else {
+        return Err(invalid("the data exceeds the maximum size of 4 GiB"));
+    };
+    let riff_size = (data_offset - 8) as u32 + data_size;
+    if riff_size_on_disk == riff_size && data_size_on_disk == data_size && data_size as usize == available {
+        return Ok(false);
+    }
+    file.set_len((data_offset + data_size as usize) as u64)
+        .and_then(|_| file.seek(SeekFrom::Start(4)))
+        .and_then(|_| file.write_all(&riff_size.to_le_bytes()))
+        .and_then(|_| file.seek(SeekFrom::Start(data_offset as u64 - 4)))
+        .and_then(|_| file.write_all(&data_size.to_le_bytes()))
+        .and_then(|_| file.flush())
+        .map_err(io_error)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn header_sizes(bytes: &[u8]) -> (u32, u32) {
+        let riff = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let data = u32::from_le_bytes(bytes[40..44].try_into().unwrap());
+        (riff, data)
+    }
+
+    #[test]
+    fn test_supported_output_formats() {
+        assert_eq!(supported_output_formats(), ["pcm", "wav"]);
+    }
+
+    #[test]
+    fn test_i16_to_u8() {
+        assert_eq!(i16_to_u8(i16::MIN), 0);
+        assert_eq!(i16_to_u8(-1), 127);
+        assert_eq!(i16_to_u8(0), 128);
+        assert_eq!(i16_to_u8(256), 129);
+        assert_eq!(i16_to_u8(i16::MAX), 255);
+    }
+
+    #[test]
+    fn test_8_bit_wave_header() {
+        let samples = [i16::MIN, 0, i16::MAX, 256];
+        let mut out = Vec::new();
+        write_wave_samples_to_buffer(Cursor::new(&mut out), samples.iter(), 8000, 2, 1).unwrap();
+        let field = |offset: usize| u16::from_le_bytes(out[offset..offset + 2].try_into().unwrap());
+        // PCM format tag, block align and bits per sample
+        assert_eq!(field(20), 1);
+        assert_eq!(field(32), 2);
+        assert_eq!(field(34), 8);
+        assert_eq!(u32::from_le_bytes(out[28..32].try_into().unwrap()), 8000 * 2);
+        assert_eq!(header_sizes(&out), (36 + 4, 4));
+        assert_eq!(&out[44..], &[0, 128, 255, 129]);
+
+        let mut streamed = Vec::new();
+        let mut writer = StreamingWaveWriter::new(Cursor::new(&mut streamed), 8000, 2, 1).unwrap();
+        writer.write_samples(samples.iter()).unwrap();
+        writer.finalize().unwrap();
+        assert_eq!(streamed, out);
+    }
+
+    #[test]
+    fn test_streaming_writer_flush_patches_header() {
+        let mut out = Vec::new();
+        {
+            let mut writer =
+                StreamingWaveWriter::new(Cursor::new(&mut out), 16000, 1, 2).unwrap();
+            writer.write_samples([1i16, 2, 3].iter()).unwrap();
+            writer.flush().unwrap();
+            writer.write_samples([4i16].iter()).unwrap();
+            // Leak the writer to simulate a crash before the last chunk is flushed
+            std::mem::forget(writer);
+        }
+        assert_eq!(out.len(), 44 + 6);
+        assert_eq!(header_sizes(&out), (36 + 6, 6));
+    }
+
+    #[test]
+    fn test_repair_truncated_wave() {
+        let samples: Vec<i16> = (0..16000).map(|i| (i % 100) as i16).collect();
+        let path = std::env::temp_dir().join(format!("sonata-test-repair-{}.wav", std::process::id()));
+        write_wave_samples_to_file(&path, samples.iter(), 16000, 2, 2).unwrap();
+        assert!(!repair_wav(&path).unwrap());
+        // Cut the file mid-frame, leaving the header claiming the full size
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..44 + 6000 * 4 + 3]).unwrap();
+        assert!(repair_wav(&path).unwrap());
+        let repaired = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(repaired.len(), 44 + 6000 * 4);
+        assert_eq!(header_sizes(&repaired), (36 + 6000 * 4, 6000 * 4));
+        // 6000 frames at 16 kHz
+        let byte_rate = u32::from_le_bytes(repaired[28..32].try_into().unwrap());
+        assert_eq!(header_sizes(&repaired).1 as f32 / byte_rate as f32, 0.375);
+        assert!(repaired[44..] == bytes[44..44 + 6000 * 4]);
+    }
+
+    #[test]
+    fn test_repair_keeps_chunks_after_the_data() {
+        let samples: Vec<i16> = (0..1000).map(|i| (i % 100) as i16).collect();
+        let path = std::env::temp_dir().join(format!("sonata-test-repair-list-{}.wav", std::process::id()));
+        write_wave_samples_to_file(&path, samples.iter(), 16000, 1, 2).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.extend_from_slice(b"LIST");
+        bytes.extend_from_slice(&12u32.to_le_bytes());
+        bytes.extend_from_slice(b"INFOISFT\0\0\0\0");
+        let riff_size = bytes.len() as u32 - 8;
+        bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(!repair_wav(&path).unwrap());
+        let unchanged = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(unchanged, bytes);
+    }
+
+    #[test]
+    fn test_repair_rejects_other_files() {
+        let path = std::env::temp_dir().join(format!("sonata-test-repair-{}.txt", std::process::id()));
+        std::fs::write(&path, b"not a wave file at all").unwrap();
+        assert!(repair_wav(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_streaming_writer_finalizes_on_drop() {
+        let mut out = Vec::new();
+        {
+            let mut writer = StreamingWaveWriter::new(Cursor::new(&mut out), 16000, 2, 2)
+                .unwrap()
+                .with_flush_interval(2);
+            writer.write_samples([1i16, 2, 3, 4].iter()).unwrap();
+            writer.write_samples([5i16, 6].iter()).unwrap();
+        }
+        assert_eq!(header_sizes(&out), (36 + 12, 12));
+        let mut expected = Vec::new();
+        write_wave_samples_to_buffer(Cursor::new(&mut expected), [1i16, 2, 3, 4, 5, 6].iter(), 16000, 2, 2)
+            .unwrap();
+        assert_eq!(out, expected);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::hanning_window;
+
+/// Length of each analysis window in milliseconds
+const WINDOW_MS: usize = 20;
+
+/// Change the duration of interleaved samples by `factor` without changing their pitch.
+///
+/// Implements Waveform Similarity Overlap-Add (WSOLA): windows are taken from the input
+/// at a hop of `synthesis_hop / factor`, and each window's position is adjusted within a
+/// small tolerance so that it best continues the previously copied window. The windows
+/// are then overlap-added at a fixed synthesis hop. Alignment is computed on the channel
+/// average and applied to all channels so they stay in sync.
+pub(crate) fn time_stretch(
+    samples: &[f32],
+    sample_rate: usize,
+    num_channels: usize,
+    factor: f32,
+) -> Vec<f32> {
+    let num_channels = num_channels.max(1);
+    let num_frames = samples.len() / num_channels;
+    let out_frames = (num_frames as f32 * factor).round() as usize;
+    let window_len = (sample_rate * WINDOW_MS / 1000).max(4);
+    if num_frames < window_len * 2 {
+        // Too short to find any similarity, fall back to resampling by repetition/dropping
+        return Vec::from_iter((0..out_frames).flat_map(|i| {
+            let src = ((i as f32 / factor) as usize).min(num_frames.saturating_sub(1));
+            samples[src * num_channels..(src + 1) * num_channels].iter().copied()
+        }));
+    }
+    let synthesis_hop = window_len / 2;
+    let analysis_hop = synthesis_hop as f32 / factor;
+    let tolerance = window_len / 4;
+
+    // Mono mix used to measure waveform similarity
+    let mix: Vec<f32> = samples
+        .chunks_exact(num_channels)
+        .map(|frame| frame.iter().sum::<f32>() / num_channels as f32)
+        .collect();
+    let window = hanning_window::get_hann_window(window_len);
+    let last_start = num_frames - window_len;
+
+    let mut output = vec![0f32; (out_frames + window_len) * num_channels];
+    let mut norm = vec![0f32; out_frames + window_len];
+    let mut prev_start = 0usize;
+    let mut segment = 0usize;
+    loop {
+        let out_pos = segment * synthesis_hop;
+        if out_pos >= out_frames {
+            break;
+        }
+        let nominal = ((segment as f32 * analysis_hop).round() as usize).min(last_start);
+        let start = if segment == 0 {
+            0
+        } else {
+            let natural = (prev_start + synthesis_hop).min(last_start);
+            best_match(&mix, natural, nominal, tolerance, window_len, last_start)
+        };
+        for (i, w) in window.iter().enumerate() {
+            let src = (start + i) * num_channels;
+            let dst = (out_pos + i) * num_channels;
+            for c in 0..num_channels {
+                output[dst + c] += samples[src + c] * w;
+            }
+            norm[out_pos + i] += w;
+        }
+        prev_start = start;
+        segment += 1;
+    }
+    output.truncate(out_frames * num_channels);
+    for (frame, n) in output.chunks_exact_mut(num_channels).zip(norm) {
+        if n > 1e-3 {
+            frame.iter_mut().for_each(|s| *s /= n);
+        }
+    }
+    output
+}
+
+/// Find the window start within `nominal ± tolerance` that best correlates with the
+/// window starting at `natural`.
+fn best_match(
+    mix: &[f32],
+    natural: usize,
+    nominal: usize,
+    tolerance: usize,
+    window_len: usize,
+    last_start: usize,
+) -> usize {
+    let reference = &mix[natural..natural + window_len];
+    let lower = nominal.saturating_sub(tolerance);
+    let upper = (nominal + tolerance).min(last_start);
+    let mut best_start = nominal;
+    let mut best_score = f32::NEG_INFINITY;
+    for candidate in lower..=upper {
+        let score: f32 = reference
+            .iter()
+            .zip(&mix[candidate..candidate + window_len])
+            .map(|(a, b)| a * b)
+            .sum();
+        if score > best_score {
+            best_score = score;
+            best_start = candidate;
+        }
+    }
+    best_start
+}
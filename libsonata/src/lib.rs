@@ -1,5 +1,5 @@
 use ffi_support::{call_with_result, define_string_destructor, ErrorCode, ExternError, FfiStr};
-use sonata_core::{AudioSamples, SonataError, SonataModel, SonataResult};
+use sonata_core::{SonataError, SonataModel, SonataResult};
 use sonata_synth::{AudioOutputConfig, SonataSpeechSynthesizer, SYNTHESIS_THREAD_POOL};
 use std::any::Any;
 use std::ops::Deref;
@@ -159,6 +159,7 @@ impl SynthesisParams {
             volume: Some(self.volume),
             pitch: Some(self.pitch),
             appended_silence_ms: Some(self.appended_silence_ms),
+            ..Default::default()
         }
     }
 }
@@ -395,17 +396,22 @@ fn _do_synthesize(
         synth_mode::SYNTH_MODE_LAZY => {
             let stream = synth
                 .synthesize_lazy(text, audio_output_config)?
-                .map(|wr| wr.map(|aud| aud.samples));
+                .map(|wr| wr.map(|aud| aud.as_wave_bytes()));
             iterate_stream(stream, params.callback)
         }
         synth_mode::SYNTH_MODE_PARALLEL => {
             let stream = synth
                 .synthesize_parallel(text, audio_output_config)?
-                .map(|wr| wr.map(|aud| aud.samples));
+                .map(|wr| wr.map(|aud| aud.as_wave_bytes()));
             iterate_stream(stream, params.callback)
         }
         synth_mode::SYNTH_MODE_REALTIME => {
             let stream = synth.synthesize_streamed(text, audio_output_config, 72, 3)?;
+            // Every chunk is converted at the same scale, so that the stream keeps its level
+            let sample_converter = stream.sample_converter().clone();
+            let stream = stream.map(move |wr| {
+                wr.and_then(|samples| Ok(samples.as_wave_bytes_with(Some(&sample_converter))?))
+            });
             iterate_stream(stream, params.callback)
         }
         _ => Err(SonataFFIError::invalid_synthesis_mode())
@@ -414,13 +420,12 @@ fn _do_synthesize(
 
 #[inline(always)]
 fn iterate_stream(
-    stream: impl Iterator<Item = SonataResult<AudioSamples>> + Send + Sync + 'static,
+    stream: impl Iterator<Item = SonataResult<Vec<u8>>> + Send + Sync + 'static,
     callback: SpeechSynthesisCallback,
 ) -> SonataFFIResult<()> {
     for result in stream {
         match result {
-            Ok(audio) => {
-                let wav_bytes = audio.as_wave_bytes();
+            Ok(wav_bytes) => {
                 let event = SynthesisEvent::with_speech(wav_bytes);
                 if callback(event) != 0 {
                     return Ok(());
@@ -2,7 +2,7 @@ use clap::Parser;
 use serde::Deserialize;
 use sonata_piper::PiperSynthesisConfig;
 use sonata_synth::{
-    AudioOutputConfig, AudioSamples, SonataModel, SonataResult, SonataSpeechSynthesizer,
+    AudioOutputConfig, SonataModel, SonataResult, SonataSpeechSynthesizer,
 };
 use std::fs::File;
 use std::io::{self, prelude::*};
@@ -67,12 +67,39 @@ struct Cli {
     /// Extra silence (in milliseconds) to append to the end of each sentence (default `0`)
     #[arg(long)]
     silence: Option<u32>,
+    /// Exact number of silent frames to append to the end of each sentence, after all other effects
+    #[arg(long)]
+    silence_frames: Option<u32>,
+    /// Cutoff frequency (in Hz) of a high-pass filter to remove low-frequency rumble, e.g. `80`
+    #[arg(long)]
+    highpass: Option<u32>,
+    /// Turn down sibilance ("s" sounds) louder than this level (in dBFS), e.g. `-30`
+    #[arg(long, allow_negative_numbers = true)]
+    de_esser: Option<f32>,
+    /// Resample the output to this rate (in Hz). Defaults to the model's native rate
+    #[arg(long)]
+    sample_rate: Option<u32>,
+    /// Pad each sentence with silence so that its number of frames is a multiple of this block size
+    #[arg(long)]
+    block_frames: Option<u32>,
+    /// Fade in the first milliseconds of each sentence to suppress an initial pop (default `5` when given without a value)
+    #[arg(long, value_name = "MS", num_args = 0..=1, default_missing_value = "5")]
+    quiet_start: Option<u32>,
+    /// Number of output channels. `1` downmixes multi-channel models to mono
+    #[arg(long)]
+    channels: Option<u16>,
+    /// Gain (in dB) applied to the speech, e.g. `-6`
+    #[arg(long, allow_negative_numbers = true)]
+    gain_db: Option<f32>,
     /// Number of mel frames to stream for each chunk
     #[arg(long)]
     chunk_size: Option<usize>,
     /// Number of mel frames to use for padding current chunk (improves naturalness)
     #[arg(long)]
     chunk_padding: Option<usize>,
+    /// Log the synthesis counters (sentences, seconds of audio, inference time) after each request
+    #[arg(long)]
+    stats: bool,
 }
 
 #[derive(Deserialize, Default)]
@@ -87,6 +114,14 @@ struct SynthesisRequest {
     pitch: Option<u8>,
     volume: Option<u8>,
     appended_silence_ms: Option<u32>,
+    appended_silence_frames: Option<u32>,
+    highpass_cutoff_hz: Option<u32>,
+    de_esser_threshold_db: Option<f32>,
+    sample_rate: Option<u32>,
+    block_frames: Option<u32>,
+    quiet_start_ms: Option<u32>,
+    channels: Option<u16>,
+    gain_db: Option<f32>,
     chunk_size: Option<usize>,
     chunk_padding: Option<usize>,
 }
@@ -106,6 +141,16 @@ impl SynthesisRequest {
             pitch: self.pitch,
             volume: self.volume,
             appended_silence_ms: self.appended_silence_ms,
+            appended_silence_frames: self.appended_silence_frames,
+            highpass_cutoff_hz: self.highpass_cutoff_hz,
+            de_esser_threshold_db: self.de_esser_threshold_db,
+            de_esser_frequency_hz: None,
+            sample_rate: self.sample_rate,
+            block_frames: self.block_frames,
+            autotune: None,
+            quiet_start_ms: self.quiet_start_ms,
+            channels: self.channels,
+            gain_db: self.gain_db,
         }
     }
 }
@@ -131,6 +176,11 @@ fn process_synthesis_request(
 ) -> anyhow::Result<()> {
     synth.set_fallback_synthesis_config(&req.as_piper_synth_config(default_synth_config))?;
     let output_config = Some(req.as_audio_output_config());
+    log::debug!(
+        "Output sample rate: {} Hz (model native rate: {} Hz)",
+        synth.output_sample_rate(output_config.as_ref())?,
+        synth.native_sample_rate()?
+    );
     if let Some(output_file) = args.output_file.as_ref() {
         if req.mode.is_some() {
             log::warn!("Synthesis mode has no effect when output-file is set");
@@ -142,13 +192,13 @@ fn process_synthesis_request(
         SynthesisMode::Lazy => {
             let stream = synth
                 .synthesize_lazy(req.text, output_config)?
-                .map(|res| res.map(|aud| aud.samples));
+                .map(|res| res.map(|aud| aud.as_wave_bytes()));
             consume_stream(stream)?
         }
         SynthesisMode::Parallel => {
             let stream = synth
                 .synthesize_parallel(req.text, output_config)?
-                .map(|res| res.map(|aud| aud.samples));
+                .map(|res| res.map(|aud| aud.as_wave_bytes()));
             consume_stream(stream)?
         }
         SynthesisMode::Realtime => {
@@ -158,12 +208,27 @@ fn process_synthesis_request(
                 req.chunk_size.unwrap_or(100),
                 req.chunk_padding.unwrap_or(3),
             )?;
+            // Every chunk is converted at the same scale, so that the stream keeps its level
+            let sample_converter = stream.sample_converter().clone();
+            let stream = stream.map(move |res| {
+                res.and_then(|samples| Ok(samples.as_wave_bytes_with(Some(&sample_converter))?))
+            });
             consume_stream(stream)?
         }
     };
     Ok(())
 }
 
+fn log_synthesis_stats() {
+    let stats = sonata_synth::stats();
+    log::info!(
+        "Synthesized {} sentences, {:.2} seconds of audio in {:.0} ms of inference",
+        stats.num_syntheses,
+        stats.audio_seconds,
+        stats.inference_ms
+    );
+}
+
 fn write_to_stdout(data: &[u8]) -> anyhow::Result<()> {
     let mut stdout = io::stdout().lock();
     stdout.write_all(data)?;
@@ -172,10 +237,9 @@ fn write_to_stdout(data: &[u8]) -> anyhow::Result<()> {
 }
 
 #[inline(always)]
-fn consume_stream(stream: impl Iterator<Item = SonataResult<AudioSamples>>) -> anyhow::Result<()> {
+fn consume_stream(stream: impl Iterator<Item = SonataResult<Vec<u8>>>) -> anyhow::Result<()> {
     for result in stream {
-        let audio = result?;
-        let wav_bytes = audio.as_wave_bytes();
+        let wav_bytes = result?;
         write_to_stdout(&wav_bytes)?;
     }
     Ok(())
@@ -226,10 +290,21 @@ fn main() -> anyhow::Result<()> {
             volume: args.volume,
             pitch: args.pitch,
             appended_silence_ms: args.silence,
+            appended_silence_frames: args.silence_frames,
+            highpass_cutoff_hz: args.highpass,
+            de_esser_threshold_db: args.de_esser,
+            sample_rate: args.sample_rate,
+            block_frames: args.block_frames,
+            quiet_start_ms: args.quiet_start,
+            channels: args.channels,
+            gain_db: args.gain_db,
             chunk_size: args.chunk_size,
             chunk_padding: args.chunk_padding,
         };
         process_synthesis_request(&args, &synth, &default_synth_config, req)?;
+        if args.stats {
+            log_synthesis_stats();
+        }
     } else {
         for i in 0.. {
             args.output_file = args.output_file.map(|file| {
@@ -251,6 +326,9 @@ fn main() -> anyhow::Result<()> {
                     if let Some(ref file) = args.output_file {
                         log::info!("Wrote output to file: {}", file.display());
                     }
+                    if args.stats {
+                        log_synthesis_stats();
+                    }
                 }
                 Err(e) => log::error!("Invalid json input. Error: {}", e.to_string()),
             };
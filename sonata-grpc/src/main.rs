@@ -62,7 +62,11 @@ struct Voice(Arc<SonataSpeechSynthesizer>);
 
 impl Voice {
     fn new(model: Arc<dyn SonataModel + Send + Sync>) -> SonataResult<Self> {
-        let synth = Arc::new(SonataSpeechSynthesizer::new(model)?);
+        let synth = Arc::new(
+            SonataSpeechSynthesizer::builder(model)
+                .with_overrides_from_env()
+                .build()?,
+        );
         Ok(Self(synth))
     }
     fn model_ref(&self) -> &dyn SonataModel {
@@ -328,6 +332,7 @@ impl SonataGrpc for SonataGrpcService {
             volume: args.volume.map(|i| i as u8),
             pitch: args.pitch.map(|i| i as u8),
             appended_silence_ms: args.appended_silence_ms,
+            ..Default::default()
         });
         let sonata_stream =
             self._create_speech_synthesis_stream(&req.voice_id, req.text, output_config)?;
@@ -364,6 +369,7 @@ impl SonataGrpc for SonataGrpcService {
             volume: args.volume.map(|i| i as u8),
             pitch: args.pitch.map(|i| i as u8),
             appended_silence_ms: args.appended_silence_ms,
+            ..Default::default()
         });
         let voice_id = &req.voice_id;
         let voices = self.0.read().unwrap();
@@ -389,18 +395,21 @@ impl SonataGrpc for SonataGrpcService {
                     return;
                 }
             };
+            // Every chunk is converted at the same scale, so that the stream keeps its level
+            let sample_converter = realtime_speech_stream.sample_converter().clone();
             for wav_result in realtime_speech_stream {
-                let wav = match wav_result {
-                    Ok(wav) => wav,
+                let wav_samples = wav_result.and_then(|wav| {
+                    Ok(wav.as_wave_bytes_with(Some(&sample_converter))?)
+                });
+                let wav_samples = match wav_samples {
+                    Ok(wav_samples) => wav_samples,
                     Err(e) => {
                         let err = Err(SonataGrpcError::from(e).into());
                         tx.blocking_send(err).ok();
                         return;
                     }
                 };
-                let synth_result = grpc::WaveSamples {
-                    wav_samples: wav.as_wave_bytes(),
-                };
+                let synth_result = grpc::WaveSamples { wav_samples };
                 if tx.blocking_send(Ok(synth_result)).is_err() {
                     return;
                 }
@@ -408,6 +417,25 @@ impl SonataGrpc for SonataGrpcService {
         });
         Ok(Response::new(ReceiverStream::new(rx)))
     }
+    async fn get_synthesis_stats(
+        &self,
+        _request: Request<grpc::Empty>,
+    ) -> Result<Response<grpc::SynthesisStats>, Status> {
+        Ok(Response::new(sonata_synth::stats().into()))
+    }
+}
+
+impl From<sonata_synth::SynthesisStats> for grpc::SynthesisStats {
+    fn from(other: sonata_synth::SynthesisStats) -> Self {
+        Self {
+            num_syntheses: other.num_syntheses,
+            audio_seconds: other.audio_seconds,
+            inference_ms: other.inference_ms,
+            cache_hits: other.cache_hits,
+            cache_misses: other.cache_misses,
+            cache_hit_rate: other.cache_hit_rate(),
+        }
+    }
 }
 
 fn setup_logging() {
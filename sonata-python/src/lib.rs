@@ -1,21 +1,28 @@
-use sonata_core::{SonataError, SonataModel, Audio, AudioInfo};
+use sonata_core::{SonataError, SonataModel, AlignmentFormat, Audio, VisemeSet, AudioInfo, AudioSamples, CancellationToken, ClippingStats, NoiseTensor, PhonemeDurations, RawOutput};
 use sonata_synth::{
-    AudioOutputConfig, SonataSpeechStreamLazy, SonataSpeechStreamParallel,
-    SonataSpeechSynthesizer, RealtimeSpeechStream
+    AudioOutputConfig, MarkdownStripping, ParallelSynthesisOptions, PunctuationPauses, SentenceEvent, SonataSpeechStreamLazy, SonataSpeechStreamParallel,
+    SonataSpeechSynthesizer, RealtimeSpeechStream, SseAudioFormat, SseStream, StreamStats, StreamingConfig, SynthesisStats, TextPreprocessing, TrailingPunctuation, UnicodeNormalization
 };
-use sonata_piper::PiperSynthesisConfig;
-use libtashkeel_base::{LibtashkeelResult, DynamicInferenceEngine as TashkeelInferenceEngine, do_tashkeel};
+use sonata_piper::{ExecutionProvider, LanguageDetection, LoadOptions, PiperSynthesisConfig, VoiceCheck, VoiceStatus};
+use libtashkeel_base::{DynamicInferenceEngine as TashkeelInferenceEngine, do_tashkeel};
 use once_cell::sync::Lazy;
 use pyo3::create_exception;
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
-use pyo3::types::PyBytes;
+use pyo3::types::{PyBytes, PyDict, PyType};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
 
-static LIBTASHKEEL_ENGINE: Lazy<LibtashkeelResult<TashkeelInferenceEngine>>=
-    Lazy::new(|| libtashkeel_base::create_inference_engine(None));
+static LIBTASHKEEL_ENGINE: Lazy<Result<TashkeelInferenceEngine, SonataError>>=
+    Lazy::new(sonata_piper::new_tashkeel_engine);
+/// Config path and weak reference of a model created from Python
+type LoadedModel = (String, Weak<dyn SonataModel + Send + Sync>);
+/// Weak references to every model and synthesizer created from Python, used by `loaded_models()`
+static LOADED_MODELS: Lazy<Mutex<Vec<LoadedModel>>> =
+    Lazy::new(Default::default);
+static LOADED_SYNTHESIZERS: Lazy<Mutex<Vec<Weak<SonataSpeechSynthesizer>>>> =
+    Lazy::new(Default::default);
 type PySonataResult<T> = Result<T, PySonataError>;
 
 create_exception!(
@@ -74,19 +81,44 @@ struct PyAudioOutputConfig(AudioOutputConfig);
 #[pymethods]
 impl PyAudioOutputConfig {
     #[new]
+    #[allow(clippy::too_many_arguments)]
     fn new(
         rate: Option<u8>,
         volume: Option<u8>,
         pitch: Option<u8>,
         appended_silence_ms: Option<u32>,
+        highpass_cutoff_hz: Option<u32>,
+        sample_rate: Option<u32>,
+        appended_silence_frames: Option<u32>,
+        de_esser_threshold_db: Option<f32>,
+        de_esser_frequency_hz: Option<u32>,
+        block_frames: Option<u32>,
+        quiet_start_ms: Option<u32>,
+        channels: Option<u16>,
+        gain_db: Option<f32>,
     ) -> Self {
         Self(AudioOutputConfig {
             rate,
             volume,
             pitch,
             appended_silence_ms,
+            highpass_cutoff_hz,
+            sample_rate,
+            appended_silence_frames,
+            de_esser_threshold_db,
+            de_esser_frequency_hz,
+            block_frames,
+            autotune: None,
+            quiet_start_ms,
+            channels,
+            gain_db,
         })
     }
+    /// Parse `key=value` pairs separated by `;`, e.g. `"rate=slow;pitch=+2;volume=80"`
+    #[classmethod]
+    fn from_str(_cls: &PyType, config: &str) -> PySonataResult<Self> {
+        Ok(Self(config.parse()?))
+    }
 }
 
 impl From<PyAudioOutputConfig> for AudioOutputConfig {
@@ -107,6 +139,23 @@ impl WaveSamples {
     fn save_to_file(&self, filename: &str) -> PySonataResult<()> {
         Ok(self.0.save_to_file(&PathBuf::from(filename)).map_err(SonataError::from)?)
     }
+    fn slice(&self, start_ms: f32, end_ms: f32) -> PySonataResult<Self> {
+        Ok(Self(self.0.slice(start_ms, end_ms).map_err(SonataError::from)?))
+    }
+    /// Split the audio at the pauses of at least `min_silence_ms` that are quieter than `threshold_dbfs`
+    fn split_at_silence(&self, min_silence_ms: u32, threshold_dbfs: f32) -> Vec<Self> {
+        self.0
+            .split_at_silence(min_silence_ms, threshold_dbfs)
+            .into_iter()
+            .map(Self)
+            .collect()
+    }
+    fn time_stretch(&self, factor: f32) -> PySonataResult<Self> {
+        Ok(Self(self.0.time_stretch(factor).map_err(SonataError::from)?))
+    }
+    fn with_bit_depth(&self, bit_depth: u16) -> PySonataResult<Self> {
+        Ok(Self(self.0.clone().with_bit_depth(bit_depth).map_err(SonataError::from)?))
+    }
     #[getter]
     fn sample_rate(&self) -> usize {
         self.0.info.sample_rate
@@ -131,6 +180,131 @@ impl WaveSamples {
     fn real_time_factor(&self) -> Option<f32> {
         self.0.real_time_factor()
     }
+    /// `inference_ms`, `duration_ms`, `real_time_factor` and `num_samples` in one dict,
+    /// e.g. for logging. The inference values are `None` when they aren't known.
+    #[getter]
+    fn metrics<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let metrics = PyDict::new(py);
+        metrics.set_item("inference_ms", self.0.inference_ms())?;
+        metrics.set_item("duration_ms", self.0.duration_ms())?;
+        metrics.set_item("real_time_factor", self.0.real_time_factor())?;
+        metrics.set_item("num_samples", self.0.len())?;
+        Ok(metrics)
+    }
+    #[getter]
+    fn sentence_index(&self) -> Option<usize> {
+        self.0.sentence.as_ref().map(|sentence| sentence.index)
+    }
+    #[getter]
+    fn sentence_text(&self) -> Option<String> {
+        self.0.sentence.as_ref().map(|sentence| sentence.text.clone())
+    }
+    /// Whether the model output was implausibly short, so the end of the speech might be cut off
+    #[getter]
+    fn truncation_suspected(&self) -> bool {
+        self.0.truncation_suspected
+    }
+    fn clipping_stats(&self) -> PyClippingStats {
+        self.0.clipping_stats().into()
+    }
+    /// The loudness of the speech in dBFS, ignoring pauses, or `None` if the audio is silent
+    fn loudness_db(&self) -> Option<f32> {
+        self.0.loudness_db()
+    }
+    /// Export the phoneme alignment as `textgrid`, `srt` or `vtt`
+    fn export_alignment(&self, format: &str) -> PySonataResult<String> {
+        let format: AlignmentFormat = format.parse().map_err(SonataError::OperationError)?;
+        Ok(self.0.export_alignment(format).map_err(SonataError::from)?)
+    }
+    /// The visemes of the given set (`oculus` or `preston_blair`) as `(viseme, start_ms, end_ms)`
+    fn visemes(&self, viseme_set: &str) -> PySonataResult<Vec<(&'static str, f32, f32)>> {
+        let viseme_set: VisemeSet = viseme_set.parse().map_err(SonataError::OperationError)?;
+        let visemes = self.0.visemes(viseme_set).map_err(SonataError::from)?;
+        Ok(visemes
+            .into_iter()
+            .map(|timing| (timing.viseme, timing.start_ms, timing.end_ms))
+            .collect())
+    }
+}
+
+#[pyclass(module = "piper", frozen)]
+#[pyo3(name = "ClippingStats")]
+struct PyClippingStats {
+    #[pyo3(get)]
+    clipped_samples: usize,
+    #[pyo3(get)]
+    total_samples: usize,
+    #[pyo3(get)]
+    clipped_percentage: f32,
+    #[pyo3(get)]
+    peak: f32,
+}
+
+impl From<ClippingStats> for PyClippingStats {
+    fn from(other: ClippingStats) -> Self {
+        Self {
+            clipped_samples: other.clipped_samples,
+            total_samples: other.total_samples,
+            clipped_percentage: other.clipped_percentage(),
+            peak: other.peak,
+        }
+    }
+}
+
+/// An output tensor of the model, as returned by `PiperModel.infer_raw`
+#[pyclass(module = "piper", frozen)]
+#[pyo3(name = "RawOutput")]
+struct PyRawOutput {
+    #[pyo3(get)]
+    name: String,
+    #[pyo3(get)]
+    shape: Vec<usize>,
+    #[pyo3(get)]
+    data: Vec<f32>,
+}
+
+impl From<RawOutput> for PyRawOutput {
+    fn from(other: RawOutput) -> Self {
+        Self {
+            name: other.name,
+            shape: other.shape,
+            data: other.data,
+        }
+    }
+}
+
+/// The duration of each symbol of a sentence, as returned by `Sonata.predict_durations`.
+/// Edit `durations_ms` and pass it to `Sonata.synthesize_with_durations`.
+#[pyclass(module = "piper")]
+#[pyo3(name = "PhonemeDurations")]
+#[derive(Clone)]
+struct PyPhonemeDurations {
+    #[pyo3(get)]
+    phonemes: String,
+    #[pyo3(get)]
+    symbols: Vec<String>,
+    #[pyo3(get, set)]
+    durations_ms: Vec<f32>,
+}
+
+impl From<PhonemeDurations> for PyPhonemeDurations {
+    fn from(other: PhonemeDurations) -> Self {
+        Self {
+            phonemes: other.phonemes,
+            symbols: other.symbols,
+            durations_ms: other.durations_ms,
+        }
+    }
+}
+
+impl From<PyPhonemeDurations> for PhonemeDurations {
+    fn from(other: PyPhonemeDurations) -> Self {
+        Self {
+            phonemes: other.phonemes,
+            symbols: other.symbols,
+            durations_ms: other.durations_ms,
+        }
+    }
 }
 
 #[pyclass(weakref, module = "piper")]
@@ -148,6 +322,11 @@ impl LazySpeechStream {
         slf
     }
 
+    /// The number of clips left, e.g. for showing progress
+    fn __length_hint__(&self) -> usize {
+        self.0.len()
+    }
+
     fn __next__(&mut self, py: Python) -> Option<WaveSamples> {
         let next_item = py.allow_threads(|| self.0.next());
         let audio_result = match next_item {
@@ -179,6 +358,11 @@ impl ParallelSpeechStream {
         slf
     }
 
+    /// The number of clips left, e.g. for showing progress
+    fn __length_hint__(&self) -> usize {
+        self.0.len()
+    }
+
     fn __next__(&mut self, py: Python) -> Option<WaveSamples> {
         let next_item = py.allow_threads(|| self.0.next());
         let audio_result = match next_item {
@@ -195,8 +379,69 @@ impl ParallelSpeechStream {
     }
 }
 
+#[pyclass(module = "piper", frozen)]
+#[pyo3(name = "StreamStats")]
+struct PyStreamStats {
+    #[pyo3(get)]
+    chunk_size: usize,
+    #[pyo3(get)]
+    chunk_padding: usize,
+    #[pyo3(get)]
+    produced_chunks: u64,
+    #[pyo3(get)]
+    consumed_chunks: u64,
+    #[pyo3(get)]
+    buffered_frames: u64,
+}
+
+impl From<StreamStats> for PyStreamStats {
+    fn from(other: StreamStats) -> Self {
+        Self {
+            chunk_size: other.chunk_size,
+            chunk_padding: other.chunk_padding,
+            produced_chunks: other.produced_chunks,
+            consumed_chunks: other.consumed_chunks,
+            buffered_frames: other.buffered_frames,
+        }
+    }
+}
+
+/// Yields the server-sent event frames of a speech stream, as strings
+#[pyclass(weakref, module = "piper")]
+#[pyo3(name = "SseStream")]
+struct PySseStream(SseStream);
+
+#[pymethods]
+impl PySseStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> Option<String> {
+        py.allow_threads(|| self.0.next())
+    }
+}
+
 #[pyclass(weakref, module = "piper")]
-struct PyRealtimeSpeechStream(RealtimeSpeechStream);
+struct PyRealtimeSpeechStream {
+    stream: RealtimeSpeechStream,
+    /// The chunks received so far, when the full clip is kept
+    full_clip: Option<Audio>,
+}
+
+impl PyRealtimeSpeechStream {
+    fn next_samples(&mut self, py: Python) -> Option<PySonataResult<AudioSamples>> {
+        let result = py.allow_threads(|| self.stream.next())?;
+        Some(result.map_err(PySonataError::from).inspect(|samples| {
+            if let Some(ref mut full_clip) = self.full_clip {
+                full_clip
+                    .samples
+                    .as_mut_vec()
+                    .extend_from_slice(samples.as_slice());
+            }
+        }))
+    }
+}
 
 #[pymethods]
 impl PyRealtimeSpeechStream {
@@ -205,15 +450,58 @@ impl PyRealtimeSpeechStream {
     }
 
     fn __next__(&mut self, py: Python) -> Option<PyObject> {
-        let result = py.allow_threads(|| self.0.next())?;
-        match result {
-            Ok(samples) => Some(PyBytes::new(py, &samples.as_wave_bytes()).into()),
+        match self.next_samples(py)? {
+            Ok(samples) => {
+                let wave_bytes = samples.as_wave_bytes_with(Some(self.stream.sample_converter()));
+                self.stream.recycle(samples);
+                match wave_bytes {
+                    Ok(wave_bytes) => Some(PyBytes::new(py, &wave_bytes).into()),
+                    Err(e) => {
+                        PyErr::from(PySonataError::from(SonataError::from(e))).restore(py);
+                        None
+                    }
+                }
+            }
             Err(e) => {
-                PyErr::from(PySonataError::from(e)).restore(py);
+                PyErr::from(e).restore(py);
                 None
             }
         }
     }
+
+    /// Diagnostics of the chunk scheduling of the stream so far, for tuning its latency
+    fn stats(&self) -> PyStreamStats {
+        self.stream.stats().into()
+    }
+
+    /// Consume the rest of the stream and return the whole clip, including the chunks
+    /// already yielded. Requires the stream to be created with `keep_full_clip=True`.
+    fn full_clip(&mut self, py: Python) -> PySonataResult<WaveSamples> {
+        if self.full_clip.is_none() {
+            return Err(SonataError::OperationError(
+                "The full clip wasn't kept. Pass `keep_full_clip=True` when creating the stream"
+                    .to_string(),
+            )
+            .into());
+        }
+        while let Some(result) = self.next_samples(py) {
+            self.stream.recycle(result?);
+        }
+        let full_clip = self.full_clip.as_mut().unwrap();
+        let samples = full_clip.samples.take();
+        let mut clip = full_clip.clone();
+        clip.samples = samples.into();
+        Ok(WaveSamples(clip))
+    }
+
+    /// Change the output config of the chunks that are yet to be synthesized.
+    /// Chunks already synthesized keep the previous config, and the sample rate can't change.
+    fn set_output_config(&self, audio_output_config: Option<PyAudioOutputConfig>) -> PySonataResult<()> {
+        Ok(self
+            .stream
+            .config_handle()
+            .update(audio_output_config.map(|o| o.into()))?)
+    }
 }
 
 #[pyclass(weakref, module = "piper")]
@@ -238,36 +526,65 @@ impl PiperScales {
     }
 }
 
-#[pyclass(weakref, module = "piper")]
-#[pyo3(name = "PiperModel")]
-struct PiperModel(Arc<dyn SonataModel + Send + Sync>);
+/// Cancels an in-progress operation, such as loading a model, when `cancel()` is called
+#[pyclass(weakref, module = "piper", frozen)]
+#[pyo3(name = "CancellationToken")]
+#[derive(Clone)]
+struct PyCancellationToken(CancellationToken);
 
 #[pymethods]
-impl PiperModel {
+impl PyCancellationToken {
     #[new]
-    fn new(config_path: &str) -> PySonataResult<Self> {
-        let vits =
-            sonata_piper::from_config_path(&PathBuf::from(config_path))?;
-        Ok(Self(vits))
+    fn new() -> Self {
+        Self(CancellationToken::new())
+    }
+    fn cancel(&self) {
+        self.0.cancel()
     }
     #[getter]
-    fn get_speaker(&self) -> PySonataResult<Option<String>> {
-        match self
-            .0
-            .get_fallback_synthesis_config()?
-            .downcast_ref::<PiperSynthesisConfig>()
-        {
-            Some(synth_config) => match synth_config.speaker {
-                Some(sid) => Ok(self.0.speaker_id_to_name(&sid)?),
-                None => Ok(None),
-            },
+    fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
+
+/// A speaker given by id, by name, or by (name, index) when several speakers share a name
+#[derive(FromPyObject)]
+enum SpeakerSelector {
+    Id(i64),
+    Name(String),
+    NameIndex(String, usize),
+}
+
+/// The name of the speaker in the model's fallback synthesis config
+fn current_speaker(model: &dyn SonataModel) -> PySonataResult<Option<String>> {
+    match model
+        .get_fallback_synthesis_config()?
+        .downcast_ref::<PiperSynthesisConfig>()
+    {
+        Some(synth_config) => match synth_config.speaker {
+            Some(sid) => Ok(model.speaker_id_to_name(&sid)?),
             None => Ok(None),
-        }
+        },
+        None => Ok(None),
     }
-    #[setter]
-    fn set_speaker(&self, name: String) -> PySonataResult<()> {
-        let sid = match self.0.speaker_name_to_id(&name)? {
-            Some(sname) => sname,
+}
+
+/// Resolve the speaker with `model`'s speaker names and set it in the model's fallback
+/// synthesis config
+fn select_speaker(model: &dyn SonataModel, speaker: SpeakerSelector) -> PySonataResult<()> {
+    let sid = match speaker {
+        SpeakerSelector::Id(sid) => match model.speaker_id_to_name(&sid)? {
+            Some(_) => sid,
+            None => {
+                return Err(SonataError::OperationError(format!(
+                    "A speaker with the given id `{}` was not found",
+                    sid
+                ))
+                .into())
+            }
+        },
+        SpeakerSelector::Name(name) => match model.speaker_name_to_id(&name)? {
+            Some(sid) => sid,
             None => {
                 return Err(SonataError::OperationError(format!(
                     "A speaker with the given name `{}` was not found",
@@ -275,20 +592,102 @@ impl PiperModel {
                 ))
                 .into())
             }
-        };
-        match self
-            .0
-            .get_fallback_synthesis_config()?
-            .downcast::<PiperSynthesisConfig>()
-        {
-            Ok(mut synth_config) => {
-                synth_config.speaker = Some(sid);
-                Ok(self.0.set_fallback_synthesis_config(&synth_config)?)
-            }
-            Err(_) => {
-                Err(SonataError::OperationError("Cannot set synthesis config".to_string()).into())
+        },
+        SpeakerSelector::NameIndex(name, index) => {
+            match model.speaker_name_index_to_id(&name, index)? {
+                Some(sid) => sid,
+                None => {
+                    return Err(SonataError::OperationError(format!(
+                        "No speaker at index `{}` among the speakers named `{}`",
+                        index, name
+                    ))
+                    .into())
+                }
             }
         }
+    };
+    match model
+        .get_fallback_synthesis_config()?
+        .downcast::<PiperSynthesisConfig>()
+    {
+        Ok(mut synth_config) => {
+            synth_config.speaker = Some(sid);
+            Ok(model.set_fallback_synthesis_config(&synth_config)?)
+        }
+        Err(_) => {
+            Err(SonataError::OperationError("Cannot set synthesis config".to_string()).into())
+        }
+    }
+}
+
+#[pyclass(weakref, module = "piper")]
+#[pyo3(name = "PiperModel")]
+struct PiperModel(Arc<dyn SonataModel + Send + Sync>);
+
+#[pymethods]
+impl PiperModel {
+    /// `on_progress` is called with a description of each load stage.
+    /// Loading stops before the next stage once `cancellation_token` is cancelled.
+    /// `execution_providers` (e.g. `["cuda", "cpu"]`) defaults to the ones given to
+    /// `set_default_execution_providers`. With `retry_truncated_output`, inference is run
+    /// again when its output looks cut off. With `language_detection_threshold`, the espeak
+    /// voice of each sentence is picked by detecting its language, falling back to the
+    /// model's default voice when the confidence (between 0 and 1) is below the threshold.
+    #[new]
+    fn new(
+        py: Python,
+        config_path: &str,
+        on_progress: Option<PyObject>,
+        cancellation_token: Option<PyCancellationToken>,
+        execution_providers: Option<Vec<String>>,
+        retry_truncated_output: Option<bool>,
+        language_detection_threshold: Option<f32>,
+    ) -> PySonataResult<Self> {
+        let execution_providers = execution_providers
+            .map(|providers| parse_execution_providers(&providers))
+            .transpose()?;
+        let options = LoadOptions {
+            on_progress: on_progress.map(|callback| {
+                Box::new(move |stage: &sonata_piper::LoadStage| {
+                    Python::with_gil(|py| {
+                        if let Err(e) = callback.call1(py, (stage.to_string(),)) {
+                            e.print(py);
+                        }
+                    })
+                }) as sonata_piper::LoadProgressCallback
+            }),
+            cancellation_token: cancellation_token.map(|token| token.0),
+            execution_providers,
+            retry_truncated_output: retry_truncated_output.unwrap_or_default(),
+            language_detection: language_detection_threshold
+                .map(|min_confidence| LanguageDetection { min_confidence }),
+        };
+        let config_path = PathBuf::from(config_path);
+        let vits = py.allow_threads(|| {
+            sonata_piper::from_config_path_with_options(&config_path, &options)
+        })?;
+        LOADED_MODELS
+            .lock()
+            .unwrap()
+            .push((config_path.display().to_string(), Arc::downgrade(&vits)));
+        Ok(Self(vits))
+    }
+    #[getter]
+    fn get_speaker(&self) -> PySonataResult<Option<String>> {
+        current_speaker(self.0.as_ref())
+    }
+    #[setter]
+    fn set_speaker(&self, speaker: SpeakerSelector) -> PySonataResult<()> {
+        select_speaker(self.0.as_ref(), speaker)
+    }
+    /// Approximate memory used by the model in bytes, estimated from the size of its ONNX files
+    fn estimated_memory_bytes(&self) -> Option<u64> {
+        self.0.estimated_memory_bytes()
+    }
+    /// The decoder output tensors for `phonemes`, before conversion and effects (for debugging)
+    fn infer_raw(&self, py: Python, phonemes: String) -> PySonataResult<Vec<PyRawOutput>> {
+        let outputs = py.allow_threads(|| self.0.infer_raw(phonemes))?;
+        Ok(outputs.into_iter().map(PyRawOutput::from).collect())
     }
     fn get_scales(&self) -> PySonataResult<PiperScales> {
         match self
@@ -331,9 +730,28 @@ struct Sonata(Arc<SonataSpeechSynthesizer>);
 #[pymethods]
 impl Sonata {
     #[staticmethod]
-    fn with_piper(vits_model: &PiperModel) -> PySonataResult<Self> {
+    fn with_piper(
+        vits_model: &PiperModel,
+        overrides_path: Option<String>,
+        stable: Option<bool>,
+        speaker_names_path: Option<String>,
+    ) -> PySonataResult<Self> {
         let model = Arc::clone(&vits_model.0);
-        let synthesizer = Arc::new(SonataSpeechSynthesizer::new(model)?);
+        let mut builder = SonataSpeechSynthesizer::builder(model);
+        if let Some(path) = speaker_names_path {
+            builder = builder.with_speaker_names(path);
+        }
+        if stable.unwrap_or_default() {
+            builder = builder.with_stable_preset();
+        }
+        if let Some(path) = overrides_path {
+            builder = builder.with_overrides(path);
+        }
+        let synthesizer = Arc::new(builder.build()?);
+        LOADED_SYNTHESIZERS
+            .lock()
+            .unwrap()
+            .push(Arc::downgrade(&synthesizer));
         Ok(Self(synthesizer))
     }
     fn synthesize(
@@ -359,27 +777,139 @@ impl Sonata {
         &self,
         text: String,
         audio_output_config: Option<PyAudioOutputConfig>,
+        include_sentence_info: Option<bool>,
+        max_concurrency: Option<usize>,
+        synchronous: Option<bool>,
+    ) -> PySonataResult<ParallelSpeechStream> {
+        let options = ParallelSynthesisOptions {
+            include_sentence_info: include_sentence_info.unwrap_or_default(),
+            max_concurrency,
+            synchronous: synchronous.unwrap_or_default(),
+            ..Default::default()
+        };
+        Ok(self
+            .0
+            .synthesize_parallel_with_options(text, audio_output_config.map(|o| o.into()), options)?
+            .into())
+    }
+
+    /// Synthesize sentences that were already split, one clip per sentence, without splitting them again
+    fn synthesize_from_sentences(
+        &self,
+        sentences: Vec<String>,
+        audio_output_config: Option<PyAudioOutputConfig>,
+        max_concurrency: Option<usize>,
+        synchronous: Option<bool>,
     ) -> PySonataResult<ParallelSpeechStream> {
+        let options = ParallelSynthesisOptions {
+            max_concurrency,
+            synchronous: synchronous.unwrap_or_default(),
+            ..Default::default()
+        };
         Ok(self
             .0
-            .synthesize_parallel(text, audio_output_config.map(|o| o.into()))?
+            .synthesize_from_sentences(sentences, audio_output_config.map(|o| o.into()), options)?
             .into())
     }
 
+    /// Stream sentences that were already split, each as one unit, without splitting them again
+    fn synthesize_streamed_from_sentences(
+        &self,
+        sentences: Vec<String>,
+        audio_output_config: Option<PyAudioOutputConfig>,
+        chunk_size: Option<usize>,
+        chunk_padding: Option<usize>,
+    ) -> PySonataResult<PyRealtimeSpeechStream> {
+        let streaming_config = StreamingConfig {
+            chunk_size: chunk_size.unwrap_or(45),
+            chunk_padding: chunk_padding.unwrap_or(3),
+            ..Default::default()
+        };
+        let stream = self.0.synthesize_streamed_from_sentences(
+            sentences,
+            audio_output_config.map(|o| o.into()),
+            streaming_config,
+        )?;
+        Ok(PyRealtimeSpeechStream {
+            stream,
+            full_clip: None,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn synthesize_streamed(
         &self,
         text: String,
         audio_output_config: Option<PyAudioOutputConfig>,
         chunk_size: Option<usize>,
         chunk_padding: Option<usize>,
+        low_memory: Option<bool>,
+        keep_full_clip: Option<bool>,
+        fade_in_ms: Option<u32>,
+        incremental: Option<bool>,
     ) -> PySonataResult<PyRealtimeSpeechStream> {
-        let stream = self.0.synthesize_streamed(
+        let streaming_config = StreamingConfig {
+            chunk_size: chunk_size.unwrap_or(45),
+            chunk_padding: chunk_padding.unwrap_or(3),
+            low_memory: low_memory.unwrap_or(false),
+            fade_in_ms,
+            incremental: incremental.unwrap_or(false),
+            ..Default::default()
+        };
+        let audio_output_config: Option<AudioOutputConfig> = audio_output_config.map(|o| o.into());
+        let full_clip = match keep_full_clip {
+            Some(true) => {
+                let info = AudioInfo {
+                    sample_rate: self.0.output_sample_rate(audio_output_config.as_ref())?,
+                    num_channels: self.0.output_num_channels(audio_output_config.as_ref())?,
+                    ..self.0.audio_output_info()?
+                };
+                let mut full_clip = Audio::with_info(Vec::new().into(), info, None);
+                // At the fixed scale of the chunks, so that it has their level
+                full_clip.fixed_scale = true;
+                Some(full_clip)
+            }
+            _ => None,
+        };
+        let stream =
+            self.0
+                .synthesize_streamed_with_config(text, audio_output_config, streaming_config)?;
+        Ok(PyRealtimeSpeechStream { stream, full_clip })
+    }
+
+    /// Stream the speech as server-sent events: a `format` event with the `sample_rate`,
+    /// `channels` and `encoding` as JSON, an `audio` event with the base64 encoded audio of
+    /// each chunk, and a final `end` event. `format` is `"pcm_s16le"` (the default) or `"wav"`.
+    fn synthesize_sse(
+        &self,
+        text: String,
+        audio_output_config: Option<PyAudioOutputConfig>,
+        chunk_size: Option<usize>,
+        chunk_padding: Option<usize>,
+        format: Option<&str>,
+    ) -> PySonataResult<PySseStream> {
+        let format = match format.unwrap_or("pcm_s16le") {
+            "pcm_s16le" => SseAudioFormat::Pcm16,
+            "wav" => SseAudioFormat::Wav,
+            other => {
+                return Err(SonataError::OperationError(format!(
+                    "Invalid SSE audio format `{}`. Expected `pcm_s16le` or `wav`",
+                    other
+                ))
+                .into())
+            }
+        };
+        let streaming_config = StreamingConfig {
+            chunk_size: chunk_size.unwrap_or(45),
+            chunk_padding: chunk_padding.unwrap_or(3),
+            ..Default::default()
+        };
+        Ok(PySseStream(self.0.synthesize_sse(
             text,
             audio_output_config.map(|o| o.into()),
-            chunk_size.unwrap_or(45),
-            chunk_padding.unwrap_or(3),
-        )?;
-        Ok(PyRealtimeSpeechStream(stream))
+            streaming_config,
+            format,
+        )?))
     }
 
     fn synthesize_to_file(
@@ -392,6 +922,274 @@ impl Sonata {
             .synthesize_to_file(&PathBuf::from(filename), text, audio_output_config.map(|o| o.into()))?;
         Ok(())
     }
+    fn synthesize_streamed_to_file(
+        &self,
+        filename: &str,
+        text: String,
+        audio_output_config: Option<PyAudioOutputConfig>,
+        chunk_size: Option<usize>,
+        chunk_padding: Option<usize>,
+    ) -> PySonataResult<()> {
+        let streaming_config = StreamingConfig {
+            chunk_size: chunk_size.unwrap_or(45),
+            chunk_padding: chunk_padding.unwrap_or(3),
+            low_memory: true,
+            ..Default::default()
+        };
+        self.0.synthesize_streamed_to_file(
+            &PathBuf::from(filename),
+            text,
+            audio_output_config.map(|o| o.into()),
+            streaming_config,
+        )?;
+        Ok(())
+    }
+    fn synthesize_sentences_to_files(
+        &self,
+        output_dir: &str,
+        text: String,
+        audio_output_config: Option<PyAudioOutputConfig>,
+    ) -> PySonataResult<Vec<(String, String)>> {
+        let files = self.0.synthesize_sentences_to_files(
+            &PathBuf::from(output_dir),
+            text,
+            audio_output_config.map(|o| o.into()),
+        )?;
+        Ok(files
+            .into_iter()
+            .map(|(path, sentence)| (path.to_string_lossy().into_owned(), sentence))
+            .collect())
+    }
+    /// Synthesize each row of a CSV, JSON or JSON Lines manifest into `<id>.wav` in `output_dir`.
+    /// Returns `(written, failed)`: the `(id, path)` of each file written and the
+    /// `(id, reason)` of each row that failed. With `max_buffered_bytes`, rows run in parallel
+    /// only while their estimated audio fits in that much memory.
+    #[allow(clippy::type_complexity)]
+    fn synthesize_manifest(
+        &self,
+        py: Python,
+        manifest_path: &str,
+        output_dir: &str,
+        audio_output_config: Option<PyAudioOutputConfig>,
+        max_buffered_bytes: Option<usize>,
+    ) -> PySonataResult<(Vec<(String, String)>, Vec<(String, String)>)> {
+        let report = py.allow_threads(|| {
+            self.0.synthesize_manifest_with_memory_budget(
+                &PathBuf::from(manifest_path),
+                &PathBuf::from(output_dir),
+                audio_output_config.map(|o| o.into()),
+                max_buffered_bytes,
+            )
+        })?;
+        let written = report
+            .written
+            .into_iter()
+            .map(|(id, path)| (id, path.to_string_lossy().into_owned()))
+            .collect();
+        Ok((written, report.failed))
+    }
+    /// Estimate the cost of synthesizing `text` without running the model,
+    /// as `(num_phonemes, num_frames)`
+    fn cost_estimate(&self, py: Python, text: &str) -> PySonataResult<(usize, usize)> {
+        let estimate = py.allow_threads(|| self.0.cost_estimate(text))?;
+        Ok((estimate.num_phonemes, estimate.num_frames))
+    }
+    /// Predict the duration of each symbol of each sentence of `text`, without synthesizing it
+    fn predict_durations(&self, py: Python, text: &str) -> PySonataResult<Vec<PyPhonemeDurations>> {
+        let sentences = py.allow_threads(|| self.0.predict_durations(text))?;
+        Ok(sentences.into_iter().map(PyPhonemeDurations::from).collect())
+    }
+    /// Synthesize sentences returned by `predict_durations`, with their (edited) durations
+    fn synthesize_with_durations(
+        &self,
+        py: Python,
+        sentences: Vec<PyPhonemeDurations>,
+        audio_output_config: Option<PyAudioOutputConfig>,
+    ) -> PySonataResult<WaveSamples> {
+        let sentences: Vec<PhonemeDurations> = sentences.into_iter().map(PhonemeDurations::from).collect();
+        let audio = py.allow_threads(|| {
+            self.0
+                .synthesize_with_durations(&sentences, audio_output_config.map(|o| o.into()))
+        })?;
+        Ok(WaveSamples(audio))
+    }
+    /// The `(name, shape)` of each noise input of the model for `phonemes`
+    fn noise_shapes(&self, phonemes: &str) -> PySonataResult<Vec<(String, Vec<usize>)>> {
+        Ok(self.0.noise_shapes(phonemes)?)
+    }
+    /// Synthesize one sentence of `phonemes` with pre-generated noise, given as a list of
+    /// `(name, shape, data)` with `data` flattened in row-major order
+    fn synthesize_with_noise(
+        &self,
+        py: Python,
+        phonemes: &str,
+        noise: Vec<(String, Vec<usize>, Vec<f32>)>,
+        audio_output_config: Option<PyAudioOutputConfig>,
+    ) -> PySonataResult<WaveSamples> {
+        let noise: Vec<NoiseTensor> = noise
+            .into_iter()
+            .map(|(name, shape, data)| NoiseTensor { name, shape, data })
+            .collect();
+        let audio = py.allow_threads(|| {
+            self.0
+                .synthesize_with_noise(phonemes, &noise, audio_output_config.map(|o| o.into()))
+        })?;
+        Ok(WaveSamples(audio))
+    }
+    /// Whether the model takes a reference style embedding
+    fn has_style_input(&self) -> bool {
+        self.0.has_style_input()
+    }
+    /// Synthesize `text` in the speaking style of a reference style embedding (experimental)
+    fn synthesize_with_style(
+        &self,
+        py: Python,
+        text: &str,
+        style: Vec<f32>,
+        audio_output_config: Option<PyAudioOutputConfig>,
+    ) -> PySonataResult<WaveSamples> {
+        let audio = py.allow_threads(|| {
+            self.0
+                .synthesize_with_style(text, &style, audio_output_config.map(|o| o.into()))
+        })?;
+        Ok(WaveSamples(audio))
+    }
+    /// `callback` is called with `(index, text, phonemes)` as each segment of the speech
+    /// starts synthesizing, ahead of its audio. Pass `None` to remove it.
+    fn set_sentence_callback(&self, callback: Option<PyObject>) {
+        self.0.set_sentence_callback(callback.map(|callback| {
+            Box::new(move |event: SentenceEvent| {
+                Python::with_gil(|py| {
+                    let args = (event.index, event.text, event.phonemes);
+                    if let Err(e) = callback.call1(py, args) {
+                        e.print(py);
+                    }
+                })
+            }) as Box<dyn FnMut(SentenceEvent) + Send>
+        }));
+    }
+    /// Clean up input text before synthesis.
+    /// `strip_markdown` is one of `"off"`, `"basic"` or `"aggressive"`.
+    /// `trailing_punctuation` is one of `"off"`, `"strip"` (remove emoji, quotes and the like
+    /// at the end of sentences) or `"ensure"` (also end them with a period if they have no `.`, `?` or `!`).
+    fn set_text_preprocessing(
+        &self,
+        strip_markdown: Option<&str>,
+        collapse_whitespace: Option<bool>,
+        unicode_normalization: Option<&str>,
+        trailing_punctuation: Option<&str>,
+    ) -> PySonataResult<()> {
+        let unicode_normalization = match unicode_normalization.unwrap_or("off") {
+            "off" => UnicodeNormalization::Off,
+            "nfc" => UnicodeNormalization::Nfc,
+            "nfkc" => UnicodeNormalization::Nfkc,
+            other => {
+                return Err(SonataError::OperationError(format!(
+                    "Invalid unicode normalization form `{}`. Expected `off`, `nfc` or `nfkc`",
+                    other
+                ))
+                .into())
+            }
+        };
+        let strip_markdown = match strip_markdown.unwrap_or("off") {
+            "off" => MarkdownStripping::Off,
+            "basic" => MarkdownStripping::Basic,
+            "aggressive" => MarkdownStripping::Aggressive,
+            other => {
+                return Err(SonataError::OperationError(format!(
+                    "Invalid markdown stripping level `{}`. Expected `off`, `basic` or `aggressive`",
+                    other
+                ))
+                .into())
+            }
+        };
+        let trailing_punctuation = match trailing_punctuation.unwrap_or("off") {
+            "off" => TrailingPunctuation::Off,
+            "strip" => TrailingPunctuation::Strip,
+            "ensure" => TrailingPunctuation::Ensure,
+            other => {
+                return Err(SonataError::OperationError(format!(
+                    "Invalid trailing punctuation handling `{}`. Expected `off`, `strip` or `ensure`",
+                    other
+                ))
+                .into())
+            }
+        };
+        self.0.set_text_preprocessing(TextPreprocessing {
+            unicode_normalization,
+            strip_markdown,
+            collapse_whitespace: collapse_whitespace.unwrap_or_default(),
+            trailing_punctuation,
+        });
+        Ok(())
+    }
+    /// Measure the loudness of the voice on a standard phrase and store the gain that brings
+    /// later outputs to `target_db` (in dBFS, -20 by default). Returns the gain, in dB,
+    /// which can be saved and restored with `calibrated_gain_db`.
+    fn calibrate_gain(&self, py: Python, target_db: Option<f32>) -> PySonataResult<f32> {
+        let target_db = target_db.unwrap_or(sonata_synth::DEFAULT_LOUDNESS_TARGET_DB);
+        Ok(py.allow_threads(|| self.0.calibrate_gain(target_db))?)
+    }
+    /// The gain (in dB) added to every output, as set by `calibrate_gain`
+    #[getter]
+    fn get_calibrated_gain_db(&self) -> Option<f32> {
+        self.0.calibrated_gain_db()
+    }
+    #[setter]
+    fn set_calibrated_gain_db(&self, gain_db: Option<f32>) {
+        self.0.set_calibrated_gain_db(gain_db)
+    }
+    /// The sample rate the model produces audio at
+    #[getter]
+    fn native_sample_rate(&self) -> PySonataResult<usize> {
+        Ok(self.0.native_sample_rate()?)
+    }
+    /// The sample rate of audio synthesized with the given config (or the default config)
+    fn output_sample_rate(
+        &self,
+        audio_output_config: Option<PyAudioOutputConfig>,
+    ) -> PySonataResult<usize> {
+        Ok(self
+            .0
+            .output_sample_rate(audio_output_config.map(|o| o.0).as_ref())?)
+    }
+    /// A hash of everything that affects the speech synthesized for `text`, e.g. to key a cache
+    fn input_hash(
+        &self,
+        text: &str,
+        audio_output_config: Option<PyAudioOutputConfig>,
+    ) -> PySonataResult<u64> {
+        Ok(self
+            .0
+            .input_hash(text, audio_output_config.map(|o| o.0).as_ref())?)
+    }
+    /// Insert silence after punctuation, e.g. `{".": 400, ",": 150}` (in milliseconds).
+    /// Pass `"default"` for the default pauses, or `None` to leave pauses to the model.
+    fn set_punctuation_pauses(&self, pauses: Option<&PyAny>) -> PyResult<()> {
+        let pauses = match pauses {
+            None => None,
+            Some(pauses) => match pauses.extract::<&str>() {
+                Ok("default") => Some(PunctuationPauses::default()),
+                Ok(other) => {
+                    return Err(SonataException::new_err(format!(
+                        "Invalid punctuation pauses `{}`. Expected a dict or `\"default\"`",
+                        other
+                    )))
+                }
+                Err(_) => Some(PunctuationPauses::new(pauses.extract::<HashMap<char, u32>>()?)),
+            },
+        };
+        self.0.set_punctuation_pauses(pauses);
+        Ok(())
+    }
+    /// Use `audio_output_config` for calls that don't pass one. Pass `None` to clear it.
+    fn set_default_output_config(&self, audio_output_config: Option<PyAudioOutputConfig>) {
+        self.0
+            .set_default_output_config(audio_output_config.map(|o| o.into()));
+    }
+    fn make_silence(&self, duration_ms: u32) -> PySonataResult<WaveSamples> {
+        Ok(WaveSamples(self.0.make_silence(duration_ms)?))
+    }
     #[getter]
     fn language(&self) -> PySonataResult<Option<String>> {
         Ok(self.0.get_language()?)
@@ -400,11 +1198,199 @@ impl Sonata {
     fn speakers(&self) -> PySonataResult<Option<HashMap<i64, String>>> {
         Ok(self.0.get_speakers()?.cloned())
     }
+    /// Like `PiperModel.speaker`, resolving names with the synthesizer's speaker names
+    #[getter]
+    fn get_speaker(&self) -> PySonataResult<Option<String>> {
+        current_speaker(self.0.as_ref())
+    }
+    #[setter]
+    fn set_speaker(&self, speaker: SpeakerSelector) -> PySonataResult<()> {
+        select_speaker(self.0.as_ref(), speaker)
+    }
     fn get_audio_output_info(&self) -> PySonataResult<PyWaveInfo> {
         Ok(self.0.audio_output_info()?.into())
     }
 }
 
+/// Information about a model that is still alive
+#[pyclass(module = "piper", frozen)]
+#[pyo3(name = "LoadedModelInfo")]
+struct PyLoadedModelInfo {
+    #[pyo3(get)]
+    config_path: String,
+    #[pyo3(get)]
+    language: Option<String>,
+    #[pyo3(get)]
+    num_speakers: usize,
+    #[pyo3(get)]
+    estimated_memory_bytes: Option<u64>,
+    /// Number of alive `Sonata` synthesizers using this model
+    #[pyo3(get)]
+    num_synthesizers: usize,
+}
+
+/// Best-effort list of the models created in this process that haven't been freed yet
+#[pyfunction]
+fn loaded_models() -> PySonataResult<Vec<PyLoadedModelInfo>> {
+    let mut synthesizers = LOADED_SYNTHESIZERS.lock().unwrap();
+    synthesizers.retain(|synth| synth.strong_count() > 0);
+    let synth_models: Vec<_> = synthesizers
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|synth| Arc::as_ptr(&synth.clone_model()) as *const ())
+        .collect();
+    let mut models = LOADED_MODELS.lock().unwrap();
+    models.retain(|(_, model)| model.strong_count() > 0);
+    let mut infos = Vec::with_capacity(models.len());
+    for (config_path, model) in models.iter() {
+        let Some(model) = model.upgrade() else {
+            continue;
+        };
+        let model_ptr = Arc::as_ptr(&model) as *const ();
+        infos.push(PyLoadedModelInfo {
+            config_path: config_path.clone(),
+            language: model.get_language()?,
+            num_speakers: model.get_speakers()?.map(HashMap::len).unwrap_or_default(),
+            estimated_memory_bytes: model.estimated_memory_bytes(),
+            num_synthesizers: synth_models.iter().filter(|ptr| **ptr == model_ptr).count(),
+        });
+    }
+    Ok(infos)
+}
+
+/// Release the onnxruntime environment for a clean shutdown.
+///
+/// This must be the last call into pysonata: no model can be loaded afterwards.
+/// Fails if any model is still alive, so delete all models and `Sonata` objects first.
+#[pyfunction]
+fn shutdown() -> PySonataResult<()> {
+    sonata_piper::release_ort_environment()?;
+    Ok(())
+}
+
+fn parse_execution_providers(providers: &[String]) -> PySonataResult<Vec<ExecutionProvider>> {
+    Ok(providers
+        .iter()
+        .map(|provider| provider.parse())
+        .collect::<Result<_, SonataError>>()?)
+}
+
+/// Set the execution providers (e.g. `["cuda", "cpu"]`) of the models loaded without
+/// explicit providers. Must be called before the first model is loaded.
+#[pyfunction]
+fn set_default_execution_providers(providers: Vec<String>) -> PySonataResult<()> {
+    sonata_piper::set_default_execution_providers(parse_execution_providers(&providers)?)?;
+    Ok(())
+}
+
+/// Load the onnxruntime shared library from `path` instead of `ORT_DYLIB_PATH` or the
+/// default location. Must be called before the first model is loaded, and needs a build
+/// with the `ort-dylib` feature.
+#[pyfunction]
+fn set_ort_library_path(path: &str) -> PySonataResult<()> {
+    sonata_piper::set_ort_library_path(path)?;
+    Ok(())
+}
+
+#[pyclass(module = "piper", frozen)]
+#[pyo3(name = "VoiceStatus")]
+struct PyVoiceStatus {
+    #[pyo3(get)]
+    config_path: String,
+    /// One of `"ok"`, `"bad_config"`, `"missing_onnx"` or `"invalid_onnx"`
+    #[pyo3(get)]
+    status: &'static str,
+    /// The config error, or the path of the missing or invalid ONNX file
+    #[pyo3(get)]
+    detail: Option<String>,
+}
+
+#[pymethods]
+impl PyVoiceStatus {
+    #[getter]
+    fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+impl From<VoiceStatus> for PyVoiceStatus {
+    fn from(other: VoiceStatus) -> Self {
+        let (status, detail) = match other.check {
+            VoiceCheck::Ok => ("ok", None),
+            VoiceCheck::BadConfig(error) => ("bad_config", Some(error)),
+            VoiceCheck::MissingOnnx(path) => ("missing_onnx", Some(path.to_string_lossy().into_owned())),
+            VoiceCheck::InvalidOnnx(path) => ("invalid_onnx", Some(path.to_string_lossy().into_owned())),
+        };
+        Self {
+            config_path: other.config_path.to_string_lossy().into_owned(),
+            status,
+            detail,
+        }
+    }
+}
+
+/// Check the voices in a directory without loading them, e.g. after downloading a voice pack
+#[pyfunction]
+fn validate_voice_pack(dir: &str) -> PySonataResult<Vec<PyVoiceStatus>> {
+    let statuses = sonata_piper::validate_voice_pack(&PathBuf::from(dir))?;
+    Ok(statuses.into_iter().map(PyVoiceStatus::from).collect())
+}
+
+/// Names of the audio output formats available in this build
+#[pyfunction]
+pub fn supported_output_formats() -> Vec<String> {
+    sonata_core::supported_output_formats()
+}
+
+/// A snapshot of the counters of all the synthesis in this process, as returned by `stats`
+#[pyclass(module = "piper", frozen)]
+#[pyo3(name = "SynthesisStats")]
+struct PySynthesisStats {
+    #[pyo3(get)]
+    num_syntheses: u64,
+    #[pyo3(get)]
+    audio_seconds: f64,
+    #[pyo3(get)]
+    inference_ms: f64,
+    #[pyo3(get)]
+    cache_hits: u64,
+    #[pyo3(get)]
+    cache_misses: u64,
+    #[pyo3(get)]
+    cache_hit_rate: Option<f64>,
+}
+
+impl From<SynthesisStats> for PySynthesisStats {
+    fn from(other: SynthesisStats) -> Self {
+        Self {
+            num_syntheses: other.num_syntheses,
+            audio_seconds: other.audio_seconds,
+            inference_ms: other.inference_ms,
+            cache_hits: other.cache_hits,
+            cache_misses: other.cache_misses,
+            cache_hit_rate: other.cache_hit_rate(),
+        }
+    }
+}
+
+#[pyfunction]
+fn stats() -> PySynthesisStats {
+    sonata_synth::stats().into()
+}
+
+/// Count a lookup of an application's cache of synthesized audio in `stats().cache_hit_rate`
+#[pyfunction]
+fn record_cache_lookup(hit: bool) {
+    sonata_synth::record_cache_lookup(hit)
+}
+
+/// Fix the header of a wave file whose streamed recording was interrupted, so that it's
+/// playable. Returns whether the file had to be changed.
+#[pyfunction]
+fn repair_wav(path: &str) -> PySonataResult<bool> {
+    Ok(sonata_core::repair_wav(&PathBuf::from(path)).map_err(SonataError::from)?)
+}
+
 #[pyfunction]
 pub fn phonemize_text(
     text: &str,
@@ -446,12 +1432,30 @@ fn pysonata(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add("SonataException", _py.get_type::<SonataException>())?;
     m.add_class::<Sonata>()?;
     m.add_class::<PiperModel>()?;
+    m.add_class::<PyCancellationToken>()?;
     m.add_class::<PiperScales>()?;
     m.add_class::<PyAudioOutputConfig>()?;
     m.add_class::<WaveSamples>()?;
+    m.add_class::<PyClippingStats>()?;
+    m.add_class::<PyRawOutput>()?;
+    m.add_class::<PyPhonemeDurations>()?;
     m.add_class::<LazySpeechStream>()?;
     m.add_class::<ParallelSpeechStream>()?;
     m.add_class::<PyRealtimeSpeechStream>()?;
+    m.add_class::<PySseStream>()?;
+    m.add_class::<PyStreamStats>()?;
     m.add_function(wrap_pyfunction!(phonemize_text, m)?)?;
+    m.add_function(wrap_pyfunction!(supported_output_formats, m)?)?;
+    m.add_function(wrap_pyfunction!(repair_wav, m)?)?;
+    m.add_function(wrap_pyfunction!(stats, m)?)?;
+    m.add_function(wrap_pyfunction!(record_cache_lookup, m)?)?;
+    m.add_class::<PySynthesisStats>()?;
+    m.add_function(wrap_pyfunction!(loaded_models, m)?)?;
+    m.add_function(wrap_pyfunction!(shutdown, m)?)?;
+    m.add_function(wrap_pyfunction!(set_default_execution_providers, m)?)?;
+    m.add_function(wrap_pyfunction!(set_ort_library_path, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_voice_pack, m)?)?;
+    m.add_class::<PyVoiceStatus>()?;
+    m.add_class::<PyLoadedModelInfo>()?;
     Ok(())
 }
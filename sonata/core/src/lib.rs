@@ -2,16 +2,34 @@ use std::any::Any;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
+mod sentences;
 
+pub use sentences::{ends_with_abbreviation, split_sentences, CLOSING_PUNCTUATION, SENTENCE_TERMINATORS};
 pub use audio_ops::{
+    AlignmentFormat,
     Audio,
+    AudioError,
     AudioInfo,
     AudioSamples,
-    WaveWriterError
+    AutotuneConfig,
+    ClippingStats,
+    PhonemeTiming,
+    SampleConversionFn,
+    SampleConverter,
+    Scale,
+    SentenceInfo,
+    StreamingWaveWriter,
+    VisemeSet,
+    VisemeTiming,
+    WaveWriterError,
+    repair_wav,
+    supported_output_formats
 };
 
-
 pub type SonataResult<T> = Result<T, SonataError>;
 pub type SonataAudioResult = SonataResult<Audio>;
 pub type AudioStreamIterator<'a> = Box<dyn Iterator<Item = SonataResult<AudioSamples>> + Send + Sync + 'a>;
@@ -49,6 +67,28 @@ impl From<WaveWriterError> for SonataError {
     }
 }
 
+impl From<AudioError> for SonataError {
+    fn from(error: AudioError) -> Self {
+        SonataError::OperationError(error.to_string())
+    }
+}
+
+/// A cloneable flag used to ask a long running operation to stop early
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
 /// A wrapper type that holds sentence phonemes
 pub struct Phonemes(pub Vec<String>);
 
@@ -90,6 +130,93 @@ impl std::string::ToString for Phonemes {
     }
 }
 
+/// An output tensor of a model's inference session, before any post-processing
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawOutput {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+/// Noise fed to a model in place of the noise it would sample, see
+/// [`SonataModel::speak_with_noise`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseTensor {
+    /// The name of the noise input of the model
+    pub name: String,
+    pub shape: Vec<usize>,
+    /// The values in row-major order
+    pub data: Vec<f32>,
+}
+
+/// A cheap estimate of the work it takes to synthesize some text, made without running the
+/// model, e.g. to enforce quotas before synthesis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Number of phonemes, excluding padding and sentence markers
+    pub num_phonemes: usize,
+    /// Estimated number of decoder frames to synthesize, which the compute scales with
+    pub num_frames: usize,
+    /// Estimated number of samples of the output, at the model's sample rate
+    pub num_samples: usize,
+}
+
+impl std::ops::Add for CostEstimate {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            num_phonemes: self.num_phonemes + other.num_phonemes,
+            num_frames: self.num_frames + other.num_frames,
+            num_samples: self.num_samples + other.num_samples,
+        }
+    }
+}
+
+/// How long each input symbol of a sentence is spoken, as predicted by the model's duration
+/// predictor. Edit `durations_ms` and pass it back to [`SonataModel::speak_with_durations`]
+/// to control the timing of the speech.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhonemeDurations {
+    /// The phonemes of the sentence
+    pub phonemes: String,
+    /// The symbols the model is run on, including padding and the start and end markers
+    pub symbols: Vec<String>,
+    /// The duration of each symbol, in milliseconds
+    pub durations_ms: Vec<f32>,
+}
+
+const STABLE_NOISE_SCALE: f32 = 0.2;
+const STABLE_NOISE_W: f32 = 0.3;
+
+/// Synthesis parameters layered over a model's config, e.g. from a deployment's override file.
+/// Fields left as `None` keep the model's current value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SynthesisOverrides {
+    pub speaker: Option<i64>,
+    pub noise_scale: Option<f32>,
+    pub length_scale: Option<f32>,
+    pub noise_w: Option<f32>,
+}
+
+impl SynthesisOverrides {
+    /// Low noise scales, for when consistent output matters more than natural-sounding speech.
+    ///
+    /// With less noise, the same text comes out nearly the same every time, but the speech is
+    /// flatter and more monotonous, and the timing of phonemes varies less from one to the next.
+    /// The length scale and speaker are left unchanged.
+    pub fn stable() -> Self {
+        Self {
+            noise_scale: Some(STABLE_NOISE_SCALE),
+            noise_w: Some(STABLE_NOISE_W),
+            ..Default::default()
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 pub trait SonataModel {
     fn audio_output_info(&self) -> SonataResult<AudioInfo>;
     fn phonemize_text(&self, text: &str) -> SonataResult<Phonemes>;
@@ -112,19 +239,132 @@ pub trait SonataModel {
             .and_then(|speakers| speakers.get(sid))
             .cloned())
     }
+    /// Ids of all the speakers with the given name, in ascending order
+    fn speaker_name_to_ids(&self, name: &str) -> SonataResult<Vec<i64>> {
+        let mut sids: Vec<i64> = self
+            .get_speakers()?
+            .map(|speakers| {
+                speakers
+                    .iter()
+                    .filter(|(_, sname)| *sname == name)
+                    .map(|(sid, _)| *sid)
+                    .collect()
+            })
+            .unwrap_or_default();
+        sids.sort_unstable();
+        Ok(sids)
+    }
+    /// Returns an error if more than one speaker has the given name.
+    /// Use [`SonataModel::speaker_name_index_to_id`] to select one of them.
     fn speaker_name_to_id(&self, name: &str) -> SonataResult<Option<i64>> {
-        Ok(self.get_speakers()?.and_then(|speakers| {
-            for (sid, sname) in speakers {
-                if sname == name {
-                    return Some(*sid);
-                }
-            }
-            None
-        }))
+        let sids = self.speaker_name_to_ids(name)?;
+        match sids.as_slice() {
+            [] => Ok(None),
+            [sid] => Ok(Some(*sid)),
+            _ => Err(SonataError::OperationError(format!(
+                "Speaker name `{}` is ambiguous. It is shared by the speakers with ids {:?}. Select the speaker by id or by (name, index)",
+                name, sids
+            ))),
+        }
+    }
+    /// Select the `index`th speaker (ordered by id) among the speakers with the given name
+    fn speaker_name_index_to_id(&self, name: &str, index: usize) -> SonataResult<Option<i64>> {
+        Ok(self.speaker_name_to_ids(name)?.get(index).copied())
     }
     fn properties(&self) -> SonataResult<HashMap<String, String>> {
         Ok(HashMap::with_capacity(0))
     }
+    /// Validate the overrides against the model config and apply them to the fallback
+    /// synthesis config. Nothing is applied if any of the overrides conflicts with the model.
+    fn apply_synthesis_overrides(&self, overrides: &SynthesisOverrides) -> SonataResult<()> {
+        if overrides.is_empty() {
+            Ok(())
+        } else {
+            Err(SonataError::OperationError(
+                "Synthesis overrides are not supported for this model".to_string(),
+            ))
+        }
+    }
+    /// An approximation of the memory used by the loaded model, if known.
+    /// Meant as a guide for capacity planning, not an exact measurement.
+    fn estimated_memory_bytes(&self) -> Option<u64> {
+        None
+    }
+    /// Run the model on `phonemes` and return the output tensors of its decoder as they come
+    /// out of the inference session, before conversion and effects. Meant for debugging,
+    /// e.g. to tell whether a quality issue comes from the model or from post-processing.
+    fn infer_raw(&self, _phonemes: String) -> SonataResult<Vec<RawOutput>> {
+        Err(SonataError::OperationError(
+            "Raw inference output is not supported for this model".to_string(),
+        ))
+    }
+    /// Run the encoder and duration predictor of the model on `phonemes`, without decoding
+    fn predict_phoneme_durations(
+        &self,
+        _phonemes: String,
+    ) -> SonataResult<PhonemeDurations> {
+        Err(SonataError::OperationError(
+            "Predicting phoneme durations is not supported for this model".to_string(),
+        ))
+    }
+    /// Synthesize the phonemes of `durations`, with each symbol lasting the given duration
+    /// instead of the predicted one. There must be one duration per symbol.
+    fn speak_with_durations(
+        &self,
+        _durations: &PhonemeDurations,
+    ) -> SonataAudioResult {
+        Err(SonataError::OperationError(
+            "Overriding phoneme durations is not supported for this model".to_string(),
+        ))
+    }
+    /// Estimate the cost of synthesizing `phonemes` from their number and the speaking rate,
+    /// without running the model
+    fn estimate_cost(&self, _phonemes: &str) -> SonataResult<CostEstimate> {
+        Err(SonataError::OperationError(
+            "Cost estimation is not supported for this model".to_string(),
+        ))
+    }
+    /// The name and shape of each noise input of the model for `phonemes`, in the order of the
+    /// model's inputs. Models that sample the noise inside their inference graph have none.
+    fn noise_shapes(&self, _phonemes: &str) -> SonataResult<Vec<(String, Vec<usize>)>> {
+        Err(SonataError::OperationError(
+            "Noise injection is not supported for this model".to_string(),
+        ))
+    }
+    /// Synthesize `phonemes` with the given noise instead of sampling it, e.g. for reproducible
+    /// output. There must be one tensor per noise input, with the shape given by
+    /// [`Self::noise_shapes`].
+    fn speak_with_noise(
+        &self,
+        _phonemes: String,
+        _noise: &[NoiseTensor],
+    ) -> SonataAudioResult {
+        Err(SonataError::OperationError(
+            "Noise injection is not supported for this model".to_string(),
+        ))
+    }
+    /// Whether the model takes a reference style embedding, see [`Self::speak_with_style`]
+    fn has_style_input(&self) -> bool {
+        false
+    }
+    /// Synthesize `phonemes` in the speaking style of a reference style embedding
+    /// (prosody transfer). The embedding must have as many values as the model expects.
+    fn speak_with_style(
+        &self,
+        _phonemes: String,
+        _style: &[f32],
+    ) -> SonataAudioResult {
+        Err(SonataError::OperationError(
+            "Style embeddings are not supported for this model".to_string(),
+        ))
+    }
+    /// Feed everything about the model that affects its output into `state`: the identity of
+    /// the model and its current synthesis config (speaker, scales, etc.).
+    fn hash_output_state(&self, _state: &mut dyn Hasher) -> SonataResult<()> {
+        Err(SonataError::OperationError(
+            "Hashing the synthesis inputs is not supported for this model".to_string(),
+        ))
+    }
 
     fn supports_streaming_output(&self) -> bool {
         false
@@ -139,5 +379,108 @@ pub trait SonataModel {
                 "Streaming synthesis is not supported for this model".to_string(),
             ))
     }
+    /// Whether the model can start speaking a sentence before all of it is encoded.
+    /// See [`SonataModel::stream_incremental_synthesis`].
+    fn supports_incremental_synthesis(&self) -> bool {
+        false
+    }
+    /// Experimental: stream `phonemes` a few words at a time. Each group of words is encoded
+    /// with a couple of words of context on each side, and only its own frames are decoded,
+    /// so the first audio is ready once the start of the sentence is encoded. The joins
+    /// between groups can sound slightly less smooth than with [`SonataModel::stream_synthesis`].
+    fn stream_incremental_synthesis(
+        &self,
+        _phonemes: String,
+        _chunk_padding: usize,
+    ) -> SonataResult<AudioStreamIterator> {
+        Err(SonataError::OperationError(
+            "Incremental synthesis is not supported for this model".to_string(),
+        ))
+    }
+}
+
+/// FNV-1a, used for hashes that must not change between runs or Rust releases
+/// (unlike the standard library's `DefaultHasher`)
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SpeakersModel(HashMap<i64, String>);
+
+    impl SonataModel for SpeakersModel {
+        fn audio_output_info(&self) -> SonataResult<AudioInfo> {
+            unimplemented!()
+        }
+        fn phonemize_text(&self, _text: &str) -> SonataResult<Phonemes> {
+            unimplemented!()
+        }
+        fn speak_batch(&self, _phoneme_batches: Vec<String>) -> SonataResult<Vec<Audio>> {
+            unimplemented!()
+        }
+        fn speak_one_sentence(&self, _phonemes: String) -> SonataAudioResult {
+            unimplemented!()
+        }
+        fn get_default_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+            unimplemented!()
+        }
+        fn get_fallback_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+            unimplemented!()
+        }
+        fn set_fallback_synthesis_config(&self, _synthesis_config: &dyn Any) -> SonataResult<()> {
+            unimplemented!()
+        }
+        fn get_speakers(&self) -> SonataResult<Option<&HashMap<i64, String>>> {
+            Ok(Some(&self.0))
+        }
+    }
+
+    #[test]
+    fn test_stable_overrides_only_lower_the_noise() {
+        let stable = SynthesisOverrides::stable();
+        assert!(!stable.is_empty());
+        assert_eq!(stable.speaker, None);
+        assert_eq!(stable.length_scale, None);
+    }
+
+    #[test]
+    fn test_ambiguous_speaker_name() {
+        let model = SpeakersModel(HashMap::from([
+            (7, "alice".to_string()),
+            (2, "alice".to_string()),
+            (4, "bob".to_string()),
+        ]));
+        assert_eq!(model.speaker_name_to_id("bob").unwrap(), Some(4));
+        assert_eq!(model.speaker_name_to_id("carol").unwrap(), None);
+        let err = model.speaker_name_to_id("alice").unwrap_err().to_string();
+        assert!(err.contains("[2, 7]"));
+        assert_eq!(model.speaker_name_index_to_id("alice", 0).unwrap(), Some(2));
+        assert_eq!(model.speaker_name_index_to_id("alice", 1).unwrap(), Some(7));
+        assert_eq!(model.speaker_name_index_to_id("alice", 2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_stable_hasher_is_fnv1a() {
+        let mut hasher = StableHasher::default();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63dc4c8601ec8c);
+    }
+}
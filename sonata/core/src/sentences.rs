@@ -0,0 +1,90 @@
+/// Characters that end a sentence
+pub const SENTENCE_TERMINATORS: &[char] = &['.', '!', '?', '…', '։', '。', '！', '？'];
+/// Characters that may follow a terminator and still belong to the sentence
+pub const CLOSING_PUNCTUATION: &[char] = &['"', '\'', ')', ']', '»', '”', '’'];
+
+/// Split text into sentences, keeping the terminating punctuation with each sentence.
+/// The sentences are trimmed slices of `text`.
+///
+/// Sentences end at a terminator followed by whitespace (or the end of the text),
+/// and at line breaks. A period does not end a sentence after an abbreviation-like
+/// token such as `Dr.` or `e.g.`.
+pub fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\n' || c == '\r' {
+            push_sentence(&mut sentences, &text[start..i]);
+            start = i + c.len_utf8();
+            continue;
+        }
+        if !SENTENCE_TERMINATORS.contains(&c) {
+            continue;
+        }
+        let mut end = i + c.len_utf8();
+        while let Some(&(j, next)) = chars.peek() {
+            if SENTENCE_TERMINATORS.contains(&next) || CLOSING_PUNCTUATION.contains(&next) {
+                end = j + next.len_utf8();
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let at_boundary = chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+        if at_boundary && !(c == '.' && ends_with_abbreviation(&text[start..end])) {
+            push_sentence(&mut sentences, &text[start..end]);
+            start = end;
+        }
+    }
+    push_sentence(&mut sentences, &text[start..]);
+    sentences
+}
+
+fn push_sentence<'a>(sentences: &mut Vec<&'a str>, sentence: &'a str) {
+    let sentence = sentence.trim();
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+}
+
+/// Whether the last token of `text` looks like an abbreviation (`Dr.`, `Mrs.`, `e.g.`, `U.S.`)
+pub fn ends_with_abbreviation(text: &str) -> bool {
+    let token = text.rsplit(char::is_whitespace).next().unwrap_or_default();
+    let word = token.trim_end_matches('.');
+    if word.is_empty() {
+        return false;
+    }
+    if word.contains('.') {
+        return true;
+    }
+    let first_is_upper = word.chars().next().is_some_and(char::is_uppercase);
+    first_is_upper && word.chars().count() <= 3 && word.chars().all(char::is_alphabetic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_sentences() {
+        let sentences = split_sentences("Hello there!  How are you? I'm fine.\nThanks");
+        assert_eq!(
+            sentences,
+            vec!["Hello there!", "How are you?", "I'm fine.", "Thanks"]
+        );
+    }
+
+    #[test]
+    fn test_split_sentences_keeps_abbreviations_and_decimals() {
+        let sentences = split_sentences("Dr. Smith paid 3.5 dollars, e.g. a lot. \"Really?\" he said.");
+        assert_eq!(
+            sentences,
+            vec![
+                "Dr. Smith paid 3.5 dollars, e.g. a lot.",
+                "\"Really?\"",
+                "he said."
+            ]
+        );
+    }
+}
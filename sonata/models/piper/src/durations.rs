@@ -0,0 +1,171 @@
+use ndarray::{Array3, ArrayView3};
+use sonata_core::{SonataError, SonataResult};
+
+use crate::truncation::SAMPLES_PER_FRAME;
+
+/// The average duration of a phoneme in speech at the normal rate
+const AVERAGE_PHONEME_MS: f32 = 80.0;
+
+/// The number of decoder frames `num_phonemes` are expected to take at `length_scale`,
+/// without running the duration predictor
+pub(crate) fn estimate_frames(num_phonemes: usize, sample_rate: usize, length_scale: f32) -> usize {
+    let duration_ms = num_phonemes as f32 * AVERAGE_PHONEME_MS * length_scale;
+    (duration_ms * sample_rate as f32 / (SAMPLES_PER_FRAME as f32 * 1000.0)).ceil() as usize
+}
+
+/// Convert durations in decoder frames to milliseconds
+pub(crate) fn frames_to_ms(frames: &[f32], sample_rate: usize) -> Vec<f32> {
+    let ms_per_frame = SAMPLES_PER_FRAME as f32 * 1000.0 / sample_rate as f32;
+    frames.iter().map(|frames| frames * ms_per_frame).collect()
+}
+
+/// The start and end of each symbol in milliseconds, from its duration in decoder frames.
+/// Like the decoder, it rounds the running total of the frames.
+pub(crate) fn spans_ms(frames: &[f32], sample_rate: usize) -> Vec<(f32, f32)> {
+    let ms_per_frame = SAMPLES_PER_FRAME as f32 * 1000.0 / sample_rate as f32;
+    let mut start = 0;
+    boundaries(frames)
+        .into_iter()
+        .map(|end| {
+            let span = (start as f32 * ms_per_frame, end as f32 * ms_per_frame);
+            start = end;
+            span
+        })
+        .collect()
+}
+
+/// Convert durations in milliseconds to decoder frames, rejecting negative and non-finite ones
+pub(crate) fn ms_to_frames(durations_ms: &[f32], sample_rate: usize) -> SonataResult<Vec<f32>> {
+    let frames_per_ms = sample_rate as f32 / (SAMPLES_PER_FRAME as f32 * 1000.0);
+    durations_ms
+        .iter()
+        .map(|duration_ms| {
+            if duration_ms.is_finite() && *duration_ms >= 0.0 {
+                Ok(duration_ms * frames_per_ms)
+            } else {
+                Err(SonataError::OperationError(format!(
+                    "Invalid phoneme duration: {} ms",
+                    duration_ms
+                )))
+            }
+        })
+        .collect()
+}
+
+/// The frame each symbol ends at. Rounding the running total rather than each duration keeps
+/// the rounding errors from adding up.
+fn boundaries(durations: &[f32]) -> Vec<usize> {
+    let mut total = 0f32;
+    durations
+        .iter()
+        .map(|duration| {
+            total += duration;
+            total.round() as usize
+        })
+        .collect()
+}
+
+/// Stretch or squeeze the latent frames (`[1, channels, frames]`) of each symbol from its
+/// predicted duration to the new one (both in frames), by interpolating between frames.
+/// A symbol that had no frames takes the frames around the point it was at.
+pub(crate) fn retime(
+    z: ArrayView3<f32>,
+    predicted: &[f32],
+    durations: &[f32],
+) -> SonataResult<Array3<f32>> {
+    if predicted.len() != durations.len() {
+        return Err(SonataError::OperationError(format!(
+            "Expected {} phoneme durations, got {}",
+            predicted.len(),
+            durations.len()
+        )));
+    }
+    let num_channels = z.shape()[1];
+    let num_frames = z.shape()[2];
+    let old_ends = boundaries(predicted);
+    let new_ends = boundaries(durations);
+    let new_len = new_ends.last().copied().unwrap_or_default();
+    if num_frames == 0 || new_len == 0 {
+        return Err(SonataError::OperationError(
+            "The phoneme durations add up to less than one frame".to_string(),
+        ));
+    }
+    let last_frame = (num_frames - 1) as f32;
+    let mut retimed = Array3::<f32>::zeros((1, num_channels, new_len));
+    let (mut old_start, mut new_start) = (0, 0);
+    for (old_end, new_end) in old_ends.into_iter().zip(new_ends) {
+        let old_end = old_end.min(num_frames);
+        let old_len = old_end.saturating_sub(old_start) as f32;
+        let new_len = (new_end - new_start) as f32;
+        for frame in new_start..new_end {
+            // Sample the middle of the new frame's span of the old frames
+            let position = old_start as f32 + ((frame - new_start) as f32 + 0.5) * old_len / new_len - 0.5;
+            let position = if old_end > old_start {
+                position.clamp(old_start as f32, (old_end - 1) as f32)
+            } else {
+                position.clamp(0.0, last_frame)
+            };
+            let (before, frac) = (position.floor() as usize, position.fract());
+            let after = (before + 1).min(num_frames - 1);
+            for channel in 0..num_channels {
+                retimed[[0, channel, frame]] =
+                    z[[0, channel, before]] * (1.0 - frac) + z[[0, channel, after]] * frac;
+            }
+        }
+        old_start = old_start.max(old_end);
+        new_start = new_end;
+    }
+    Ok(retimed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latent(frames: &[f32]) -> Array3<f32> {
+        Array3::from_shape_vec((1, 1, frames.len()), frames.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_unchanged_durations_keep_the_frames() {
+        let z = latent(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        let retimed = retime(z.view(), &[2.0, 0.0, 3.0], &[2.0, 0.0, 3.0]).unwrap();
+        assert_eq!(retimed, z);
+    }
+
+    #[test]
+    fn test_each_symbol_is_retimed_on_its_own() {
+        // Two symbols of two frames each: the first is doubled, the second halved
+        let z = latent(&[1.0, 3.0, 10.0, 10.0]);
+        let retimed = retime(z.view(), &[2.0, 2.0], &[4.0, 1.0]).unwrap();
+        assert_eq!(retimed.shape(), [1, 1, 5]);
+        assert_eq!(retimed.as_slice().unwrap(), [1.0, 1.5, 2.5, 3.0, 10.0]);
+    }
+
+    #[test]
+    fn test_invalid_durations_are_rejected() {
+        let z = latent(&[1.0, 2.0]);
+        assert!(retime(z.view(), &[1.0, 1.0], &[1.0]).is_err());
+        assert!(retime(z.view(), &[1.0, 1.0], &[0.2, 0.2]).is_err());
+        assert!(ms_to_frames(&[10.0, -1.0], 22050).is_err());
+        assert!(ms_to_frames(&[f32::NAN], 22050).is_err());
+        let frames = ms_to_frames(&[100.0], 22050).unwrap();
+        assert!((frames_to_ms(&frames, 22050)[0] - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_spans_follow_each_other() {
+        // 256 samples per frame at 25.6 kHz are 10 ms per frame
+        let spans = spans_ms(&[1.4, 0.2, 2.4], 25600);
+        assert_eq!(spans, [(0.0, 10.0), (10.0, 20.0), (20.0, 40.0)]);
+        assert!(spans_ms(&[], 25600).is_empty());
+    }
+
+    #[test]
+    fn test_frame_estimate_scales_with_phonemes_and_rate() {
+        assert_eq!(estimate_frames(0, 22050, 1.0), 0);
+        // 10 phonemes take about 800 ms, i.e. 69 frames of 256 samples at 22.05 kHz
+        assert_eq!(estimate_frames(10, 22050, 1.0), 69);
+        assert!(estimate_frames(10, 22050, 2.0) > estimate_frames(10, 22050, 1.0));
+    }
+}
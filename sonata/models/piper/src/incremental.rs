@@ -0,0 +1,70 @@
+use std::ops::Range;
+
+/// Words in the first group of a sentence, few so that its audio is ready early
+const FIRST_GROUP_WORDS: usize = 2;
+/// Words in each of the following groups
+const GROUP_WORDS: usize = 6;
+/// Words encoded on each side of a group, so that its prosody fits in with its neighbours
+const CONTEXT_WORDS: usize = 2;
+
+/// A group of words to synthesize, and the words encoded along with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Window {
+    /// The words given to the encoder, including the group
+    pub(crate) words: Range<usize>,
+    /// The words whose audio is kept
+    pub(crate) group: Range<usize>,
+}
+
+/// Split a sentence of `num_words` words into groups, in the order they are spoken
+pub(crate) fn plan_windows(num_words: usize) -> Vec<Window> {
+    let mut windows = Vec::new();
+    let mut start = 0;
+    while start < num_words {
+        let group_words = if start == 0 { FIRST_GROUP_WORDS } else { GROUP_WORDS };
+        let end = (start + group_words).min(num_words);
+        windows.push(Window {
+            words: start.saturating_sub(CONTEXT_WORDS)..(end + CONTEXT_WORDS).min(num_words),
+            group: start..end,
+        });
+        start = end;
+    }
+    windows
+}
+
+/// The latent frames taken by the input ids in `ids`, given the duration (in frames)
+/// of each input id of the window
+pub(crate) fn group_frames(durations: &[f32], ids: Range<usize>) -> Range<usize> {
+    let frames_before = |id: usize| {
+        durations[..id.min(durations.len())].iter().sum::<f32>().round() as usize
+    };
+    frames_before(ids.start)..frames_before(ids.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_cover_every_word_once() {
+        let windows = plan_windows(13);
+        assert_eq!(
+            windows,
+            [
+                Window { words: 0..4, group: 0..2 },
+                Window { words: 0..10, group: 2..8 },
+                Window { words: 6..13, group: 8..13 },
+            ]
+        );
+        assert_eq!(plan_windows(1), [Window { words: 0..1, group: 0..1 }]);
+        assert!(plan_windows(0).is_empty());
+    }
+
+    #[test]
+    fn test_group_frames_follow_the_durations() {
+        let durations = [2.0, 3.0, 1.0, 4.0];
+        assert_eq!(group_frames(&durations, 0..2), 0..5);
+        assert_eq!(group_frames(&durations, 2..4), 5..10);
+        assert_eq!(group_frames(&durations, 4..4), 10..10);
+    }
+}
@@ -0,0 +1,313 @@
+use sonata_core::split_sentences;
+
+/// Opt-in detection of the language of each sentence, to pick the espeak voice for it.
+/// Only the languages the model was trained on are detected, i.e. the languages of its
+/// espeak voice and its language code. Sentences in any other language are spoken with
+/// the default voice.
+///
+/// The detector is a small heuristic, not a statistical model. A sentence is first assigned
+/// the script most of its letters are written in. Scripts that are used by a single supported
+/// language (e.g. Armenian or Greek) decide the language on their own. For Cyrillic,
+/// letters unique to Ukrainian pick `uk` over `ru`. For Latin, every word is looked up in
+/// short lists of function words (`the`, `und`, `les`, ...) of English, German, French,
+/// Spanish, Italian, Portuguese and Dutch.
+///
+/// Its limits:
+/// - Latin sentences with fewer than two function words (e.g. headlines or lists of names)
+///   get a low confidence and fall back to the default voice
+/// - closely related languages that share a script and most function words (e.g. Spanish
+///   and Portuguese) are told apart less reliably
+/// - languages outside the lists above are never detected. A Latin sentence in Polish is
+///   scored against the listed ones and, at best, falls back to the default voice
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageDetection {
+    /// Sentences detected with a confidence (between 0 and 1) below this are spoken with
+    /// the default voice of the model
+    pub min_confidence: f32,
+}
+
+impl Default for LanguageDetection {
+    fn default() -> Self {
+        Self {
+            min_confidence: 0.5,
+        }
+    }
+}
+
+/// The language of a sentence, as an espeak voice
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Detection {
+    pub(crate) voice: &'static str,
+    /// Between 0 and 1
+    pub(crate) confidence: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Armenian,
+    Greek,
+    Georgian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+}
+
+impl Script {
+    fn of(c: char) -> Option<Self> {
+        match c {
+            'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' => Some(Self::Latin),
+            '\u{0400}'..='\u{04FF}' => Some(Self::Cyrillic),
+            '\u{0531}'..='\u{058F}' => Some(Self::Armenian),
+            '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Some(Self::Greek),
+            '\u{10A0}'..='\u{10FF}' => Some(Self::Georgian),
+            '\u{0590}'..='\u{05FF}' => Some(Self::Hebrew),
+            '\u{0600}'..='\u{06FF}' => Some(Self::Arabic),
+            '\u{0900}'..='\u{097F}' => Some(Self::Devanagari),
+            _ => None,
+        }
+    }
+}
+
+const SCRIPTS: [Script; 8] = [
+    Script::Latin,
+    Script::Cyrillic,
+    Script::Armenian,
+    Script::Greek,
+    Script::Georgian,
+    Script::Hebrew,
+    Script::Arabic,
+    Script::Devanagari,
+];
+
+const FUNCTION_WORDS: [(&str, &[&str]); 7] = [
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "was", "of", "to", "in", "that", "it", "with", "for", "you",
+            "this", "have", "not", "be", "on", "at", "by",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ich", "du", "ein", "eine", "mit", "den",
+            "dem", "zu", "auf", "für", "sie", "wir", "es", "sind",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "de", "le", "la", "les", "et", "est", "une", "des", "du", "je", "vous", "nous", "pas", "que",
+            "qui", "dans", "pour", "sur", "avec", "il",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "de", "el", "los", "las", "y", "es", "una", "del", "que", "en", "por", "con", "para", "yo",
+            "está", "pero", "muy", "como", "se", "su",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "lo", "gli", "le", "e", "è", "una", "di", "che", "non", "per", "con", "sono",
+            "della", "questo", "io", "ma", "nel", "anche", "del",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "de", "o", "os", "as", "e", "é", "um", "uma", "do", "da", "que", "não", "em", "para", "com",
+            "eu", "você", "mas", "muito", "no",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "is", "van", "niet", "ik", "je", "dat", "met", "op", "voor",
+            "zijn", "wij", "maar", "ook", "naar", "er", "te",
+        ],
+    ),
+];
+
+/// Number of function words of a language at which its score stops growing
+const FULL_CONFIDENCE_WORDS: usize = 2;
+
+/// The most likely of `languages` for `sentence`, or `None` if it has no letters of a supported
+/// script, is in another language or, for Latin, has no function words of one of `languages`
+pub(crate) fn detect(sentence: &str, languages: &[&str]) -> Option<Detection> {
+    let mut script_counts = [0usize; SCRIPTS.len()];
+    for c in sentence.chars() {
+        if let Some(script) = Script::of(c) {
+            script_counts[SCRIPTS.iter().position(|s| *s == script).unwrap()] += 1;
+        }
+    }
+    let num_letters: usize = script_counts.iter().sum();
+    let (index, count) = script_counts
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, count)| **count)
+        .filter(|(_, count)| **count > 0)?;
+    let script_share = *count as f32 / num_letters as f32;
+    let single = |voice| {
+        Some(Detection {
+            voice,
+            confidence: script_share,
+        })
+    };
+    let detection = match SCRIPTS[index] {
+        Script::Latin => detect_latin(sentence, languages).map(|detection| Detection {
+            confidence: detection.confidence * script_share,
+            ..detection
+        }),
+        Script::Cyrillic if sentence.chars().any(|c| "іїєґІЇЄҐ".contains(c)) && languages.contains(&"uk") => {
+            single("uk")
+        }
+        Script::Cyrillic => single("ru"),
+        Script::Armenian => single("hy"),
+        Script::Greek => single("el"),
+        Script::Georgian => single("ka"),
+        Script::Hebrew => single("he"),
+        Script::Arabic => single("ar"),
+        Script::Devanagari => single("hi"),
+    };
+    detection.filter(|detection| languages.contains(&detection.voice))
+}
+
+/// Scores each of `languages` by the function words of it in `sentence`. The confidence is
+/// the lead of the best language over the runner-up, scaled down when it has only a few hits.
+fn detect_latin(sentence: &str, languages: &[&str]) -> Option<Detection> {
+    let words: Vec<String> = sentence
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut scores: Vec<(&'static str, usize)> = FUNCTION_WORDS
+        .iter()
+        .filter(|(voice, _)| languages.contains(voice))
+        .map(|(voice, function_words)| {
+            let hits = words
+                .iter()
+                .filter(|word| function_words.contains(&word.as_str()))
+                .count();
+            (*voice, hits)
+        })
+        .collect();
+    scores.sort_by_key(|&(_, hits)| std::cmp::Reverse(hits));
+    let (voice, best) = scores.first().copied()?;
+    if best == 0 {
+        return None;
+    }
+    let runner_up = scores.get(1).map_or(0, |&(_, hits)| hits);
+    let lead = (best - runner_up) as f32 / best as f32;
+    let support = best.min(FULL_CONFIDENCE_WORDS) as f32 / FULL_CONFIDENCE_WORDS as f32;
+    Some(Detection {
+        voice,
+        confidence: lead * support,
+    })
+}
+
+/// Splits `text` into sentences and picks the voice of each: the one detected among
+/// `languages` when it's confident enough, otherwise `default_voice`. Neighbouring sentences
+/// with the same voice are merged, so that espeak sees as much context as possible.
+pub(crate) fn split_by_voice<'a>(
+    text: &'a str,
+    detection: &LanguageDetection,
+    languages: &[&str],
+    default_voice: impl Fn(&str) -> &'static str,
+) -> Vec<(&'static str, &'a str)> {
+    let mut sections: Vec<(&'static str, &'a str)> = Vec::new();
+    let mut start = 0;
+    for sentence in split_sentences(text) {
+        let voice = detect(sentence, languages)
+            .filter(|detected| detected.confidence >= detection.min_confidence)
+            .map_or_else(|| default_voice(sentence), |detected| detected.voice);
+        let offset = sentence.as_ptr() as usize - text.as_ptr() as usize;
+        let end = offset + sentence.len();
+        match sections.last_mut() {
+            Some((last_voice, section)) if *last_voice == voice => {
+                *section = &text[start..end];
+            }
+            _ => {
+                start = offset;
+                sections.push((voice, &text[start..end]));
+            }
+        }
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// All the languages the detector knows
+    const ALL: &[&str] = &["hy", "ru", "uk", "el", "ka", "he", "ar", "hi", "en", "de", "fr", "es", "it", "pt", "nl"];
+
+    #[test]
+    fn test_detects_languages_by_script_and_function_words() {
+        let voice = |sentence| detect(sentence, ALL).map(|detection| detection.voice);
+        assert_eq!(voice("Բարև, ինչպե՞ս ես"), Some("hy"));
+        assert_eq!(voice("Привет, как дела?"), Some("ru"));
+        assert_eq!(voice("Привіт, як справи? Це їжа."), Some("uk"));
+        assert_eq!(voice("Καλημέρα σας"), Some("el"));
+        assert_eq!(voice("The cat is on the table."), Some("en"));
+        assert_eq!(voice("Der Hund ist nicht mit mir."), Some("de"));
+        assert_eq!(voice("Je ne sais pas pour vous."), Some("fr"));
+        assert_eq!(voice("12345"), None);
+        assert_eq!(voice("Sonata"), None);
+    }
+
+    #[test]
+    fn test_short_or_ambiguous_sentences_have_low_confidence() {
+        let confidence = |sentence| detect(sentence, ALL).unwrap().confidence;
+        assert_eq!(confidence("The cat and the dog."), 1.0);
+        assert!(confidence("Hello the world") < 1.0);
+        // `is` is a function word of English and Dutch
+        assert!(confidence("The cat is on the table.") < 1.0);
+        // `de` and `que` are function words of French, Spanish and Portuguese
+        assert_eq!(confidence("de que"), 0.0);
+    }
+
+    #[test]
+    fn test_split_by_voice_falls_back_below_the_threshold() {
+        let detection = LanguageDetection::default();
+        let text = "Բարև ձեզ։ The cat is on the table. And it is not a dog! Sonata.";
+        let sections = split_by_voice(text, &detection, ALL, |_| "hy+en");
+        assert_eq!(
+            sections,
+            [
+                ("hy", "Բարև ձեզ։"),
+                ("en", "The cat is on the table. And it is not a dog!"),
+                ("hy+en", "Sonata."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_trained_languages_are_detected() {
+        let voice = |sentence, languages| detect(sentence, languages).map(|detection| detection.voice);
+        assert_eq!(voice("Der Hund ist nicht mit mir.", &["en"]), None);
+        assert_eq!(voice("Привіт, як справи? Це їжа.", &["ru"]), Some("ru"));
+        assert_eq!(voice("Καλημέρα σας", &["hy", "en"]), None);
+        // Dutch shares `is` with English, but isn't a candidate
+        assert_eq!(detect("The cat is on the table.", &["en"]).unwrap().confidence, 1.0);
+        let sections = split_by_voice(
+            "Բարև ձեզ։ Der Hund ist nicht mit mir. The cat is on the table.",
+            &LanguageDetection::default(),
+            &["hy", "en"],
+            |_| "hy+en",
+        );
+        assert_eq!(
+            sections,
+            [
+                ("hy", "Բարև ձեզ։"),
+                ("hy+en", "Der Hund ist nicht mit mir."),
+                ("en", "The cat is on the table."),
+            ]
+        );
+    }
+}
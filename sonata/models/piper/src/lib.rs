@@ -1,22 +1,39 @@
 use espeak_phonemizer::text_to_phonemes;
 use libtashkeel_base::do_tashkeel;
 use ndarray::Axis;
-use ndarray::{Array, Array1, Array2, ArrayView, Dim, IxDynImpl};
+use ndarray::{Array, Array1, Array2, Array3, ArrayView, Dim, Ix3, IxDynImpl};
 use ort::{Session, SessionInputs, SessionOutputs, Value};
 use serde::Deserialize;
 use sonata_core::{
-    Audio, AudioInfo, AudioSamples, AudioStreamIterator, Phonemes, SonataAudioResult, SonataError,
-    SonataModel, SonataResult,
+    Audio, AudioInfo, AudioSamples, AudioStreamIterator, CancellationToken, CostEstimate, NoiseTensor, PhonemeDurations, PhonemeTiming,
+    Phonemes, RawOutput, SonataAudioResult, SonataError, SonataModel, SonataResult, StableHasher, SynthesisOverrides,
 };
 use std::any::Any;
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::ptr::null;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, OnceLock, RwLock, RwLockReadGuard};
 use regex::Regex;
 
+mod durations;
+mod incremental;
+mod language;
+mod noise;
+mod ort_library;
+mod providers;
+mod style;
+mod truncation;
+mod voice_pack;
+pub use language::LanguageDetection;
+pub use ort_library::{ort_library_path, set_ort_library_path, ORT_DYLIB_PATH_ENV_VAR};
+pub use providers::{set_default_execution_providers, ExecutionProvider};
+pub use voice_pack::{validate_voice_pack, VoiceCheck, VoiceStatus};
+
 const MIN_CHUNK_SIZE: isize = 44;
 const MAX_CHUNK_SIZE: usize = 1024;
 const BOS: char = '^';
@@ -32,6 +49,56 @@ where
     HashMap::from_iter(input.iter().map(|(k, v)| (v.to_owned(), k.to_owned())))
 }
 
+/// Copy all the outputs of a session, for [`SonataModel::infer_raw`]
+fn raw_outputs(outputs: &SessionOutputs) -> SonataResult<Vec<RawOutput>> {
+    outputs
+        .iter()
+        .map(|(name, value)| match value.try_extract_tensor::<f32>() {
+            Ok(tensor) => Ok(RawOutput {
+                name: name.to_string(),
+                shape: tensor.shape().to_vec(),
+                data: tensor.iter().copied().collect(),
+            }),
+            Err(e) => Err(SonataError::OperationError(format!(
+                "Failed to read the model output `{}`. Error: {}",
+                name, e
+            ))),
+        })
+        .collect()
+}
+
+/// Optional inputs of VITS variants for one inference
+#[derive(Clone, Copy, Default)]
+struct ExtraInputs<'a> {
+    /// A reference style embedding, for models with a style input
+    style: Option<&'a [f32]>,
+    /// Noise to feed instead of sampling it, for models with noise inputs
+    noise: Option<&'a [NoiseTensor]>,
+}
+
+/// Insert the style embedding and the noise of `extra` into the `inputs` of a session, at the
+/// position of their input. `sequence_len` is the number of input ids.
+fn insert_extra_inputs(
+    inputs: &mut Vec<ort::SessionInputValue>,
+    style_input: Option<&style::StyleInput>,
+    noise_inputs: &[noise::NoiseInput],
+    extra: ExtraInputs,
+    sequence_len: usize,
+) -> SonataResult<()> {
+    // Noise that has to be reproducible (e.g. from a seeded synthesizer) comes in `extra`
+    let mut rng = rand::thread_rng();
+    let mut tensors = noise::noise_tensors(noise_inputs, extra.noise, sequence_len, &mut rng)?;
+    tensors.extend(style::style_tensor(style_input, extra.style)?);
+    tensors.sort_by_key(|(index, _)| *index);
+    for (index, tensor) in tensors {
+        inputs.insert(
+            index.min(inputs.len()),
+            ort::SessionInputValue::from(Value::from_array(tensor).unwrap()),
+        );
+    }
+    Ok(())
+}
+
 fn load_model_config(config_path: &Path) -> SonataResult<(ModelConfig, PiperSynthesisConfig)> {
     let file = match File::open(config_path) {
         Ok(file) => file,
@@ -66,49 +133,261 @@ fn create_tashkeel_engine(
     config: &ModelConfig,
 ) -> SonataResult<Option<libtashkeel_base::DynamicInferenceEngine>> {
     if config.espeak.voice == "ar" {
-        match libtashkeel_base::create_inference_engine(None) {
-            Ok(engine) => Ok(Some(engine)),
-            Err(msg) => Err(SonataError::OperationError(format!(
-                "Failed to create inference engine for libtashkeel. {}",
-                msg
-            ))),
-        }
+        new_tashkeel_engine().map(Some)
     } else {
         Ok(None)
     }
 }
 
-fn create_inference_session(model_path: &Path) -> Result<ort::Session, ort::Error> {
-    Session::builder()?
-        // .with_parallel_execution(true)?
-        // .with_inter_threads(16)?
-        // .with_optimization_level(ort::GraphOptimizationLevel::Level3)?
-        // .with_memory_pattern(false)?
-        .commit_from_file(model_path)
+/// Create the engine that adds diacritics to Arabic text. It runs on the onnxruntime
+/// environment of the models, so it can't be created after [`release_ort_environment`].
+pub fn new_tashkeel_engine() -> SonataResult<libtashkeel_base::DynamicInferenceEngine> {
+    let _environment = ort_environment()?;
+    match libtashkeel_base::create_inference_engine(None) {
+        Ok(engine) => Ok(engine),
+        Err(msg) => Err(SonataError::OperationError(format!(
+            "Failed to create inference engine for libtashkeel. {}",
+            msg
+        ))),
+    }
+}
+
+/// Whether the ONNX Runtime environment was released by [`release_ort_environment`].
+/// Sessions are created under the read lock so that releasing can't race with loading a model.
+static ORT_ENVIRONMENT_RELEASED: RwLock<bool> = RwLock::new(false);
+
+/// Load onnxruntime for creating a session, which must be done while holding the returned
+/// guard. Fails once the environment was released: ort's global handle still points to it.
+fn ort_environment() -> SonataResult<RwLockReadGuard<'static, bool>> {
+    let released = ORT_ENVIRONMENT_RELEASED.read().unwrap();
+    if *released {
+        return Err(SonataError::OperationError(
+            "Cannot create an inference session after the onnxruntime environment was released"
+                .to_string(),
+        ));
+    }
+    ort_library::load()?;
+    Ok(released)
+}
+
+fn create_inference_session(
+    model_path: &Path,
+    execution_providers: Option<&[ExecutionProvider]>,
+) -> SonataResult<ort::Session> {
+    let _environment = ort_environment()?;
+    let execution_providers = providers::session_execution_providers(execution_providers);
+    let session = Session::builder().and_then(|builder| {
+        let builder = if execution_providers.is_empty() {
+            builder
+        } else {
+            builder.with_execution_providers(execution_providers)?
+        };
+        builder
+            // .with_parallel_execution(true)?
+            // .with_inter_threads(16)?
+            // .with_optimization_level(ort::GraphOptimizationLevel::Level3)?
+            // .with_memory_pattern(false)?
+            .commit_from_file(model_path)
+    });
+    match session {
+        Ok(session) => Ok(session),
+        Err(err) => Err(SonataError::OperationError(format!(
+            "Failed to initialize onnxruntime inference session: `{}`",
+            err
+        ))),
+    }
+}
+
+/// Release the global onnxruntime environment, e.g. for a clean shutdown under a leak checker.
+///
+/// This must be the last call into sonata: no model can be loaded afterwards. It fails if
+/// any inference session is still alive, so drop all models (and the synthesizers holding
+/// them) first. Calling it again after a successful release does nothing.
+pub fn release_ort_environment() -> SonataResult<()> {
+    let mut released = ORT_ENVIRONMENT_RELEASED.write().unwrap();
+    if *released {
+        return Ok(());
+    }
+    let environment = match ort::get_environment() {
+        Ok(environment) => environment,
+        Err(err) => {
+            return Err(SonataError::OperationError(format!(
+                "Failed to access the onnxruntime environment: `{}`",
+                err
+            )))
+        }
+    };
+    // Every session holds a reference to the environment, besides the global one
+    let num_sessions = Arc::strong_count(environment) - 1;
+    if num_sessions > 0 {
+        return Err(SonataError::OperationError(format!(
+            "Cannot release the onnxruntime environment while {} inference session(s) are still alive",
+            num_sessions
+        )));
+    }
+    // The global reference is never dropped, so releasing the environment here can't be
+    // followed by a second release. It still points to the released environment though, so
+    // every path that creates a session checks the flag first (see `ort_environment`).
+    unsafe {
+        if let Some(release_env) = ort::api().as_ref().ReleaseEnv {
+            release_env(environment.ptr());
+        }
+    }
+    *released = true;
+    Ok(())
+}
+
+/// The ONNX files of a model
+struct ModelWeights {
+    paths: Vec<PathBuf>,
+    size: u64,
+    digest: OnceLock<u64>,
+}
+
+impl ModelWeights {
+    fn new(paths: &[&Path]) -> Self {
+        Self {
+            paths: Vec::from_iter(paths.iter().map(|path| path.to_path_buf())),
+            // Zero if the sizes can't be read
+            size: paths
+                .iter()
+                .map(|path| std::fs::metadata(path).map(|m| m.len()).unwrap_or_default())
+                .sum(),
+            digest: OnceLock::new(),
+        }
+    }
+    /// A stable hash of the contents of the files, read once on first use
+    fn digest(&self) -> SonataResult<u64> {
+        if let Some(digest) = self.digest.get() {
+            return Ok(*digest);
+        }
+        let mut state = StableHasher::default();
+        for path in self.paths.iter() {
+            let read_error = |e: std::io::Error| {
+                SonataError::OperationError(format!("Failed to read `{}`. {}", path.display(), e))
+            };
+            let mut reader = BufReader::new(File::open(path).map_err(read_error)?);
+            loop {
+                let bytes = reader.fill_buf().map_err(read_error)?;
+                if bytes.is_empty() {
+                    break;
+                }
+                state.write(bytes);
+                let len = bytes.len();
+                reader.consume(len);
+            }
+        }
+        Ok(*self.digest.get_or_init(|| state.finish()))
+    }
+}
+
+/// A step reported while loading a model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadStage {
+    ReadingConfig,
+    CreatingSession(PathBuf),
+    Finished,
+}
+
+impl fmt::Display for LoadStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadStage::ReadingConfig => write!(f, "reading config"),
+            LoadStage::CreatingSession(path) => {
+                write!(f, "creating session for `{}`", path.display())
+            }
+            LoadStage::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+pub type LoadProgressCallback = Box<dyn Fn(&LoadStage) + Send + Sync>;
+
+/// Options for [`from_config_path_with_options`]
+#[derive(Default)]
+pub struct LoadOptions {
+    /// Called at the start of every load stage
+    pub on_progress: Option<LoadProgressCallback>,
+    /// Checked before every load stage. A stage that has already started
+    /// (e.g. creating an onnxruntime session) runs to completion.
+    pub cancellation_token: Option<CancellationToken>,
+    /// Execution providers for the model's sessions, in order of preference.
+    /// Defaults to the ones given to [`set_default_execution_providers`].
+    pub execution_providers: Option<Vec<ExecutionProvider>>,
+    /// Run inference once more when the output is implausibly short for its input
+    /// (see [`Audio::truncation_suspected`]), keeping the better of the two takes
+    pub retry_truncated_output: bool,
+    /// Pick the espeak voice of each sentence by detecting its language, instead of
+    /// using the model's default voice for all of them
+    pub language_detection: Option<LanguageDetection>,
+}
+
+impl LoadOptions {
+    fn report(&self, stage: LoadStage) -> SonataResult<()> {
+        if self
+            .cancellation_token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Err(SonataError::OperationError(
+                "Model loading was cancelled".to_string(),
+            ));
+        }
+        if let Some(ref on_progress) = self.on_progress {
+            on_progress(&stage);
+        }
+        Ok(())
+    }
 }
 
 pub fn from_config_path(config_path: &Path) -> SonataResult<Arc<dyn SonataModel + Send + Sync>> {
+    from_config_path_with_options(config_path, &LoadOptions::default())
+}
+
+/// Load a model, reporting progress and allowing the load to be cancelled
+pub fn from_config_path_with_options(
+    config_path: &Path,
+    options: &LoadOptions,
+) -> SonataResult<Arc<dyn SonataModel + Send + Sync>> {
+    options.report(LoadStage::ReadingConfig)?;
     let (config, synth_config) = load_model_config(config_path)?;
-    if config.streaming.unwrap_or_default() {
-        Ok(Arc::new(VitsStreamingModel::from_config(
+    let onnx_paths = onnx_paths(config_path, &config)?;
+    let model: Arc<dyn SonataModel + Send + Sync> = if config.streaming.unwrap_or_default() {
+        Arc::new(VitsStreamingModel::from_config(
             config,
             synth_config,
-            &config_path.with_file_name("encoder.onnx"),
-            &config_path.with_file_name("decoder.onnx"),
-        )?))
+            &onnx_paths[0],
+            &onnx_paths[1],
+            options,
+        )?)
     } else {
-        let Some(onnx_filename) = config_path.file_stem() else {
-            return Err(SonataError::OperationError(format!(
-                "Invalid config filename format `{}`",
-                config_path.display()
-            )));
-        };
-        Ok(Arc::new(VitsModel::from_config(
+        Arc::new(VitsModel::from_config(
             config,
             synth_config,
-            &config_path.with_file_name(onnx_filename),
-        )?))
+            &onnx_paths[0],
+            options,
+        )?)
+    };
+    options.report(LoadStage::Finished)?;
+    Ok(model)
+}
+
+/// The ONNX files of a model: the encoder and the decoder for streaming models,
+/// or the config path without its `.json` extension otherwise
+fn onnx_paths(config_path: &Path, config: &ModelConfig) -> SonataResult<Vec<PathBuf>> {
+    if config.streaming.unwrap_or_default() {
+        return Ok(vec![
+            config_path.with_file_name("encoder.onnx"),
+            config_path.with_file_name("decoder.onnx"),
+        ]);
     }
+    let Some(onnx_filename) = config_path.file_stem() else {
+        return Err(SonataError::OperationError(format!(
+            "Invalid config filename format `{}`",
+            config_path.display()
+        )));
+    };
+    Ok(vec![config_path.with_file_name(onnx_filename)])
 }
 
 #[derive(Deserialize, Default)]
@@ -173,6 +452,7 @@ trait VitsModelCommons {
     fn get_config(&self) -> &ModelConfig;
     fn get_speaker_map(&self) -> &HashMap<i64, String>;
     fn get_tashkeel_engine(&self) -> Option<&libtashkeel_base::DynamicInferenceEngine>;
+    fn get_language_detection(&self) -> Option<&LanguageDetection>;
     fn get_meta_ids(&self) -> (i64, i64, i64) {
         let config = self.get_config();
         let pad_id = *config.phoneme_id_map.get(&PAD).unwrap().first().unwrap();
@@ -180,6 +460,60 @@ trait VitsModelCommons {
         let eos_id = *config.phoneme_id_map.get(&EOS).unwrap().first().unwrap();
         (pad_id, bos_id, eos_id)
     }
+    /// Number of phoneme ids in `input_ids`, not counting padding and the start and end markers
+    fn count_phonemes(&self, input_ids: &[i64]) -> usize {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        input_ids
+            .iter()
+            .filter(|id| ![pad_id, bos_id, eos_id].contains(id))
+            .count()
+    }
+    /// The symbol of each phoneme id, e.g. `_` for padding
+    fn input_id_symbols(&self, input_ids: &[i64]) -> Vec<String> {
+        let symbols: HashMap<i64, char> = self
+            .get_config()
+            .phoneme_id_map
+            .iter()
+            .filter_map(|(symbol, ids)| Some((*ids.first()?, *symbol)))
+            .collect();
+        input_ids
+            .iter()
+            .map(|id| symbols.get(id).map_or_else(|| id.to_string(), char::to_string))
+            .collect()
+    }
+    /// When each phoneme of `input_ids` is pronounced, from the duration of every id in frames.
+    /// Padding and the start and end markers are left out, as gaps between the phonemes.
+    fn phoneme_timings(&self, input_ids: &[i64], frames: &[f32]) -> Vec<PhonemeTiming> {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let sample_rate = self.get_config().audio.sample_rate as usize;
+        self.input_id_symbols(input_ids)
+            .into_iter()
+            .zip(input_ids)
+            .zip(durations::spans_ms(frames, sample_rate))
+            .filter(|((_, id), _)| ![pad_id, bos_id, eos_id].contains(id))
+            .map(|((phoneme, _), (start_ms, end_ms))| PhonemeTiming {
+                phoneme,
+                start_ms,
+                end_ms,
+            })
+            .collect()
+    }
+    fn estimate_cost_of(&self, phonemes: &str) -> CostEstimate {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let input_ids = self.phonemes_to_input_ids(phonemes, pad_id, bos_id, eos_id);
+        let num_phonemes = self.count_phonemes(&input_ids);
+        let length_scale = self.get_synth_config().read().unwrap().length_scale;
+        let num_frames = durations::estimate_frames(
+            num_phonemes,
+            self.get_config().audio.sample_rate as usize,
+            length_scale,
+        );
+        CostEstimate {
+            num_phonemes,
+            num_frames,
+            num_samples: num_frames * truncation::SAMPLES_PER_FRAME,
+        }
+    }
     fn language(&self) -> Option<String> {
         self.get_config()
             .language
@@ -197,6 +531,37 @@ trait VitsModelCommons {
                 .unwrap_or("unknown".to_string()),
         )])
     }
+    /// The languages the voice was trained on: those of its espeak voice and its language
+    /// code, e.g. `en` for `en-us` and `en_US`
+    fn trained_languages(&self) -> Vec<String> {
+        let config = self.get_config();
+        let primary = |code: &str| code.split(['-', '_', '+']).next().unwrap_or_default().to_lowercase();
+        let mut languages = vec![primary(&config.espeak.voice)];
+        if let Some(ref language) = config.language {
+            languages.push(primary(&language.code));
+        }
+        languages.dedup();
+        languages
+    }
+    /// The model is identified by its voice key, its settings and the contents of its weights
+    fn hash_model_state(&self, mut state: &mut dyn Hasher, weights_digest: u64) {
+        let config = self.get_config();
+        config.key.hash(&mut state);
+        config.audio.sample_rate.hash(&mut state);
+        config.audio.quality.hash(&mut state);
+        config.espeak.voice.hash(&mut state);
+        config.num_speakers.hash(&mut state);
+        weights_digest.hash(&mut state);
+        self.get_tashkeel_engine().is_some().hash(&mut state);
+        self.get_language_detection()
+            .map(|detection| detection.min_confidence.to_bits())
+            .hash(&mut state);
+        let synth_config = self.get_synth_config().read().unwrap();
+        synth_config.speaker.hash(&mut state);
+        synth_config.length_scale.to_bits().hash(&mut state);
+        synth_config.noise_scale.to_bits().hash(&mut state);
+        synth_config.noise_w.to_bits().hash(&mut state);
+    }
     fn factory_synthesis_config(&self) -> PiperSynthesisConfig {
         let config = self.get_config();
 
@@ -215,13 +580,18 @@ trait VitsModelCommons {
     fn speakers(&self) -> SonataResult<HashMap<i64, String>> {
         Ok(self.get_speaker_map().clone())
     }
+    /// Speakers missing from the speaker map can still be selected by id
+    fn has_speaker(&self, sid: i64) -> bool {
+        self.get_speaker_map().contains_key(&sid)
+            || (0..self.get_config().num_speakers as i64).contains(&sid)
+    }
     fn _do_set_default_synth_config(&self, new_config: &PiperSynthesisConfig) -> SonataResult<()> {
         let mut synth_config = self.get_synth_config().write().unwrap();
         synth_config.length_scale = new_config.length_scale;
         synth_config.noise_scale = new_config.noise_scale;
         synth_config.noise_w = new_config.noise_w;
         if let Some(sid) = new_config.speaker {
-            if self.get_speaker_map().contains_key(&sid) {
+            if self.has_speaker(sid) {
                 synth_config.speaker = Some(sid);
             } else {
                 return Err(SonataError::OperationError(format!(
@@ -232,6 +602,59 @@ trait VitsModelCommons {
         }
         Ok(())
     }
+    fn _do_apply_synthesis_overrides(&self, overrides: &SynthesisOverrides) -> SonataResult<()> {
+        let config = self.get_config();
+        let mut conflicts = Vec::new();
+        if let Some(sid) = overrides.speaker {
+            if config.num_speakers <= 1 {
+                conflicts.push(format!(
+                    "speaker `{}` was given, but the model has a single speaker",
+                    sid
+                ));
+            } else if !self.has_speaker(sid) {
+                conflicts.push(format!(
+                    "no speaker was found with the id `{}` (the model has {} speakers)",
+                    sid, config.num_speakers
+                ));
+            }
+        }
+        let scales = [
+            ("noise_scale", overrides.noise_scale),
+            ("length_scale", overrides.length_scale),
+            ("noise_w", overrides.noise_w),
+        ];
+        for (name, value) in scales {
+            match value {
+                Some(value) if !value.is_finite() || value < 0.0 => {
+                    conflicts.push(format!("`{}` must be a non-negative number, got {}", name, value))
+                }
+                Some(value) if name == "length_scale" && value == 0.0 => {
+                    conflicts.push("`length_scale` must be greater than zero".to_string())
+                }
+                _ => {}
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(SonataError::OperationError(format!(
+                "Invalid synthesis overrides: {}",
+                conflicts.join("; ")
+            )));
+        }
+        let mut synth_config = self.get_synth_config().write().unwrap();
+        if let Some(sid) = overrides.speaker {
+            synth_config.speaker = Some(sid);
+        }
+        if let Some(noise_scale) = overrides.noise_scale {
+            synth_config.noise_scale = noise_scale;
+        }
+        if let Some(length_scale) = overrides.length_scale {
+            synth_config.length_scale = length_scale;
+        }
+        if let Some(noise_w) = overrides.noise_w {
+            synth_config.noise_w = noise_w;
+        }
+        Ok(())
+    }
     fn phonemes_to_input_ids(
         &self,
         phonemes: &str,
@@ -344,15 +767,18 @@ trait VitsModelCommons {
     }
 
     fn process_non_ipa_section(&self, phonemes_string: &mut String, text: &str, config: &ModelConfig) -> SonataResult<()> {
-        let russian_regex = regex::Regex::new(r"[А-Яа-я]").unwrap();
-
-        // Determine the language: 'ru' if Russian characters are found, otherwise 'hy'
-        let language = if russian_regex.is_match(text) {
-            "ru+en"
-        } else {
-            "hy+en"
+        let Some(detection) = self.get_language_detection() else {
+            return self.phonemize_with_voice(phonemes_string, text, default_voice(text));
         };
+        let languages = self.trained_languages();
+        let languages = Vec::from_iter(languages.iter().map(String::as_str));
+        for (voice, section) in language::split_by_voice(text, detection, &languages, default_voice) {
+            self.phonemize_with_voice(phonemes_string, section, voice)?;
+        }
+        Ok(())
+    }
 
+    fn phonemize_with_voice(&self, phonemes_string: &mut String, text: &str, language: &str) -> SonataResult<()> {
         // Get the phonemes result
         let result = match text_to_phonemes(text, language, None, true, false) {
             Ok(ph) => ph,
@@ -399,18 +825,36 @@ trait VitsModelCommons {
     }
 }
 
+/// The espeak voice of text whose language isn't detected: 'ru' if Russian characters are
+/// found, otherwise 'hy'
+fn default_voice(text: &str) -> &'static str {
+    let russian_regex = regex::Regex::new(r"[А-Яа-я]").unwrap();
+    if russian_regex.is_match(text) {
+        "ru+en"
+    } else {
+        "hy+en"
+    }
+}
+
 pub struct VitsModel {
     synth_config: RwLock<PiperSynthesisConfig>,
     config: ModelConfig,
     speaker_map: HashMap<i64, String>,
     session: ort::Session,
+    style_input: Option<style::StyleInput>,
+    noise_inputs: Vec<noise::NoiseInput>,
     tashkeel_engine: Option<libtashkeel_base::DynamicInferenceEngine>,
+    weights: ModelWeights,
+    retry_truncated_output: bool,
+    language_detection: Option<LanguageDetection>,
 }
 
 impl VitsModel {
     pub fn new(config_path: PathBuf, onnx_path: &Path) -> SonataResult<Self> {
         match load_model_config(&config_path) {
-            Ok((config, synth_config)) => Self::from_config(config, synth_config, onnx_path),
+            Ok((config, synth_config)) => {
+                Self::from_config(config, synth_config, onnx_path, &LoadOptions::default())
+            }
             Err(error) => Err(error),
         }
     }
@@ -418,39 +862,57 @@ impl VitsModel {
         config: ModelConfig,
         synth_config: PiperSynthesisConfig,
         onnx_path: &Path,
+        options: &LoadOptions,
     ) -> SonataResult<Self> {
-        let session = match create_inference_session(onnx_path) {
-            Ok(session) => session,
-            Err(err) => {
-                return Err(SonataError::OperationError(format!(
-                    "Failed to initialize onnxruntime inference session: `{}`",
-                    err
-                )))
-            }
-        };
+        options.report(LoadStage::CreatingSession(onnx_path.to_path_buf()))?;
+        let session = create_inference_session(onnx_path, options.execution_providers.as_deref())?;
         let speaker_map = reversed_mapping(&config.speaker_id_map);
-        let tashkeel_engine = if config.espeak.voice == "ar" {
-            match libtashkeel_base::create_inference_engine(None) {
-                Ok(engine) => Some(engine),
-                Err(msg) => {
-                    return Err(SonataError::OperationError(format!(
-                        "Failed to create inference engine for libtashkeel. {}",
-                        msg
-                    )))
-                }
-            }
-        } else {
-            None
-        };
+        let tashkeel_engine = create_tashkeel_engine(&config)?;
         Ok(Self {
             synth_config: RwLock::new(synth_config),
             config,
             speaker_map,
+            style_input: style::StyleInput::find(&session),
+            noise_inputs: noise::NoiseInput::find_all(&session),
             session,
             tashkeel_engine,
+            weights: ModelWeights::new(&[onnx_path]),
+            retry_truncated_output: options.retry_truncated_output,
+            language_detection: options.language_detection.clone(),
+        })
+    }
+    fn infer_with_values(&self, input_phonemes: Vec<i64>, extra: ExtraInputs) -> SonataAudioResult {
+        let sample_rate = self.config.audio.sample_rate as usize;
+        let length_scale = self.synth_config.read().unwrap().length_scale;
+        let num_phonemes = self.count_phonemes(&input_phonemes);
+        truncation::infer_checked(self.retry_truncated_output, || {
+            let ((audio, frames), inference_ms) = self.run_inference(input_phonemes.clone(), extra, |outputs| {
+                let extract = |output: &ort::DynValue| match output.try_extract_tensor::<f32>() {
+                    Ok(out) => Ok(Vec::from_iter(out.view().iter().copied())),
+                    Err(e) => Err(SonataError::OperationError(format!(
+                        "Failed to run model inference. Error: {}",
+                        e
+                    ))),
+                };
+                // Only some exports output the predicted durations
+                let frames = outputs.get("p_duration").map(extract).transpose()?;
+                Ok((extract(&outputs[0])?, frames))
+            })?;
+            let truncation_suspected =
+                truncation::is_short_for_phonemes(audio.len(), sample_rate, num_phonemes, length_scale);
+            let mut audio = Audio::new(audio.into(), sample_rate, Some(inference_ms));
+            audio.phoneme_timings = frames.map(|frames| self.phoneme_timings(&input_phonemes, &frames));
+            Ok(truncation::flagged(audio, truncation_suspected))
         })
     }
-    fn infer_with_values(&self, input_phonemes: Vec<i64>) -> SonataAudioResult {
+    /// Run the session and read its outputs with `read_outputs`,
+    /// returning the result along with the inference time in milliseconds
+    fn run_inference<T>(
+        &self,
+        input_phonemes: Vec<i64>,
+        extra: ExtraInputs,
+        read_outputs: impl FnOnce(&SessionOutputs) -> SonataResult<T>,
+    ) -> SonataResult<(T, f32)> {
         let synth_config = self.synth_config.read().unwrap();
 
         let input_len = input_phonemes.len();
@@ -487,6 +949,7 @@ impl VitsModel {
                     Value::from_array(sid_tensor).unwrap()
                 ));
             }
+            insert_extra_inputs(&mut inputs, self.style_input.as_ref(), &self.noise_inputs, extra, input_len)?;
             match session.run(SessionInputs::from(inputs.as_slice())) {
                 Ok(out) => out,
                 Err(e) => {
@@ -498,24 +961,7 @@ impl VitsModel {
             }
         };
         let inference_ms = timer.elapsed().as_millis() as f32;
-
-        let outputs = match outputs[0].try_extract_tensor::<f32>() {
-            Ok(out) => out,
-            Err(e) => {
-                return Err(SonataError::OperationError(format!(
-                    "Failed to run model inference. Error: {}",
-                    e
-                )))
-            }
-        };
-
-        let audio = Vec::from(outputs.view().as_slice().unwrap());
-
-        Ok(Audio::new(
-            audio.into(),
-            self.config.audio.sample_rate as usize,
-            Some(inference_ms),
-        ))
+        Ok((read_outputs(&outputs)?, inference_ms))
     }
     pub fn get_input_output_info(&self) -> SonataResult<Vec<String>> {
         todo!()
@@ -535,6 +981,9 @@ impl VitsModelCommons for VitsModel {
     fn get_tashkeel_engine(&self) -> Option<&libtashkeel_base::DynamicInferenceEngine> {
         self.tashkeel_engine.as_ref()
     }
+    fn get_language_detection(&self) -> Option<&LanguageDetection> {
+        self.language_detection.as_ref()
+    }
 }
 
 impl SonataModel for VitsModel {
@@ -551,7 +1000,7 @@ impl SonataModel for VitsModel {
         );
         let mut retval = Vec::new();
         for phonemes in phoneme_batches.into_iter() {
-            retval.push(self.infer_with_values(phonemes)?);
+            retval.push(self.infer_with_values(phonemes, ExtraInputs::default())?);
         }
         Ok(retval)
     }
@@ -559,7 +1008,7 @@ impl SonataModel for VitsModel {
     fn speak_one_sentence(&self, phonemes: String) -> SonataAudioResult {
         let (pad_id, bos_id, eos_id) = self.get_meta_ids();
         let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
-        self.infer_with_values(phonemes)
+        self.infer_with_values(phonemes, ExtraInputs::default())
     }
     fn get_default_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
         Ok(Box::new(PiperSynthesisConfig {
@@ -586,12 +1035,59 @@ impl SonataModel for VitsModel {
     fn get_speakers(&self) -> SonataResult<Option<&HashMap<i64, String>>> {
         Ok(Some(self.get_speaker_map()))
     }
-    fn speaker_name_to_id(&self, name: &str) -> SonataResult<Option<i64>> {
-        Ok(self.config.speaker_id_map.get(name).copied())
-    }
     fn properties(&self) -> SonataResult<HashMap<String, String>> {
         Ok(self.get_properties())
     }
+    fn apply_synthesis_overrides(&self, overrides: &SynthesisOverrides) -> SonataResult<()> {
+        self._do_apply_synthesis_overrides(overrides)
+    }
+    /// Estimated from the size of the ONNX model files, since the weights dominate the
+    /// memory of a loaded session. Doesn't include onnxruntime's allocations for
+    /// intermediate tensors (which grow with input length) or the tashkeel model.
+    fn estimated_memory_bytes(&self) -> Option<u64> {
+        Some(self.weights.size)
+    }
+    fn hash_output_state(&self, state: &mut dyn Hasher) -> SonataResult<()> {
+        self.hash_model_state(state, self.weights.digest()?);
+        Ok(())
+    }
+    fn infer_raw(&self, phonemes: String) -> SonataResult<Vec<RawOutput>> {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let input_phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        Ok(self.run_inference(input_phonemes, ExtraInputs::default(), raw_outputs)?.0)
+    }
+    fn estimate_cost(&self, phonemes: &str) -> SonataResult<CostEstimate> {
+        Ok(self.estimate_cost_of(phonemes))
+    }
+    fn has_style_input(&self) -> bool {
+        self.style_input.is_some()
+    }
+    fn speak_with_style(&self, phonemes: String, style: &[f32]) -> SonataAudioResult {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let extra = ExtraInputs {
+            style: Some(style),
+            ..Default::default()
+        };
+        self.infer_with_values(phonemes, extra)
+    }
+    fn noise_shapes(&self, phonemes: &str) -> SonataResult<Vec<(String, Vec<usize>)>> {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let sequence_len = self.phonemes_to_input_ids(phonemes, pad_id, bos_id, eos_id).len();
+        self.noise_inputs
+            .iter()
+            .map(|input| Ok((input.name().to_string(), input.shape(sequence_len)?)))
+            .collect()
+    }
+    fn speak_with_noise(&self, phonemes: String, noise: &[NoiseTensor]) -> SonataAudioResult {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let extra = ExtraInputs {
+            noise: Some(noise),
+            ..Default::default()
+        };
+        self.infer_with_values(phonemes, extra)
+    }
     fn audio_output_info(&self) -> SonataResult<AudioInfo> {
         self.get_audio_output_info()
     }
@@ -603,7 +1099,12 @@ pub struct VitsStreamingModel {
     speaker_map: HashMap<i64, String>,
     encoder_model: ort::Session,
     decoder_model: Arc<ort::Session>,
+    style_input: Option<style::StyleInput>,
+    noise_inputs: Vec<noise::NoiseInput>,
     tashkeel_engine: Option<libtashkeel_base::DynamicInferenceEngine>,
+    weights: ModelWeights,
+    retry_truncated_output: bool,
+    language_detection: Option<LanguageDetection>,
 }
 
 impl VitsStreamingModel {
@@ -612,49 +1113,54 @@ impl VitsStreamingModel {
         synth_config: PiperSynthesisConfig,
         encoder_path: &Path,
         decoder_path: &Path,
+        options: &LoadOptions,
     ) -> SonataResult<Self> {
-        let encoder_model = match create_inference_session(encoder_path) {
-            Ok(model) => model,
-            Err(err) => {
-                return Err(SonataError::OperationError(format!(
-                    "Failed to initialize onnxruntime inference session: `{}`",
-                    err
-                )))
-            }
-        };
-        let decoder_model = match create_inference_session(decoder_path) {
-            Ok(model) => Arc::new(model),
-            Err(err) => {
-                return Err(SonataError::OperationError(format!(
-                    "Failed to initialize onnxruntime inference session: `{}`",
-                    err
-                )))
-            }
-        };
+        options.report(LoadStage::CreatingSession(encoder_path.to_path_buf()))?;
+        let encoder_model = create_inference_session(encoder_path, options.execution_providers.as_deref())?;
+        options.report(LoadStage::CreatingSession(decoder_path.to_path_buf()))?;
+        let decoder_model = Arc::new(create_inference_session(
+            decoder_path,
+            options.execution_providers.as_deref(),
+        )?);
         let speaker_map = reversed_mapping(&config.speaker_id_map);
         let tashkeel_engine = create_tashkeel_engine(&config)?;
         Ok(Self {
             synth_config: RwLock::new(synth_config),
             config,
             speaker_map,
+            style_input: style::StyleInput::find(&encoder_model),
+            noise_inputs: noise::NoiseInput::find_all(&encoder_model),
             encoder_model,
             decoder_model,
             tashkeel_engine,
+            weights: ModelWeights::new(&[encoder_path, decoder_path]),
+            retry_truncated_output: options.retry_truncated_output,
+            language_detection: options.language_detection.clone(),
         })
     }
 
-    fn infer_with_values(&self, input_phonemes: Vec<i64>) -> SonataAudioResult {
-        let timer = std::time::Instant::now();
-        let encoder_output = self.infer_encoder(input_phonemes)?;
-        let audio = encoder_output.infer_decoder(self.decoder_model.as_ref())?;
-        let inference_ms = timer.elapsed().as_millis() as f32;
-        Ok(Audio::new(
-            audio,
-            self.config.audio.sample_rate as usize,
-            Some(inference_ms),
-        ))
+    fn infer_with_values(&self, input_phonemes: Vec<i64>, extra: ExtraInputs) -> SonataAudioResult {
+        truncation::infer_checked(self.retry_truncated_output, || {
+            let timer = std::time::Instant::now();
+            let encoder_output = self.infer_encoder(input_phonemes.clone(), extra)?;
+            let num_frames = encoder_output.y_mask.sum().round() as usize;
+            let audio = encoder_output.infer_decoder(self.decoder_model.as_ref())?;
+            let inference_ms = timer.elapsed().as_millis() as f32;
+            let truncation_suspected = truncation::is_short_for_frames(audio.len(), num_frames);
+            let mut audio = Audio::new(audio, self.config.audio.sample_rate as usize, Some(inference_ms));
+            // Only encoders that output the predicted durations (`p_duration`) have timings
+            audio.phoneme_timings = encoder_output
+                .durations()
+                .ok()
+                .map(|frames| self.phoneme_timings(&input_phonemes, &frames));
+            Ok(truncation::flagged(audio, truncation_suspected))
+        })
     }
-    fn infer_encoder(&self, input_phonemes: Vec<i64>) -> SonataResult<EncoderOutputs> {
+    fn infer_encoder(
+        &self,
+        input_phonemes: Vec<i64>,
+        extra: ExtraInputs,
+    ) -> SonataResult<EncoderOutputs> {
         let synth_config = self.synth_config.read().unwrap();
 
         let input_len = input_phonemes.len();
@@ -692,6 +1198,7 @@ impl VitsStreamingModel {
                     Value::from_array(sid_tensor).unwrap()
                 ));
             }
+            insert_extra_inputs(&mut inputs, self.style_input.as_ref(), &self.noise_inputs, extra, input_len)?;
             match session.run(SessionInputs::from(inputs.as_slice())) {
                 Ok(ort_values) => EncoderOutputs::from_values(ort_values),
                 Err(e) => Err(SonataError::OperationError(format!(
@@ -716,6 +1223,9 @@ impl VitsModelCommons for VitsStreamingModel {
     fn get_tashkeel_engine(&self) -> Option<&libtashkeel_base::DynamicInferenceEngine> {
         self.tashkeel_engine.as_ref()
     }
+    fn get_language_detection(&self) -> Option<&LanguageDetection> {
+        self.language_detection.as_ref()
+    }
 }
 
 impl SonataModel for VitsStreamingModel {
@@ -732,14 +1242,14 @@ impl SonataModel for VitsStreamingModel {
         );
         let mut retval = Vec::new();
         for phonemes in phoneme_batches.into_iter() {
-            retval.push(self.infer_with_values(phonemes)?);
+            retval.push(self.infer_with_values(phonemes, ExtraInputs::default())?);
         }
         Ok(retval)
     }
     fn speak_one_sentence(&self, phonemes: String) -> SonataAudioResult {
         let (pad_id, bos_id, eos_id) = self.get_meta_ids();
         let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
-        self.infer_with_values(phonemes)
+        self.infer_with_values(phonemes, ExtraInputs::default())
     }
     fn get_default_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
         Ok(Box::new(PiperSynthesisConfig {
@@ -766,12 +1276,92 @@ impl SonataModel for VitsStreamingModel {
     fn get_speakers(&self) -> SonataResult<Option<&HashMap<i64, String>>> {
         Ok(Some(self.get_speaker_map()))
     }
-    fn speaker_name_to_id(&self, name: &str) -> SonataResult<Option<i64>> {
-        Ok(self.config.speaker_id_map.get(name).copied())
-    }
     fn properties(&self) -> SonataResult<HashMap<String, String>> {
         Ok(self.get_properties())
     }
+    fn apply_synthesis_overrides(&self, overrides: &SynthesisOverrides) -> SonataResult<()> {
+        self._do_apply_synthesis_overrides(overrides)
+    }
+    fn estimated_memory_bytes(&self) -> Option<u64> {
+        Some(self.weights.size)
+    }
+    fn hash_output_state(&self, state: &mut dyn Hasher) -> SonataResult<()> {
+        self.hash_model_state(state, self.weights.digest()?);
+        Ok(())
+    }
+    fn infer_raw(&self, phonemes: String) -> SonataResult<Vec<RawOutput>> {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let input_phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        self.infer_encoder(input_phonemes, ExtraInputs::default())?
+            .run_decoder(self.decoder_model.as_ref(), raw_outputs)
+    }
+    fn predict_phoneme_durations(&self, phonemes: String) -> SonataResult<PhonemeDurations> {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let input_ids = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let symbols = self.input_id_symbols(&input_ids);
+        let frames = self.infer_encoder(input_ids, ExtraInputs::default())?.durations()?;
+        Ok(PhonemeDurations {
+            phonemes,
+            symbols,
+            durations_ms: durations::frames_to_ms(&frames, self.config.audio.sample_rate as usize),
+        })
+    }
+    /// The latent frames of each symbol are stretched or squeezed to the new duration before
+    /// decoding, so large changes sound less natural than a different `length_scale`
+    fn speak_with_durations(&self, durations: &PhonemeDurations) -> SonataAudioResult {
+        let sample_rate = self.config.audio.sample_rate as usize;
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let input_ids = self.phonemes_to_input_ids(&durations.phonemes, pad_id, bos_id, eos_id);
+        if durations.durations_ms.len() != input_ids.len() {
+            return Err(SonataError::OperationError(format!(
+                "Expected {} phoneme durations (one per input symbol), got {}",
+                input_ids.len(),
+                durations.durations_ms.len()
+            )));
+        }
+        let frames = durations::ms_to_frames(&durations.durations_ms, sample_rate)?;
+        let timer = std::time::Instant::now();
+        let phoneme_timings = self.phoneme_timings(&input_ids, &frames);
+        let mut encoder_outputs = self.infer_encoder(input_ids, ExtraInputs::default())?;
+        encoder_outputs.retime(&frames)?;
+        let audio = encoder_outputs.infer_decoder(self.decoder_model.as_ref())?;
+        let inference_ms = timer.elapsed().as_millis() as f32;
+        let mut audio = Audio::new(audio, sample_rate, Some(inference_ms));
+        audio.phoneme_timings = Some(phoneme_timings);
+        Ok(audio)
+    }
+    fn estimate_cost(&self, phonemes: &str) -> SonataResult<CostEstimate> {
+        Ok(self.estimate_cost_of(phonemes))
+    }
+    fn has_style_input(&self) -> bool {
+        self.style_input.is_some()
+    }
+    fn speak_with_style(&self, phonemes: String, style: &[f32]) -> SonataAudioResult {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let extra = ExtraInputs {
+            style: Some(style),
+            ..Default::default()
+        };
+        self.infer_with_values(phonemes, extra)
+    }
+    fn noise_shapes(&self, phonemes: &str) -> SonataResult<Vec<(String, Vec<usize>)>> {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let sequence_len = self.phonemes_to_input_ids(phonemes, pad_id, bos_id, eos_id).len();
+        self.noise_inputs
+            .iter()
+            .map(|input| Ok((input.name().to_string(), input.shape(sequence_len)?)))
+            .collect()
+    }
+    fn speak_with_noise(&self, phonemes: String, noise: &[NoiseTensor]) -> SonataAudioResult {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let extra = ExtraInputs {
+            noise: Some(noise),
+            ..Default::default()
+        };
+        self.infer_with_values(phonemes, extra)
+    }
     fn audio_output_info(&self) -> SonataResult<AudioInfo> {
         self.get_audio_output_info()
     }
@@ -786,7 +1376,7 @@ impl SonataModel for VitsStreamingModel {
     ) -> SonataResult<AudioStreamIterator> {
         let (pad_id, bos_id, eos_id) = self.get_meta_ids();
         let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
-        let encoder_outputs = self.infer_encoder(phonemes)?;
+        let encoder_outputs = self.infer_encoder(phonemes, ExtraInputs::default())?;
         let streamer = Box::new(SpeechStreamer::new(
             Arc::clone(&self.decoder_model),
             encoder_outputs,
@@ -795,12 +1385,91 @@ impl SonataModel for VitsStreamingModel {
         ));
         Ok(streamer)
     }
+    fn supports_incremental_synthesis(&self) -> bool {
+        // The frames of each group of words are found from the predicted durations
+        self.encoder_model
+            .outputs
+            .iter()
+            .any(|output| output.name == "p_duration")
+    }
+    fn stream_incremental_synthesis(
+        &self,
+        phonemes: String,
+        chunk_padding: usize,
+    ) -> SonataResult<AudioStreamIterator> {
+        if !self.supports_incremental_synthesis() {
+            return Err(SonataError::OperationError(
+                "Incremental synthesis needs an encoder that outputs phoneme durations (`p_duration`)"
+                    .to_string(),
+            ));
+        }
+        let words: Vec<String> = phonemes.split_whitespace().map(String::from).collect();
+        Ok(Box::new(IncrementalStreamer {
+            model: self,
+            windows: incremental::plan_windows(words.len()).into_iter(),
+            words,
+            chunk_padding,
+        }))
+    }
+}
+
+/// Streams a sentence a group of words at a time, see [`SonataModel::stream_incremental_synthesis`]
+struct IncrementalStreamer<'a> {
+    model: &'a VitsStreamingModel,
+    words: Vec<String>,
+    windows: std::vec::IntoIter<incremental::Window>,
+    chunk_padding: usize,
+}
+
+impl IncrementalStreamer<'_> {
+    fn input_ids(&self, words: std::ops::Range<usize>) -> Vec<i64> {
+        let (pad_id, bos_id, eos_id) = self.model.get_meta_ids();
+        self.model
+            .phonemes_to_input_ids(&self.words[words].join(" "), pad_id, bos_id, eos_id)
+    }
+    /// The number of input ids of `words` that come before the word after them,
+    /// i.e. all of them but the end marker
+    fn num_ids_before(&self, words: std::ops::Range<usize>) -> usize {
+        if words.is_empty() {
+            return 0;
+        }
+        self.input_ids(words).len().saturating_sub(1)
+    }
+    fn synthesize_window(&self, window: incremental::Window) -> SonataResult<AudioSamples> {
+        let input_ids = self.input_ids(window.words.clone());
+        if input_ids.is_empty() {
+            return Ok(Vec::new().into());
+        }
+        let num_ids = input_ids.len();
+        let encoder_outputs = self.model.infer_encoder(input_ids, ExtraInputs::default())?;
+        let start = self.num_ids_before(window.words.start..window.group.start);
+        let end = match window.group.end == window.words.end {
+            true => num_ids,
+            false => self.num_ids_before(window.words.start..window.group.end),
+        };
+        let frames = incremental::group_frames(&encoder_outputs.durations()?, start..end);
+        let mut audio = encoder_outputs.decode_frames(
+            self.model.decoder_model.as_ref(),
+            frames,
+            self.chunk_padding,
+        )?;
+        audio.crossfade(42);
+        Ok(audio)
+    }
+}
+
+impl Iterator for IncrementalStreamer<'_> {
+    type Item = SonataResult<AudioSamples>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let window = self.windows.next()?;
+        Some(self.synthesize_window(window))
+    }
 }
 
 struct EncoderOutputs {
     z: Array<f32, Dim<IxDynImpl>>,
     y_mask: Array<f32, Dim<IxDynImpl>>,
-    #[allow(dead_code)]
     p_duration: Option<Array<f32, Dim<IxDynImpl>>>,
     g: Array<f32, Dim<IxDynImpl>>,
 }
@@ -862,7 +1531,71 @@ impl EncoderOutputs {
         };
         Ok(Self { z, y_mask, p_duration, g })
     }
+    /// The predicted duration of each input id, in frames
+    fn durations(&self) -> SonataResult<Vec<f32>> {
+        match self.p_duration {
+            Some(ref p_duration) => Ok(p_duration.iter().copied().collect()),
+            None => Err(SonataError::OperationError(
+                "The model doesn't output phoneme durations (`p_duration`)".to_string(),
+            )),
+        }
+    }
+    /// Retime the latent frames to the given duration (in frames) of each input id
+    fn retime(&mut self, durations: &[f32]) -> SonataResult<()> {
+        let predicted = self.durations()?;
+        let z = match self.z.view().into_dimensionality::<Ix3>() {
+            Ok(z) => durations::retime(z, &predicted, durations)?,
+            Err(e) => {
+                return Err(SonataError::OperationError(format!(
+                    "Unexpected shape of the encoder output: {}",
+                    e
+                )))
+            }
+        };
+        self.y_mask = Array3::<f32>::ones((1, 1, z.shape()[2])).into_dyn();
+        self.z = z.into_dyn();
+        Ok(())
+    }
+    /// Decode `frames` of the latent frames on their own. Up to `padding` frames on each side
+    /// are decoded along with them for context, and cut from the audio.
+    fn decode_frames(
+        &self,
+        session: &ort::Session,
+        frames: std::ops::Range<usize>,
+        padding: usize,
+    ) -> SonataResult<AudioSamples> {
+        let num_frames = self.z.shape()[2];
+        let end = frames.end.min(num_frames);
+        let start = frames.start.min(end);
+        if start == end {
+            return Ok(Vec::new().into());
+        }
+        let padded = ndarray::Slice::from(start.saturating_sub(padding)..(end + padding).min(num_frames));
+        let window = Self {
+            z: self.z.slice_axis(Axis(2), padded).to_owned(),
+            y_mask: self.y_mask.slice_axis(Axis(2), padded).to_owned(),
+            p_duration: None,
+            g: self.g.clone(),
+        };
+        let audio = window.infer_decoder(session)?;
+        let skip = (start - padded.start as usize) * truncation::SAMPLES_PER_FRAME;
+        let len = (end - start) * truncation::SAMPLES_PER_FRAME;
+        Ok(audio.as_slice().iter().skip(skip).take(len).copied().collect::<Vec<f32>>().into())
+    }
     fn infer_decoder(&self, session: &ort::Session) -> SonataResult<AudioSamples> {
+        self.run_decoder(session, |outputs| match outputs[0].try_extract_tensor::<f32>() {
+            Ok(out) => Ok(Vec::from(out.view().as_slice().unwrap()).into()),
+            Err(e) => Err(SonataError::OperationError(format!(
+                "Failed to run model inference. Error: {}",
+                e
+            ))),
+        })
+    }
+    fn run_decoder<T>(
+        &self,
+        session: &ort::Session,
+        read_outputs: impl FnOnce(&SessionOutputs) -> SonataResult<T>,
+    ) -> SonataResult<T> {
         let outputs = {
             let mut inputs = vec![
                 ort::SessionInputValue::from(
@@ -887,13 +1620,7 @@ impl EncoderOutputs {
                 }
             }
         };
-        match outputs[0].try_extract_tensor::<f32>() {
-            Ok(out) => Ok(Vec::from(out.view().as_slice().unwrap()).into()),
-            Err(e) => Err(SonataError::OperationError(format!(
-                "Failed to run model inference. Error: {}",
-                e
-            ))),
-        }
+        read_outputs(&outputs)
     }
 }
 
@@ -1053,3 +1780,66 @@ impl Iterator for AdaptiveMelChunker {
         Some((chunk_index, audio_index))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Options that record the stages they are told about
+    fn recording_options() -> (LoadOptions, Arc<Mutex<Vec<String>>>) {
+        let stages = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&stages);
+        let options = LoadOptions {
+            on_progress: Some(Box::new(move |stage: &LoadStage| {
+                recorded.lock().unwrap().push(stage.to_string())
+            })),
+            ..Default::default()
+        };
+        (options, stages)
+    }
+
+    #[test]
+    fn test_load_reports_progress_until_it_fails() {
+        let (options, stages) = recording_options();
+        let result = from_config_path_with_options(Path::new("does/not/exist.json"), &options);
+        assert!(result.is_err());
+        assert_eq!(*stages.lock().unwrap(), ["reading config"]);
+    }
+
+    #[test]
+    fn test_cancelled_load_stops_before_the_next_stage() {
+        let (mut options, stages) = recording_options();
+        let token = CancellationToken::new();
+        options.cancellation_token = Some(token.clone());
+        assert!(options.report(LoadStage::ReadingConfig).is_ok());
+
+        token.cancel();
+        let error = options
+            .report(LoadStage::CreatingSession(PathBuf::from("model.onnx")))
+            .unwrap_err();
+        assert!(error.to_string().contains("cancelled"));
+        let error = from_config_path_with_options(Path::new("does/not/exist.json"), &options)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("cancelled"));
+        assert_eq!(*stages.lock().unwrap(), ["reading config"]);
+    }
+
+    #[test]
+    fn test_weights_digest_follows_the_contents() {
+        let dir = std::env::temp_dir().join("sonata_test_weights_digest");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.onnx"), dir.join("b.onnx"));
+        std::fs::write(&a, [1u8; 10000]).unwrap();
+        std::fs::write(&b, [2u8; 10000]).unwrap();
+        let weights = ModelWeights::new(&[&a]);
+        assert_eq!(weights.size, 10000);
+        let digest = weights.digest().unwrap();
+        assert_eq!(digest, ModelWeights::new(&[&a]).digest().unwrap());
+        assert_ne!(digest, ModelWeights::new(&[&b]).digest().unwrap());
+        assert_ne!(digest, ModelWeights::new(&[&a, &b]).digest().unwrap());
+        assert!(ModelWeights::new(&[&dir.join("missing.onnx")]).digest().is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
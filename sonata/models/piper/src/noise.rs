@@ -0,0 +1,212 @@
+use ndarray::{Array, IxDyn};
+use rand::{Rng, RngCore};
+use sonata_core::{NoiseTensor, SonataError, SonataResult};
+
+/// Inputs whose name starts with this take noise that the model would otherwise sample
+/// inside its graph, e.g. `noise` or `noise_dp` in VITS exports made for reproducibility
+const NOISE_INPUT_PREFIX: &str = "noise";
+/// Noise inputs of the (stochastic) duration predictor, which runs over the input ids.
+/// The noise of the other inputs is added to the latent frames, after the upsampling.
+const DURATION_NOISE_INPUTS: &[&str] = &["noise_dp", "noise_w"];
+
+/// A noise input of an inference session
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NoiseInput {
+    /// Position of the input in the session's inputs
+    pub(crate) index: usize,
+    name: String,
+    /// Dimensions of the input, with `-1` for dynamic ones (e.g. `[1, 2, -1]`)
+    dimensions: Vec<i64>,
+    /// Whether the input takes noise for every frame rather than every input id
+    per_frame: bool,
+}
+
+impl NoiseInput {
+    /// The noise inputs of `session`, in the order of its inputs
+    pub(crate) fn find_all(session: &ort::Session) -> Vec<Self> {
+        session
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| input.name.starts_with(NOISE_INPUT_PREFIX))
+            .map(|(index, input)| Self {
+                index,
+                name: input.name.clone(),
+                dimensions: input
+                    .input_type
+                    .tensor_dimensions()
+                    .cloned()
+                    .unwrap_or_default(),
+                per_frame: !DURATION_NOISE_INPUTS.contains(&input.name.as_str()),
+            })
+            .collect()
+    }
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+    /// The shape of the input for a sequence of `sequence_len` input ids. A dynamic first
+    /// dimension is the batch. A dynamic last dimension is the sequence for the noise of the
+    /// duration predictor, and the number of frames, which isn't known before inference,
+    /// for the other inputs.
+    pub(crate) fn shape(&self, sequence_len: usize) -> SonataResult<Vec<usize>> {
+        let last = self.dimensions.len().saturating_sub(1);
+        self.dimensions
+            .iter()
+            .enumerate()
+            .map(|(i, dim)| match *dim {
+                dim if dim > 0 => Ok(dim as usize),
+                _ if i == last && self.per_frame => Err(SonataError::OperationError(format!(
+                    "Noise input `{}` ({:?}) takes noise for every frame of the speech, whose number isn't known before inference",
+                    self.name, self.dimensions
+                ))),
+                _ if i == last => Ok(sequence_len),
+                _ if i == 0 => Ok(1),
+                _ => Err(SonataError::OperationError(format!(
+                    "The size of noise input `{}` ({:?}) doesn't follow from the phonemes",
+                    self.name, self.dimensions
+                ))),
+            })
+            .collect()
+    }
+}
+
+/// The tensors to feed into `noise_inputs` for a sequence of `sequence_len` input ids, along
+/// with the index of their input. `noise` must have exactly one tensor of the right shape for
+/// each input. Without `noise`, standard normal noise is sampled from `rng` for each input.
+pub(crate) fn noise_tensors(
+    noise_inputs: &[NoiseInput],
+    noise: Option<&[NoiseTensor]>,
+    sequence_len: usize,
+    rng: &mut dyn RngCore,
+) -> SonataResult<Vec<(usize, Array<f32, IxDyn>)>> {
+    let Some(noise) = noise else {
+        return noise_inputs
+            .iter()
+            .map(|input| {
+                let shape = input.shape(sequence_len)?;
+                let data = standard_normal(rng, shape.iter().product());
+                Ok((input.index, Array::from_shape_vec(IxDyn(&shape), data).unwrap()))
+            })
+            .collect();
+    };
+    if noise_inputs.is_empty() {
+        return Err(SonataError::OperationError(
+            "The model doesn't take noise inputs. It samples its noise inside the inference graph".to_string(),
+        ));
+    }
+    if let Some(unknown) = noise
+        .iter()
+        .find(|tensor| !noise_inputs.iter().any(|input| input.name == tensor.name))
+    {
+        return Err(SonataError::OperationError(format!(
+            "The model has no noise input named `{}`",
+            unknown.name
+        )));
+    }
+    noise_inputs
+        .iter()
+        .map(|input| {
+            let mut tensors = noise.iter().filter(|tensor| tensor.name == input.name);
+            let (Some(tensor), None) = (tensors.next(), tensors.next()) else {
+                return Err(SonataError::OperationError(format!(
+                    "Expected exactly one tensor for noise input `{}`",
+                    input.name
+                )));
+            };
+            let shape = input.shape(sequence_len)?;
+            if tensor.shape != shape || tensor.data.len() != shape.iter().product::<usize>() {
+                return Err(SonataError::OperationError(format!(
+                    "Noise input `{}` must have shape {:?}, got {:?} with {} values",
+                    input.name,
+                    shape,
+                    tensor.shape,
+                    tensor.data.len()
+                )));
+            }
+            let array = Array::from_shape_vec(IxDyn(&shape), tensor.data.clone()).unwrap();
+            Ok((input.index, array))
+        })
+        .collect()
+}
+
+/// `len` samples of standard normal noise from `rng`, with the Box-Muller transform
+fn standard_normal(rng: &mut dyn RngCore, len: usize) -> Vec<f32> {
+    let mut noise = vec![0f32; len];
+    for pair in noise.chunks_mut(2) {
+        // Map to (0, 1] so that the logarithm is finite
+        let u1 = 1.0 - rng.gen::<f32>();
+        let u2 = rng.gen::<f32>();
+        let radius = (-2.0 * u1.ln()).sqrt();
+        let (sin, cos) = (std::f32::consts::TAU * u2).sin_cos();
+        pair[0] = radius * cos;
+        if let Some(second) = pair.get_mut(1) {
+            *second = radius * sin;
+        }
+    }
+    noise
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn noise_input(index: usize, name: &str, dimensions: Vec<i64>) -> NoiseInput {
+        NoiseInput {
+            index,
+            name: name.to_string(),
+            dimensions,
+            per_frame: !DURATION_NOISE_INPUTS.contains(&name),
+        }
+    }
+
+    fn tensor(name: &str, shape: Vec<usize>) -> NoiseTensor {
+        NoiseTensor {
+            name: name.to_string(),
+            data: vec![0.5; shape.iter().product()],
+            shape,
+        }
+    }
+
+    #[test]
+    fn test_noise_shapes_follow_the_sequence() {
+        assert_eq!(noise_input(3, "noise_dp", vec![-1, 2, -1]).shape(9).unwrap(), [1, 2, 9]);
+        assert_eq!(noise_input(3, "noise", vec![1, 192, 9]).shape(20).unwrap(), [1, 192, 9]);
+        assert!(noise_input(3, "noise_dp", vec![1, -1, -1]).shape(9).is_err());
+        // The number of frames follows from the predicted durations, not the input ids
+        let error = noise_input(3, "noise", vec![1, 192, -1]).shape(9).unwrap_err();
+        assert!(error.to_string().contains("every frame"));
+    }
+
+    #[test]
+    fn test_sampled_noise_comes_from_the_rng() {
+        let inputs = [noise_input(3, "noise_dp", vec![1, 2, -1])];
+        let sample = |seed: u64| {
+            let tensors = noise_tensors(&inputs, None, 5, &mut StdRng::seed_from_u64(seed)).unwrap();
+            tensors[0].1.clone()
+        };
+        assert_eq!(sample(7), sample(7));
+        assert_ne!(sample(7), sample(8));
+    }
+
+    #[test]
+    fn test_injected_noise_is_validated_strictly() {
+        let inputs = [noise_input(3, "noise_dp", vec![1, 2, -1]), noise_input(4, "noise_w", vec![1, 4, -1])];
+        let noise = [tensor("noise_w", vec![1, 4, 5]), tensor("noise_dp", vec![1, 2, 5])];
+        let mut rng = StdRng::seed_from_u64(0);
+        let tensors = noise_tensors(&inputs, Some(&noise), 5, &mut rng).unwrap();
+        assert_eq!(tensors.iter().map(|(index, _)| *index).collect::<Vec<_>>(), [3, 4]);
+        assert_eq!(tensors[1].1.shape(), [1, 4, 5]);
+        assert!(noise_tensors(&inputs, Some(&noise), 6, &mut rng).is_err());
+        assert!(noise_tensors(&inputs, Some(&noise[..1]), 5, &mut rng).is_err());
+        let duplicated = [noise[0].clone(), noise[0].clone(), noise[1].clone()];
+        assert!(noise_tensors(&inputs, Some(&duplicated), 5, &mut rng).is_err());
+        let unknown = [noise[0].clone(), noise[1].clone(), tensor("noise_z", vec![1])];
+        assert!(noise_tensors(&inputs, Some(&unknown), 5, &mut rng).is_err());
+        assert!(noise_tensors(&[], Some(&[]), 5, &mut rng).is_err());
+        let sampled = noise_tensors(&inputs, None, 5, &mut rng).unwrap();
+        assert_eq!(sampled[0].1.shape(), [1, 2, 5]);
+        assert!(noise_tensors(&[], None, 5, &mut rng).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use sonata_core::{SonataError, SonataResult};
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+/// Environment variable with the path of the onnxruntime library
+pub const ORT_DYLIB_PATH_ENV_VAR: &str = "ORT_DYLIB_PATH";
+
+static LIBRARY_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+/// The outcome of loading the library, set before the first inference session is created
+static LOADED: OnceLock<Result<(), String>> = OnceLock::new();
+
+/// Load the onnxruntime shared library from `path`, for deployments where it isn't on the
+/// library search path.
+///
+/// The library is looked up in this order:
+/// 1. the path set with this function
+/// 2. the path in the `ORT_DYLIB_PATH` environment variable
+/// 3. `libonnxruntime.so` (`onnxruntime.dll` on Windows, `libonnxruntime.dylib` on macOS)
+///    next to the executable, then on the library search path
+///
+/// This must be called before the first model is loaded. onnxruntime is only loaded at
+/// runtime when sonata is built with the `ort-dylib` feature. Otherwise it is linked into
+/// the binary, and this returns an error.
+pub fn set_ort_library_path(path: impl AsRef<Path>) -> SonataResult<()> {
+    let path = path.as_ref();
+    if !cfg!(feature = "ort-dylib") {
+        return Err(SonataError::OperationError(
+            "Cannot set the onnxruntime library path: onnxruntime is linked into this build. Build with the `ort-dylib` feature to load it at runtime".to_string(),
+        ));
+    }
+    let mut library_path = LIBRARY_PATH.write().unwrap();
+    if LOADED.get().is_some() {
+        return Err(SonataError::OperationError(
+            "The onnxruntime library path must be set before the first model is loaded".to_string(),
+        ));
+    }
+    if !path.is_file() {
+        return Err(SonataError::FailedToLoadResource(format!(
+            "The onnxruntime library `{}` doesn't exist",
+            path.display()
+        )));
+    }
+    *library_path = Some(path.to_path_buf());
+    Ok(())
+}
+
+/// The path the onnxruntime library is loaded from when it is set with
+/// [`set_ort_library_path`] or `ORT_DYLIB_PATH`, in that order
+pub fn ort_library_path() -> Option<PathBuf> {
+    resolve_library_path(LIBRARY_PATH.read().unwrap().as_ref())
+}
+
+/// The path set with [`set_ort_library_path`], if any, or else the one in `ORT_DYLIB_PATH`
+fn resolve_library_path(set_path: Option<&PathBuf>) -> Option<PathBuf> {
+    set_path.cloned().or_else(|| {
+        std::env::var_os(ORT_DYLIB_PATH_ENV_VAR)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+/// Load the onnxruntime library, the first time it is called
+#[cfg(feature = "ort-dylib")]
+pub(crate) fn load() -> SonataResult<()> {
+    let loaded = LOADED.get_or_init(|| {
+        // Held until the library is loaded, so that the path isn't set in the meantime.
+        // Taking the lock again (e.g. with `ort_library_path`) could deadlock with a writer.
+        let library_path = LIBRARY_PATH.read().unwrap();
+        let path = resolve_library_path(library_path.as_ref());
+        let location = match path {
+            Some(ref path) => format!("`{}`", path.display()),
+            None => "from the default location".to_string(),
+        };
+        if path.as_ref().is_some_and(|path| !path.is_file()) {
+            return Err(format!(
+                "The onnxruntime library {} doesn't exist. Check the path set with `set_ort_library_path` or `{}`",
+                location, ORT_DYLIB_PATH_ENV_VAR
+            ));
+        }
+        // ort panics if the library can't be loaded
+        let committed = std::panic::catch_unwind(|| match path {
+            Some(ref path) => ort::init_from(path.to_string_lossy()).commit(),
+            None => ort::init().commit(),
+        });
+        drop(library_path);
+        match committed {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(format!(
+                "Failed to initialize the onnxruntime library {}: {}",
+                location, e
+            )),
+            Err(_) => Err(format!(
+                "Failed to load the onnxruntime library {}. Set its path with `set_ort_library_path` or `{}`",
+                location, ORT_DYLIB_PATH_ENV_VAR
+            )),
+        }
+    });
+    loaded.clone().map_err(SonataError::FailedToLoadResource)
+}
+
+/// onnxruntime is linked into the binary
+#[cfg(not(feature = "ort-dylib"))]
+pub(crate) fn load() -> SonataResult<()> {
+    LOADED.get_or_init(|| Ok(()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_library_is_rejected() {
+        let result = set_ort_library_path("/nonexistent/libonnxruntime.so");
+        assert!(result.is_err());
+        assert_eq!(LIBRARY_PATH.read().unwrap().as_ref(), None);
+    }
+}
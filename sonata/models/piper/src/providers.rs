@@ -0,0 +1,105 @@
+use sonata_core::{SonataError, SonataResult};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+/// A backend onnxruntime can run inference on.
+///
+/// Providers that aren't available in the onnxruntime build are skipped with a warning,
+/// and inference falls back to the CPU if none of them is available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionProvider {
+    Cpu,
+    Cuda,
+    TensorRt,
+    CoreMl,
+    DirectMl,
+    Nnapi,
+}
+
+impl ExecutionProvider {
+    fn dispatch(self) -> ort::ExecutionProviderDispatch {
+        match self {
+            Self::Cpu => ort::ExecutionProviderDispatch::CPU(Default::default()),
+            Self::Cuda => ort::ExecutionProviderDispatch::CUDA(Default::default()),
+            Self::TensorRt => ort::ExecutionProviderDispatch::TensorRT(Default::default()),
+            Self::CoreMl => ort::ExecutionProviderDispatch::CoreML(Default::default()),
+            Self::DirectMl => ort::ExecutionProviderDispatch::DirectML(Default::default()),
+            Self::Nnapi => ort::ExecutionProviderDispatch::NNAPI(Default::default()),
+        }
+    }
+}
+
+impl FromStr for ExecutionProvider {
+    type Err = SonataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cpu" => Ok(Self::Cpu),
+            "cuda" => Ok(Self::Cuda),
+            "tensorrt" => Ok(Self::TensorRt),
+            "coreml" => Ok(Self::CoreMl),
+            "directml" => Ok(Self::DirectMl),
+            "nnapi" => Ok(Self::Nnapi),
+            _ => Err(SonataError::OperationError(format!(
+                "Unknown execution provider `{}`. Supported providers are `cpu`, `cuda`, `tensorrt`, `coreml`, `directml` and `nnapi`",
+                s
+            ))),
+        }
+    }
+}
+
+static DEFAULT_EXECUTION_PROVIDERS: RwLock<Option<Vec<ExecutionProvider>>> = RwLock::new(None);
+/// Set once the first inference session is created, after which the defaults can't change
+static SESSION_CREATED: AtomicBool = AtomicBool::new(false);
+
+/// Set the execution providers, in order of preference, used by the models loaded without
+/// explicit providers (see [`crate::LoadOptions::execution_providers`]).
+///
+/// This must be called before the first model is loaded, so that all the models of the
+/// process use the same providers. Models loaded without any providers use the ones
+/// onnxruntime was initialized with.
+pub fn set_default_execution_providers(providers: Vec<ExecutionProvider>) -> SonataResult<()> {
+    let mut defaults = DEFAULT_EXECUTION_PROVIDERS.write().unwrap();
+    if SESSION_CREATED.load(Ordering::SeqCst) {
+        return Err(SonataError::OperationError(
+            "The default execution providers must be set before the first model is loaded"
+                .to_string(),
+        ));
+    }
+    *defaults = Some(providers);
+    Ok(())
+}
+
+/// The providers for a new session: the explicit ones if given, otherwise the defaults
+pub(crate) fn session_execution_providers(
+    explicit: Option<&[ExecutionProvider]>,
+) -> Vec<ort::ExecutionProviderDispatch> {
+    let defaults = DEFAULT_EXECUTION_PROVIDERS.read().unwrap();
+    SESSION_CREATED.store(true, Ordering::SeqCst);
+    explicit
+        .or(defaults.as_deref())
+        .unwrap_or_default()
+        .iter()
+        .map(|provider| provider.dispatch())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_execution_provider() {
+        assert_eq!("CUDA".parse::<ExecutionProvider>().unwrap(), ExecutionProvider::Cuda);
+        assert_eq!("coreml".parse::<ExecutionProvider>().unwrap(), ExecutionProvider::CoreMl);
+        assert!("tpu".parse::<ExecutionProvider>().is_err());
+    }
+
+    #[test]
+    fn test_defaults_cannot_change_after_a_session_is_created() {
+        let providers = session_execution_providers(Some(&[ExecutionProvider::Cpu]));
+        assert_eq!(providers.len(), 1);
+        assert!(set_default_execution_providers(vec![ExecutionProvider::Cuda]).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use ndarray::{Array, IxDyn};
+use sonata_core::{SonataError, SonataResult};
+
+/// Names of the input that VITS variants with prosody transfer take a style embedding in
+const STYLE_INPUT_NAMES: &[&str] = &["style", "style_embedding", "ref_style"];
+
+/// The style embedding input of an inference session
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StyleInput {
+    /// Position of the input in the session's inputs
+    pub(crate) index: usize,
+    /// Dimensions of the input, with `-1` for dynamic ones (e.g. `[1, 256]`)
+    dimensions: Vec<i64>,
+}
+
+impl StyleInput {
+    /// The style embedding input of `session`, if it has one
+    pub(crate) fn find(session: &ort::Session) -> Option<Self> {
+        session
+            .inputs
+            .iter()
+            .enumerate()
+            .find(|(_, input)| STYLE_INPUT_NAMES.contains(&input.name.as_str()))
+            .map(|(index, input)| Self {
+                index,
+                dimensions: input
+                    .input_type
+                    .tensor_dimensions()
+                    .cloned()
+                    .unwrap_or_default(),
+            })
+    }
+    /// Number of values of the embedding, if the graph fixes it
+    fn embedding_len(&self) -> Option<usize> {
+        self.dimensions
+            .last()
+            .filter(|dim| **dim > 0)
+            .map(|dim| *dim as usize)
+    }
+    /// The tensor to feed `style` into the input, with a batch dimension if the input has one
+    pub(crate) fn tensor(&self, style: &[f32]) -> SonataResult<Array<f32, IxDyn>> {
+        match self.embedding_len() {
+            Some(len) if len != style.len() => {
+                return Err(SonataError::OperationError(format!(
+                    "Expected a style embedding of {} values, got {}",
+                    len,
+                    style.len()
+                )))
+            }
+            None if style.is_empty() => {
+                return Err(SonataError::OperationError(
+                    "The style embedding is empty".to_string(),
+                ))
+            }
+            _ => {}
+        }
+        let shape = match self.dimensions.len() {
+            1 => vec![style.len()],
+            _ => vec![1, style.len()],
+        };
+        Ok(Array::from_shape_vec(IxDyn(&shape), style.to_vec()).unwrap())
+    }
+}
+
+/// The tensor to feed `style` into the style input of the model, along with the index of the input
+pub(crate) fn style_tensor(
+    style_input: Option<&StyleInput>,
+    style: Option<&[f32]>,
+) -> SonataResult<Option<(usize, Array<f32, IxDyn>)>> {
+    let Some(style) = style else {
+        return Ok(None);
+    };
+    let Some(style_input) = style_input else {
+        return Err(SonataError::OperationError(
+            "The model doesn't take a style embedding. Only VITS variants with a `style` input support prosody transfer".to_string(),
+        ));
+    };
+    Ok(Some((style_input.index, style_input.tensor(style)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_style_embedding_is_validated_against_the_input() {
+        let input = StyleInput {
+            index: 3,
+            dimensions: vec![1, 4],
+        };
+        assert_eq!(input.tensor(&[0.1, 0.2, 0.3, 0.4]).unwrap().shape(), [1, 4]);
+        assert!(input.tensor(&[0.1, 0.2]).is_err());
+        let dynamic = StyleInput {
+            index: 3,
+            dimensions: vec![-1],
+        };
+        assert_eq!(dynamic.tensor(&[0.1, 0.2]).unwrap().shape(), [2]);
+        assert!(dynamic.tensor(&[]).is_err());
+        assert!(style_tensor(None, Some(&[0.1])).is_err());
+        assert!(style_tensor(None, None).unwrap().is_none());
+        assert_eq!(style_tensor(Some(&input), Some(&[0.0; 4])).unwrap().unwrap().0, 3);
+    }
+}
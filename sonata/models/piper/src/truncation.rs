@@ -0,0 +1,87 @@
+use sonata_core::{Audio, SonataAudioResult};
+
+/// Even in fast speech, phonemes are rarely shorter than this on average
+const MIN_PHONEME_MS: f32 = 20.0;
+/// Number of audio samples the decoder produces per frame
+pub(crate) const SAMPLES_PER_FRAME: usize = 256;
+
+/// Whether `num_samples` is implausibly short for `num_phonemes` spoken at `length_scale`
+pub(crate) fn is_short_for_phonemes(
+    num_samples: usize,
+    sample_rate: usize,
+    num_phonemes: usize,
+    length_scale: f32,
+) -> bool {
+    let min_samples = num_phonemes as f32 * MIN_PHONEME_MS * length_scale * sample_rate as f32 / 1000.0;
+    (num_samples as f32) < min_samples
+}
+
+/// Whether the decoder returned fewer samples than the `num_frames` the encoder predicted
+pub(crate) fn is_short_for_frames(num_samples: usize, num_frames: usize) -> bool {
+    num_samples < num_frames * SAMPLES_PER_FRAME * 9 / 10
+}
+
+/// Run `infer`, which flags suspected truncation on its output.
+/// When the output is flagged and `retry` is set, run it once more and keep the better take.
+pub(crate) fn infer_checked(retry: bool, mut infer: impl FnMut() -> SonataAudioResult) -> SonataAudioResult {
+    let mut audio = infer()?;
+    if audio.truncation_suspected && retry {
+        log::warn!("The model output is shorter than expected. Retrying inference");
+        let retried = infer()?;
+        if !retried.truncation_suspected || retried.len() > audio.len() {
+            audio = retried;
+        }
+    }
+    if audio.truncation_suspected {
+        log::warn!(
+            "The model output ({} ms) is shorter than expected. The end of the speech might be cut off",
+            audio.duration_ms()
+        );
+    }
+    Ok(audio)
+}
+
+/// Set the truncation flag of `audio`
+pub(crate) fn flagged(mut audio: Audio, truncation_suspected: bool) -> Audio {
+    audio.truncation_suspected = truncation_suspected;
+    audio
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio(num_samples: usize, truncation_suspected: bool) -> Audio {
+        flagged(Audio::new(vec![0.0; num_samples].into(), 16000, None), truncation_suspected)
+    }
+
+    #[test]
+    fn test_short_output_is_detected() {
+        // 10 phonemes take at least 200 ms, i.e. 3200 samples at 16 kHz
+        assert!(is_short_for_phonemes(3000, 16000, 10, 1.0));
+        assert!(!is_short_for_phonemes(8000, 16000, 10, 1.0));
+        assert!(!is_short_for_phonemes(3000, 16000, 10, 0.5));
+        assert!(is_short_for_frames(100 * 256 / 2, 100));
+        assert!(!is_short_for_frames(100 * 256 - 1000, 100));
+        assert!(!is_short_for_frames(100 * 256, 100));
+    }
+
+    #[test]
+    fn test_truncated_output_is_retried() {
+        let mut takes = vec![audio(500, true), audio(400, true)].into_iter();
+        let result = infer_checked(true, || Ok(takes.next().unwrap())).unwrap();
+        assert_eq!(result.len(), 500);
+        assert!(result.truncation_suspected);
+        let mut takes = vec![audio(500, true), audio(900, false)].into_iter();
+        let result = infer_checked(true, || Ok(takes.next().unwrap())).unwrap();
+        assert!(!result.truncation_suspected);
+        let mut num_takes = 0;
+        let result = infer_checked(false, || {
+            num_takes += 1;
+            Ok(audio(500, true))
+        })
+        .unwrap();
+        assert_eq!(num_takes, 1);
+        assert!(result.truncation_suspected);
+    }
+}
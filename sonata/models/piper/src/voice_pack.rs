@@ -0,0 +1,156 @@
+use crate::{load_model_config, onnx_paths};
+use sonata_core::{SonataError, SonataResult};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The first byte of a serialized ONNX `ModelProto` (field 1, `ir_version`, as a varint)
+const ONNX_MODEL_TAG: u8 = 0x08;
+
+/// The result of checking one voice of a voice pack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoiceCheck {
+    Ok,
+    /// The config can't be read or parsed
+    BadConfig(String),
+    /// An ONNX file the config refers to doesn't exist
+    MissingOnnx(PathBuf),
+    /// An ONNX file the config refers to is empty or doesn't look like an ONNX model
+    InvalidOnnx(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoiceStatus {
+    pub config_path: PathBuf,
+    pub check: VoiceCheck,
+}
+
+impl VoiceStatus {
+    pub fn is_ok(&self) -> bool {
+        self.check == VoiceCheck::Ok
+    }
+}
+
+/// Check every voice config (`*.onnx.json` or `config.json`) in `dir` and its subdirectories.
+///
+/// This parses the configs and checks that the ONNX files they refer to are present and
+/// start like an ONNX model, without creating inference sessions, so it is fast enough to
+/// run right after downloading a voice pack. A voice that passes can still fail to load
+/// if its ONNX file is truncated or corrupted further in.
+pub fn validate_voice_pack(dir: &Path) -> SonataResult<Vec<VoiceStatus>> {
+    let mut config_paths = Vec::new();
+    find_configs(dir, &mut config_paths)?;
+    config_paths.sort();
+    Ok(config_paths
+        .into_iter()
+        .map(|config_path| {
+            let check = check_voice(&config_path);
+            VoiceStatus { config_path, check }
+        })
+        .collect())
+}
+
+fn find_configs(dir: &Path, config_paths: &mut Vec<PathBuf>) -> SonataResult<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Err(SonataError::FailedToLoadResource(format!(
+                "Failed to read voice pack directory `{}`: {}",
+                dir.display(),
+                e
+            )))
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_configs(&path, config_paths)?;
+        } else if is_voice_config(&path) {
+            config_paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_voice_config(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(".onnx.json") || name == "config.json")
+}
+
+fn check_voice(config_path: &Path) -> VoiceCheck {
+    let config = match load_model_config(config_path) {
+        Ok((config, _)) => config,
+        Err(e) => return VoiceCheck::BadConfig(e.to_string()),
+    };
+    let paths = match onnx_paths(config_path, &config) {
+        Ok(paths) => paths,
+        Err(e) => return VoiceCheck::BadConfig(e.to_string()),
+    };
+    for onnx_path in paths {
+        let mut first_byte = [0u8];
+        match File::open(&onnx_path) {
+            Ok(mut file) => {
+                if file.read_exact(&mut first_byte).is_err() || first_byte[0] != ONNX_MODEL_TAG {
+                    return VoiceCheck::InvalidOnnx(onnx_path);
+                }
+            }
+            Err(_) => return VoiceCheck::MissingOnnx(onnx_path),
+        }
+    }
+    VoiceCheck::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "audio": {"sample_rate": 22050},
+        "espeak": {"voice": "en-us"},
+        "inference": {"noise_scale": 0.667, "length_scale": 1.0, "noise_w": 0.8},
+        "num_speakers": 1,
+        "speaker_id_map": {},
+        "num_symbols": 0,
+        "phoneme_map": {},
+        "phoneme_id_map": {},
+        "other_phonemes": {}
+    }"#;
+
+    #[test]
+    fn test_validate_voice_pack() {
+        let dir = std::env::temp_dir().join("sonata_test_validate_voice_pack");
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("good.onnx.json"), CONFIG).unwrap();
+        std::fs::write(dir.join("good.onnx"), [ONNX_MODEL_TAG, 7]).unwrap();
+        std::fs::write(dir.join("invalid.onnx.json"), CONFIG).unwrap();
+        std::fs::write(dir.join("invalid.onnx"), b"<html>").unwrap();
+        std::fs::write(dir.join("nested").join("missing.onnx.json"), CONFIG).unwrap();
+        std::fs::write(dir.join("broken.onnx.json"), "{").unwrap();
+        std::fs::write(dir.join("notes.json"), "{").unwrap();
+        let statuses = validate_voice_pack(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        let checks: Vec<_> = statuses
+            .iter()
+            .map(|status| {
+                let name = status.config_path.file_name().unwrap().to_string_lossy();
+                (name.into_owned(), status.check.clone())
+            })
+            .collect();
+        assert_eq!(checks.len(), 4);
+        assert!(matches!(checks[0], (ref name, VoiceCheck::BadConfig(_)) if name == "broken.onnx.json"));
+        assert_eq!(checks[1], ("good.onnx.json".to_string(), VoiceCheck::Ok));
+        assert_eq!(
+            checks[2],
+            ("invalid.onnx.json".to_string(), VoiceCheck::InvalidOnnx(dir.join("invalid.onnx")))
+        );
+        assert_eq!(
+            checks[3],
+            (
+                "missing.onnx.json".to_string(),
+                VoiceCheck::MissingOnnx(dir.join("nested").join("missing.onnx"))
+            )
+        );
+    }
+}
@@ -1,6 +1,10 @@
 mod dev_utils;
 use sonata_synth::{AudioOutputConfig, SonataSpeechSynthesizer};
 
+// Reports allocation counts alongside timings, e.g. to compare the low-memory realtime stream
+#[global_allocator]
+static ALLOC: divan::AllocProfiler = divan::AllocProfiler::system();
+
 fn main() {
     dev_utils::init();
     divan::main();
@@ -16,6 +20,7 @@ fn provide_params(
 mod speech_streams {
     use super::*;
     use divan::{black_box, Bencher};
+    use sonata_synth::StreamingConfig;
 
     #[divan::bench(threads = 4)]
     fn bench_lazy_stream(bencher: Bencher) {
@@ -54,6 +59,28 @@ mod speech_streams {
             },
         );
     }
+
+    /// The same stream with and without the buffer pool, consumed the same way
+    #[divan::bench(args = [false, true])]
+    fn bench_realtime_stream_low_memory(bencher: Bencher, low_memory: bool) {
+        bencher.with_inputs(provide_params("rt")).bench_local_refs(
+            |(synth, text, output_config)| {
+                let stream = synth
+                    .synthesize_streamed_with_config(
+                        text.clone(),
+                        output_config.clone(),
+                        StreamingConfig {
+                            chunk_size: 72,
+                            chunk_padding: 3,
+                            low_memory,
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+                dev_utils::iterate_recycling_stream(black_box(stream)).unwrap();
+            },
+        );
+    }
     #[divan::bench]
     fn bench_lazy_stream_latency(bencher: Bencher) {
         bencher.with_inputs(provide_params("std")).bench_local_refs(
@@ -0,0 +1,175 @@
+use crate::AudioOutputConfig;
+use sonata_core::SonataError;
+use std::str::FromStr;
+
+const KEYS: &str =
+    "rate, pitch, volume, silence, silence_frames, highpass, de_esser, de_esser_frequency, sample_rate, block_frames, quiet_start, channels, gain";
+
+/// Parses `key=value` pairs separated by `;`, e.g. `rate=slow;pitch=+2;volume=80`.
+///
+/// `rate`, `pitch` and `volume` take a percentage (`0` to `100`), a signed change
+/// relative to the unmodified setting (e.g. `+2`), or an SSML-like name: `x-slow` to
+/// `x-fast` for the rate, `x-low` to `x-high` for the pitch and `silent` to `x-loud` for
+/// the volume. `silence` is in milliseconds, `de_esser` (the threshold) is in dBFS,
+/// `highpass`, `de_esser_frequency` and `sample_rate` are in Hz, and `silence_frames` and
+/// `block_frames` are in frames. `quiet_start` is in milliseconds, or `on` for the default.
+/// `channels` is the number of output channels, e.g. `1` to downmix to mono, and `gain`
+/// is in dB.
+impl FromStr for AudioOutputConfig {
+    type Err = SonataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = AudioOutputConfig::default();
+        for pair in s.split(';').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let Some((key, value)) = pair.split_once('=') else {
+                return Err(invalid(format!("expected `key=value`, found `{}`", pair)));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let is_set = match key {
+                "rate" => set(&mut config.rate, parse_percent(key, value, NEUTRAL_RATE, RATE_NAMES)?),
+                "pitch" => set(&mut config.pitch, parse_percent(key, value, 50, PITCH_NAMES)?),
+                "volume" => set(&mut config.volume, parse_percent(key, value, 100, VOLUME_NAMES)?),
+                "silence" => set(&mut config.appended_silence_ms, parse_number(key, value)?),
+                "silence_frames" => set(&mut config.appended_silence_frames, parse_number(key, value)?),
+                "highpass" => {
+                    let cutoff_hz = parse_number(key, value)?;
+                    if cutoff_hz == 0 {
+                        return Err(invalid("`highpass` must be greater than 0 Hz".to_string()));
+                    }
+                    set(&mut config.highpass_cutoff_hz, cutoff_hz)
+                }
+                "de_esser" => set(&mut config.de_esser_threshold_db, parse_number(key, value)?),
+                "de_esser_frequency" => {
+                    set(&mut config.de_esser_frequency_hz, parse_number(key, value)?)
+                }
+                "sample_rate" => set(&mut config.sample_rate, parse_number(key, value)?),
+                "block_frames" => set(&mut config.block_frames, parse_number(key, value)?),
+                "quiet_start" => {
+                    let quiet_start_ms = match value {
+                        "on" => AudioOutputConfig::DEFAULT_QUIET_START_MS,
+                        _ => parse_number(key, value)?,
+                    };
+                    set(&mut config.quiet_start_ms, quiet_start_ms)
+                }
+                "channels" => set(&mut config.channels, parse_number(key, value)?),
+                "gain" => set(&mut config.gain_db, parse_number(key, value)?),
+                _ => {
+                    return Err(invalid(format!(
+                        "unknown key `{}`. Supported keys are: {}",
+                        key, KEYS
+                    )))
+                }
+            };
+            if is_set {
+                return Err(invalid(format!("`{}` is given more than once", key)));
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// The rate percentage that leaves the speed unchanged
+const NEUTRAL_RATE: u8 = 10;
+// Percentages of `RATE_RANGE`: 0.5x, 0.75x, 1x, 1.5x and 2x the speed
+const RATE_NAMES: &[(&str, u8)] = &[
+    ("x-slow", 0),
+    ("slow", 5),
+    ("medium", NEUTRAL_RATE),
+    ("fast", 20),
+    ("x-fast", 30),
+];
+// Percentages of `PITCH_RANGE`: 0.6x to 1.4x the pitch
+const PITCH_NAMES: &[(&str, u8)] = &[
+    ("x-low", 10),
+    ("low", 30),
+    ("medium", 50),
+    ("high", 70),
+    ("x-high", 90),
+];
+const VOLUME_NAMES: &[(&str, u8)] = &[
+    ("silent", 0),
+    ("x-soft", 20),
+    ("soft", 40),
+    ("medium", 60),
+    ("loud", 80),
+    ("x-loud", 100),
+];
+
+/// Set `field` to `value`, returning whether it was already set
+fn set<T>(field: &mut Option<T>, value: T) -> bool {
+    field.replace(value).is_some()
+}
+
+fn parse_percent(
+    key: &str,
+    value: &str,
+    neutral: u8,
+    names: &[(&str, u8)],
+) -> Result<u8, SonataError> {
+    if let Some((_, percent)) = names.iter().find(|(name, _)| *name == value) {
+        return Ok(*percent);
+    }
+    let percent = if value.starts_with(['+', '-']) {
+        neutral as i32 + parse_number::<i32>(key, value)?
+    } else {
+        parse_number(key, value)?
+    };
+    match u8::try_from(percent) {
+        Ok(percent) if percent <= 100 => Ok(percent),
+        _ => Err(invalid(format!(
+            "`{}={}` is out of range. `{}` must be between 0 and 100",
+            key, value, key
+        ))),
+    }
+}
+
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T, SonataError> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("invalid value `{}` for `{}`", value, key)))
+}
+
+fn invalid(reason: String) -> SonataError {
+    SonataError::OperationError(format!("Invalid audio output config: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output_config() {
+        let config: AudioOutputConfig = "rate=slow; pitch=+2;volume=80;silence=250".parse().unwrap();
+        assert_eq!(config.rate, Some(5));
+        assert_eq!(config.pitch, Some(52));
+        assert_eq!(config.volume, Some(80));
+        assert_eq!(config.appended_silence_ms, Some(250));
+        assert_eq!(config.sample_rate, None);
+        let config: AudioOutputConfig = "rate=-5;volume=x-loud;sample_rate=48000;block_frames=512".parse().unwrap();
+        assert_eq!(config.rate, Some(5));
+        assert_eq!(config.volume, Some(100));
+        assert_eq!(config.sample_rate, Some(48000));
+        assert_eq!(config.block_frames, Some(512));
+        let config: AudioOutputConfig = "de_esser=-24.5;de_esser_frequency=7000".parse().unwrap();
+        assert_eq!(config.de_esser_threshold_db, Some(-24.5));
+        assert_eq!(config.de_esser_frequency_hz, Some(7000));
+        let config: AudioOutputConfig = "quiet_start=on".parse().unwrap();
+        assert_eq!(config.quiet_start_ms, Some(AudioOutputConfig::DEFAULT_QUIET_START_MS));
+        let config: AudioOutputConfig = "quiet_start=12".parse().unwrap();
+        assert_eq!(config.quiet_start_ms, Some(12));
+        let config: AudioOutputConfig = "channels=1".parse().unwrap();
+        assert_eq!(config.channels, Some(1));
+        assert!("".parse::<AudioOutputConfig>().is_ok());
+    }
+
+    #[test]
+    fn test_parse_output_config_errors() {
+        let error = |s: &str| s.parse::<AudioOutputConfig>().err().unwrap().to_string();
+        assert!(error("speed=2").contains("unknown key `speed`"));
+        assert!(error("rate").contains("expected `key=value`"));
+        assert!(error("pitch=+60").contains("between 0 and 100"));
+        assert!(error("volume=loudest").contains("invalid value `loudest`"));
+        assert!(error("rate=1;rate=2").contains("more than once"));
+        assert!(error("highpass=0").contains("greater than 0 Hz"));
+    }
+}
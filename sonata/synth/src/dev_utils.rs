@@ -2,7 +2,8 @@ use core::hint::black_box;
 use once_cell::sync::Lazy;
 use sonata_piper::from_config_path as voice_from_config_path;
 use sonata_synth::{
-    AudioOutputConfig, AudioSamples, SonataModel, SonataResult, SonataSpeechSynthesizer,
+    AudioOutputConfig, AudioSamples, RealtimeSpeechStream, SonataModel, SonataResult,
+    SonataSpeechSynthesizer,
 };
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -33,12 +34,19 @@ fn model_directory(kind: &str) -> PathBuf {
     PathBuf::from(CRATE_DIR).join("models").join(kind)
 }
 
+/// Load a model that isn't shared with other tests, for tests that change its synthesis config
+#[allow(dead_code)]
+pub fn load_unshared_voice() -> Arc<dyn SonataModel + Send + Sync> {
+    voice_from_config_path(&model_directory("std").join("model.onnx.json")).unwrap()
+}
+
 pub fn gen_params(kind: &str) -> (SonataSpeechSynthesizer, String, Option<AudioOutputConfig>) {
     let output_config = Some(AudioOutputConfig {
         rate: Some(50),
         volume: Some(50),
         pitch: Some(50),
         appended_silence_ms: None,
+        ..Default::default()
     });
     let text = TEXT.join("\n");
     if kind == "std" {
@@ -65,3 +73,12 @@ pub fn iterate_stream(
     }
     Ok(())
 }
+
+#[inline(always)]
+pub fn iterate_recycling_stream(mut stream: RealtimeSpeechStream) -> SonataResult<()> {
+    while let Some(result) = stream.next() {
+        let audio = black_box(result?);
+        stream.recycle(audio);
+    }
+    Ok(())
+}
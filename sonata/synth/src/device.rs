@@ -0,0 +1,310 @@
+use crate::{AudioOutputConfig, SonataSpeechSynthesizer, StreamingConfig};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use flume::{Receiver, SendTimeoutError, Sender, TryRecvError};
+use sonata_core::{AudioSamples, SonataError, SonataResult};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often to check on the device while waiting for it to play the queued samples
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How long the device may go without asking for samples before playback is given up
+const STALL_TIMEOUT: Duration = Duration::from_secs(2);
+/// The number of chunks queued for the device at most. Once the queue is full, the
+/// chunks are taken from the speech stream only as fast as the device plays them. The
+/// stream itself still synthesizes ahead into its own buffer.
+const MAX_QUEUED_CHUNKS: usize = 4;
+
+impl SonataSpeechSynthesizer {
+    /// Play the speech for `text` on the default output device of the system,
+    /// returning once it has been played.
+    ///
+    /// The speech is streamed, so playback starts as soon as the first chunk is ready.
+    /// The audio is resampled to the device's sample rate (overriding the output config's
+    /// `sample_rate`), and copied into every channel of the device if it has more channels
+    /// than the model.
+    pub fn synthesize_to_device(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> SonataResult<()> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| device_error("No output device is available"))?;
+        let supported_config = device
+            .default_output_config()
+            .map_err(|e| device_error(e.to_string()))?;
+        let stream_config = supported_config.config();
+        let mut output_config = output_config
+            .or_else(|| self.default_output_config())
+            .unwrap_or_default();
+        output_config.sample_rate = Some(stream_config.sample_rate.0);
+        let num_channels = self.output_num_channels(Some(&output_config))?.max(1);
+
+        let (sender, source) = playback_queue();
+        let mut watch = PlaybackWatch::new(source.status(), STALL_TIMEOUT);
+        let stream = match supported_config.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, source),
+            SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, source),
+            SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, source),
+            SampleFormat::I32 => build_stream::<i32>(&device, &stream_config, source),
+            format => {
+                return Err(device_error(format!(
+                    "Unsupported device sample format `{}`",
+                    format
+                )))
+            }
+        }?;
+        stream.play().map_err(|e| device_error(e.to_string()))?;
+
+        let device_channels = stream_config.channels as usize;
+        let mut speech =
+            self.synthesize_streamed_with_config(text, Some(output_config), StreamingConfig::default())?;
+        while let Some(result) = speech.next() {
+            let samples = result?;
+            let mut frames = to_device_frames(&samples, num_channels, device_channels);
+            speech.recycle(samples);
+            loop {
+                match sender.send_timeout(frames, DRAIN_POLL_INTERVAL) {
+                    Ok(()) => break,
+                    Err(SendTimeoutError::Timeout(unsent)) => {
+                        watch.check()?;
+                        frames = unsent;
+                    }
+                    Err(SendTimeoutError::Disconnected(_)) => {
+                        return Err(device_error("The output stream stopped"))
+                    }
+                }
+            }
+        }
+        drop(sender);
+        watch.wait_until_drained()?;
+        // Give the device time to play the last buffer it was handed
+        let buffer_duration_ms = match stream_config.buffer_size {
+            cpal::BufferSize::Fixed(num_frames) => {
+                num_frames as u64 * 1000 / stream_config.sample_rate.0 as u64
+            }
+            cpal::BufferSize::Default => 100,
+        };
+        std::thread::sleep(Duration::from_millis(buffer_duration_ms));
+        Ok(())
+    }
+}
+
+/// Interleave `samples` for a device with `device_channels` channels, copying the channels
+/// round-robin if the device has more of them
+fn to_device_frames(samples: &AudioSamples, num_channels: usize, device_channels: usize) -> Vec<f32> {
+    let mut frames = Vec::with_capacity(samples.len() / num_channels * device_channels);
+    for frame in samples.as_slice().chunks(num_channels) {
+        frames.extend((0..device_channels).map(|channel| frame[channel % frame.len()]));
+    }
+    frames
+}
+
+/// A bounded queue of chunks for the device, and the source that plays them
+fn playback_queue() -> (Sender<Vec<f32>>, PlaybackSource) {
+    let (sender, receiver) = flume::bounded(MAX_QUEUED_CHUNKS);
+    let source = PlaybackSource {
+        receiver,
+        chunk: Vec::new(),
+        position: 0,
+        status: Arc::default(),
+    };
+    (sender, source)
+}
+
+/// What the callbacks of the device report to the thread feeding it
+#[derive(Default)]
+struct PlaybackStatus {
+    /// Set once the queue is closed and all of its samples were played
+    drained: AtomicBool,
+    /// Set when the stream reports an error, after which the device may stop playing
+    failed: AtomicBool,
+    /// The number of device buffers filled so far
+    buffers_filled: AtomicUsize,
+}
+
+/// Watches the [`PlaybackStatus`] of a device so that waiting on it fails instead of
+/// hanging when the device stops asking for samples
+struct PlaybackWatch {
+    status: Arc<PlaybackStatus>,
+    stall_timeout: Duration,
+    buffers_filled: usize,
+    last_progress: Instant,
+}
+
+impl PlaybackWatch {
+    fn new(status: Arc<PlaybackStatus>, stall_timeout: Duration) -> Self {
+        Self {
+            status,
+            stall_timeout,
+            buffers_filled: 0,
+            last_progress: Instant::now(),
+        }
+    }
+    /// Fail if the stream reported an error, or the device filled no buffer for longer
+    /// than the stall timeout
+    fn check(&mut self) -> SonataResult<()> {
+        if self.status.failed.load(Ordering::SeqCst) {
+            return Err(device_error("The output stream failed"));
+        }
+        let buffers_filled = self.status.buffers_filled.load(Ordering::SeqCst);
+        if buffers_filled != self.buffers_filled {
+            self.buffers_filled = buffers_filled;
+            self.last_progress = Instant::now();
+        } else if self.last_progress.elapsed() > self.stall_timeout {
+            return Err(device_error("The output device stopped playing"));
+        }
+        Ok(())
+    }
+    /// Wait until the device has played all the samples of the closed queue
+    fn wait_until_drained(&mut self) -> SonataResult<()> {
+        while !self.status.drained.load(Ordering::SeqCst) {
+            self.check()?;
+            std::thread::sleep(DRAIN_POLL_INTERVAL);
+        }
+        Ok(())
+    }
+}
+
+/// The samples the device callback plays, from the chunks of a [`playback_queue`]
+struct PlaybackSource {
+    receiver: Receiver<Vec<f32>>,
+    /// The chunk being played, and how much of it was played
+    chunk: Vec<f32>,
+    position: usize,
+    status: Arc<PlaybackStatus>,
+}
+
+impl PlaybackSource {
+    fn status(&self) -> Arc<PlaybackStatus> {
+        Arc::clone(&self.status)
+    }
+    /// Fill a buffer of the device, with silence when no samples are ready. Never blocks,
+    /// since it runs on the device's realtime thread.
+    fn fill<T: FromSample<f32>>(&mut self, data: &mut [T]) {
+        for sample in data.iter_mut() {
+            if self.position == self.chunk.len() {
+                match self.receiver.try_recv() {
+                    Ok(chunk) => (self.chunk, self.position) = (chunk, 0),
+                    Err(TryRecvError::Empty) => {}
+                    Err(TryRecvError::Disconnected) => {
+                        self.status.drained.store(true, Ordering::SeqCst)
+                    }
+                }
+            }
+            *sample = T::from_sample_(match self.chunk.get(self.position) {
+                Some(value) => {
+                    self.position += 1;
+                    *value
+                }
+                None => 0.0,
+            });
+        }
+        self.status.buffers_filled.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Build an output stream that plays the samples of `source`
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut source: PlaybackSource,
+) -> SonataResult<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let status = source.status();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| source.fill(data),
+            move |e| {
+                log::error!("Audio output stream error: {}", e);
+                status.failed.store(true, Ordering::SeqCst);
+            },
+            None,
+        )
+        .map_err(|e| device_error(e.to_string()))
+}
+
+fn device_error(reason: impl Into<String>) -> SonataError {
+    SonataError::OperationError(format!("Failed to play audio: {}", reason.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Play `source` into buffers of `buffer_len` samples until it is drained, like a device
+    fn play(mut source: PlaybackSource, buffer_len: usize) -> Vec<f32> {
+        let status = source.status();
+        let mut played = Vec::new();
+        while !status.drained.load(Ordering::SeqCst) {
+            let mut buffer = vec![1.0f32; buffer_len];
+            source.fill(&mut buffer);
+            played.extend(buffer);
+        }
+        played
+    }
+
+    #[test]
+    fn test_chunks_play_in_order_then_silence() {
+        let (sender, source) = playback_queue();
+        let sink = std::thread::spawn(move || play(source, 3));
+        for chunk in [vec![0.1, 0.2], vec![0.3], vec![0.4, 0.5, 0.6, 0.7]] {
+            sender.send(chunk).unwrap();
+        }
+        drop(sender);
+        let played = sink.join().unwrap();
+        let speech = Vec::from_iter(played.iter().copied().filter(|sample| *sample != 0.0));
+        assert_eq!(speech, [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7]);
+        assert_eq!(played.len() % 3, 0);
+    }
+
+    #[test]
+    fn test_queue_is_bounded() {
+        let (sender, _source) = playback_queue();
+        for _ in 0..MAX_QUEUED_CHUNKS {
+            sender.try_send(vec![0.5; 16]).unwrap();
+        }
+        assert!(sender.try_send(vec![0.5; 16]).is_err());
+    }
+
+    #[test]
+    fn test_waiting_on_a_stalled_device_fails() {
+        let (sender, source) = playback_queue();
+        let mut watch = PlaybackWatch::new(source.status(), Duration::from_millis(50));
+        drop(sender);
+        // Nothing plays the source, so it is never drained
+        assert!(watch.wait_until_drained().is_err());
+    }
+
+    #[test]
+    fn test_waiting_on_a_failed_stream_fails() {
+        let (_sender, source) = playback_queue();
+        let mut watch = PlaybackWatch::new(source.status(), STALL_TIMEOUT);
+        source.status.failed.store(true, Ordering::SeqCst);
+        assert!(watch.check().is_err());
+    }
+
+    #[test]
+    fn test_waiting_on_a_playing_device_succeeds() {
+        let (sender, source) = playback_queue();
+        let mut watch = PlaybackWatch::new(source.status(), Duration::from_millis(50));
+        let sink = std::thread::spawn(move || play(source, 3));
+        sender.send(vec![0.5; 16]).unwrap();
+        drop(sender);
+        watch.wait_until_drained().unwrap();
+        sink.join().unwrap();
+    }
+
+    #[test]
+    fn test_device_frames_copy_the_channels() {
+        let mono: AudioSamples = vec![0.1, 0.2].into();
+        assert_eq!(to_device_frames(&mono, 1, 2), [0.1, 0.1, 0.2, 0.2]);
+        let stereo: AudioSamples = vec![0.1, 0.2, 0.3, 0.4].into();
+        assert_eq!(to_device_frames(&stereo, 2, 2), stereo.as_slice());
+    }
+}
@@ -0,0 +1,526 @@
+use crate::{utils, AudioOutputConfig, PITCH_RANGE, RATE_RANGE, VOLUME_RANGE};
+#[cfg(feature = "autotune")]
+use audio_ops::Autotune;
+use audio_ops::{BiquadFilter, DeEsser, Resampler};
+use sonata_core::{SonataError, SonataResult};
+
+/// Default center frequency of the sibilance band compressed by the de-esser
+const DE_ESSER_FREQUENCY_HZ: u32 = 6500;
+
+/// Applies the effects of an [`AudioOutputConfig`] to a stream of samples.
+///
+/// Every stage keeps its state between calls to [`OutputProcessor::process`], so processing
+/// a segment chunk by chunk yields the same samples as processing it in one go. The stages
+/// run in the order: downmix to mono, gain, rate/volume/pitch (sonic), high-pass filter, de-esser,
+/// autotune, resampling.
+/// Appended silence is fed through the same stages when the segment is finished.
+pub(crate) struct OutputProcessor {
+    /// Number of channels of the input, when it's downmixed to mono
+    downmix_channels: Option<usize>,
+    /// Linear factor of the gain
+    gain: Option<f32>,
+    sonic: Option<SonicStream>,
+    highpass: Option<BiquadFilter>,
+    de_esser: Option<DeEsser>,
+    #[cfg(feature = "autotune")]
+    autotune: Option<Autotune>,
+    resampler: Option<Resampler>,
+    sample_rate: usize,
+    num_channels: usize,
+    /// Number of channels of the appended silence, which is run through the downmix too
+    appended_silence_channels: usize,
+    /// Number of silent frames run through the stages at the end of the segment
+    appended_silence_frames: usize,
+    /// Number of silent frames added after the stages at the end of the segment
+    padding_frames: usize,
+    /// The segment is padded to a multiple of this many frames
+    block_frames: Option<usize>,
+    /// Number of samples output so far
+    output_len: usize,
+    scratch: Vec<f32>,
+}
+
+impl OutputProcessor {
+    pub(crate) fn new(
+        config: &AudioOutputConfig,
+        sample_rate: usize,
+        num_channels: usize,
+    ) -> SonataResult<Self> {
+        let downmix_channels = match config.channels {
+            Some(0) => {
+                return Err(SonataError::OperationError(
+                    "Number of output channels must be greater than zero".to_string(),
+                ))
+            }
+            Some(1) if num_channels > 1 => Some(num_channels),
+            Some(channels) if channels as usize != num_channels.max(1) => {
+                return Err(SonataError::OperationError(format!(
+                    "Can't convert {} channel(s) to {}. Only downmixing to mono is supported",
+                    num_channels, channels
+                )))
+            }
+            _ => None,
+        };
+        let gain = match config.gain_db {
+            Some(gain_db) if !gain_db.is_finite() => {
+                return Err(SonataError::OperationError(format!(
+                    "Invalid gain: {}",
+                    gain_db
+                )))
+            }
+            Some(gain_db) => Some(10f32.powf(gain_db / 20.0)),
+            None => None,
+        };
+        // The stages after the downmix see the output channels
+        let appended_silence_channels = num_channels.max(1);
+        let num_channels = config.output_num_channels(num_channels);
+        let resampler = match config.sample_rate {
+            Some(0) => {
+                return Err(SonataError::OperationError(
+                    "Output sample rate must be greater than zero".to_string(),
+                ))
+            }
+            Some(output_rate) if output_rate as usize != sample_rate => Some(Resampler::new(
+                sample_rate,
+                output_rate as usize,
+                num_channels,
+            )),
+            _ => None,
+        };
+        let de_esser = match config.de_esser_threshold_db {
+            Some(threshold_db) if !threshold_db.is_finite() => {
+                return Err(SonataError::OperationError(format!(
+                    "Invalid de-esser threshold: {}",
+                    threshold_db
+                )))
+            }
+            Some(threshold_db) => Some(DeEsser::new(
+                sample_rate,
+                config.de_esser_frequency_hz.unwrap_or(DE_ESSER_FREQUENCY_HZ) as f32,
+                threshold_db,
+                num_channels,
+            )),
+            None => None,
+        };
+        if config.block_frames == Some(0) {
+            return Err(SonataError::OperationError(
+                "Block size must be greater than zero".to_string(),
+            ));
+        }
+        let num_channels = num_channels.max(1);
+        #[cfg(feature = "autotune")]
+        let autotune = match config.autotune {
+            Some(autotune_config) => Some(Autotune::new(autotune_config, sample_rate, num_channels)?),
+            None => None,
+        };
+        #[cfg(not(feature = "autotune"))]
+        if config.autotune.is_some() {
+            return Err(SonataError::OperationError(
+                "Autotune is not available. Sonata was built without the `autotune` feature".to_string(),
+            ));
+        }
+        let highpass = match config.highpass_cutoff_hz {
+            Some(cutoff_hz) => Some(BiquadFilter::highpass(sample_rate, cutoff_hz as f32, num_channels)?),
+            None => None,
+        };
+        Ok(Self {
+            downmix_channels,
+            gain,
+            sonic: uses_sonic(config).then(|| SonicStream::new(config, sample_rate, num_channels)),
+            highpass,
+            de_esser,
+            #[cfg(feature = "autotune")]
+            autotune,
+            resampler,
+            sample_rate,
+            num_channels,
+            appended_silence_channels,
+            appended_silence_frames: config
+                .appended_silence_ms
+                .map_or(0, |time_ms| (time_ms as usize * sample_rate) / 1000),
+            padding_frames: config.appended_silence_frames.unwrap_or_default() as usize,
+            block_frames: config.block_frames.map(|block_frames| block_frames as usize),
+            output_len: 0,
+            scratch: Vec::new(),
+        })
+    }
+    /// Apply the rate, volume and pitch of `config` to the rest of the segment.
+    /// The other effects keep the settings the processor was created with.
+    pub(crate) fn set_prosody(&mut self, config: &AudioOutputConfig) {
+        match self.sonic {
+            Some(ref mut sonic) => sonic.set_prosody(config),
+            None if uses_sonic(config) => {
+                self.sonic = Some(SonicStream::new(config, self.sample_rate, self.num_channels))
+            }
+            None => {}
+        }
+    }
+    /// Process a chunk of the segment, appending the output that is ready so far to `out`
+    pub(crate) fn process(&mut self, samples: &[f32], out: &mut Vec<f32>) {
+        let start = out.len();
+        let downmixed;
+        let samples = match self.downmix_channels {
+            Some(num_channels) => {
+                downmixed = downmix_to_mono(samples, num_channels);
+                &downmixed
+            }
+            None => samples,
+        };
+        let amplified;
+        let samples = match self.gain {
+            Some(gain) => {
+                amplified = samples.iter().map(|sample| sample * gain).collect::<Vec<_>>();
+                &amplified
+            }
+            None => samples,
+        };
+        match self.sonic {
+            Some(ref mut sonic) => {
+                sonic.write(samples);
+                sonic.read_into(out);
+            }
+            None => out.extend_from_slice(samples),
+        }
+        if let Some(ref mut highpass) = self.highpass {
+            highpass.process(&mut out[start..]);
+        }
+        if let Some(ref mut de_esser) = self.de_esser {
+            de_esser.process(&mut out[start..]);
+        }
+        #[cfg(feature = "autotune")]
+        if let Some(ref mut autotune) = self.autotune {
+            self.scratch.clear();
+            self.scratch.extend(out.drain(start..));
+            autotune.process(&self.scratch, out);
+        }
+        if let Some(ref mut resampler) = self.resampler {
+            self.scratch.clear();
+            self.scratch.extend(out.drain(start..));
+            resampler.process(&self.scratch, out);
+        }
+        self.output_len += out.len() - start;
+    }
+    /// End the segment: append the appended silence and the samples still held by the stages
+    pub(crate) fn finish(mut self, out: &mut Vec<f32>) {
+        if self.appended_silence_frames > 0 {
+            let silence = vec![0f32; self.appended_silence_frames * self.appended_silence_channels];
+            self.process(&silence, out);
+        }
+        let start = out.len();
+        if let Some(ref mut sonic) = self.sonic {
+            sonic.flush();
+            sonic.read_into(out);
+        }
+        if let Some(ref mut highpass) = self.highpass {
+            highpass.process(&mut out[start..]);
+        }
+        if let Some(ref mut de_esser) = self.de_esser {
+            de_esser.process(&mut out[start..]);
+        }
+        #[cfg(feature = "autotune")]
+        if let Some(ref mut autotune) = self.autotune {
+            self.scratch.clear();
+            self.scratch.extend(out.drain(start..));
+            autotune.process(&self.scratch, out);
+            autotune.finish(out);
+        }
+        if let Some(ref mut resampler) = self.resampler {
+            self.scratch.clear();
+            self.scratch.extend(out.drain(start..));
+            resampler.process(&self.scratch, out);
+            resampler.finish(out);
+        }
+        let mut padding_frames = self.padding_frames;
+        if let Some(block_frames) = self.block_frames {
+            let num_frames = (self.output_len + out.len() - start) / self.num_channels + padding_frames;
+            padding_frames += num_frames.next_multiple_of(block_frames) - num_frames;
+        }
+        out.resize(out.len() + padding_frames * self.num_channels, 0.0);
+    }
+}
+
+/// The average of the channels of each frame of interleaved `samples`
+fn downmix_to_mono(samples: &[f32], num_channels: usize) -> Vec<f32> {
+    samples
+        .chunks_exact(num_channels)
+        .map(|frame| frame.iter().sum::<f32>() / num_channels as f32)
+        .collect()
+}
+
+fn uses_sonic(config: &AudioOutputConfig) -> bool {
+    config.rate.is_some() || config.volume.is_some() || config.pitch.is_some()
+}
+
+struct SonicStream {
+    stream: sonic_sys::sonicStream,
+    num_channels: usize,
+}
+
+// The stream is exclusively owned and only accessed through `&mut self`
+unsafe impl Send for SonicStream {}
+
+impl SonicStream {
+    fn new(config: &AudioOutputConfig, sample_rate: usize, num_channels: usize) -> Self {
+        let num_channels = num_channels.max(1);
+        let stream = unsafe { sonic_sys::sonicCreateStream(sample_rate as i32, num_channels as i32) };
+        let mut sonic = Self {
+            stream,
+            num_channels,
+        };
+        sonic.set_prosody(config);
+        sonic
+    }
+    /// Set the speed, volume and pitch, using the unmodified value for the ones missing from `config`
+    fn set_prosody(&mut self, config: &AudioOutputConfig) {
+        let param = |percent: Option<u8>, range: (f32, f32)| {
+            percent.map_or(1.0, |percent| utils::percent_to_param(percent, range.0, range.1))
+        };
+        unsafe {
+            sonic_sys::sonicSetSpeed(self.stream, param(config.rate, RATE_RANGE));
+            sonic_sys::sonicSetVolume(self.stream, param(config.volume, VOLUME_RANGE));
+            sonic_sys::sonicSetPitch(self.stream, param(config.pitch, PITCH_RANGE));
+        }
+    }
+    fn write(&mut self, samples: &[f32]) {
+        let num_frames = samples.len() / self.num_channels;
+        if num_frames > 0 {
+            unsafe {
+                sonic_sys::sonicWriteFloatToStream(self.stream, samples.as_ptr(), num_frames as i32);
+            }
+        }
+    }
+    fn flush(&mut self) {
+        unsafe {
+            sonic_sys::sonicFlushStream(self.stream);
+        }
+    }
+    /// Append the frames sonic has ready to `out`
+    fn read_into(&mut self, out: &mut Vec<f32>) {
+        unsafe {
+            let num_frames = sonic_sys::sonicSamplesAvailable(self.stream);
+            if num_frames <= 0 {
+                return;
+            }
+            let num_samples = num_frames as usize * self.num_channels;
+            out.reserve_exact(num_samples);
+            let num_read = sonic_sys::sonicReadFloatFromStream(
+                self.stream,
+                out.spare_capacity_mut().as_mut_ptr().cast(),
+                num_frames,
+            );
+            out.set_len(out.len() + num_read.max(0) as usize * self.num_channels);
+        }
+    }
+}
+
+impl Drop for SonicStream {
+    fn drop(&mut self) {
+        unsafe {
+            sonic_sys::sonicDestroyStream(self.stream);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process_in_chunks(config: &AudioOutputConfig, samples: &[f32], chunk_len: usize) -> Vec<f32> {
+        process_frames_in_chunks(config, samples, 1, chunk_len)
+    }
+
+    fn process_frames_in_chunks(
+        config: &AudioOutputConfig,
+        samples: &[f32],
+        num_channels: usize,
+        chunk_len: usize,
+    ) -> Vec<f32> {
+        let mut processor = OutputProcessor::new(config, 16000, num_channels).unwrap();
+        let mut out = Vec::new();
+        for chunk in samples.chunks(chunk_len) {
+            processor.process(chunk, &mut out);
+        }
+        processor.finish(&mut out);
+        out
+    }
+
+    #[test]
+    fn test_chunked_processing_matches_whole() {
+        let config = AudioOutputConfig {
+            volume: Some(60),
+            highpass_cutoff_hz: Some(120),
+            de_esser_threshold_db: Some(-30.0),
+            sample_rate: Some(22050),
+            appended_silence_ms: Some(20),
+            ..Default::default()
+        };
+        let samples: Vec<f32> = (0..4000).map(|i| ((i as f32) * 0.03).sin() * 0.5).collect();
+        let whole = process_in_chunks(&config, &samples, samples.len());
+        let chunked = process_in_chunks(&config, &samples, 357);
+        assert_eq!(whole, chunked);
+        // 4000 samples plus 320 of silence at 16 kHz, resampled to 22.05 kHz
+        assert_eq!(whole.len(), (4320 * 22050usize).div_ceil(16000));
+    }
+
+    #[test]
+    fn test_stereo_padding_keeps_frames_whole() {
+        let config = AudioOutputConfig {
+            appended_silence_ms: Some(10),
+            appended_silence_frames: Some(7),
+            ..Default::default()
+        };
+        let samples: Vec<f32> = [0.5, -0.5].repeat(100);
+        let out = process_frames_in_chunks(&config, &samples, 2, 50);
+        // 100 frames, 160 frames of silence and 7 frames of padding
+        assert_eq!(out.len(), (100 + 160 + 7) * 2);
+        assert_eq!(out[..200], samples[..]);
+        assert!(out[200..].iter().all(|sample| *sample == 0.0));
+        let resampled = process_frames_in_chunks(
+            &AudioOutputConfig {
+                sample_rate: Some(8000),
+                ..config
+            },
+            &samples,
+            2,
+            50,
+        );
+        assert_eq!(resampled.len(), (130 + 7) * 2);
+    }
+
+    #[test]
+    fn test_downmixing_dual_mono_reproduces_mono() {
+        let config = AudioOutputConfig {
+            channels: Some(1),
+            appended_silence_ms: Some(10),
+            ..Default::default()
+        };
+        let mono: Vec<f32> = (0..500).map(|i| ((i as f32) * 0.07).sin() * 0.8).collect();
+        let dual_mono: Vec<f32> = mono.iter().flat_map(|sample| [*sample, *sample]).collect();
+        let out = process_frames_in_chunks(&config, &dual_mono, 2, 64);
+        assert_eq!(out.len(), 500 + 160);
+        assert_eq!(out[..500], mono[..]);
+        assert_eq!(process_frames_in_chunks(&config, &[0.25, -0.75, 1.0, 0.5], 2, 4)[..2], [-0.25, 0.75]);
+        assert_eq!(process_frames_in_chunks(&config, &mono, 1, 64)[..500], mono[..]);
+        assert!(OutputProcessor::new(&AudioOutputConfig { channels: Some(2), ..config }, 16000, 1).is_err());
+        assert!(OutputProcessor::new(&AudioOutputConfig { channels: Some(0), ..config }, 16000, 2).is_err());
+    }
+
+    #[test]
+    fn test_gain_scales_the_samples() {
+        let config = AudioOutputConfig {
+            gain_db: Some(-20.0 * 2f32.log10()),
+            ..Default::default()
+        };
+        let samples: Vec<f32> = (0..1000).map(|i| ((i as f32) * 0.07).sin() * 0.8).collect();
+        let out = process_in_chunks(&config, &samples, 128);
+        assert_eq!(out.len(), samples.len());
+        assert!(out.iter().zip(&samples).all(|(out, sample)| (out - sample / 2.0).abs() < 1e-6));
+        let invalid = AudioOutputConfig { gain_db: Some(f32::NAN), ..config };
+        assert!(OutputProcessor::new(&invalid, 16000, 1).is_err());
+    }
+
+    #[test]
+    fn test_de_esser_is_off_by_default() {
+        let samples: Vec<f32> = (0..1600).map(|i| ((i as f32) * 2.5).sin() * 0.8).collect();
+        let out = process_in_chunks(&AudioOutputConfig::default(), &samples, 400);
+        assert_eq!(out, samples);
+        let config = AudioOutputConfig {
+            de_esser_threshold_db: Some(-30.0),
+            ..Default::default()
+        };
+        let de_essed = process_in_chunks(&config, &samples, 400);
+        assert_eq!(de_essed.len(), samples.len());
+        assert_ne!(de_essed, samples);
+    }
+
+    #[test]
+    fn test_prosody_change_applies_to_later_chunks() {
+        let samples = vec![0.5f32; 1600];
+        let mut processor = OutputProcessor::new(&AudioOutputConfig::default(), 16000, 1).unwrap();
+        let mut out = Vec::new();
+        processor.process(&samples, &mut out);
+        processor.set_prosody(&AudioOutputConfig {
+            volume: Some(50),
+            ..Default::default()
+        });
+        processor.process(&samples, &mut out);
+        processor.finish(&mut out);
+        assert_eq!(out[..1600], samples[..]);
+        assert!((out[out.len() - 1] - 0.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_segments_are_padded_to_the_block_size() {
+        let samples: Vec<f32> = [0.5, -0.5].repeat(1000);
+        for (block_frames, num_frames) in [(1, 1000 + 160 + 7), (256, 1280), (441, 1323)] {
+            let config = AudioOutputConfig {
+                appended_silence_ms: Some(10),
+                appended_silence_frames: Some(7),
+                block_frames: Some(block_frames),
+                ..Default::default()
+            };
+            let out = process_frames_in_chunks(&config, &samples, 2, 300);
+            assert_eq!(out.len(), num_frames * 2);
+            assert_eq!(out[..2000], samples[..]);
+        }
+        let resampled = AudioOutputConfig {
+            sample_rate: Some(22050),
+            block_frames: Some(512),
+            ..Default::default()
+        };
+        let out = process_frames_in_chunks(&resampled, &samples, 2, 300);
+        assert_eq!(out.len() % (512 * 2), 0);
+        assert!(OutputProcessor::new(&AudioOutputConfig { block_frames: Some(0), ..resampled }, 16000, 2).is_err());
+    }
+
+    #[test]
+    fn test_zero_output_sample_rate_is_rejected() {
+        let config = AudioOutputConfig {
+            sample_rate: Some(0),
+            ..Default::default()
+        };
+        assert!(OutputProcessor::new(&config, 16000, 1).is_err());
+    }
+
+    #[test]
+    fn test_highpass_above_nyquist_is_rejected() {
+        let config = AudioOutputConfig {
+            highpass_cutoff_hz: Some(8000),
+            ..Default::default()
+        };
+        assert!(OutputProcessor::new(&config, 16000, 1).is_err());
+        assert!(OutputProcessor::new(&config, 22050, 1).is_ok());
+    }
+
+    #[cfg(feature = "autotune")]
+    #[test]
+    fn test_autotune_keeps_the_segment_length() {
+        let config = AudioOutputConfig {
+            autotune: Some(audio_ops::AutotuneConfig {
+                scale: audio_ops::Scale::Major(0),
+                strength: 1.0,
+            }),
+            appended_silence_ms: Some(20),
+            ..Default::default()
+        };
+        let samples: Vec<f32> = (0..8000).map(|i| ((i as f32) * 0.09).sin() * 0.5).collect();
+        let whole = process_in_chunks(&config, &samples, samples.len());
+        let chunked = process_in_chunks(&config, &samples, 357);
+        assert_eq!(whole, chunked);
+        assert_eq!(whole.len(), 8320);
+        assert_ne!(whole[..8000], samples[..]);
+    }
+
+    #[cfg(not(feature = "autotune"))]
+    #[test]
+    fn test_autotune_requires_the_feature() {
+        let config = AudioOutputConfig {
+            autotune: Some(audio_ops::AutotuneConfig {
+                scale: audio_ops::Scale::Chromatic,
+                strength: 1.0,
+            }),
+            ..Default::default()
+        };
+        assert!(OutputProcessor::new(&config, 16000, 1).is_err());
+    }
+}
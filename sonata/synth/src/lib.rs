@@ -1,18 +1,54 @@
+mod config_string;
+#[cfg(feature = "device")]
+mod device;
+mod effects;
+mod manifest;
+#[cfg(test)]
+mod mock_model;
+mod normalizer;
+mod overrides;
+mod pauses;
+mod preprocessing;
+mod sentences;
+mod sse;
+mod stats;
 mod utils;
+pub use manifest::ManifestReport;
+pub use normalizer::{NumberReading, TextNormalizer};
+pub use overrides::OVERRIDES_ENV_VAR;
+pub use pauses::PunctuationPauses;
+pub use preprocessing::{MarkdownStripping, TextPreprocessing, TrailingPunctuation, UnicodeNormalization};
+pub use sentences::SentenceEvent;
+pub use sse::{SseAudioFormat, SseStream};
+pub use stats::{record_cache_lookup, stats, StreamStats, SynthesisStats};
 pub use sonata_core::*;
 
+use audio_ops::GainRamp;
 use flume::{Receiver, SendError, Sender};
 use once_cell::sync::Lazy;
-use rayon::prelude::*;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
 use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::any::Any;
 use std::collections::HashMap;
-use std::path::Path;
-use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use effects::OutputProcessor;
+use sentences::SentenceNotifier;
+use std::sync::{Arc, Mutex, RwLock};
 
 const RATE_RANGE: (f32, f32) = (0.5f32, 5.5f32);
 const VOLUME_RANGE: (f32, f32) = (0.0f32, 1.0f32);
 const PITCH_RANGE: (f32, f32) = (0.5f32, 1.5f32);
+const MAX_POOLED_BUFFERS: usize = 16;
+
+/// The phrase [`SonataSpeechSynthesizer::calibrate_gain`] measures the loudness of a voice on,
+/// with a mix of vowels, voiced and unvoiced consonants and both sentence endings
+pub const CALIBRATION_PHRASE: &str =
+    "The quick brown fox jumps over the lazy dog. Is she selling sea shells by the shore?";
+/// A loudness target for [`SonataSpeechSynthesizer::calibrate_gain`] that leaves headroom
+/// for the peaks of most voices
+pub const DEFAULT_LOUDNESS_TARGET_DB: f32 = -20.0;
 
 pub static SYNTHESIS_THREAD_POOL: Lazy<ThreadPool> = Lazy::new(|| {
     let num_cpus = std::thread::available_parallelism()
@@ -25,102 +61,683 @@ pub static SYNTHESIS_THREAD_POOL: Lazy<ThreadPool> = Lazy::new(|| {
         .unwrap()
 });
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct AudioOutputConfig {
     pub rate: Option<u8>,
     pub volume: Option<u8>,
     pub pitch: Option<u8>,
+    /// Silence appended to each segment, run through the other effects like speech
     pub appended_silence_ms: Option<u32>,
+    /// Number of silent frames appended to each segment after all the other effects,
+    /// at the output sample rate. Unlike `appended_silence_ms`, the length is exact.
+    pub appended_silence_frames: Option<u32>,
+    /// Cutoff frequency (in Hz) of a high-pass filter that removes low-frequency rumble
+    pub highpass_cutoff_hz: Option<u32>,
+    /// Enable the de-esser, which turns down sibilance louder than this level (in dBFS, e.g. `-30`)
+    pub de_esser_threshold_db: Option<f32>,
+    /// Center frequency (in Hz) of the band the de-esser compresses. Defaults to 6500 Hz.
+    pub de_esser_frequency_hz: Option<u32>,
+    /// Resample the output to this rate (in Hz). Defaults to the model's native rate.
+    /// See [`SonataSpeechSynthesizer::output_sample_rate`].
+    pub sample_rate: Option<u32>,
+    /// Pad each segment (including the pause after it) with silence so that its number of
+    /// frames is a multiple of this, for hardware that consumes audio in fixed-size blocks
+    pub block_frames: Option<u32>,
+    /// Retune the speech toward the notes of a scale. Requires the `autotune` feature, as
+    /// pitch detection is much slower than the other effects.
+    pub autotune: Option<AutotuneConfig>,
+    /// Fade in the first milliseconds of the speech, to suppress the pop some voices start with.
+    /// This only touches the very start of the utterance: the first clip, or the first chunk of
+    /// a stream (see [`StreamingConfig::fade_in_ms`], the longer of the two is used).
+    /// [`AudioOutputConfig::DEFAULT_QUIET_START_MS`] is enough for most voices.
+    pub quiet_start_ms: Option<u32>,
+    /// Number of channels of the output. Besides the model's own number, only `1` is
+    /// supported, which downmixes multi-channel output to mono by averaging its channels.
+    /// See [`SonataSpeechSynthesizer::output_num_channels`].
+    pub channels: Option<u16>,
+    /// Gain (in dB) applied to the speech before the other effects, e.g. `-6` to halve its
+    /// amplitude. A synthesizer adds its calibrated gain to this, see
+    /// [`SonataSpeechSynthesizer::calibrate_gain`]. Speech with a gain is converted to PCM at
+    /// a fixed scale (see [`Audio::fixed_scale`]), clipping what goes beyond full scale,
+    /// rather than normalized to its peak, so that clips with the same gain have the same level.
+    pub gain_db: Option<f32>,
 }
 
 impl AudioOutputConfig {
+    /// A few milliseconds: long enough to mute an initial transient, short enough to be inaudible
+    pub const DEFAULT_QUIET_START_MS: u32 = 5;
+
     fn apply(&self, mut audio: Audio) -> SonataAudioResult {
-        let mut samples = audio.samples.take();
-        if let Some(time_ms) = self.appended_silence_ms {
-            let mut silence_samples = self.generate_silence(
-                time_ms as usize,
-                audio.info.sample_rate,
-                audio.info.num_channels,
-            )?;
-            samples.append(silence_samples.take().as_mut());
-        }
+        let samples = audio.samples.take();
         let mut samples = self.apply_to_raw_samples(
             samples.into(),
             audio.info.sample_rate,
             audio.info.num_channels,
         )?;
         audio.samples.as_mut_vec().append(samples.as_mut_vec());
+        audio.info.sample_rate = self.output_sample_rate(audio.info.sample_rate);
+        audio.info.num_channels = self.output_num_channels(audio.info.num_channels);
+        if let (Some(rate), Some(timings)) = (self.rate, audio.phoneme_timings.as_mut()) {
+            // The phonemes move with the speed of the speech
+            let speed = utils::percent_to_param(rate, RATE_RANGE.0, RATE_RANGE.1);
+            for timing in timings.iter_mut() {
+                timing.start_ms /= speed;
+                timing.end_ms /= speed;
+            }
+        }
         Ok(audio)
     }
+    fn hash_into(&self, state: &mut impl Hasher) {
+        // Destructured so that new fields can't be left out of the hash
+        let Self {
+            rate,
+            volume,
+            pitch,
+            appended_silence_ms,
+            appended_silence_frames,
+            highpass_cutoff_hz,
+            de_esser_threshold_db,
+            de_esser_frequency_hz,
+            sample_rate,
+            block_frames,
+            autotune,
+            quiet_start_ms,
+            channels,
+            gain_db,
+        } = self;
+        (rate, volume, pitch).hash(state);
+        (appended_silence_ms, appended_silence_frames).hash(state);
+        highpass_cutoff_hz.hash(state);
+        de_esser_threshold_db.map(f32::to_bits).hash(state);
+        (de_esser_frequency_hz, sample_rate, block_frames).hash(state);
+        autotune.map(|config| (config.scale, config.strength.to_bits())).hash(state);
+        (quiet_start_ms, channels).hash(state);
+        gain_db.map(f32::to_bits).hash(state);
+    }
+    /// Fade in the start of `audio`, the start of the utterance, when there is a quiet start
+    fn apply_quiet_start(&self, audio: &mut Audio) {
+        if let Some(quiet_start_ms) = self.quiet_start_ms {
+            GainRamp::from_duration_ms(quiet_start_ms, audio.info.sample_rate, audio.info.num_channels)
+                .process(audio.samples.as_mut_vec());
+        }
+    }
+    /// The sample rate of the output for the given native sample rate
+    fn output_sample_rate(&self, native_sample_rate: usize) -> usize {
+        self.sample_rate
+            .map_or(native_sample_rate, |sample_rate| sample_rate as usize)
+    }
+    /// The number of channels of the output for the given native number of channels
+    fn output_num_channels(&self, native_num_channels: usize) -> usize {
+        self.channels
+            .map_or(native_num_channels, |channels| channels as usize)
+    }
     fn apply_to_raw_samples(
         &self,
         samples: AudioSamples,
         sample_rate: usize,
         num_channels: usize,
     ) -> SonataResult<AudioSamples> {
-        let samples = samples.into_vec();
-        let input_len = samples.len();
-        if input_len == 0 {
-            return Ok(samples.into());
-        }
-        let mut out_buf: Vec<f32> = Vec::new();
-        unsafe {
-            let stream = sonic_sys::sonicCreateStream(sample_rate as i32, num_channels as i32);
-            if let Some(rate) = self.rate {
-                sonic_sys::sonicSetSpeed(
-                    stream,
-                    utils::percent_to_param(rate, RATE_RANGE.0, RATE_RANGE.1),
-                );
-            }
-            if let Some(volume) = self.volume {
-                sonic_sys::sonicSetVolume(
-                    stream,
-                    utils::percent_to_param(volume, VOLUME_RANGE.0, VOLUME_RANGE.1),
-                );
-            }
-            if let Some(pitch) = self.pitch {
-                sonic_sys::sonicSetPitch(
-                    stream,
-                    utils::percent_to_param(pitch, PITCH_RANGE.0, PITCH_RANGE.1),
-                );
-            }
-            sonic_sys::sonicWriteFloatToStream(stream, samples.as_ptr(), input_len as i32);
-            sonic_sys::sonicFlushStream(stream);
-            let num_samples = sonic_sys::sonicSamplesAvailable(stream);
-            if num_samples <= 0 {
-                return Err(
-                    SonataError::OperationError("Sonic Error: failed to apply audio config. Invalid parameter value for rate, volume, or pitch".to_string())
-                );
-            }
-            out_buf.reserve_exact(num_samples as usize);
-            sonic_sys::sonicReadFloatFromStream(
-                stream,
-                out_buf.spare_capacity_mut().as_mut_ptr().cast(),
-                num_samples,
+        let mut out = Vec::new();
+        let mut processor = OutputProcessor::new(self, sample_rate, num_channels)?;
+        processor.process(samples.as_slice(), &mut out);
+        processor.finish(&mut out);
+        if !samples.is_empty() && out.is_empty() {
+            return Err(
+                SonataError::OperationError("Sonic Error: failed to apply audio config. Invalid parameter value for rate, volume, or pitch".to_string())
             );
-            sonic_sys::sonicDestroyStream(stream);
-            out_buf.set_len(num_samples as usize);
         }
-        Ok(out_buf.into())
+        Ok(out.into())
     }
-    #[inline(always)]
-    fn generate_silence(
+}
+
+/// Options controlling how [`SonataSpeechSynthesizer::synthesize_streamed_with_config`] chunks
+/// and delivers audio.
+#[derive(Clone)]
+pub struct StreamingConfig<'a> {
+    /// Number of mel frames to synthesize for the first chunk
+    pub chunk_size: usize,
+    /// Number of mel frames used to pad each chunk (improves naturalness)
+    pub chunk_padding: usize,
+    /// Reuse sample buffers across chunks instead of allocating new ones.
+    /// Buffers given back via [`RealtimeSpeechStream::recycle`] are used for subsequent chunks.
+    pub low_memory: bool,
+    /// Run the stream's worker on this pool instead of [`SYNTHESIS_THREAD_POOL`].
+    ///
+    /// The pool is only borrowed while the stream is being created: the worker is spawned
+    /// onto it and doesn't hold a reference to it. Dropping the pool while the stream is
+    /// still being consumed is fine, since rayon lets queued and running jobs finish
+    /// before the pool's threads exit.
+    pub thread_pool: Option<&'a ThreadPool>,
+    /// Fade the stream in over its first milliseconds. The ramp carries across chunks and
+    /// sentences, so it sounds the same as fading in the whole clip at once. The
+    /// [`AudioOutputConfig::quiet_start_ms`] of the stream's output config uses the same ramp.
+    pub fade_in_ms: Option<u32>,
+    /// Experimental: start each sentence before all of it is encoded, a few words at a time,
+    /// for the lowest latency to the first audio. Only models that support it benefit (see
+    /// [`SonataModel::supports_incremental_synthesis`]). Other models fall back to the
+    /// chunked streaming they use without this.
+    pub incremental: bool,
+}
+
+impl Default for StreamingConfig<'_> {
+    fn default() -> Self {
+        Self {
+            chunk_size: 72,
+            chunk_padding: 3,
+            low_memory: false,
+            thread_pool: None,
+            fade_in_ms: None,
+            incremental: false,
+        }
+    }
+}
+
+/// Options for [`SonataSpeechSynthesizer::synthesize_parallel_with_options`]
+#[derive(Clone, Default)]
+pub struct ParallelSynthesisOptions<'a> {
+    /// Split the text into sentences and attach the index and text of its sentence to
+    /// each clip (see [`Audio::sentence`]). Each clip then holds a whole sentence.
+    pub include_sentence_info: bool,
+    /// The maximum number of sentences synthesized at once for this call. Limiting it
+    /// leaves room in the shared thread pool for concurrent requests. `None` is unlimited.
+    pub max_concurrency: Option<usize>,
+    /// Synthesize on this pool instead of rayon's global pool. Parallel synthesis
+    /// finishes before returning, so the pool only needs to outlive the call.
+    pub thread_pool: Option<&'a ThreadPool>,
+    /// Synthesize the sentences one after another on the calling thread, without any thread
+    /// pool (`max_concurrency` and `thread_pool` are ignored). Slower, but useful for
+    /// debugging, as stack traces stay on the caller's thread.
+    pub synchronous: bool,
+}
+
+impl ParallelSynthesisOptions<'_> {
+    fn max_workers(&self) -> SonataResult<Option<usize>> {
+        match self.max_concurrency {
+            Some(0) => Err(SonataError::OperationError(
+                "max_concurrency must be greater than zero".to_string(),
+            )),
+            max_concurrency => Ok(max_concurrency),
+        }
+    }
+}
+
+/// A bounded pool of sample buffers shared between a realtime stream's worker and its consumer.
+#[derive(Clone, Default)]
+pub struct SampleBufferPool(Arc<Mutex<Vec<Vec<f32>>>>);
+
+impl SampleBufferPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Take an empty buffer from the pool, or a new one if the pool is exhausted.
+    pub fn acquire(&self) -> Vec<f32> {
+        self.0.lock().unwrap().pop().unwrap_or_default()
+    }
+    /// Return a buffer to the pool. Buffers beyond the pool's capacity are dropped.
+    pub fn recycle(&self, samples: AudioSamples) {
+        let mut buf = samples.into_vec();
+        if buf.capacity() == 0 {
+            return;
+        }
+        buf.clear();
+        let mut buffers = self.0.lock().unwrap();
+        if buffers.len() < MAX_POOLED_BUFFERS {
+            buffers.push(buf);
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Synthesizer settings that can be changed after construction
+#[derive(Clone, Default)]
+struct SynthesisDefaults {
+    text_preprocessing: TextPreprocessing,
+    output_config: Option<AudioOutputConfig>,
+    sentence_notifier: Option<SentenceNotifier>,
+    punctuation_pauses: Option<PunctuationPauses>,
+    sample_converter: Option<SampleConverter>,
+    calibrated_gain_db: Option<f32>,
+}
+
+impl SynthesisDefaults {
+    /// `output_config`, falling back to the default one, with the calibrated gain added to it
+    fn resolve_output_config(
         &self,
-        time_ms: usize,
-        sample_rate: usize,
-        num_channels: usize,
-    ) -> SonataResult<AudioSamples> {
-        let num_samples = (time_ms * sample_rate) / 1000;
-        let silence_samples = vec![0f32; num_samples];
-        self.apply_to_raw_samples(silence_samples.into(), sample_rate, num_channels)
+        output_config: Option<AudioOutputConfig>,
+    ) -> Option<AudioOutputConfig> {
+        with_calibrated_gain(
+            output_config.or_else(|| self.output_config.clone()),
+            self.calibrated_gain_db,
+        )
+    }
+}
+
+/// `output_config` with `calibrated_gain_db` added to its gain
+fn with_calibrated_gain(
+    output_config: Option<AudioOutputConfig>,
+    calibrated_gain_db: Option<f32>,
+) -> Option<AudioOutputConfig> {
+    let Some(calibrated_gain_db) = calibrated_gain_db else {
+        return output_config;
+    };
+    let mut output_config = output_config.unwrap_or_default();
+    output_config.gain_db = Some(output_config.gain_db.unwrap_or_default() + calibrated_gain_db);
+    Some(output_config)
+}
+
+pub struct SonataSpeechSynthesizerBuilder {
+    model: Arc<dyn SonataModel + Sync + Send>,
+    text_normalizer: Option<TextNormalizer>,
+    number_reading: Option<NumberReading>,
+    rng: Option<Box<dyn RngCore + Send>>,
+    rng_seed: Option<u64>,
+    overrides_path: Option<PathBuf>,
+    speaker_names_path: Option<PathBuf>,
+    stable: bool,
+    defaults: SynthesisDefaults,
+}
+
+impl SonataSpeechSynthesizerBuilder {
+    pub fn new(model: Arc<dyn SonataModel + Sync + Send>) -> Self {
+        Self {
+            model,
+            text_normalizer: None,
+            number_reading: None,
+            rng: None,
+            rng_seed: None,
+            overrides_path: None,
+            speaker_names_path: None,
+            stable: false,
+            defaults: SynthesisDefaults::default(),
+        }
+    }
+    /// Clean up the input text (e.g. strip markdown) before normalization
+    pub fn with_text_preprocessing(mut self, text_preprocessing: TextPreprocessing) -> Self {
+        self.defaults.text_preprocessing = text_preprocessing;
+        self
+    }
+    /// See [`SonataSpeechSynthesizer::set_default_output_config`]
+    pub fn with_default_output_config(mut self, output_config: AudioOutputConfig) -> Self {
+        self.defaults.output_config = Some(output_config);
+        self
+    }
+    /// See [`SonataSpeechSynthesizer::set_sentence_callback`]
+    pub fn with_sentence_callback(
+        mut self,
+        callback: impl FnMut(SentenceEvent) + Send + 'static,
+    ) -> Self {
+        self.defaults.sentence_notifier = Some(SentenceNotifier::new(callback));
+        self
+    }
+    /// See [`SonataSpeechSynthesizer::set_sample_converter`]
+    pub fn with_sample_converter(
+        mut self,
+        convert: impl FnMut(&[f32]) -> Vec<i16> + Send + 'static,
+    ) -> Self {
+        self.defaults.sample_converter = Some(SampleConverter::new(convert));
+        self
+    }
+    /// Dither the samples when converting them to 16-bit PCM, with
+    /// [`SampleConverter::rounding`]. This replaces a converter given to `with_sample_converter`.
+    pub fn with_dither(mut self) -> Self {
+        self.defaults.sample_converter = Some(SampleConverter::rounding(true));
+        self
+    }
+    /// See [`SonataSpeechSynthesizer::set_calibrated_gain_db`]
+    pub fn with_calibrated_gain_db(mut self, gain_db: f32) -> Self {
+        self.defaults.calibrated_gain_db = Some(gain_db);
+        self
+    }
+    /// See [`SonataSpeechSynthesizer::set_punctuation_pauses`]
+    pub fn with_punctuation_pauses(mut self, punctuation_pauses: PunctuationPauses) -> Self {
+        self.defaults.punctuation_pauses = Some(punctuation_pauses);
+        self
+    }
+    /// Replace the text normalizer selected based on the model's language
+    pub fn with_text_normalizer(mut self, text_normalizer: TextNormalizer) -> Self {
+        self.text_normalizer = Some(text_normalizer);
+        self
+    }
+    /// Read numbers digit by digit or as numbers with the text normalizer, whether it is
+    /// selected based on the model's language or given with `with_text_normalizer`
+    pub fn with_number_reading(mut self, number_reading: NumberReading) -> Self {
+        self.number_reading = Some(number_reading);
+        self
+    }
+    /// Use the given random number generator for all the noise generated by the synthesizer.
+    /// The noise of models with noise inputs (see [`SonataModel::noise_shapes`]) is drawn
+    /// from it for every sentence, instead of being sampled by the model.
+    ///
+    /// This is meant for deterministic tests (e.g. a seeded `StdRng`). Production code
+    /// should keep the default, which is seeded from the operating system's entropy source.
+    /// Note that models which sample noise inside their inference graph are not affected,
+    /// and neither is the realtime output of streaming models, which sample their own noise.
+    pub fn with_rng(mut self, rng: Box<dyn RngCore + Send>) -> Self {
+        self.rng = Some(rng);
+        self.rng_seed = None;
+        self
+    }
+    /// Use a `StdRng` seeded with `seed` as the random number generator (see [`Self::with_rng`]).
+    /// Unlike a generator given to `with_rng`, the seed is part of [`SonataSpeechSynthesizer::input_hash`].
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Some(Box::new(StdRng::seed_from_u64(seed)));
+        self.rng_seed = Some(seed);
+        self
+    }
+    /// Load default scales and speaker from a JSON file, layered over the model config.
+    ///
+    /// The file may contain `speaker` (an id or a name), `noise_scale`, `length_scale` and
+    /// `noise_w`. [`Self::build`] fails if the file contains unknown keys or values that
+    /// conflict with the model config, and then leaves the model unchanged. The overrides
+    /// are applied to the model, so they are shared by all the synthesizers using it.
+    pub fn with_overrides(mut self, path: impl AsRef<Path>) -> Self {
+        self.overrides_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+    /// Like [`Self::with_overrides`], using the file named by the
+    /// [`OVERRIDES_ENV_VAR`] environment variable, if it is set. The file is only read and
+    /// applied by [`Self::build`], after everything else that can fail.
+    pub fn with_overrides_from_env(mut self) -> Self {
+        if let Some(path) = std::env::var_os(OVERRIDES_ENV_VAR).filter(|path| !path.is_empty()) {
+            self.overrides_path = Some(PathBuf::from(path));
+        }
+        self
+    }
+    /// Load names for the model's speakers from a JSON file mapping ids to names,
+    /// e.g. `{"0": "alice", "3": "bob"}`.
+    ///
+    /// The names are merged with the model's speaker names, replacing them for the ids in
+    /// the file, so speakers of models that ship without names can be selected by name.
+    /// Unlike the overrides, the names only apply to this synthesizer.
+    pub fn with_speaker_names(mut self, path: impl AsRef<Path>) -> Self {
+        self.speaker_names_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+    /// Apply [`SynthesisOverrides::stable`], which trades naturalness for consistent output.
+    /// Values from [`Self::with_overrides`] take precedence. Like the overrides file, the
+    /// preset is applied to the model, so it is shared by all the synthesizers using it.
+    pub fn with_stable_preset(mut self) -> Self {
+        self.stable = true;
+        self
+    }
+    pub fn build(self) -> SonataResult<SonataSpeechSynthesizer> {
+        let speaker_names = match self.speaker_names_path.as_ref() {
+            Some(path) => {
+                let mut speaker_names = self.model.get_speakers()?.cloned().unwrap_or_default();
+                speaker_names.extend(overrides::load_speaker_names(path)?);
+                Some(speaker_names)
+            }
+            None => None,
+        };
+        let text_normalizer = match self.text_normalizer {
+            Some(text_normalizer) => text_normalizer,
+            None => match self.model.get_language()? {
+                Some(language) => TextNormalizer::for_language(&language),
+                None => TextNormalizer::passthrough(),
+            },
+        };
+        let text_normalizer = match self.number_reading {
+            Some(number_reading) => text_normalizer.with_number_reading(number_reading),
+            None => text_normalizer,
+        };
+        let rng = self
+            .rng
+            .unwrap_or_else(|| Box::new(StdRng::from_entropy()));
+        let synth = SonataSpeechSynthesizer {
+            model: self.model,
+            text_normalizer,
+            speaker_names,
+            rng: Arc::new(Mutex::new(rng)),
+            rng_seed: self.rng_seed,
+            defaults: RwLock::new(self.defaults),
+        };
+        // Resolve speaker names in the overrides file with the merged names
+        let mut overrides = match self.overrides_path.as_ref() {
+            Some(path) => overrides::load_overrides(path, &synth)?,
+            None => SynthesisOverrides::default(),
+        };
+        if self.stable {
+            let stable = SynthesisOverrides::stable();
+            overrides.speaker = overrides.speaker.or(stable.speaker);
+            overrides.noise_scale = overrides.noise_scale.or(stable.noise_scale);
+            overrides.length_scale = overrides.length_scale.or(stable.length_scale);
+            overrides.noise_w = overrides.noise_w.or(stable.noise_w);
+        }
+        // The model is shared, so it's only changed once everything else has succeeded,
+        // in a single call that validates the preset and the file together
+        if !overrides.is_empty() {
+            synth.model.apply_synthesis_overrides(&overrides)?;
+        }
+        if let Some(path) = self.overrides_path.as_ref() {
+            log::info!(
+                "Applied synthesis overrides from `{}`: {:?}",
+                path.display(),
+                overrides
+            );
+        }
+        Ok(synth)
     }
 }
 
-pub struct SonataSpeechSynthesizer(Arc<dyn SonataModel + Sync + Send>);
+pub struct SonataSpeechSynthesizer {
+    model: Arc<dyn SonataModel + Sync + Send>,
+    text_normalizer: TextNormalizer,
+    /// The model's speaker names merged with the ones given to the builder, if any
+    speaker_names: Option<HashMap<i64, String>>,
+    rng: SharedRng,
+    /// The seed given to the builder's `with_seed`, if any
+    rng_seed: Option<u64>,
+    defaults: RwLock<SynthesisDefaults>,
+}
 
 impl SonataSpeechSynthesizer {
+    /// Create a synthesizer for the given model with the default options.
+    /// The text normalizer is selected based on the model's language.
     pub fn new(model: Arc<dyn SonataModel + Sync + Send>) -> SonataResult<Self> {
-        Ok(Self(model))
+        Self::builder(model).build()
+    }
+    pub fn builder(model: Arc<dyn SonataModel + Sync + Send>) -> SonataSpeechSynthesizerBuilder {
+        SonataSpeechSynthesizerBuilder::new(model)
+    }
+    pub fn text_normalizer(&self) -> &TextNormalizer {
+        &self.text_normalizer
+    }
+    pub fn text_preprocessing(&self) -> TextPreprocessing {
+        self.defaults.read().unwrap().text_preprocessing.clone()
+    }
+    pub fn set_text_preprocessing(&self, text_preprocessing: TextPreprocessing) {
+        self.defaults.write().unwrap().text_preprocessing = text_preprocessing;
+    }
+    /// The sample rate the model produces audio at
+    pub fn native_sample_rate(&self) -> SonataResult<usize> {
+        Ok(self.model.audio_output_info()?.sample_rate)
+    }
+    /// The sample rate of the audio synthesized with the given output config, after
+    /// resampling. Falls back to the default output config when `output_config` is `None`.
+    pub fn output_sample_rate(
+        &self,
+        output_config: Option<&AudioOutputConfig>,
+    ) -> SonataResult<usize> {
+        let native_sample_rate = self.native_sample_rate()?;
+        Ok(match output_config {
+            Some(config) => config.output_sample_rate(native_sample_rate),
+            None => self
+                .default_output_config()
+                .map_or(native_sample_rate, |config| {
+                    config.output_sample_rate(native_sample_rate)
+                }),
+        })
+    }
+    /// The number of channels of the audio synthesized with the given output config, after
+    /// downmixing. Falls back to the default output config when `output_config` is `None`.
+    pub fn output_num_channels(
+        &self,
+        output_config: Option<&AudioOutputConfig>,
+    ) -> SonataResult<usize> {
+        let native_num_channels = self.model.audio_output_info()?.num_channels;
+        Ok(match output_config {
+            Some(config) => config.output_num_channels(native_num_channels),
+            None => self
+                .default_output_config()
+                .map_or(native_num_channels, |config| {
+                    config.output_num_channels(native_num_channels)
+                }),
+        })
+    }
+    pub fn default_output_config(&self) -> Option<AudioOutputConfig> {
+        self.defaults.read().unwrap().output_config.clone()
+    }
+    /// Use `output_config` for synthesis calls that don't pass one. A config passed to a
+    /// call replaces the default entirely (the two are not merged).
+    pub fn set_default_output_config(&self, output_config: Option<AudioOutputConfig>) {
+        self.defaults.write().unwrap().output_config = output_config;
+    }
+    /// Call `callback` with the text and phonemes of each sentence as its synthesis begins,
+    /// ahead of inference. Pass `None` to remove the callback.
+    ///
+    /// The events follow the segments the model's phonemizer splits the text into, so setting
+    /// a callback doesn't change the speech. Each event has the index of its segment and the
+    /// text it was phonemized from: the whole text, or the piece of it between two
+    /// [punctuation pauses](Self::set_punctuation_pauses). Clips of single sentences (see
+    /// [`ParallelSynthesisOptions::include_sentence_info`]) have one event per sentence.
+    ///
+    /// The callback runs on a dedicated thread and receives the events in the order they
+    /// are sent, so it never blocks synthesis. With parallel synthesis, segments start in
+    /// no particular order.
+    pub fn set_sentence_callback(&self, callback: Option<Box<dyn FnMut(SentenceEvent) + Send>>) {
+        self.defaults.write().unwrap().sentence_notifier = callback.map(SentenceNotifier::new);
+    }
+    pub fn sample_converter(&self) -> Option<SampleConverter> {
+        self.defaults.read().unwrap().sample_converter.clone()
+    }
+    /// The converter for the files the synthesizer writes with `output_config`: the one set
+    /// with [`Self::set_sample_converter`], if any. Otherwise a gain (the `gain_db` of the
+    /// config or a calibrated gain) is kept by converting at a fixed scale, since the default
+    /// conversion normalizes the peak of every clip.
+    pub(crate) fn sample_converter_for(
+        &self,
+        output_config: Option<&AudioOutputConfig>,
+    ) -> Option<SampleConverter> {
+        let defaults = self.defaults.read().unwrap();
+        if defaults.sample_converter.is_some() {
+            return defaults.sample_converter.clone();
+        }
+        let has_gain = defaults.calibrated_gain_db.is_some()
+            || output_config
+                .or(defaults.output_config.as_ref())
+                .is_some_and(|config| config.gain_db.is_some());
+        has_gain.then(|| SampleConverter::rounding(false))
+    }
+    /// Convert the samples to 16-bit PCM with `convert` instead of the default conversion
+    /// (see [`SampleConverter`]) when the synthesizer writes wave files, and for the chunks
+    /// of its streams (see [`RealtimeSpeechStream::sample_converter`]). Pass `None` to go
+    /// back to the default. To apply it to clips converted elsewhere, pass
+    /// [`Self::sample_converter`] to [`Audio::as_wave_bytes_with`].
+    pub fn set_sample_converter(&self, convert: Option<Box<SampleConversionFn>>) {
+        self.defaults.write().unwrap().sample_converter = convert.map(SampleConverter::new);
+    }
+    pub fn punctuation_pauses(&self) -> Option<PunctuationPauses> {
+        self.defaults.read().unwrap().punctuation_pauses.clone()
+    }
+    /// Split the text at punctuation and insert the mapped silence between the pieces,
+    /// e.g. a longer pause after a period than after a comma. Pass `None` to leave
+    /// pauses to the model.
+    pub fn set_punctuation_pauses(&self, punctuation_pauses: Option<PunctuationPauses>) {
+        self.defaults.write().unwrap().punctuation_pauses = punctuation_pauses;
+    }
+    /// Measure the loudness of the voice on [`CALIBRATION_PHRASE`] and store the gain that
+    /// brings it to `target_db` (in dBFS, as measured by [`Audio::loudness_db`], e.g.
+    /// [`DEFAULT_LOUDNESS_TARGET_DB`]). Returns the gain, in dB.
+    ///
+    /// The gain is added to the `gain_db` of the output config of every later synthesis call,
+    /// so that voices calibrated to the same target sound about as loud, while the `volume` of
+    /// the output config still scales the result. The gain depends on the voice, so calibrate
+    /// again after changing the speaker. Calibration takes a synthesis, so save the gain with
+    /// the voice and restore it with [`Self::set_calibrated_gain_db`] instead of calibrating
+    /// on every start.
+    pub fn calibrate_gain(&self, target_db: f32) -> SonataResult<f32> {
+        if !target_db.is_finite() {
+            return Err(SonataError::OperationError(format!(
+                "Invalid loudness target: {}",
+                target_db
+            )));
+        }
+        let mut samples = Vec::new();
+        for phonemes in self.model.phonemize_text(CALIBRATION_PHRASE)?.to_vec() {
+            let audio = speak_with_rng(self.model.as_ref(), &self.rng, phonemes)?;
+            samples.append(&mut audio.samples.into_vec());
+        }
+        let audio = Audio::with_info(samples.into(), self.model.audio_output_info()?, None);
+        let Some(loudness_db) = audio.loudness_db() else {
+            return Err(SonataError::OperationError(
+                "Can't calibrate the gain: the voice synthesized silence".to_string(),
+            ));
+        };
+        let gain_db = target_db - loudness_db;
+        self.set_calibrated_gain_db(Some(gain_db));
+        Ok(gain_db)
+    }
+    /// The gain (in dB) set by [`Self::calibrate_gain`] or [`Self::set_calibrated_gain_db`]
+    pub fn calibrated_gain_db(&self) -> Option<f32> {
+        self.defaults.read().unwrap().calibrated_gain_db
+    }
+    /// Add `gain_db` to the gain of every later synthesis call, e.g. a gain saved from an
+    /// earlier [`Self::calibrate_gain`]. Pass `None` to remove it.
+    pub fn set_calibrated_gain_db(&self, gain_db: Option<f32>) {
+        self.defaults.write().unwrap().calibrated_gain_db = gain_db;
+    }
+    fn preprocess_text(&self, text: &str) -> String {
+        self.defaults.read().unwrap().text_preprocessing.apply(text)
+    }
+    /// Generate `len` samples of standard normal noise using the synthesizer's random number generator
+    pub fn generate_noise(&self, len: usize) -> Vec<f32> {
+        let mut noise = vec![0f32; len];
+        utils::fill_standard_normal(self.rng.lock().unwrap().as_mut(), &mut noise);
+        noise
+    }
+    /// A hash of everything that affects the speech synthesized for `text` with `output_config`:
+    /// the normalized text, the output config (falling back to the default one), the way the
+    /// text is split into pieces, the [sample converter](Self::set_sample_converter), the seed
+    /// given to the builder's `with_seed`, and the model (along with the contents of its
+    /// weights, its speaker and its scales).
+    /// Meant for keying a cache of synthesized speech or detecting identical requests.
+    ///
+    /// The converter only counts as being set or not (see [`SampleConverter`]), so replace
+    /// the cached speech when replacing a converter with another one. The hash doesn't change
+    /// between runs. Without a seed the noise of every synthesis is
+    /// different, so cached speech is just one of the ways the text can be spoken. With one,
+    /// note that the generator advances as it is used.
+    pub fn input_hash(
+        &self,
+        text: &str,
+        output_config: Option<&AudioOutputConfig>,
+    ) -> SonataResult<u64> {
+        let mut state = StableHasher::default();
+        self.text_normalizer
+            .normalize(&self.preprocess_text(text))
+            .hash(&mut state);
+        let defaults = self.defaults.read().unwrap();
+        let output_config = defaults.resolve_output_config(output_config.cloned());
+        output_config.is_some().hash(&mut state);
+        if let Some(output_config) = output_config {
+            output_config.hash_into(&mut state);
+        }
+        defaults.sample_converter.hash(&mut state);
+        let punctuation_pauses = defaults.punctuation_pauses.as_ref().map(|pauses| {
+            let mut pauses_ms: Vec<_> = pauses.pauses_ms().iter().collect();
+            pauses_ms.sort();
+            pauses_ms
+        });
+        punctuation_pauses.hash(&mut state);
+        drop(defaults);
+        self.rng_seed.hash(&mut state);
+        self.model.hash_output_state(&mut state)?;
+        Ok(state.finish())
     }
 
     fn create_synthesis_task_provider(
@@ -128,10 +745,83 @@ impl SonataSpeechSynthesizer {
         text: String,
         output_config: Option<AudioOutputConfig>,
     ) -> SpeechSynthesisTaskProvider {
+        self.create_provider_for_preprocessed_text(self.preprocess_text(&text), output_config, None)
+    }
+    /// `sentence_index` is `Some` if `text` is a single sentence, with that index in the input.
+    /// Such a sentence isn't split again.
+    fn create_provider_for_preprocessed_text(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        sentence_index: Option<usize>,
+    ) -> SpeechSynthesisTaskProvider {
+        let (sentence_notifier, punctuation_pauses, output_config) = {
+            let defaults = self.defaults.read().unwrap();
+            let output_config = defaults.resolve_output_config(output_config);
+            (
+                defaults.sentence_notifier.clone(),
+                defaults.punctuation_pauses.clone(),
+                output_config,
+            )
+        };
+        // The sentence notifier only observes the segments: the text is split the same
+        // way with or without it
+        let has_notifier = sentence_notifier.is_some();
+        let sentence = sentence_index
+            .filter(|_| has_notifier)
+            .map(|index| SentenceInfo { index, text: text.clone() });
+        let mut pieces = match punctuation_pauses {
+            Some(ref pauses) => pauses.split(&text),
+            None => vec![(text, None)],
+        };
+        // Pauses only go between pieces
+        if let Some(last) = pieces.last_mut() {
+            last.1 = None;
+        }
+        let pieces = Vec::from_iter(pieces.into_iter().map(|(piece, pause_ms)| TextPiece {
+            normalized: self.text_normalizer.normalize(&piece),
+            text: has_notifier.then_some(piece),
+            pause_ms,
+        }));
         SpeechSynthesisTaskProvider {
             model: self.clone_model(),
-            text,
+            rng: Arc::clone(&self.rng),
+            sentence,
+            starts_utterance: sentence_index.is_none_or(|index| index == 0),
             output_config,
+            sentence_notifier,
+            pieces,
+            whole_pieces: false,
+        }
+    }
+    /// A provider with one segment per (preprocessed) sentence of `sentences`, in order
+    fn create_provider_for_sentences(
+        &self,
+        sentences: Vec<String>,
+        output_config: Option<AudioOutputConfig>,
+    ) -> SpeechSynthesisTaskProvider {
+        let (sentence_notifier, output_config) = {
+            let defaults = self.defaults.read().unwrap();
+            (
+                defaults.sentence_notifier.clone(),
+                defaults.resolve_output_config(output_config),
+            )
+        };
+        let has_notifier = sentence_notifier.is_some();
+        let pieces = Vec::from_iter(sentences.into_iter().map(|text| TextPiece {
+            normalized: self.text_normalizer.normalize(&text),
+            text: has_notifier.then_some(text),
+            pause_ms: None,
+        }));
+        SpeechSynthesisTaskProvider {
+            model: self.clone_model(),
+            rng: Arc::clone(&self.rng),
+            sentence: None,
+            starts_utterance: true,
+            output_config,
+            sentence_notifier,
+            pieces,
+            whole_pieces: true,
         }
     }
 
@@ -142,12 +832,113 @@ impl SonataSpeechSynthesizer {
     ) -> SonataResult<SonataSpeechStreamLazy> {
         SonataSpeechStreamLazy::new(self.create_synthesis_task_provider(text, output_config))
     }
+    /// Like [`SonataSpeechSynthesizer::synthesize_parallel`], with additional options
+    pub fn synthesize_parallel_with_options(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        options: ParallelSynthesisOptions,
+    ) -> SonataResult<SonataSpeechStreamParallel> {
+        let max_workers = options.max_workers()?;
+        let synchronous = options.synchronous;
+        let synthesize = || {
+            if !options.include_sentence_info {
+                return SonataSpeechStreamParallel::new(
+                    self.create_synthesis_task_provider(text, output_config),
+                    max_workers,
+                    synchronous,
+                );
+            }
+            let sentences = sentences::split_sentences(&self.preprocess_text(&text));
+            Ok(SonataSpeechStreamParallel {
+                precalculated_results: self
+                    .synthesize_each_sentence(sentences, output_config, max_workers, synchronous, false)
+                    .into_iter(),
+            })
+        };
+        match synchronous {
+            true => synthesize(),
+            false => utils::install_on(options.thread_pool, synthesize),
+        }
+    }
+    /// Synthesize sentences that were split upstream, each as one unit: Sonata doesn't split
+    /// them again (not at punctuation pauses, nor at the commas some models split at), and
+    /// every sentence gives one clip, in order. The text of each sentence is
+    /// still preprocessed and normalized. Each clip carries its [`SentenceInfo`], with the
+    /// index of its sentence in `sentences`, whatever `options.include_sentence_info` is.
+    pub fn synthesize_from_sentences(
+        &self,
+        sentences: Vec<String>,
+        output_config: Option<AudioOutputConfig>,
+        options: ParallelSynthesisOptions,
+    ) -> SonataResult<SonataSpeechStreamParallel> {
+        let max_workers = options.max_workers()?;
+        let synchronous = options.synchronous;
+        let synthesize = || {
+            let sentences = sentences
+                .iter()
+                .map(|sentence| self.preprocess_text(sentence))
+                .collect();
+            Ok(SonataSpeechStreamParallel {
+                precalculated_results: self
+                    .synthesize_each_sentence(sentences, output_config, max_workers, synchronous, true)
+                    .into_iter(),
+            })
+        };
+        match synchronous {
+            true => synthesize(),
+            false => utils::install_on(options.thread_pool, synthesize),
+        }
+    }
+    /// Synthesize the given (preprocessed) sentences in parallel (or one after another if
+    /// `synchronous`), one clip per sentence. See [`Self::synthesize_sentence`] for `whole`.
+    fn synthesize_each_sentence(
+        &self,
+        sentences: Vec<String>,
+        output_config: Option<AudioOutputConfig>,
+        max_workers: Option<usize>,
+        synchronous: bool,
+        whole: bool,
+    ) -> Vec<SonataAudioResult> {
+        let sentences: Vec<_> = sentences.into_iter().enumerate().collect();
+        utils::map_items(sentences, synchronous, max_workers, |(index, text)| {
+            self.synthesize_sentence(index, text, output_config.clone(), whole)
+        })
+    }
+    /// One clip of the (preprocessed) sentence at `index` of the input. A `whole` sentence
+    /// is one segment, not split at punctuation pauses or by the model.
+    fn synthesize_sentence(
+        &self,
+        index: usize,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        whole: bool,
+    ) -> SonataAudioResult {
+        let provider = if whole {
+            let mut provider = self.create_provider_for_sentences(vec![text.clone()], output_config);
+            provider.sentence = provider
+                .sentence_notifier
+                .as_ref()
+                .map(|_| SentenceInfo { index, text: text.clone() });
+            provider.starts_utterance = index == 0;
+            provider
+        } else {
+            self.create_provider_for_preprocessed_text(text.clone(), output_config, Some(index))
+        };
+        let mut audio = provider.synthesize_all()?;
+        audio.sentence = Some(SentenceInfo { index, text });
+        Ok(audio)
+    }
     pub fn synthesize_parallel(
         &self,
         text: String,
         output_config: Option<AudioOutputConfig>,
     ) -> SonataResult<SonataSpeechStreamParallel> {
-        SonataSpeechStreamParallel::new(self.create_synthesis_task_provider(text, output_config))
+        SonataSpeechStreamParallel::new(
+            self.create_synthesis_task_provider(text, output_config),
+            None,
+            false,
+        )
     }
     pub fn synthesize_streamed(
         &self,
@@ -156,15 +947,71 @@ impl SonataSpeechSynthesizer {
         chunk_size: usize,
         chunk_padding: usize,
     ) -> SonataResult<RealtimeSpeechStream> {
-        let provider = self.create_synthesis_task_provider(text, output_config);
-        let wavinfo = self.0.audio_output_info()?;
-        RealtimeSpeechStream::new(
-            provider,
-            chunk_size,
-            chunk_padding,
+        self.synthesize_streamed_with_config(
+            text,
+            output_config,
+            StreamingConfig {
+                chunk_size,
+                chunk_padding,
+                ..Default::default()
+            },
+        )
+    }
+    pub fn synthesize_streamed_with_config(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        streaming_config: StreamingConfig,
+    ) -> SonataResult<RealtimeSpeechStream> {
+        self.create_realtime_stream(output_config, streaming_config, |output_config| {
+            self.create_synthesis_task_provider(text, output_config)
+        })
+    }
+    /// Stream sentences that were split upstream, like
+    /// [`SonataSpeechSynthesizer::synthesize_from_sentences`]: every sentence is streamed
+    /// as one unit, in order, without being split again (not even at the commas some
+    /// models split at), and without punctuation pauses.
+    pub fn synthesize_streamed_from_sentences(
+        &self,
+        sentences: Vec<String>,
+        output_config: Option<AudioOutputConfig>,
+        streaming_config: StreamingConfig,
+    ) -> SonataResult<RealtimeSpeechStream> {
+        self.create_realtime_stream(output_config, streaming_config, |output_config| {
+            let sentences = sentences
+                .iter()
+                .map(|sentence| self.preprocess_text(sentence))
+                .collect();
+            self.create_provider_for_sentences(sentences, output_config)
+        })
+    }
+    fn create_realtime_stream(
+        &self,
+        output_config: Option<AudioOutputConfig>,
+        streaming_config: StreamingConfig,
+        create_provider: impl FnOnce(Option<AudioOutputConfig>) -> SpeechSynthesisTaskProvider,
+    ) -> SonataResult<RealtimeSpeechStream> {
+        // The config handle of the stream takes the config before the calibrated gain is
+        // added, so that updating it keeps the gain
+        let (output_config, calibrated_gain_db) = {
+            let defaults = self.defaults.read().unwrap();
+            (
+                output_config.or_else(|| defaults.output_config.clone()),
+                defaults.calibrated_gain_db,
+            )
+        };
+        let provider = create_provider(output_config.clone());
+        let sample_converter = self
+            .sample_converter()
+            .unwrap_or_else(|| SampleConverter::rounding(false));
+        let wavinfo = self.model.audio_output_info()?;
+        let config_handle = StreamConfigHandle::new(
+            output_config,
+            calibrated_gain_db,
             wavinfo.sample_rate,
             wavinfo.num_channels,
-        )
+        );
+        RealtimeSpeechStream::new(provider, streaming_config, config_handle, sample_converter)
     }
 
     pub fn synthesize_to_file(
@@ -173,6 +1020,9 @@ impl SonataSpeechSynthesizer {
         text: String,
         output_config: Option<AudioOutputConfig>,
     ) -> SonataResult<()> {
+        let sample_rate = self.output_sample_rate(output_config.as_ref())?;
+        let num_channels = self.output_num_channels(output_config.as_ref())?;
+        let sample_converter = self.sample_converter_for(output_config.as_ref());
         let mut samples: Vec<f32> = Vec::new();
         for result in self.synthesize_parallel(text, output_config)? {
             match result {
@@ -190,51 +1040,292 @@ impl SonataSpeechSynthesizer {
         let audio = AudioSamples::from(samples);
         Ok(audio_ops::write_wave_samples_to_file(
             filename,
-            audio.to_i16_vec().iter(),
-            self.0.audio_output_info()?.sample_rate as u32,
-            self.0.audio_output_info()?.num_channels.try_into().unwrap(),
-            self.0.audio_output_info()?.sample_width.try_into().unwrap(),
+            audio.to_i16_vec_with(sample_converter.as_ref())?.iter(),
+            sample_rate as u32,
+            num_channels.try_into().unwrap(),
+            self.model.audio_output_info()?.sample_width.try_into().unwrap(),
         )?)
     }
+    /// Stream synthesized speech to a wave file chunk by chunk.
+    /// The file header is updated after every chunk, so an interrupted
+    /// synthesis leaves a playable file containing the chunks written so far.
+    /// The chunks are converted with [`Self::sample_converter`], or at a fixed scale
+    /// (see [`SampleConverter::rounding`]) if there is none.
+    pub fn synthesize_streamed_to_file(
+        &self,
+        filename: &Path,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        streaming_config: StreamingConfig,
+    ) -> SonataResult<()> {
+        let wavinfo = self.model.audio_output_info()?;
+        let mut writer = audio_ops::StreamingWaveWriter::create(
+            filename,
+            self.output_sample_rate(output_config.as_ref())? as u32,
+            self.output_num_channels(output_config.as_ref())? as u32,
+            wavinfo.sample_width as u32,
+        )?;
+        // Normalizing the peak of every chunk would change the level from chunk to chunk
+        let mut stream =
+            self.synthesize_streamed_with_config(text, output_config, streaming_config)?;
+        while let Some(result) = stream.next() {
+            let samples = result?;
+            writer.write_samples(stream.sample_converter().convert(samples.as_slice())?.iter())?;
+            writer.flush()?;
+            stream.recycle(samples);
+        }
+        Ok(writer.finalize()?)
+    }
+    /// Synthesize each sentence of `text` to its own numbered wave file in `output_dir`.
+    /// Returns the path of each file along with the text of its sentence, in order.
+    /// The sentences are synthesized in parallel, one per thread of the current pool, and
+    /// each file is written as soon as its sentence is done.
+    pub fn synthesize_sentences_to_files(
+        &self,
+        output_dir: &Path,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> SonataResult<Vec<(PathBuf, String)>> {
+        let sentences = sentences::split_sentences(&self.preprocess_text(&text));
+        if sentences.is_empty() {
+            return Err(SonataError::OperationError(
+                "No speech data to write".to_string(),
+            ));
+        }
+        if let Err(e) = std::fs::create_dir_all(output_dir) {
+            return Err(SonataError::OperationError(format!(
+                "Failed to create output directory `{}`. Error: {}",
+                output_dir.display(),
+                e
+            )));
+        }
+        let width = sentences.len().to_string().len().max(3);
+        let sample_converter = self.sample_converter_for(output_config.as_ref());
+        let sentences: Vec<_> = sentences.into_iter().enumerate().collect();
+        // A fixed set of workers, one per thread of the pool, each writing the file of its
+        // sentence before taking the next, so that only that many clips are held at once
+        let max_workers = rayon::current_num_threads();
+        utils::map_items(sentences, false, Some(max_workers), |(index, text)| {
+            let audio = self.synthesize_sentence(index, text, output_config.clone(), false)?;
+            let filename = output_dir.join(format!("{:0width$}.wav", index + 1, width = width));
+            audio.save_to_file_with(&filename, sample_converter.as_ref())?;
+            let text = audio.sentence.map(|sentence| sentence.text).unwrap_or_default();
+            Ok((filename, text))
+        })
+        .into_iter()
+        .collect()
+    }
+    /// Estimate the cost of synthesizing `text`: its number of phonemes and the number of
+    /// decoder frames it takes, for rate limiting and billing. This only phonemizes the text
+    /// (the slow part of synthesis is the model) and doesn't notify the sentence callback.
+    pub fn cost_estimate(&self, text: &str) -> SonataResult<CostEstimate> {
+        self.phonemize_text(text)?
+            .sentences()
+            .iter()
+            .try_fold(CostEstimate::default(), |total, phonemes| {
+                Ok(total + self.model.estimate_cost(phonemes)?)
+            })
+    }
+    /// Phonemize `text` and predict the duration of each symbol of each sentence, without
+    /// synthesizing it. Edit the durations and pass them to
+    /// [`SonataSpeechSynthesizer::synthesize_with_durations`] for precise control over the
+    /// timing, e.g. for dubbing or singing.
+    pub fn predict_durations(&self, text: &str) -> SonataResult<Vec<PhonemeDurations>> {
+        self.phonemize_text(text)?
+            .to_vec()
+            .into_iter()
+            .map(|phonemes| self.model.predict_phoneme_durations(phonemes))
+            .collect()
+    }
+    /// Synthesize the sentences returned by [`SonataSpeechSynthesizer::predict_durations`]
+    /// with their (edited) durations, and join them into one clip. Each sentence must have as
+    /// many durations as symbols. Punctuation pauses and the sentence callback don't apply,
+    /// as the text was already split into sentences.
+    pub fn synthesize_with_durations(
+        &self,
+        sentences: &[PhonemeDurations],
+        output_config: Option<AudioOutputConfig>,
+    ) -> SonataAudioResult {
+        self.speak_and_join(
+            sentences,
+            |durations| self.model.speak_with_durations(durations),
+            output_config,
+        )
+    }
+    /// The name and shape of each noise tensor to pass to
+    /// [`SonataSpeechSynthesizer::synthesize_with_noise`] for `phonemes`. Empty when the model
+    /// samples its noise inside the inference graph, as most models do.
+    pub fn noise_shapes(&self, phonemes: &str) -> SonataResult<Vec<(String, Vec<usize>)>> {
+        self.model.noise_shapes(phonemes)
+    }
+    /// Synthesize one sentence of `phonemes` (as returned by
+    /// [`SonataSpeechSynthesizer::phonemize_text`]) with pre-generated noise instead of the noise
+    /// the model samples, for full control over its stochastic inputs. Only models with noise
+    /// inputs support it, and the tensors must have exactly the shapes given by
+    /// [`SonataSpeechSynthesizer::noise_shapes`]. [`SonataSpeechSynthesizer::generate_noise`]
+    /// makes seeded noise for them.
+    pub fn synthesize_with_noise(
+        &self,
+        phonemes: &str,
+        noise: &[NoiseTensor],
+        output_config: Option<AudioOutputConfig>,
+    ) -> SonataAudioResult {
+        self.speak_and_join(
+            [phonemes.to_string()],
+            |phonemes| self.model.speak_with_noise(phonemes, noise),
+            output_config,
+        )
+    }
+    /// Synthesize `text` in the speaking style of a reference style embedding (prosody
+    /// transfer), and join the sentences into one clip. This is experimental: only VITS
+    /// variants with a style input support it (see [`SonataModel::has_style_input`]), and
+    /// the embedding must come from the same model's style encoder.
+    pub fn synthesize_with_style(
+        &self,
+        text: &str,
+        style: &[f32],
+        output_config: Option<AudioOutputConfig>,
+    ) -> SonataAudioResult {
+        if !self.model.has_style_input() {
+            return Err(SonataError::OperationError(
+                "The model doesn't take a style embedding. Only VITS variants with a `style` input support prosody transfer".to_string(),
+            ));
+        }
+        let sentences = self.phonemize_text(text)?.to_vec();
+        self.speak_and_join(
+            sentences,
+            |phonemes| self.model.speak_with_style(phonemes, style),
+            output_config,
+        )
+    }
+    /// Speak each sentence with `speak`, apply `output_config` to it, and join them into one clip
+    fn speak_and_join<T>(
+        &self,
+        sentences: impl IntoIterator<Item = T>,
+        speak: impl Fn(T) -> SonataAudioResult,
+        output_config: Option<AudioOutputConfig>,
+    ) -> SonataAudioResult {
+        let output_config = self.defaults.read().unwrap().resolve_output_config(output_config);
+        let mut info = self.model.audio_output_info()?;
+        let mut samples: Vec<f32> = Vec::new();
+        let mut inference_ms = 0f32;
+        let (mut phoneme_timings, mut offset_ms) = (Some(Vec::new()), 0f32);
+        for (i, sentence) in sentences.into_iter().enumerate() {
+            let mut audio = speak(sentence)?;
+            if let Some(ref config) = output_config {
+                audio = config.apply(audio)?;
+                if i == 0 {
+                    config.apply_quiet_start(&mut audio);
+                }
+            }
+            stats::record_synthesis(&audio);
+            inference_ms += audio.inference_ms().unwrap_or_default();
+            join_timings(&mut phoneme_timings, &audio, offset_ms);
+            offset_ms += audio.duration_ms();
+            samples.append(&mut audio.samples.into_vec());
+        }
+        if let Some(ref config) = output_config {
+            info.sample_rate = config.output_sample_rate(info.sample_rate);
+            info.num_channels = config.output_num_channels(info.num_channels);
+        }
+        let mut audio = Audio::with_info(samples.into(), info, Some(inference_ms));
+        audio.phoneme_timings = phoneme_timings;
+        Ok(audio)
+    }
+    /// Silent audio of the given duration in the model's output format
+    pub fn make_silence(&self, duration_ms: u32) -> SonataAudioResult {
+        Ok(Audio::silence(self.model.audio_output_info()?, duration_ms))
+    }
     #[inline(always)]
     pub fn clone_model(&self) -> Arc<dyn SonataModel + Send + Sync> {
-        Arc::clone(&self.0)
+        Arc::clone(&self.model)
     }
 }
 
 impl SonataModel for SonataSpeechSynthesizer {
     fn audio_output_info(&self) -> SonataResult<AudioInfo> {
-        self.0.audio_output_info()
+        self.model.audio_output_info()
     }
     fn phonemize_text(&self, text: &str) -> SonataResult<Phonemes> {
-        self.0.phonemize_text(text)
+        let text = self.preprocess_text(text);
+        self.model.phonemize_text(&self.text_normalizer.normalize(&text))
     }
     fn speak_batch(&self, phoneme_batches: Vec<String>) -> SonataResult<Vec<Audio>> {
-        self.0.speak_batch(phoneme_batches)
+        self.model.speak_batch(phoneme_batches)
     }
     fn speak_one_sentence(&self, phonemes: String) -> SonataAudioResult {
-        self.0.speak_one_sentence(phonemes)
+        speak_with_rng(self.model.as_ref(), &self.rng, phonemes)
     }
     fn get_default_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
-        self.0.get_default_synthesis_config()
+        self.model.get_default_synthesis_config()
     }
     fn get_fallback_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
-        self.0.get_fallback_synthesis_config()
+        self.model.get_fallback_synthesis_config()
     }
     fn set_fallback_synthesis_config(&self, synthesis_config: &dyn Any) -> SonataResult<()> {
-        self.0.set_fallback_synthesis_config(synthesis_config)
+        self.model.set_fallback_synthesis_config(synthesis_config)
     }
     fn get_language(&self) -> SonataResult<Option<String>> {
-        self.0.get_language()
+        self.model.get_language()
     }
     fn get_speakers(&self) -> SonataResult<Option<&HashMap<i64, String>>> {
-        self.0.get_speakers()
+        match self.speaker_names {
+            Some(ref speaker_names) => Ok(Some(speaker_names)),
+            None => self.model.get_speakers(),
+        }
+    }
+    fn speaker_name_to_ids(&self, name: &str) -> SonataResult<Vec<i64>> {
+        match self.speaker_names {
+            Some(ref speaker_names) => {
+                let mut sids: Vec<i64> = speaker_names
+                    .iter()
+                    .filter(|(_, sname)| *sname == name)
+                    .map(|(sid, _)| *sid)
+                    .collect();
+                sids.sort_unstable();
+                Ok(sids)
+            }
+            None => self.model.speaker_name_to_ids(name),
+        }
     }
     fn properties(&self) -> SonataResult<HashMap<String, String>> {
-        self.0.properties()
+        self.model.properties()
+    }
+    fn apply_synthesis_overrides(&self, overrides: &SynthesisOverrides) -> SonataResult<()> {
+        self.model.apply_synthesis_overrides(overrides)
+    }
+    fn estimated_memory_bytes(&self) -> Option<u64> {
+        self.model.estimated_memory_bytes()
+    }
+    fn infer_raw(&self, phonemes: String) -> SonataResult<Vec<RawOutput>> {
+        self.model.infer_raw(phonemes)
+    }
+    fn predict_phoneme_durations(&self, phonemes: String) -> SonataResult<PhonemeDurations> {
+        self.model.predict_phoneme_durations(phonemes)
+    }
+    fn speak_with_durations(&self, durations: &PhonemeDurations) -> SonataAudioResult {
+        self.model.speak_with_durations(durations)
+    }
+    fn estimate_cost(&self, phonemes: &str) -> SonataResult<CostEstimate> {
+        self.model.estimate_cost(phonemes)
+    }
+    fn noise_shapes(&self, phonemes: &str) -> SonataResult<Vec<(String, Vec<usize>)>> {
+        self.model.noise_shapes(phonemes)
+    }
+    fn speak_with_noise(&self, phonemes: String, noise: &[NoiseTensor]) -> SonataAudioResult {
+        self.model.speak_with_noise(phonemes, noise)
+    }
+    fn has_style_input(&self) -> bool {
+        self.model.has_style_input()
+    }
+    fn speak_with_style(&self, phonemes: String, style: &[f32]) -> SonataAudioResult {
+        self.model.speak_with_style(phonemes, style)
+    }
+    fn hash_output_state(&self, state: &mut dyn Hasher) -> SonataResult<()> {
+        self.model.hash_output_state(state)
     }
     fn supports_streaming_output(&self) -> bool {
-        self.0.supports_streaming_output()
+        self.model.supports_streaming_output()
     }
     fn stream_synthesis<'a>(
         &'a self,
@@ -242,27 +1333,195 @@ impl SonataModel for SonataSpeechSynthesizer {
         #[allow(unused_variables)] chunk_size: usize,
         #[allow(unused_variables)] chunk_padding: usize,
     ) -> SonataResult<Box<dyn Iterator<Item = SonataResult<AudioSamples>> + Send + Sync + 'a>> {
-        self.0.stream_synthesis(phonemes, chunk_size, chunk_padding)
+        self.model.stream_synthesis(phonemes, chunk_size, chunk_padding)
+    }
+    fn supports_incremental_synthesis(&self) -> bool {
+        self.model.supports_incremental_synthesis()
+    }
+    fn stream_incremental_synthesis<'a>(
+        &'a self,
+        phonemes: String,
+        chunk_padding: usize,
+    ) -> SonataResult<AudioStreamIterator<'a>> {
+        self.model.stream_incremental_synthesis(phonemes, chunk_padding)
+    }
+}
+
+/// Append the phoneme timings of a clip that starts `offset_ms` into a joined clip.
+/// The joined clip only has timings if all of its clips have them.
+fn join_timings(joined: &mut Option<Vec<PhonemeTiming>>, audio: &Audio, offset_ms: f32) {
+    match (joined.as_mut(), audio.phoneme_timings.as_ref()) {
+        (Some(joined), Some(timings)) => joined.extend(timings.iter().map(|timing| PhonemeTiming {
+            phoneme: timing.phoneme.clone(),
+            start_ms: timing.start_ms + offset_ms,
+            end_ms: timing.end_ms + offset_ms,
+        })),
+        _ => *joined = None,
     }
 }
 
+/// The random number generator of a synthesizer, shared with its speech streams
+type SharedRng = Arc<Mutex<Box<dyn RngCore + Send>>>;
+
+/// Synthesize one sentence, drawing the noise of models with noise inputs from `rng`
+/// instead of letting the model sample it
+fn speak_with_rng(model: &dyn SonataModel, rng: &SharedRng, phonemes: String) -> SonataAudioResult {
+    // Models that sample their noise in the graph have no noise inputs, or don't support
+    // noise injection at all
+    let shapes = model.noise_shapes(&phonemes).unwrap_or_default();
+    if shapes.is_empty() {
+        return model.speak_one_sentence(phonemes);
+    }
+    let noise = {
+        let mut rng = rng.lock().unwrap();
+        Vec::from_iter(shapes.into_iter().map(|(name, shape)| {
+            let mut data = vec![0f32; shape.iter().product()];
+            utils::fill_standard_normal(rng.as_mut(), &mut data);
+            NoiseTensor { name, shape, data }
+        }))
+    };
+    model.speak_with_noise(phonemes, &noise)
+}
+
 struct SpeechSynthesisTaskProvider {
     model: Arc<dyn SonataModel + Sync + Send>,
-    text: String,
+    rng: SharedRng,
+    /// Set when the text is a single sentence of a longer input and there is a sentence
+    /// notifier, which then gets one event for the whole sentence
+    sentence: Option<SentenceInfo>,
+    /// Whether the text is the start of the utterance, which the quiet start fades in
+    starts_utterance: bool,
     output_config: Option<AudioOutputConfig>,
+    sentence_notifier: Option<SentenceNotifier>,
+    /// The text, split at punctuation pauses if there are any
+    pieces: Vec<TextPiece>,
+    /// Whether every piece is synthesized as one segment, joining the phonemes of the
+    /// sentences the model splits it into
+    whole_pieces: bool,
+}
+
+/// Part of the text that is phonemized on its own
+struct TextPiece {
+    /// The text before normalization, kept for the events when there is a sentence notifier
+    text: Option<String>,
+    normalized: String,
+    /// Silence to insert after the piece
+    pause_ms: Option<u32>,
+}
+
+/// Phonemes synthesized in one inference run
+struct PhonemeSegment {
+    phonemes: String,
+    /// The event announcing the segment, when there is a sentence notifier
+    event: Option<SentenceEvent>,
+    /// Silence to insert after the segment
+    pause_ms: Option<u32>,
+    /// Whether the segment starts the utterance
+    starts_utterance: bool,
 }
 
 impl SpeechSynthesisTaskProvider {
-    fn get_phonemes(&self) -> SonataResult<Vec<String>> {
-        Ok(self.model.phonemize_text(&self.text)?.to_vec())
+    fn get_phonemes(&self) -> SonataResult<Vec<PhonemeSegment>> {
+        let mut segments: Vec<PhonemeSegment> = Vec::new();
+        for piece in self.pieces.iter() {
+            let mut phonemes = self.model.phonemize_text(&piece.normalized)?.to_vec();
+            if self.whole_pieces && phonemes.len() > 1 {
+                phonemes = vec![phonemes.join(" ")];
+            }
+            let num_segments = phonemes.len();
+            for (i, phonemes) in phonemes.into_iter().enumerate() {
+                let event = piece.text.as_ref().filter(|_| self.sentence.is_none()).map(|text| {
+                    SentenceEvent {
+                        index: segments.len(),
+                        text: text.clone(),
+                        phonemes: phonemes.clone(),
+                    }
+                });
+                segments.push(PhonemeSegment {
+                    phonemes,
+                    event,
+                    pause_ms: piece.pause_ms.filter(|_| i + 1 == num_segments),
+                    starts_utterance: self.starts_utterance && segments.is_empty(),
+                });
+            }
+        }
+        // A single sentence is announced once, with all of its phonemes
+        if let Some(info) = self.sentence.as_ref() {
+            let phonemes = Vec::from_iter(segments.iter().map(|s| s.phonemes.as_str())).join(" ");
+            if let Some(first) = segments.first_mut() {
+                first.event = Some(SentenceEvent {
+                    index: info.index,
+                    text: info.text.clone(),
+                    phonemes,
+                });
+            }
+        }
+        Ok(segments)
+    }
+    /// Notify the sentence callback of the start of `segment`
+    fn announce(&self, segment: &mut PhonemeSegment) {
+        if let (Some(notifier), Some(event)) =
+            (self.sentence_notifier.as_ref(), segment.event.take())
+        {
+            notifier.notify(event);
+        }
+    }
+    fn process_segment(&self, mut segment: PhonemeSegment) -> SonataAudioResult {
+        self.announce(&mut segment);
+        let mut audio = self.process_one_sentence(segment.phonemes)?;
+        audio.fixed_scale = self.has_gain();
+        if let (true, Some(config)) = (segment.starts_utterance, self.output_config.as_ref()) {
+            config.apply_quiet_start(&mut audio);
+        }
+        if let Some(pause_ms) = segment.pause_ms {
+            let mut silence = Audio::silence(audio.info.clone(), pause_ms);
+            audio.samples.as_mut_vec().append(silence.samples.as_mut_vec());
+            if let Some(block_frames) = self.output_config.as_ref().and_then(|config| config.block_frames) {
+                audio = audio.pad_to_block_size(block_frames as usize)?;
+            }
+        }
+        stats::record_synthesis(&audio);
+        Ok(audio)
     }
     fn process_one_sentence(&self, phonemes: String) -> SonataAudioResult {
-        let wave_samples = self.model.speak_one_sentence(phonemes)?;
+        let wave_samples = speak_with_rng(self.model.as_ref(), &self.rng, phonemes)?;
         match self.output_config {
             Some(ref config) => config.apply(wave_samples),
             None => Ok(wave_samples),
         }
     }
+    /// Synthesize all the phonemized sentences and join them into one clip
+    fn synthesize_all(&self) -> SonataAudioResult {
+        let mut samples: Vec<f32> = Vec::new();
+        let mut inference_ms = 0f32;
+        let mut truncation_suspected = false;
+        let (mut phoneme_timings, mut offset_ms) = (Some(Vec::new()), 0f32);
+        for segment in self.get_phonemes()? {
+            let audio = self.process_segment(segment)?;
+            inference_ms += audio.inference_ms().unwrap_or_default();
+            truncation_suspected |= audio.truncation_suspected;
+            join_timings(&mut phoneme_timings, &audio, offset_ms);
+            offset_ms += audio.duration_ms();
+            samples.append(&mut audio.samples.into_vec());
+        }
+        let mut info = self.model.audio_output_info()?;
+        if let Some(ref config) = self.output_config {
+            info.sample_rate = config.output_sample_rate(info.sample_rate);
+            info.num_channels = config.output_num_channels(info.num_channels);
+        }
+        let mut audio = Audio::with_info(samples.into(), info, Some(inference_ms));
+        audio.truncation_suspected = truncation_suspected;
+        audio.phoneme_timings = phoneme_timings;
+        audio.fixed_scale = self.has_gain();
+        Ok(audio)
+    }
+    /// Whether the speech has a gain, which a conversion to PCM normalizing the peak of
+    /// each clip would undo
+    fn has_gain(&self) -> bool {
+        self.output_config
+            .as_ref()
+            .is_some_and(|config| config.gain_db.is_some())
+    }
     #[allow(dead_code)]
     fn process_batches(&self, phonemes: Vec<String>) -> SonataResult<Vec<Audio>> {
         let wave_samples = self.model.speak_batch(phonemes)?;
@@ -281,15 +1540,15 @@ impl SpeechSynthesisTaskProvider {
 
 pub struct SonataSpeechStreamLazy {
     provider: SpeechSynthesisTaskProvider,
-    sentence_phonemes: std::vec::IntoIter<String>,
+    segments: std::vec::IntoIter<PhonemeSegment>,
 }
 
 impl SonataSpeechStreamLazy {
     fn new(provider: SpeechSynthesisTaskProvider) -> SonataResult<Self> {
-        let sentence_phonemes = provider.get_phonemes()?.into_iter();
+        let segments = provider.get_phonemes()?.into_iter();
         Ok(Self {
             provider,
-            sentence_phonemes,
+            segments,
         })
     }
 }
@@ -298,26 +1557,37 @@ impl Iterator for SonataSpeechStreamLazy {
     type Item = SonataAudioResult;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let phonemes = self.sentence_phonemes.next()?;
-        match self.provider.process_one_sentence(phonemes) {
+        let segment = self.segments.next()?;
+        match self.provider.process_segment(segment) {
             Ok(ws) => Some(Ok(ws)),
             Err(e) => Some(Err(e)),
         }
     }
+    /// The number of clips left, since the text is phonemized up front. There is a clip
+    /// per segment the model phonemizes the text into, which may be shorter than a sentence
+    /// (e.g. Piper voices split at commas too).
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.segments.size_hint()
+    }
 }
 
+impl ExactSizeIterator for SonataSpeechStreamLazy {}
+
 #[must_use]
 pub struct SonataSpeechStreamParallel {
     precalculated_results: std::vec::IntoIter<SonataAudioResult>,
 }
 
 impl SonataSpeechStreamParallel {
-    fn new(provider: SpeechSynthesisTaskProvider) -> SonataResult<Self> {
-        let calculated_result: Vec<SonataAudioResult> = provider
-            .get_phonemes()?
-            .par_iter()
-            .map(|ph| provider.process_one_sentence(ph.to_string()))
-            .collect();
+    fn new(
+        provider: SpeechSynthesisTaskProvider,
+        max_workers: Option<usize>,
+        synchronous: bool,
+    ) -> SonataResult<Self> {
+        let calculated_result: Vec<SonataAudioResult> =
+            utils::map_items(provider.get_phonemes()?, synchronous, max_workers, |segment| {
+                provider.process_segment(segment)
+            });
         Ok(Self {
             precalculated_results: calculated_result.into_iter(),
         })
@@ -330,46 +1600,108 @@ impl Iterator for SonataSpeechStreamParallel {
     fn next(&mut self) -> Option<Self::Item> {
         self.precalculated_results.next()
     }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.precalculated_results.size_hint()
+    }
 }
 
-pub struct RealtimeSpeechStream(Receiver<SonataResult<AudioSamples>>);
+impl ExactSizeIterator for SonataSpeechStreamParallel {}
+
+pub struct RealtimeSpeechStream {
+    receiver: Receiver<SonataResult<AudioSamples>>,
+    buffer_pool: Option<SampleBufferPool>,
+    config_handle: StreamConfigHandle,
+    counters: Arc<stats::StreamCounters>,
+    chunk_padding: usize,
+    sample_converter: SampleConverter,
+}
 
 impl RealtimeSpeechStream {
     fn new(
         provider: SpeechSynthesisTaskProvider,
-        chunk_size: usize,
-        chunk_padding: usize,
-        sample_rate: usize,
-        num_channels: usize,
+        streaming_config: StreamingConfig,
+        config_handle: StreamConfigHandle,
+        sample_converter: SampleConverter,
     ) -> SonataResult<Self> {
         let phonemes = provider.get_phonemes()?.into_iter();
         let (tx, rx) = flume::unbounded();
-        SYNTHESIS_THREAD_POOL.spawn(move || {
-            let mut chunk_size = chunk_size;
+        let buffer_pool = streaming_config.low_memory.then(SampleBufferPool::new);
+        let worker_buffer_pool = buffer_pool.clone();
+        let chunk_padding = streaming_config.chunk_padding;
+        let initial_chunk_size = streaming_config.chunk_size;
+        let incremental =
+            streaming_config.incremental && provider.model.supports_incremental_synthesis();
+        let worker_config_handle = config_handle.clone();
+        let counters = Arc::new(stats::StreamCounters::default());
+        let worker_counters = Arc::clone(&counters);
+        let quiet_start_ms = config_handle.config().and_then(|config| config.quiet_start_ms);
+        let mut gain_ramp = streaming_config.fade_in_ms.max(quiet_start_ms).map(|fade_in_ms| {
+            GainRamp::from_duration_ms(
+                fade_in_ms,
+                config_handle.output_sample_rate(),
+                config_handle.output_num_channels(),
+            )
+        });
+        let thread_pool = streaming_config
+            .thread_pool
+            .unwrap_or(&SYNTHESIS_THREAD_POOL);
+        thread_pool.spawn(move || {
+            let mut chunk_size = initial_chunk_size;
             let chunk_factor = 1;
             let mut num_processed_chunks = 0;
-            for ph_sent in phonemes {
+            for mut segment in phonemes {
                 chunk_size = if num_processed_chunks != 0 {
                     chunk_size  * chunk_factor * num_processed_chunks
                 } else {
                     chunk_size
                 };
-                match provider
-                    .model
-                    .stream_synthesis(ph_sent, chunk_size, chunk_padding)
-                {
+                worker_counters.set_chunk_size(chunk_size);
+                provider.announce(&mut segment);
+                let stream = if incremental {
+                    provider
+                        .model
+                        .stream_incremental_synthesis(segment.phonemes, chunk_padding)
+                } else {
+                    provider
+                        .model
+                        .stream_synthesis(segment.phonemes, chunk_size, chunk_padding)
+                };
+                match stream {
                     Ok(stream) => {
+                        stats::record_streamed_synthesis();
                         let send_result = RealtimeSpeechStream::process_rt_stream(
                             stream,
                             &tx,
-                            provider.output_config.as_ref(),
-                            sample_rate,
-                            num_channels,
+                            &worker_config_handle,
+                            &worker_counters,
+                            worker_buffer_pool.as_ref(),
+                            &mut gain_ramp,
                         );
                         match send_result {
                             Ok(num_chunks) => num_processed_chunks += num_chunks,
                             Err(_) => return
                         };
+                        if let Some(pause_ms) = segment.pause_ms {
+                            let info = AudioInfo {
+                                sample_rate: worker_config_handle.output_sample_rate(),
+                                num_channels: worker_config_handle.output_num_channels(),
+                                sample_width: 2,
+                            };
+                            let silence = Audio::silence(info, pause_ms);
+                            let silence = match worker_config_handle
+                                .config()
+                                .and_then(|config| config.block_frames)
+                            {
+                                Some(block_frames) => silence
+                                    .pad_to_block_size(block_frames as usize)
+                                    .map_err(SonataError::from),
+                                None => Ok(silence),
+                            };
+                            let silence = silence.map(|silence| silence.samples);
+                            if RealtimeSpeechStream::send_chunk(&tx, &worker_config_handle, &worker_counters, &mut gain_ramp, silence).is_err() {
+                                return;
+                            }
+                        }
                     }
                     Err(e) => {
                         tx.send(Err(e)).ok();
@@ -378,46 +1710,221 @@ impl RealtimeSpeechStream {
                 };
             }
         });
-        Ok(Self(rx))
+        Ok(Self {
+            receiver: rx,
+            buffer_pool,
+            config_handle,
+            counters,
+            chunk_padding,
+            sample_converter,
+        })
+    }
+    /// Hand a consumed chunk back to the stream so that its buffer can be reused
+    /// for subsequent chunks. Has no effect unless the stream was created with
+    /// [`StreamingConfig::low_memory`] enabled.
+    pub fn recycle(&self, samples: AudioSamples) {
+        if let Some(ref buffer_pool) = self.buffer_pool {
+            buffer_pool.recycle(samples);
+        }
+    }
+    /// The converter for the chunks of the stream to 16-bit PCM: the synthesizer's
+    /// [`SonataSpeechSynthesizer::sample_converter`], or a fixed scale (see
+    /// [`SampleConverter::rounding`]) if it has none. Converting every chunk with it keeps
+    /// the level of the stream, where [`AudioSamples::as_wave_bytes`] would normalize each
+    /// chunk to its own peak.
+    pub fn sample_converter(&self) -> &SampleConverter {
+        &self.sample_converter
+    }
+    /// A handle for changing the output config of the chunks that are yet to be synthesized
+    pub fn config_handle(&self) -> StreamConfigHandle {
+        self.config_handle.clone()
+    }
+    /// A snapshot of the chunk scheduling of the stream so far
+    pub fn stats(&self) -> StreamStats {
+        self.counters
+            .snapshot(self.chunk_padding, self.config_handle.output_num_channels())
     }
     #[inline(always)]
     fn process_rt_stream(
         stream: AudioStreamIterator,
         tx: &Sender<SonataResult<AudioSamples>>,
-        audio_output_config: Option<&AudioOutputConfig>,
-        sample_rate: usize,
-        num_channels: usize,
+        config_handle: &StreamConfigHandle,
+        counters: &stats::StreamCounters,
+        buffer_pool: Option<&SampleBufferPool>,
+        gain_ramp: &mut Option<GainRamp>,
     ) -> Result<usize, SendError<SonataResult<AudioSamples>>> {
+        let (sample_rate, num_channels) = (config_handle.sample_rate, config_handle.num_channels);
         let mut num_chunks = 0;
-        if let Some(output_config) = audio_output_config {
-            for result in stream {
-                match result {
-                    Ok(samples) => {
-                        tx.send(output_config.apply_to_raw_samples(
-                            samples,
-                            sample_rate,
-                            num_channels,
-                        ))?;
-                        num_chunks += 1;
+        let (mut version, output_config) = config_handle.snapshot();
+        // One processor per segment, so stateful effects carry across its chunks
+        let mut processor = match output_config
+            .map(|config| OutputProcessor::new(&config, sample_rate, num_channels))
+            .transpose()
+        {
+            Ok(processor) => processor,
+            Err(e) => {
+                tx.send(Err(e))?;
+                return Ok(num_chunks);
+            }
+        };
+        for result in stream {
+            match result {
+                Ok(samples) => {
+                    if let Some((new_version, output_config)) = config_handle.changed_since(version) {
+                        version = new_version;
+                        let output_config = output_config.unwrap_or_default();
+                        if let Some(ref mut processor) = processor {
+                            processor.set_prosody(&output_config);
+                        } else {
+                            // The config was validated when it was updated
+                            processor = OutputProcessor::new(&output_config, sample_rate, num_channels).ok();
+                        }
                     }
-                    Err(e) => {
-                        tx.send(Err(e))?;
+                    let Some(ref mut processor) = processor else {
+                        // The model's buffer is dropped here, and the chunk goes out in a
+                        // pooled one, so that the buffers the consumer recycles are reused
+                        let samples = match buffer_pool {
+                            Some(pool) => {
+                                let mut out_buf = pool.acquire();
+                                out_buf.clear();
+                                out_buf.extend_from_slice(samples.as_slice());
+                                out_buf.into()
+                            }
+                            None => samples,
+                        };
+                        Self::send_chunk(tx, config_handle, counters, gain_ramp, Ok(samples))?;
+                        num_chunks += 1;
+                        continue;
+                    };
+                    let mut out_buf = buffer_pool.map(|pool| pool.acquire()).unwrap_or_default();
+                    out_buf.clear();
+                    processor.process(samples.as_slice(), &mut out_buf);
+                    Self::send_chunk(tx, config_handle, counters, gain_ramp, Ok(out_buf.into()))?;
+                    if let Some(pool) = buffer_pool {
+                        pool.recycle(samples);
                     }
-                };
-            }
-            if let Some(silence_ms) = output_config.appended_silence_ms {
-                let silence_result =
-                    output_config.generate_silence(silence_ms as usize, sample_rate, num_channels);
-                tx.send(silence_result)?;
-            }
-            Ok(num_chunks)
-        } else {
-            for result in stream {
-                tx.send(result)?;
-                num_chunks += 1;
+                    num_chunks += 1;
+                }
+                Err(e) => {
+                    tx.send(Err(e))?;
+                }
+            };
+        }
+        if let Some(processor) = processor {
+            let mut tail = buffer_pool.map(|pool| pool.acquire()).unwrap_or_default();
+            tail.clear();
+            processor.finish(&mut tail);
+            if !tail.is_empty() {
+                Self::send_chunk(tx, config_handle, counters, gain_ramp, Ok(tail.into()))?;
             }
-            Ok(num_chunks)
         }
+        Ok(num_chunks)
+    }
+    /// Send a chunk to the consumer, after applying what remains of the fade-in to it
+    fn send_chunk(
+        tx: &Sender<SonataResult<AudioSamples>>,
+        config_handle: &StreamConfigHandle,
+        counters: &stats::StreamCounters,
+        gain_ramp: &mut Option<GainRamp>,
+        mut chunk: SonataResult<AudioSamples>,
+    ) -> Result<(), SendError<SonataResult<AudioSamples>>> {
+        if let (Some(ramp), Ok(samples)) = (gain_ramp.as_mut(), chunk.as_mut()) {
+            ramp.process(samples.as_mut_vec());
+        }
+        if let Ok(ref samples) = chunk {
+            let samples_per_ms =
+                (config_handle.output_sample_rate() * config_handle.output_num_channels()) as f32 / 1000.0;
+            stats::record_audio(samples.len() as f32 / samples_per_ms);
+            counters.record_produced(samples.len());
+        }
+        tx.send(chunk)
+    }
+}
+
+/// Changes the output config of a [`RealtimeSpeechStream`] while it is being synthesized.
+///
+/// An update applies to the chunks synthesized after it: chunks that are already queued keep
+/// the config they were synthesized with. The rate, volume and pitch change from the next
+/// chunk, and the other effects from the next sentence. The output sample rate and number
+/// of channels can't change.
+#[derive(Clone)]
+pub struct StreamConfigHandle {
+    /// The output config, along with the number of times it was updated
+    cell: Arc<Mutex<(u64, Option<AudioOutputConfig>)>>,
+    /// Added to the gain of the config, as for the synthesis calls of the synthesizer
+    calibrated_gain_db: Option<f32>,
+    sample_rate: usize,
+    num_channels: usize,
+}
+
+impl StreamConfigHandle {
+    fn new(
+        output_config: Option<AudioOutputConfig>,
+        calibrated_gain_db: Option<f32>,
+        sample_rate: usize,
+        num_channels: usize,
+    ) -> Self {
+        Self {
+            cell: Arc::new(Mutex::new((0, output_config))),
+            calibrated_gain_db,
+            sample_rate,
+            num_channels,
+        }
+    }
+    pub fn config(&self) -> Option<AudioOutputConfig> {
+        self.cell.lock().unwrap().1.clone()
+    }
+    /// Replace the output config. Fails if the config is invalid or changes the output sample
+    /// rate or number of channels. The calibrated gain of the synthesizer is added to the new
+    /// config, as it was to the config the stream started with.
+    pub fn update(&self, output_config: Option<AudioOutputConfig>) -> SonataResult<()> {
+        let mut cell = self.cell.lock().unwrap();
+        if self.output_sample_rate_of(output_config.as_ref())
+            != self.output_sample_rate_of(cell.1.as_ref())
+        {
+            return Err(SonataError::OperationError(
+                "The output sample rate of a stream can't change while it is being synthesized"
+                    .to_string(),
+            ));
+        }
+        if self.output_num_channels_of(output_config.as_ref())
+            != self.output_num_channels_of(cell.1.as_ref())
+        {
+            return Err(SonataError::OperationError(
+                "The number of output channels of a stream can't change while it is being synthesized"
+                    .to_string(),
+            ));
+        }
+        if let Some(ref config) = output_config {
+            OutputProcessor::new(config, self.sample_rate, self.num_channels)?;
+        }
+        *cell = (cell.0 + 1, output_config);
+        Ok(())
+    }
+    fn output_sample_rate(&self) -> usize {
+        self.output_sample_rate_of(self.cell.lock().unwrap().1.as_ref())
+    }
+    fn output_sample_rate_of(&self, output_config: Option<&AudioOutputConfig>) -> usize {
+        output_config.map_or(self.sample_rate, |config| config.output_sample_rate(self.sample_rate))
+    }
+    fn output_num_channels(&self) -> usize {
+        self.output_num_channels_of(self.cell.lock().unwrap().1.as_ref())
+    }
+    fn output_num_channels_of(&self, output_config: Option<&AudioOutputConfig>) -> usize {
+        output_config.map_or(self.num_channels, |config| {
+            config.output_num_channels(self.num_channels)
+        })
+    }
+    /// The config with the calibrated gain, along with its version
+    fn snapshot(&self) -> (u64, Option<AudioOutputConfig>) {
+        let (version, output_config) = self.cell.lock().unwrap().clone();
+        (version, with_calibrated_gain(output_config, self.calibrated_gain_db))
+    }
+    /// The config, if it was updated since `version`
+    fn changed_since(&self, version: u64) -> Option<(u64, Option<AudioOutputConfig>)> {
+        let cell = self.cell.lock().unwrap();
+        (cell.0 != version)
+            .then(|| (cell.0, with_calibrated_gain(cell.1.clone(), self.calibrated_gain_db)))
     }
 }
 
@@ -425,6 +1932,373 @@ impl Iterator for RealtimeSpeechStream {
     type Item = SonataResult<AudioSamples>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.recv().ok()
+        let result = self.receiver.recv().ok()?;
+        if let Ok(ref samples) = result {
+            self.counters.record_consumed(samples.len());
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_model::{MockModel, NoisyMockModel, OverridableMockModel, StreamingMockModel};
+
+    fn mock_synth() -> SonataSpeechSynthesizer {
+        SonataSpeechSynthesizer::builder(Arc::new(MockModel))
+            .with_text_normalizer(TextNormalizer::passthrough())
+            .build()
+            .unwrap()
+    }
+
+    fn clip_lengths(stream: impl Iterator<Item = SonataAudioResult>) -> Vec<usize> {
+        stream.map(|audio| audio.unwrap().len()).collect()
+    }
+
+    #[test]
+    fn test_sentence_callback_doesnt_change_the_segments() {
+        let text = "Hello there! How are you? Fine, thanks.".to_string();
+        let without_callback = clip_lengths(mock_synth().synthesize_lazy(text.clone(), None).unwrap());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let synth = mock_synth();
+        synth.set_sentence_callback(Some(Box::new(move |event| tx.send(event).unwrap())));
+        let with_callback = clip_lengths(synth.synthesize_lazy(text.clone(), None).unwrap());
+        assert_eq!(with_callback, without_callback);
+        let parallel = clip_lengths(synth.synthesize_parallel(text.clone(), None).unwrap());
+        assert_eq!(parallel, without_callback);
+        drop(synth);
+
+        // The lazy stream's events come first and in order, the parallel ones in any order
+        let events: Vec<_> = rx.iter().collect();
+        let phonemes: Vec<_> = events.iter().take(2).map(|event| event.phonemes.as_str()).collect();
+        assert_eq!(phonemes, ["hello there! how are you? fine", "thanks"]);
+        assert_eq!(events[1].index, 1);
+        assert_eq!(events[1].text, text);
+        assert_eq!(events.len(), 4);
+    }
+
+    #[test]
+    fn test_streams_count_the_clips_left() {
+        let text = "Hi, yo. Bye.".to_string();
+        let mut stream = mock_synth().synthesize_lazy(text.clone(), None).unwrap();
+        assert_eq!(stream.len(), 3);
+        assert!(!stream.next().unwrap().unwrap().is_empty());
+        assert_eq!(stream.len(), 2);
+        assert_eq!(clip_lengths(stream).len(), 2);
+        let options = ParallelSynthesisOptions {
+            include_sentence_info: true,
+            ..Default::default()
+        };
+        let stream = mock_synth().synthesize_parallel_with_options(text, None, options).unwrap();
+        assert_eq!(stream.len(), 2);
+    }
+
+    #[test]
+    fn test_quiet_start_fades_in_the_first_clip_only() {
+        let config = AudioOutputConfig {
+            quiet_start_ms: Some(AudioOutputConfig::DEFAULT_QUIET_START_MS),
+            ..Default::default()
+        };
+        let text = "Hi, yo. Bye.".to_string();
+        let clips = Vec::from_iter(
+            mock_synth()
+                .synthesize_lazy(text.clone(), Some(config.clone()))
+                .unwrap()
+                .map(|audio| audio.unwrap().samples.into_vec()),
+        );
+        // 5 ms at 16 kHz
+        assert_eq!(clips[0][0], 0.0);
+        assert!(clips[0][..80].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(clips[0][80..].iter().all(|sample| *sample == 0.5));
+        assert!(clips[1..].iter().flatten().all(|sample| *sample == 0.5));
+
+        let options = ParallelSynthesisOptions {
+            include_sentence_info: true,
+            ..Default::default()
+        };
+        let sentences = Vec::from_iter(
+            mock_synth()
+                .synthesize_parallel_with_options(text, Some(config), options)
+                .unwrap()
+                .map(|audio| audio.unwrap().samples.into_vec()),
+        );
+        assert_eq!(sentences[0][..80], clips[0][..80]);
+        assert!(sentences[1].iter().all(|sample| *sample == 0.5));
+    }
+
+    #[test]
+    fn test_punctuation_pauses_insert_their_silence_between_segments() {
+        let synth = mock_synth();
+        synth.set_punctuation_pauses(Some(PunctuationPauses::default()));
+        let samples = Vec::from_iter(
+            synth
+                .synthesize_lazy("First. Second, third".to_string(), None)
+                .unwrap()
+                .flat_map(|audio| audio.unwrap().samples.into_vec()),
+        );
+        // The lengths of the runs of silence between the speech of the segments
+        let mut gaps = Vec::new();
+        let mut silent = 0;
+        for sample in samples {
+            if sample == 0.0 {
+                silent += 1;
+            } else if silent > 0 {
+                gaps.push(silent);
+                silent = 0;
+            }
+        }
+        assert_eq!(silent, 0);
+        let samples_per_ms = mock_model::SAMPLE_RATE / 1000;
+        assert_eq!(gaps, [400 * samples_per_ms, 150 * samples_per_ms]);
+    }
+
+    #[test]
+    fn test_sentences_from_upstream_are_one_segment_each() {
+        let synth = mock_synth();
+        synth.set_punctuation_pauses(Some(PunctuationPauses::default()));
+        let sentences = vec!["Hello, world".to_string(), "Again".to_string()];
+        let clips = Vec::from_iter(
+            synth
+                .synthesize_from_sentences(sentences, None, ParallelSynthesisOptions::default())
+                .unwrap()
+                .map(Result::unwrap),
+        );
+        assert_eq!(clips.len(), 2);
+        // The phonemes of "hello world", without a pause after the comma
+        let samples = clips[0].samples.as_slice();
+        assert_eq!(samples.len(), 11 * mock_model::SAMPLES_PER_PHONEME);
+        assert!(samples.iter().all(|sample| *sample != 0.0));
+        assert_eq!(clips[1].sentence.as_ref().unwrap().index, 1);
+    }
+
+    #[test]
+    fn test_clips_with_the_same_gain_have_the_same_level() {
+        let synth = mock_synth();
+        let output_config = AudioOutputConfig {
+            gain_db: Some(-6.0),
+            ..Default::default()
+        };
+        let clips = synth
+            .synthesize_lazy("First. Second sentence".to_string(), Some(output_config))
+            .unwrap();
+        let levels = Vec::from_iter(clips.map(|audio| {
+            let bytes = audio.unwrap().as_wave_bytes();
+            let middle = bytes.len() / 4 * 2;
+            i16::from_le_bytes([bytes[middle], bytes[middle + 1]])
+        }));
+        // The 0.5 of the model at -6 dB, rather than the peak of each clip at full scale
+        assert_eq!(levels.len(), 2);
+        assert!(levels.iter().all(|level| (level - 8212).abs() <= 1), "{:?}", levels);
+    }
+
+    #[test]
+    fn test_failed_builds_leave_the_model_unchanged() {
+        let model = Arc::new(OverridableMockModel::default());
+        let overrides_path = std::env::temp_dir().join(format!("sonata-test-build-overrides-{}.json", std::process::id()));
+        std::fs::write(&overrides_path, r#"{"speaker": "nobody"}"#).unwrap();
+        let result = SonataSpeechSynthesizer::builder(model.clone())
+            .with_stable_preset()
+            .with_overrides(&overrides_path)
+            .build();
+        assert!(result.is_err());
+        let result = SonataSpeechSynthesizer::builder(model.clone())
+            .with_stable_preset()
+            .with_speaker_names(overrides_path.with_extension("missing"))
+            .build();
+        assert!(result.is_err());
+        assert!(model.applied.lock().unwrap().is_empty());
+        // The file takes precedence over the preset, and both are applied at once
+        std::fs::write(&overrides_path, r#"{"noise_scale": 0.5}"#).unwrap();
+        SonataSpeechSynthesizer::builder(model.clone())
+            .with_stable_preset()
+            .with_overrides(&overrides_path)
+            .build()
+            .unwrap();
+        std::fs::remove_file(&overrides_path).ok();
+        let expected = SynthesisOverrides {
+            noise_scale: Some(0.5),
+            ..SynthesisOverrides::stable()
+        };
+        assert_eq!(*model.applied.lock().unwrap(), [expected]);
+    }
+
+    #[test]
+    fn test_invalid_overrides_from_env_leave_the_model_unchanged() {
+        let model = Arc::new(OverridableMockModel::default());
+        let overrides_path = std::env::temp_dir().join(format!("sonata-test-env-overrides-{}.json", std::process::id()));
+        std::fs::write(&overrides_path, r#"{"noise_scale": 0.5, "pitch": 3}"#).unwrap();
+        std::env::set_var(OVERRIDES_ENV_VAR, &overrides_path);
+        let result = SonataSpeechSynthesizer::builder(model.clone())
+            .with_stable_preset()
+            .with_overrides_from_env()
+            .build();
+        std::env::remove_var(OVERRIDES_ENV_VAR);
+        std::fs::remove_file(&overrides_path).ok();
+        assert!(result.is_err());
+        assert!(model.applied.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_streamed_files_keep_the_level_across_chunks() {
+        let synth = SonataSpeechSynthesizer::builder(Arc::new(StreamingMockModel { incremental: false }))
+            .with_text_normalizer(TextNormalizer::passthrough())
+            .build()
+            .unwrap();
+        let filename = std::env::temp_dir().join(format!(
+            "sonata_test_streamed_files_keep_the_level_{}.wav",
+            std::process::id()
+        ));
+        let streaming_config = StreamingConfig {
+            chunk_size: 2,
+            ..Default::default()
+        };
+        synth
+            .synthesize_streamed_to_file(&filename, "Hello there".to_string(), None, streaming_config)
+            .unwrap();
+        let bytes = std::fs::read(&filename).unwrap();
+        std::fs::remove_file(&filename).ok();
+        let samples = Vec::from_iter(bytes[44..].chunks_exact(2).map(|pcm| i16::from_le_bytes([pcm[0], pcm[1]])));
+        assert_eq!(samples.len(), "hello there".len() * mock_model::SAMPLES_PER_PHONEME);
+        // The mock speaks at half of full scale in every chunk
+        assert!(samples.iter().all(|sample| *sample == 16384));
+    }
+
+    #[test]
+    fn test_written_files_keep_the_calibrated_gain() {
+        let synth = mock_synth();
+        let target_db = -20.0;
+        synth.calibrate_gain(target_db).unwrap();
+        let filename = std::env::temp_dir().join("sonata_test_written_files_keep_the_calibrated_gain.wav");
+        let written_loudness = |output_config| {
+            synth.synthesize_to_file(&filename, "Hello there".to_string(), output_config).unwrap();
+            let bytes = std::fs::read(&filename).unwrap();
+            let samples = Vec::from_iter(
+                bytes[44..]
+                    .chunks_exact(2)
+                    .map(|pcm| i16::from_le_bytes([pcm[0], pcm[1]]) as f32 / i16::MAX as f32),
+            );
+            Audio::new(samples.into(), mock_model::SAMPLE_RATE, None).loudness_db().unwrap()
+        };
+        assert!((written_loudness(None) - target_db).abs() < 0.1);
+        let output_config = AudioOutputConfig {
+            gain_db: Some(-6.0),
+            ..Default::default()
+        };
+        assert!((written_loudness(Some(output_config)) - (target_db - 6.0)).abs() < 0.1);
+        std::fs::remove_file(&filename).ok();
+    }
+
+    #[test]
+    fn test_streamed_sentences_are_one_segment_each() {
+        let synth = SonataSpeechSynthesizer::builder(Arc::new(StreamingMockModel { incremental: false }))
+            .with_text_normalizer(TextNormalizer::passthrough())
+            .build()
+            .unwrap();
+        let streaming_config = || StreamingConfig {
+            chunk_size: 100,
+            ..Default::default()
+        };
+        let sentences = vec!["Hello, world.".to_string(), "Bye".to_string()];
+        let segments = synth.create_provider_for_sentences(sentences.clone(), None).get_phonemes().unwrap();
+        assert_eq!(Vec::from_iter(segments.iter().map(|s| s.phonemes.as_str())), ["hello world", "bye"]);
+        let stream = synth
+            .synthesize_streamed_from_sentences(sentences, None, streaming_config())
+            .unwrap();
+        let chunks = Vec::from_iter(stream.map(|chunk| chunk.unwrap().len()));
+        assert_eq!(chunks, ["hello world".len(), "bye".len()].map(|len| len * mock_model::SAMPLES_PER_PHONEME));
+        // The model splits the joined text at the comma as well
+        let stream = synth
+            .synthesize_streamed_with_config("Hello, world. Bye".to_string(), None, streaming_config())
+            .unwrap();
+        assert_eq!(stream.count(), 3);
+    }
+
+    #[test]
+    fn test_incremental_streams_follow_the_predicted_durations() {
+        let chunk_lengths = |incremental_model, incremental| {
+            let model = StreamingMockModel {
+                incremental: incremental_model,
+            };
+            let synth = SonataSpeechSynthesizer::builder(Arc::new(model))
+                .with_text_normalizer(TextNormalizer::passthrough())
+                .build()
+                .unwrap();
+            let streaming_config = StreamingConfig {
+                chunk_size: 100,
+                incremental,
+                ..Default::default()
+            };
+            let stream = synth
+                .synthesize_streamed_with_config("Hello there my friend".to_string(), None, streaming_config)
+                .unwrap();
+            Vec::from_iter(stream.map(|chunk| chunk.unwrap().len()))
+        };
+        let chunked = chunk_lengths(true, false);
+        assert_eq!(chunked, ["hello there my friend".len() * mock_model::SAMPLES_PER_PHONEME]);
+        let incremental = chunk_lengths(true, true);
+        let words = ["hello ", "there ", "my ", "friend"];
+        assert_eq!(incremental, words.map(|word| word.len() * mock_model::SAMPLES_PER_PHONEME));
+        // Models without durations stream the usual chunks
+        assert_eq!(chunk_lengths(false, true), chunked);
+        let model = StreamingMockModel { incremental: false };
+        assert!(model.stream_incremental_synthesis("hello".to_string(), 3).is_err());
+    }
+
+    #[test]
+    fn test_noise_inputs_are_drawn_from_the_synthesizer_rng() {
+        let samples_with_seed = |seed: u64| {
+            let synth = SonataSpeechSynthesizer::builder(Arc::new(NoisyMockModel))
+                .with_text_normalizer(TextNormalizer::passthrough())
+                .with_rng(Box::new(StdRng::seed_from_u64(seed)))
+                .build()
+                .unwrap();
+            let stream = synth.synthesize_lazy("Hello there. Bye".to_string(), None).unwrap();
+            Vec::from_iter(stream.flat_map(|audio| audio.unwrap().samples.into_vec()))
+        };
+        let samples = samples_with_seed(7);
+        assert_eq!(samples.len(), "hello there".len() + "bye".len());
+        assert_eq!(samples, samples_with_seed(7));
+        assert_ne!(samples, samples_with_seed(8));
+    }
+
+    #[test]
+    fn test_joined_clips_keep_the_phoneme_timings() {
+        let options = ParallelSynthesisOptions {
+            include_sentence_info: true,
+            ..Default::default()
+        };
+        let clips: Vec<Audio> = mock_synth()
+            .synthesize_parallel_with_options("Hi, yo. Bye.".to_string(), None, options)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        // The first sentence is phonemized as two segments, whose clips are joined
+        let timings = clips[0].phoneme_timings.as_ref().unwrap();
+        let spans = Vec::from_iter(timings.iter().map(|t| (t.phoneme.as_str(), t.start_ms, t.end_ms)));
+        assert_eq!(spans, [("h", 0.0, 10.0), ("i", 10.0, 20.0), ("y", 20.0, 30.0), ("o", 30.0, 40.0)]);
+        assert_eq!(clips[1].phoneme_timings.as_ref().unwrap().len(), 3);
+        let text_grid = clips[0].export_alignment(AlignmentFormat::TextGrid).unwrap();
+        assert!(text_grid.contains("xmax = 0.04"));
+    }
+
+    #[test]
+    fn test_visemes_of_synthesized_speech() {
+        let options = ParallelSynthesisOptions {
+            include_sentence_info: true,
+            ..Default::default()
+        };
+        let audio = mock_synth()
+            .synthesize_parallel_with_options("Mama, ok.".to_string(), None, options)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let visemes = audio.visemes(VisemeSet::Oculus).unwrap();
+        let names = Vec::from_iter(visemes.iter().map(|viseme| viseme.viseme));
+        assert_eq!(names, ["PP", "aa", "PP", "aa", "oh", "kk"]);
+        assert_eq!((visemes[4].start_ms, visemes[5].end_ms), (40.0, 60.0));
+        assert!(mock_synth().make_silence(100).unwrap().visemes(VisemeSet::Oculus).is_err());
     }
 }
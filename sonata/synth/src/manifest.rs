@@ -0,0 +1,488 @@
+use crate::overrides::{resolve_speaker, SpeakerOverride};
+use crate::utils::{self, MemoryBudget};
+use crate::{AudioOutputConfig, SonataSpeechSynthesizer};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sonata_core::{SonataError, SonataModel, SonataResult, SynthesisOverrides};
+use std::any::Any;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// A row of a manifest, e.g. `{"id": "utt_001", "text": "Hello!", "speaker": "alice", "length_scale": 1.1}`
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestRow {
+    id: String,
+    text: String,
+    speaker: Option<SpeakerOverride>,
+    noise_scale: Option<f32>,
+    length_scale: Option<f32>,
+    noise_w: Option<f32>,
+}
+
+/// Bytes held for each sample of a row until its file is written: the synthesized `f32`
+/// samples and their conversion to 16-bit
+const BYTES_PER_BUFFERED_SAMPLE: usize = std::mem::size_of::<f32>() + std::mem::size_of::<i16>();
+
+/// A row that couldn't be synthesized: its id (or `row <number>` if it has none) and the reason
+type RowFailure = (String, String);
+
+/// Restores the model's synthesis config when dropped, so that it's restored even when
+/// processing the manifest stops early with an error or a panic
+struct FallbackConfigGuard<'a> {
+    model: &'a dyn SonataModel,
+    original_config: Option<Box<dyn Any>>,
+}
+
+impl<'a> FallbackConfigGuard<'a> {
+    fn new(model: &'a dyn SonataModel) -> SonataResult<Self> {
+        Ok(Self {
+            original_config: Some(model.get_fallback_synthesis_config()?),
+            model,
+        })
+    }
+    /// Go back to the original config, e.g. before applying the overrides of the next rows
+    fn reset(&self) -> SonataResult<()> {
+        match self.original_config.as_ref() {
+            Some(config) => self.model.set_fallback_synthesis_config(config.as_ref()),
+            None => Ok(()),
+        }
+    }
+    /// Restore the original config, returning the error instead of logging it
+    fn restore(mut self) -> SonataResult<()> {
+        let result = self.reset();
+        self.original_config = None;
+        result
+    }
+}
+
+impl Drop for FallbackConfigGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.reset() {
+            log::error!("Failed to restore the synthesis config after the manifest. Error: {}", e);
+        }
+    }
+}
+
+/// The outcome of [`SonataSpeechSynthesizer::synthesize_manifest`]
+#[derive(Debug, Default)]
+pub struct ManifestReport {
+    /// The id of each row that was synthesized and the file it was written to
+    pub written: Vec<(String, PathBuf)>,
+    /// The id of each row that failed (or `row <number>` if it has none) and the reason
+    pub failed: Vec<(String, String)>,
+    /// The most memory that the audio of the rows in flight took at once, in bytes
+    pub peak_buffered_bytes: usize,
+}
+
+impl SonataSpeechSynthesizer {
+    /// Synthesize each row of a manifest into `<id>.wav` in `output_dir`, in parallel.
+    ///
+    /// The manifest is a JSON array of rows (`.json`), one JSON row per line (`.jsonl`), or
+    /// a CSV file with a header (`.csv`). Each row has an `id` and a `text`, and optionally
+    /// a `speaker` (id or name), `length_scale`, `noise_scale` and `noise_w` that override the
+    /// model's synthesis config for that row. A row that fails is reported without stopping
+    /// the others.
+    ///
+    /// Rows with the same overrides are synthesized together, and the model's synthesis config
+    /// is restored at the end, also when this returns early with an error. Since the config is shared, other synthesis using the same model
+    /// while the manifest is processed picks up the overrides of the current rows.
+    pub fn synthesize_manifest(
+        &self,
+        manifest_path: &Path,
+        output_dir: &Path,
+        output_config: Option<AudioOutputConfig>,
+    ) -> SonataResult<ManifestReport> {
+        self.synthesize_manifest_with_memory_budget(manifest_path, output_dir, output_config, None)
+    }
+    /// Like [`SonataSpeechSynthesizer::synthesize_manifest`], but with at most
+    /// `max_buffered_bytes` of audio held in memory at once, to keep long jobs within a RAM
+    /// budget. Rows only start when their estimated audio (see
+    /// [`SonataSpeechSynthesizer::cost_estimate`]) fits in the rest of the budget, so fewer
+    /// rows run in parallel when they are long. A row larger than the whole budget runs alone.
+    ///
+    /// The estimate is replaced by the actual size of the audio once it's synthesized,
+    /// which can take the total over the budget when the estimate was too low.
+    /// [`ManifestReport::peak_buffered_bytes`] reports the peak.
+    pub fn synthesize_manifest_with_memory_budget(
+        &self,
+        manifest_path: &Path,
+        output_dir: &Path,
+        output_config: Option<AudioOutputConfig>,
+        max_buffered_bytes: Option<usize>,
+    ) -> SonataResult<ManifestReport> {
+        let rows = load_manifest(manifest_path)?;
+        if let Err(e) = std::fs::create_dir_all(output_dir) {
+            return Err(SonataError::OperationError(format!(
+                "Failed to create output directory `{}`. Error: {}",
+                output_dir.display(),
+                e
+            )));
+        }
+        let mut report = ManifestReport::default();
+        let mut seen_ids = HashSet::new();
+        let mut groups: Vec<(SynthesisOverrides, Vec<ManifestRow>)> = Vec::new();
+        for row in rows {
+            let row = match row.and_then(|row| self.check_row(row, &mut seen_ids)) {
+                Ok(row) => row,
+                Err(failure) => {
+                    report.failed.push(failure);
+                    continue;
+                }
+            };
+            let (overrides, row) = row;
+            match groups.iter_mut().find(|(group, _)| *group == overrides) {
+                Some((_, rows)) => rows.push(row),
+                None => groups.push((overrides, vec![row])),
+            }
+        }
+        let config_guard = FallbackConfigGuard::new(self.model.as_ref())?;
+        let sample_converter = self.sample_converter_for(output_config.as_ref());
+        let budget = MemoryBudget::new(max_buffered_bytes);
+        for (overrides, rows) in groups {
+            config_guard.reset()?;
+            if let Err(e) = self.model.apply_synthesis_overrides(&overrides) {
+                let reason = e.to_string();
+                report
+                    .failed
+                    .extend(rows.into_iter().map(|row| (row.id, reason.clone())));
+                continue;
+            }
+            // A fixed set of workers, one per thread of the pool, take the rows in turn. A
+            // worker waiting for its reservation holds no rayon job that another row needs.
+            let max_workers = rayon::current_num_threads();
+            let results = utils::map_items(rows, false, Some(max_workers), |row| {
+                let filename = output_dir.join(format!("{}.wav", row.id));
+                // Rows whose size can't be estimated take the whole budget
+                let estimated_bytes = self
+                    .estimate_buffered_bytes(&row.text, output_config.as_ref())
+                    .unwrap_or_else(|_| budget.limit().unwrap_or_default());
+                let mut reservation = budget.reserve(estimated_bytes);
+                let result = self
+                    .create_synthesis_task_provider(row.text, output_config.clone())
+                    .synthesize_all()
+                    .and_then(|audio| {
+                        reservation.resize(audio.len() * BYTES_PER_BUFFERED_SAMPLE);
+                        Ok(audio.save_to_file_with(&filename, sample_converter.as_ref())?)
+                    });
+                (row.id, filename, result)
+            });
+            for (id, filename, result) in results {
+                match result {
+                    Ok(()) => report.written.push((id, filename)),
+                    Err(e) => report.failed.push((id, e.to_string())),
+                }
+            }
+        }
+        config_guard.restore()?;
+        report.peak_buffered_bytes = budget.peak();
+        Ok(report)
+    }
+    /// The memory that the audio of `text` is expected to take until it's written
+    fn estimate_buffered_bytes(
+        &self,
+        text: &str,
+        output_config: Option<&AudioOutputConfig>,
+    ) -> SonataResult<usize> {
+        let num_samples = self.cost_estimate(text)?.num_samples;
+        let info = self.model.audio_output_info()?;
+        let output_samples = num_samples * self.output_sample_rate(output_config)? / info.sample_rate.max(1)
+            * info.num_channels.max(1);
+        Ok(output_samples * BYTES_PER_BUFFERED_SAMPLE)
+    }
+    /// Validate the row, returning it with its overrides
+    fn check_row(
+        &self,
+        mut row: ManifestRow,
+        seen_ids: &mut HashSet<String>,
+    ) -> Result<(SynthesisOverrides, ManifestRow), RowFailure> {
+        let fail = |reason: &str| Err((row.id.clone(), reason.to_string()));
+        if row.id.is_empty()
+            || row.id == "."
+            || row.id == ".."
+            || row.id.contains(['/', '\\', '\0'])
+        {
+            return fail("the id must be a valid file name");
+        }
+        if row.text.trim().is_empty() {
+            return fail("the text is empty");
+        }
+        if !seen_ids.insert(row.id.clone()) {
+            return fail("the id is used by an earlier row");
+        }
+        let speaker = match row.speaker.take() {
+            Some(speaker) => match resolve_speaker(speaker, self, "Invalid speaker") {
+                Ok(sid) => Some(sid),
+                Err(e) => return fail(&e.to_string()),
+            },
+            None => None,
+        };
+        let overrides = SynthesisOverrides {
+            speaker,
+            noise_scale: row.noise_scale,
+            length_scale: row.length_scale,
+            noise_w: row.noise_w,
+        };
+        Ok((overrides, row))
+    }
+}
+
+/// Read the rows of a manifest. Rows that can't be parsed are returned as failures.
+fn load_manifest(path: &Path) -> SonataResult<Vec<Result<ManifestRow, RowFailure>>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return Err(SonataError::FailedToLoadResource(format!(
+                "Failed to read manifest from `{}`: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let values: Vec<Result<Value, String>> = match extension.as_deref() {
+        Some("json") => match serde_json::from_str::<Vec<Value>>(&contents) {
+            Ok(values) => values.into_iter().map(Ok).collect(),
+            Err(e) => return Err(invalid(e.to_string())),
+        },
+        Some("jsonl") => contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| e.to_string()))
+            .collect(),
+        Some("csv") => csv_to_values(&contents)?.into_iter().map(Ok).collect(),
+        _ => {
+            return Err(invalid(format!(
+                "unsupported file extension for `{}`. Supported extensions are `json`, `jsonl` and `csv`",
+                path.display()
+            )))
+        }
+    };
+    Ok(values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let value = value.map_err(|reason| (format!("row {}", i + 1), reason))?;
+            let label = match value.get("id") {
+                Some(Value::String(id)) => id.clone(),
+                _ => format!("row {}", i + 1),
+            };
+            serde_json::from_value(value).map_err(|e| (label, e.to_string()))
+        })
+        .collect())
+}
+
+/// Convert CSV records to JSON objects keyed by the header, leaving out empty fields.
+/// Speaker ids and scales are converted to numbers, so that they parse like JSON rows.
+fn csv_to_values(contents: &str) -> SonataResult<Vec<Value>> {
+    let mut records = parse_csv(contents).into_iter();
+    let Some(header) = records.next() else {
+        return Err(invalid("the CSV file has no header".to_string()));
+    };
+    let header: Vec<String> = header.into_iter().map(|name| name.trim().to_string()).collect();
+    Ok(records
+        .filter(|record| record.iter().any(|field| !field.is_empty()))
+        .map(|record| {
+            let mut fields = Map::new();
+            for (name, field) in header.iter().zip(record) {
+                if field.is_empty() {
+                    continue;
+                }
+                let number = match name.as_str() {
+                    "speaker" => field.parse::<i64>().ok().map(Value::from),
+                    "noise_scale" | "length_scale" | "noise_w" => {
+                        field.parse::<f64>().ok().map(Value::from)
+                    }
+                    _ => None,
+                };
+                fields.insert(name.clone(), number.unwrap_or(Value::String(field)));
+            }
+            Value::Object(fields)
+        })
+        .collect())
+}
+
+/// Split CSV text into records. Fields may be quoted, with `""` for a quote inside a quoted field.
+fn parse_csv(contents: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+fn invalid(reason: String) -> SonataError {
+    SonataError::OperationError(format!("Invalid manifest: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_model::MockModel;
+    use sonata_core::{Audio, AudioInfo, Phonemes, SonataAudioResult};
+    use std::sync::{Arc, Mutex};
+
+    fn load(extension: &str, contents: &str) -> Vec<Result<ManifestRow, RowFailure>> {
+        let path = std::env::temp_dir().join(format!("sonata-test-manifest.{}", extension));
+        std::fs::write(&path, contents).unwrap();
+        let rows = load_manifest(&path);
+        std::fs::remove_file(&path).unwrap();
+        rows.unwrap()
+    }
+
+    fn row(id: &str, text: &str) -> ManifestRow {
+        ManifestRow {
+            id: id.to_string(),
+            text: text.to_string(),
+            speaker: None,
+            noise_scale: None,
+            length_scale: None,
+            noise_w: None,
+        }
+    }
+
+    /// A model whose synthesis config is a length scale
+    struct ScaledModel(Mutex<f32>);
+
+    impl SonataModel for ScaledModel {
+        fn audio_output_info(&self) -> SonataResult<AudioInfo> {
+            MockModel.audio_output_info()
+        }
+        fn phonemize_text(&self, text: &str) -> SonataResult<Phonemes> {
+            MockModel.phonemize_text(text)
+        }
+        fn speak_batch(&self, phoneme_batches: Vec<String>) -> SonataResult<Vec<Audio>> {
+            MockModel.speak_batch(phoneme_batches)
+        }
+        fn speak_one_sentence(&self, phonemes: String) -> SonataAudioResult {
+            MockModel.speak_one_sentence(phonemes)
+        }
+        fn get_default_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+            Ok(Box::new(1.0f32))
+        }
+        fn get_fallback_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+            Ok(Box::new(*self.0.lock().unwrap()))
+        }
+        fn set_fallback_synthesis_config(&self, synthesis_config: &dyn Any) -> SonataResult<()> {
+            *self.0.lock().unwrap() = *synthesis_config.downcast_ref::<f32>().unwrap();
+            Ok(())
+        }
+        fn apply_synthesis_overrides(&self, overrides: &SynthesisOverrides) -> SonataResult<()> {
+            if let Some(length_scale) = overrides.length_scale {
+                *self.0.lock().unwrap() = length_scale;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_config_guard_restores_the_config() {
+        let model = ScaledModel(Mutex::new(1.0));
+        let overrides = SynthesisOverrides {
+            length_scale: Some(2.0),
+            ..Default::default()
+        };
+        let guard = FallbackConfigGuard::new(&model).unwrap();
+        model.apply_synthesis_overrides(&overrides).unwrap();
+        guard.reset().unwrap();
+        assert_eq!(*model.0.lock().unwrap(), 1.0);
+        model.apply_synthesis_overrides(&overrides).unwrap();
+        // E.g. when synthesizing the manifest stops at an error
+        drop(guard);
+        assert_eq!(*model.0.lock().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_rows_over_the_budget_run_one_at_a_time() {
+        let dir = std::env::temp_dir().join("sonata-test-manifest-budget");
+        let manifest_path = std::env::temp_dir().join("sonata-test-manifest-budget.jsonl");
+        let rows = Vec::from_iter((0..6).map(|i| format!("{{\"id\": \"{}\", \"text\": \"Row number {}.\"}}", i, i)));
+        std::fs::write(&manifest_path, rows.join("\n")).unwrap();
+        let synth = SonataSpeechSynthesizer::builder(Arc::new(MockModel))
+            .with_text_normalizer(crate::TextNormalizer::passthrough())
+            .build()
+            .unwrap();
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let report = pool
+            .install(|| synth.synthesize_manifest_with_memory_budget(&manifest_path, &dir, None, Some(1)))
+            .unwrap();
+        assert_eq!(report.written.len(), 6);
+        assert!(report.failed.is_empty());
+        // The mock model can't estimate the rows, so each one takes the whole budget
+        let row_bytes = "row number 0".len() * crate::mock_model::SAMPLES_PER_PHONEME * BYTES_PER_BUFFERED_SAMPLE;
+        assert_eq!(report.peak_buffered_bytes, row_bytes);
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&manifest_path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_csv_manifest() {
+        let rows = load(
+            "csv",
+            "id,text,speaker,length_scale\r\na,\"Hello, \"\"world\"\"\",3,1.2\nb,Hi,alice,\n\nc,Bye,,fast\n",
+        );
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[0].as_ref().unwrap(),
+            &ManifestRow {
+                speaker: Some(SpeakerOverride::Id(3)),
+                length_scale: Some(1.2),
+                ..row("a", "Hello, \"world\"")
+            }
+        );
+        assert_eq!(
+            rows[1].as_ref().unwrap(),
+            &ManifestRow {
+                speaker: Some(SpeakerOverride::Name("alice".to_string())),
+                ..row("b", "Hi")
+            }
+        );
+        let (id, reason) = rows[2].as_ref().unwrap_err();
+        assert_eq!(id, "c");
+        assert!(reason.contains("invalid type"));
+    }
+
+    #[test]
+    fn test_parse_json_manifests() {
+        let rows = load("json", r#"[{"id": "a", "text": "Hi", "noise_w": 0.5}, {"text": "No id"}]"#);
+        assert_eq!(
+            rows[0].as_ref().unwrap(),
+            &ManifestRow {
+                noise_w: Some(0.5),
+                ..row("a", "Hi")
+            }
+        );
+        assert_eq!(rows[1].as_ref().unwrap_err().0, "row 2");
+        let rows = load("jsonl", "{\"id\": \"a\", \"text\": \"Hi\"}\n\nnot json\n");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].as_ref().unwrap(), &row("a", "Hi"));
+        assert_eq!(rows[1].as_ref().unwrap_err().0, "row 2");
+        let path = std::env::temp_dir().join("sonata-test-manifest.txt");
+        std::fs::write(&path, "").unwrap();
+        assert!(load_manifest(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
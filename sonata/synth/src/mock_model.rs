@@ -0,0 +1,207 @@
+use crate::{
+    Audio, AudioInfo, AudioStreamIterator, NoiseTensor, PhonemeDurations, PhonemeTiming, Phonemes, SonataAudioResult,
+    SonataError, SonataModel, SonataResult, SynthesisOverrides,
+};
+use std::any::Any;
+use std::sync::Mutex;
+
+/// Sample rate of the mock model's speech
+pub(crate) const SAMPLE_RATE: usize = 16000;
+/// Samples of speech per phoneme (character) of the mock model
+pub(crate) const SAMPLES_PER_PHONEME: usize = 160;
+
+/// A model without an inference session, for unit tests of the synthesizer.
+///
+/// Like the Piper phonemizer, it splits the text into segments at periods and commas only,
+/// and its "phonemes" are the lowercase characters of each segment. Every phoneme is spoken
+/// as [`SAMPLES_PER_PHONEME`] samples at half of full scale, and reported in the clip's
+/// phoneme timings.
+pub(crate) struct MockModel;
+
+impl SonataModel for MockModel {
+    fn audio_output_info(&self) -> SonataResult<AudioInfo> {
+        Ok(AudioInfo {
+            sample_rate: SAMPLE_RATE,
+            num_channels: 1,
+            sample_width: 2,
+        })
+    }
+    fn phonemize_text(&self, text: &str) -> SonataResult<Phonemes> {
+        Ok(Phonemes::new(Vec::from_iter(
+            text.split(['.', ','])
+                .map(str::trim)
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_lowercase),
+        )))
+    }
+    fn speak_batch(&self, phoneme_batches: Vec<String>) -> SonataResult<Vec<Audio>> {
+        phoneme_batches
+            .into_iter()
+            .map(|phonemes| self.speak_one_sentence(phonemes))
+            .collect()
+    }
+    fn speak_one_sentence(&self, phonemes: String) -> SonataAudioResult {
+        let samples = vec![0.5; phonemes.chars().count() * SAMPLES_PER_PHONEME];
+        let mut audio = Audio::new(samples.into(), SAMPLE_RATE, Some(1.0));
+        let phoneme_ms = (SAMPLES_PER_PHONEME * 1000 / SAMPLE_RATE) as f32;
+        audio.phoneme_timings = Some(Vec::from_iter(phonemes.chars().enumerate().map(
+            |(i, phoneme)| PhonemeTiming {
+                phoneme: phoneme.to_string(),
+                start_ms: i as f32 * phoneme_ms,
+                end_ms: (i + 1) as f32 * phoneme_ms,
+            },
+        )));
+        Ok(audio)
+    }
+    fn get_default_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
+    fn get_fallback_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
+    fn set_fallback_synthesis_config(&self, _synthesis_config: &dyn Any) -> SonataResult<()> {
+        Ok(())
+    }
+}
+
+/// A [`MockModel`] with a noise input of one value per phoneme, whose speech is the noise
+pub(crate) struct NoisyMockModel;
+
+impl SonataModel for NoisyMockModel {
+    fn audio_output_info(&self) -> SonataResult<AudioInfo> {
+        MockModel.audio_output_info()
+    }
+    fn phonemize_text(&self, text: &str) -> SonataResult<Phonemes> {
+        MockModel.phonemize_text(text)
+    }
+    fn speak_batch(&self, phoneme_batches: Vec<String>) -> SonataResult<Vec<Audio>> {
+        MockModel.speak_batch(phoneme_batches)
+    }
+    fn speak_one_sentence(&self, phonemes: String) -> SonataAudioResult {
+        MockModel.speak_one_sentence(phonemes)
+    }
+    fn noise_shapes(&self, phonemes: &str) -> SonataResult<Vec<(String, Vec<usize>)>> {
+        Ok(vec![("noise".to_string(), vec![1, phonemes.chars().count()])])
+    }
+    fn speak_with_noise(&self, _phonemes: String, noise: &[NoiseTensor]) -> SonataAudioResult {
+        Ok(Audio::new(noise[0].data.clone().into(), SAMPLE_RATE, Some(1.0)))
+    }
+    fn get_default_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
+    fn get_fallback_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
+    fn set_fallback_synthesis_config(&self, _synthesis_config: &dyn Any) -> SonataResult<()> {
+        Ok(())
+    }
+}
+
+/// A [`MockModel`] that streams its speech in chunks of phonemes. With `incremental`, it
+/// predicts a duration for every phoneme and streams a chunk per word from them, like a
+/// model whose encoder outputs phoneme durations.
+pub(crate) struct StreamingMockModel {
+    pub(crate) incremental: bool,
+}
+
+impl SonataModel for StreamingMockModel {
+    fn audio_output_info(&self) -> SonataResult<AudioInfo> {
+        MockModel.audio_output_info()
+    }
+    fn phonemize_text(&self, text: &str) -> SonataResult<Phonemes> {
+        MockModel.phonemize_text(text)
+    }
+    fn speak_batch(&self, phoneme_batches: Vec<String>) -> SonataResult<Vec<Audio>> {
+        MockModel.speak_batch(phoneme_batches)
+    }
+    fn speak_one_sentence(&self, phonemes: String) -> SonataAudioResult {
+        MockModel.speak_one_sentence(phonemes)
+    }
+    fn predict_phoneme_durations(&self, phonemes: String) -> SonataResult<PhonemeDurations> {
+        if !self.incremental {
+            return Err(SonataError::OperationError("No durations".to_string()));
+        }
+        let phoneme_ms = (SAMPLES_PER_PHONEME * 1000 / SAMPLE_RATE) as f32;
+        Ok(PhonemeDurations {
+            symbols: Vec::from_iter(phonemes.chars().map(String::from)),
+            durations_ms: vec![phoneme_ms; phonemes.chars().count()],
+            phonemes,
+        })
+    }
+    fn stream_synthesis<'a>(
+        &'a self,
+        phonemes: String,
+        chunk_size: usize,
+        _chunk_padding: usize,
+    ) -> SonataResult<AudioStreamIterator<'a>> {
+        let samples = self.speak_one_sentence(phonemes)?.samples.into_vec();
+        let chunks = Vec::from_iter(
+            samples
+                .chunks(chunk_size.max(1) * SAMPLES_PER_PHONEME)
+                .map(|chunk| Ok(chunk.to_vec().into())),
+        );
+        Ok(Box::new(chunks.into_iter()))
+    }
+    fn supports_incremental_synthesis(&self) -> bool {
+        self.incremental
+    }
+    fn stream_incremental_synthesis<'a>(
+        &'a self,
+        phonemes: String,
+        _chunk_padding: usize,
+    ) -> SonataResult<AudioStreamIterator<'a>> {
+        let durations = self.predict_phoneme_durations(phonemes.clone())?;
+        let mut durations_ms = durations.durations_ms.iter();
+        let chunks = Vec::from_iter(phonemes.split_inclusive(' ').map(|word| {
+            let word_ms: f32 = durations_ms.by_ref().take(word.chars().count()).sum();
+            let num_samples = (word_ms * SAMPLE_RATE as f32 / 1000.0).round() as usize;
+            Ok(vec![0.5; num_samples].into())
+        }));
+        Ok(Box::new(chunks.into_iter()))
+    }
+    fn get_default_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
+    fn get_fallback_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
+    fn set_fallback_synthesis_config(&self, _synthesis_config: &dyn Any) -> SonataResult<()> {
+        Ok(())
+    }
+}
+
+/// A [`MockModel`] that records the overrides applied to it
+#[derive(Default)]
+pub(crate) struct OverridableMockModel {
+    pub(crate) applied: Mutex<Vec<SynthesisOverrides>>,
+}
+
+impl SonataModel for OverridableMockModel {
+    fn audio_output_info(&self) -> SonataResult<AudioInfo> {
+        MockModel.audio_output_info()
+    }
+    fn phonemize_text(&self, text: &str) -> SonataResult<Phonemes> {
+        MockModel.phonemize_text(text)
+    }
+    fn speak_batch(&self, phoneme_batches: Vec<String>) -> SonataResult<Vec<Audio>> {
+        MockModel.speak_batch(phoneme_batches)
+    }
+    fn speak_one_sentence(&self, phonemes: String) -> SonataAudioResult {
+        MockModel.speak_one_sentence(phonemes)
+    }
+    fn apply_synthesis_overrides(&self, overrides: &SynthesisOverrides) -> SonataResult<()> {
+        if !overrides.is_empty() {
+            self.applied.lock().unwrap().push(overrides.clone());
+        }
+        Ok(())
+    }
+    fn get_default_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
+    fn get_fallback_synthesis_config(&self) -> SonataResult<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
+    fn set_fallback_synthesis_config(&self, _synthesis_config: &dyn Any) -> SonataResult<()> {
+        Ok(())
+    }
+}
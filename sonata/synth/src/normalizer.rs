@@ -0,0 +1,603 @@
+/// Built-in text normalizers applied to the input text before phonemization.
+///
+/// Use [`TextNormalizer::for_language`] to get the normalizer for a model's language.
+/// Languages without a built-in normalizer get a passthrough normalizer that leaves
+/// the text unchanged, apart from the number reading markup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextNormalizer {
+    language: String,
+    kind: NormalizerKind,
+    number_reading: NumberReading,
+}
+
+/// How numbers written in digits are read.
+///
+/// Parts of the text can be read differently from the normalizer's setting with inline
+/// markup: `digits{101}` is read digit by digit and `cardinal{101}` as a number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum NumberReading {
+    /// As a number, e.g. `101` as "one hundred one"
+    #[default]
+    Cardinal,
+    /// Digit by digit, e.g. `101` as "one oh one", for room and phone numbers, years and codes
+    Digits,
+}
+
+/// Inline markup that selects the number reading of the enclosed text
+const NUMBER_READING_MARKUP: [(&str, NumberReading); 2] = [
+    ("digits{", NumberReading::Digits),
+    ("cardinal{", NumberReading::Cardinal),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NormalizerKind {
+    English,
+    // Recognized languages whose rules are not implemented yet.
+    // espeak-ng already handles numbers and common abbreviations for these.
+    Armenian,
+    Russian,
+    Passthrough,
+}
+
+/// Writing systems the phonemizer can read for some of the built-in languages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Armenian,
+}
+
+impl NormalizerKind {
+    /// Scripts that can be spoken with this language, or `None` if unknown
+    fn scripts(self) -> Option<&'static [Script]> {
+        match self {
+            NormalizerKind::English => Some(&[Script::Latin]),
+            // Piper's Armenian voices switch to Russian for Cyrillic text,
+            // and both fall back to English for Latin text
+            NormalizerKind::Armenian => Some(&[Script::Armenian, Script::Cyrillic, Script::Latin]),
+            NormalizerKind::Russian => Some(&[Script::Cyrillic, Script::Latin]),
+            NormalizerKind::Passthrough => None,
+        }
+    }
+}
+
+impl TextNormalizer {
+    /// Get the built-in normalizer for the given language code (e.g. `en`, `en_US`, `en-gb`).
+    /// Falls back to a passthrough normalizer, with a logged warning, for unsupported languages.
+    pub fn for_language(code: &str) -> Self {
+        let primary = code
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        let kind = match primary.as_str() {
+            "en" => NormalizerKind::English,
+            "hy" => NormalizerKind::Armenian,
+            "ru" => NormalizerKind::Russian,
+            _ => {
+                log::warn!(
+                    "No built-in text normalizer for language `{}`. Text will be passed through unchanged",
+                    code
+                );
+                NormalizerKind::Passthrough
+            }
+        };
+        Self {
+            language: primary,
+            kind,
+            number_reading: NumberReading::default(),
+        }
+    }
+    /// A normalizer that leaves the text unchanged
+    pub fn passthrough() -> Self {
+        Self {
+            language: String::new(),
+            kind: NormalizerKind::Passthrough,
+            number_reading: NumberReading::default(),
+        }
+    }
+    /// Read numbers outside of number reading markup this way
+    pub fn with_number_reading(mut self, number_reading: NumberReading) -> Self {
+        self.number_reading = number_reading;
+        self
+    }
+    pub fn number_reading(&self) -> NumberReading {
+        self.number_reading
+    }
+    /// The primary language subtag this normalizer was selected for
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    pub fn is_passthrough(&self) -> bool {
+        self.kind == NormalizerKind::Passthrough
+    }
+    /// Normalize the text for the language.
+    ///
+    /// For languages with a built-in normalizer, words written in a script the language
+    /// can't be spoken in (e.g. CJK words given to an English voice) are skipped with a
+    /// logged warning. Their punctuation is kept, and `ipa{...}` sections are left as is.
+    ///
+    /// Numbers are read according to the number reading and the `digits{...}` and
+    /// `cardinal{...}` markup, which is removed. For languages without rules for numbers,
+    /// digits to be read one by one are separated with spaces, so that the phonemizer
+    /// doesn't read them as a number.
+    pub fn normalize(&self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        for (section, number_reading) in number_reading_sections(text, self.number_reading) {
+            output.push_str(&self.normalize_section(section, number_reading));
+        }
+        output
+    }
+    fn normalize_section(&self, text: &str, number_reading: NumberReading) -> String {
+        let skipped;
+        let text = match self.kind.scripts() {
+            Some(scripts) => {
+                skipped = scripts::skip_unsupported_words(text, scripts);
+                &skipped
+            }
+            None => text,
+        };
+        match (self.kind, number_reading) {
+            (NormalizerKind::English, _) => english::normalize(text, number_reading),
+            (_, NumberReading::Digits) => separate_digits(text),
+            (_, NumberReading::Cardinal) => text.to_string(),
+        }
+    }
+}
+
+/// Split the text at number reading markup, pairing each part with the way numbers are read in it
+fn number_reading_sections(text: &str, default: NumberReading) -> Vec<(&str, NumberReading)> {
+    let mut sections = Vec::new();
+    let mut rest = text;
+    loop {
+        let markup = NUMBER_READING_MARKUP
+            .iter()
+            .filter_map(|(tag, reading)| rest.find(tag).map(|start| (start, tag.len(), *reading)))
+            .min_by_key(|(start, _, _)| *start);
+        let Some((start, tag_len, reading)) = markup else {
+            break;
+        };
+        let inner_start = start + tag_len;
+        let inner_end = rest[inner_start..]
+            .find('}')
+            .map_or(rest.len(), |end| inner_start + end);
+        sections.push((&rest[..start], default));
+        sections.push((&rest[inner_start..inner_end], reading));
+        rest = &rest[(inner_end + 1).min(rest.len())..];
+    }
+    sections.push((rest, default));
+    sections.retain(|(section, _)| !section.is_empty());
+    sections
+}
+
+/// Put a space between consecutive digits, e.g. `Room 101` becomes `Room 1 0 1`
+fn separate_digits(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() * 2);
+    let mut previous = None;
+    for c in text.chars() {
+        if c.is_ascii_digit() && previous.is_some_and(|p: char| p.is_ascii_digit()) {
+            output.push(' ');
+        }
+        output.push(c);
+        previous = Some(c);
+    }
+    output
+}
+
+impl Default for TextNormalizer {
+    fn default() -> Self {
+        Self::passthrough()
+    }
+}
+
+mod scripts {
+    use super::Script;
+    use unicode_normalization::char::is_combining_mark;
+
+    fn script_of(c: char) -> Option<Script> {
+        match c as u32 {
+            0x41..=0x5A
+            | 0x61..=0x7A
+            | 0xAA
+            | 0xBA
+            | 0xC0..=0x2AF
+            | 0x1D00..=0x1DBF
+            | 0x1E00..=0x1EFF
+            | 0x2C60..=0x2C7F
+            | 0xA720..=0xA7FF
+            | 0xAB30..=0xAB6F
+            | 0xFB00..=0xFB06 => Some(Script::Latin),
+            0x400..=0x52F | 0x1C80..=0x1C8F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => {
+                Some(Script::Cyrillic)
+            }
+            0x531..=0x58F | 0xFB13..=0xFB17 => Some(Script::Armenian),
+            _ => None,
+        }
+    }
+
+    fn is_supported(word: &str, scripts: &[Script]) -> bool {
+        word.chars()
+            .all(|c| !c.is_alphabetic() || script_of(c).is_some_and(|s| scripts.contains(&s)))
+    }
+
+    pub(super) fn skip_unsupported_words(text: &str, scripts: &[Script]) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut skipped = Vec::new();
+        let mut rest = text;
+        loop {
+            let (plain, ipa) = match rest.find("ipa{") {
+                Some(start) => {
+                    let end = rest[start..].find('}').map_or(rest.len(), |i| start + i + 1);
+                    (&rest[..start], &rest[start..end])
+                }
+                None => (rest, ""),
+            };
+            for token in plain.split_inclusive(char::is_whitespace) {
+                let word = token.trim_end_matches(char::is_whitespace);
+                if is_supported(word, scripts) {
+                    output.push_str(token);
+                    continue;
+                }
+                skipped.push(word);
+                let kept: String = word
+                    .chars()
+                    .filter(|c| !c.is_alphabetic() && !is_combining_mark(*c))
+                    .collect();
+                let whitespace = &token[word.len()..];
+                if !kept.is_empty() || whitespace.contains('\n') {
+                    output.push_str(&kept);
+                    output.push_str(whitespace);
+                }
+            }
+            output.push_str(ipa);
+            rest = &rest[plain.len() + ipa.len()..];
+            if rest.is_empty() {
+                break;
+            }
+        }
+        if !skipped.is_empty() {
+            log::warn!(
+                "Skipping {} word(s) written in a script the voice can't speak: {:?}",
+                skipped.len(),
+                skipped
+            );
+        }
+        output
+    }
+}
+
+mod english {
+    use super::NumberReading;
+
+    const ABBREVIATIONS: &[(&str, &str)] = &[
+        ("Mr.", "Mister"),
+        ("Mrs.", "Missus"),
+        ("Ms.", "Miss"),
+        ("Dr.", "Doctor"),
+        ("Prof.", "Professor"),
+        ("Jr.", "Junior"),
+        ("Sr.", "Senior"),
+        ("vs.", "versus"),
+        ("e.g.", "for example"),
+        ("i.e.", "that is"),
+        ("etc.", "et cetera"),
+    ];
+    /// Abbreviations that can end a sentence, unlike titles that come before a name
+    const SENTENCE_FINAL_ABBREVIATIONS: &[&str] = &["etc."];
+    const SYMBOLS: &[(char, &str)] = &[
+        ('&', "and"),
+        ('%', "percent"),
+        ('+', "plus"),
+        ('@', "at"),
+    ];
+    const ONES: [&str; 20] = [
+        "zero",
+        "one",
+        "two",
+        "three",
+        "four",
+        "five",
+        "six",
+        "seven",
+        "eight",
+        "nine",
+        "ten",
+        "eleven",
+        "twelve",
+        "thirteen",
+        "fourteen",
+        "fifteen",
+        "sixteen",
+        "seventeen",
+        "eighteen",
+        "nineteen",
+    ];
+    const TENS: [&str; 10] = [
+        "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+    ];
+    const SCALES: [&str; 7] = [
+        "",
+        "thousand",
+        "million",
+        "billion",
+        "trillion",
+        "quadrillion",
+        "quintillion",
+    ];
+
+    /// Zero when reading digit by digit, e.g. "four oh four"
+    const ZERO_DIGIT: &str = "oh";
+
+    pub(super) fn normalize(text: &str, number_reading: NumberReading) -> String {
+        let text = expand_abbreviations(text);
+        let text = expand_symbols(&text);
+        expand_numbers(&text, number_reading)
+    }
+
+    fn expand_abbreviations(text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let pieces = Vec::from_iter(text.split_inclusive(char::is_whitespace));
+        for (i, piece) in pieces.iter().enumerate() {
+            let token = piece.trim_end_matches(char::is_whitespace);
+            let separator = &piece[token.len()..];
+            let Some((abbr, expansion)) = ABBREVIATIONS.iter().find(|(abbr, _)| *abbr == token) else {
+                output.push_str(piece);
+                continue;
+            };
+            output.push_str(expansion);
+            // The period of an abbreviation that ends the sentence also ends the sentence
+            let next_token = pieces[i + 1..].iter().map(|piece| piece.trim()).find(|token| !token.is_empty());
+            let ends_sentence = separator.contains('\n')
+                || next_token.is_none_or(|token| token.starts_with(char::is_uppercase));
+            if SENTENCE_FINAL_ABBREVIATIONS.contains(abbr) && ends_sentence {
+                output.push('.');
+            }
+            output.push_str(separator);
+        }
+        output
+    }
+
+    fn expand_symbols(text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut pending_space = false;
+        for c in text.chars() {
+            if let Some((_, word)) = SYMBOLS.iter().find(|(symbol, _)| *symbol == c) {
+                if !output.is_empty() && !output.ends_with(' ') {
+                    output.push(' ');
+                }
+                output.push_str(word);
+                pending_space = true;
+                continue;
+            }
+            if pending_space && !c.is_whitespace() && !c.is_ascii_punctuation() {
+                output.push(' ');
+            }
+            pending_space = false;
+            output.push(c);
+        }
+        output
+    }
+
+    fn expand_numbers(text: &str, number_reading: NumberReading) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut output = String::with_capacity(text.len());
+        let mut i = 0;
+        while i < chars.len() {
+            if !chars[i].is_ascii_digit() {
+                output.push(chars[i]);
+                i += 1;
+                continue;
+            }
+            let mut integer = String::new();
+            while i < chars.len() {
+                if chars[i].is_ascii_digit() {
+                    integer.push(chars[i]);
+                    i += 1;
+                } else if chars[i] == ','
+                    && chars.get(i + 1..i + 4).is_some_and(|group| {
+                        group.iter().all(|c| c.is_ascii_digit())
+                            && !chars.get(i + 4).is_some_and(|c| c.is_ascii_digit())
+                    })
+                {
+                    // Thousands separator
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            let mut fraction = String::new();
+            if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) {
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    fraction.push(chars[i]);
+                    i += 1;
+                }
+            }
+            if output.ends_with('-')
+                && output[..output.len() - 1]
+                    .chars()
+                    .last()
+                    .is_none_or(char::is_whitespace)
+            {
+                output.pop();
+                output.push_str("minus ");
+            }
+            match number_reading {
+                NumberReading::Cardinal => output.push_str(&integer_to_words(&integer)),
+                NumberReading::Digits => output.push_str(&digits_to_words(&integer)),
+            }
+            if !fraction.is_empty() {
+                output.push_str(" point");
+                for digit in fraction.chars() {
+                    output.push(' ');
+                    output.push_str(ONES[digit.to_digit(10).unwrap() as usize]);
+                }
+            }
+        }
+        output
+    }
+
+    fn integer_to_words(digits: &str) -> String {
+        match digits.parse::<u64>() {
+            Ok(number) => cardinal(number),
+            // Too large to read as a number, read digit by digit
+            Err(_) => digits
+                .chars()
+                .map(|d| ONES[d.to_digit(10).unwrap() as usize])
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    fn digits_to_words(digits: &str) -> String {
+        digits
+            .chars()
+            .map(|d| match d.to_digit(10).unwrap() {
+                0 => ZERO_DIGIT,
+                d => ONES[d as usize],
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub(super) fn cardinal(number: u64) -> String {
+        if number == 0 {
+            return ONES[0].to_string();
+        }
+        let mut groups = Vec::new();
+        let mut remaining = number;
+        let mut scale = 0;
+        while remaining > 0 {
+            let group = (remaining % 1000) as usize;
+            if group > 0 {
+                let mut words = below_thousand(group);
+                if !SCALES[scale].is_empty() {
+                    words.push(' ');
+                    words.push_str(SCALES[scale]);
+                }
+                groups.push(words);
+            }
+            remaining /= 1000;
+            scale += 1;
+        }
+        groups.reverse();
+        groups.join(" ")
+    }
+
+    fn below_thousand(number: usize) -> String {
+        let mut words = Vec::new();
+        let hundreds = number / 100;
+        let rest = number % 100;
+        if hundreds > 0 {
+            words.push(format!("{} hundred", ONES[hundreds]));
+        }
+        if rest >= 20 {
+            let tens = TENS[rest / 10];
+            match rest % 10 {
+                0 => words.push(tens.to_string()),
+                ones => words.push(format!("{}-{}", tens, ONES[ones])),
+            }
+        } else if rest > 0 {
+            words.push(ONES[rest].to_string());
+        }
+        words.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_language() {
+        assert_eq!(TextNormalizer::for_language("en_US").language(), "en");
+        assert_eq!(TextNormalizer::for_language("EN-gb").language(), "en");
+        assert!(!TextNormalizer::for_language("hy").is_passthrough());
+        assert!(TextNormalizer::for_language("xx").is_passthrough());
+    }
+
+    #[test]
+    fn test_unsupported_language_is_passthrough() {
+        let text = "Dr. Smith paid 100% & 25 dollars";
+        assert_eq!(TextNormalizer::for_language("xx").normalize(text), text);
+        assert_eq!(TextNormalizer::for_language("ru").normalize(text), text);
+    }
+
+    #[test]
+    fn test_english_numbers() {
+        assert_eq!(english::cardinal(0), "zero");
+        assert_eq!(english::cardinal(42), "forty-two");
+        assert_eq!(english::cardinal(1_000_001), "one million one");
+        assert_eq!(english::cardinal(2024), "two thousand twenty-four");
+        let normalizer = TextNormalizer::for_language("en");
+        assert_eq!(
+            normalizer.normalize("It costs 1,250.5 or -3"),
+            "It costs one thousand two hundred fifty point five or minus three"
+        );
+    }
+
+    #[test]
+    fn test_number_reading() {
+        let normalizer = TextNormalizer::for_language("en");
+        assert_eq!(normalizer.normalize("Room 101"), "Room one hundred one");
+        assert_eq!(
+            normalizer.normalize("Room digits{101}, floor 3"),
+            "Room one oh one, floor three"
+        );
+        let digits = normalizer.with_number_reading(NumberReading::Digits);
+        assert_eq!(
+            digits.normalize("Call 555-0199 in cardinal{2024}"),
+            "Call five five five-oh one nine nine in two thousand twenty-four"
+        );
+        assert_eq!(digits.normalize("Pi is 3.14"), "Pi is three point one four");
+        let russian = TextNormalizer::for_language("ru");
+        assert_eq!(russian.normalize("Дом digits{101} и 25"), "Дом 1 0 1 и 25");
+        assert_eq!(TextNormalizer::passthrough().normalize("digits{42"), "4 2");
+    }
+
+    #[test]
+    fn test_english_abbreviations_and_symbols() {
+        let normalizer = TextNormalizer::for_language("en");
+        assert_eq!(
+            normalizer.normalize("Dr. Smith & Mr. Jones"),
+            "Doctor Smith and Mister Jones"
+        );
+        assert_eq!(normalizer.normalize("50%."), "fifty percent.");
+        assert_eq!(normalizer.normalize("A+B"), "A plus B");
+        // The expansion adds no period that would end a sentence
+        assert_eq!(
+            normalizer.normalize("Pens, ink, etc. are on sale"),
+            "Pens, ink, et cetera are on sale"
+        );
+        assert_eq!(
+            normalizer.normalize("Pens, ink, etc. Next sentence"),
+            "Pens, ink, et cetera. Next sentence"
+        );
+        assert_eq!(normalizer.normalize("Pens,\tink, etc."), "Pens,\tink, et cetera.");
+        assert_eq!(normalizer.normalize("Ask\nDr. Smith"), "Ask\nDoctor Smith");
+    }
+
+    #[test]
+    fn test_unsupported_scripts_are_skipped() {
+        let normalizer = TextNormalizer::for_language("en");
+        assert_eq!(
+            normalizer.normalize("Hello 你好世界 world. 東京。\nBye"),
+            "Hello world. 。\nBye"
+        );
+        assert_eq!(
+            normalizer.normalize("Say ipa{θɪŋk} 思考 now"),
+            "Say ipa{θɪŋk} now"
+        );
+        let armenian = TextNormalizer::for_language("hy");
+        assert_eq!(armenian.normalize("Բարեւ, привет, hi 你好"), "Բարեւ, привет, hi ");
+        assert_eq!(TextNormalizer::passthrough().normalize("你好"), "你好");
+    }
+
+    #[test]
+    fn test_combining_diacritics() {
+        let normalizer = TextNormalizer::for_language("en");
+        let decomposed = "Cafe\u{301} nai\u{308}ve costs 5";
+        assert_eq!(normalizer.normalize(decomposed), "Cafe\u{301} nai\u{308}ve costs five");
+    }
+}
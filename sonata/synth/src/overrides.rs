@@ -0,0 +1,145 @@
+use serde::Deserialize;
+use sonata_core::{SonataError, SonataModel, SonataResult, SynthesisOverrides};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Environment variable holding the path of the synthesis overrides file
+pub const OVERRIDES_ENV_VAR: &str = "SONATA_SYNTHESIS_OVERRIDES";
+
+/// The contents of an overrides file, e.g. `{"speaker": "alice", "length_scale": 1.1}`
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OverridesFile {
+    speaker: Option<SpeakerOverride>,
+    noise_scale: Option<f32>,
+    length_scale: Option<f32>,
+    noise_w: Option<f32>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum SpeakerOverride {
+    Id(i64),
+    Name(String),
+}
+
+fn parse_overrides_file(contents: &str) -> SonataResult<OverridesFile> {
+    match serde_json::from_str(contents) {
+        Ok(overrides) => Ok(overrides),
+        Err(e) => Err(SonataError::OperationError(format!(
+            "Invalid synthesis overrides file: {}",
+            e
+        ))),
+    }
+}
+
+/// Read the overrides file at `path`, resolving speaker names using the model
+pub(crate) fn load_overrides(
+    path: &Path,
+    model: &dyn SonataModel,
+) -> SonataResult<SynthesisOverrides> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return Err(SonataError::FailedToLoadResource(format!(
+                "Failed to read synthesis overrides from `{}`: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    let file = parse_overrides_file(&contents)?;
+    let speaker = match file.speaker {
+        Some(speaker) => Some(resolve_speaker(speaker, model, "Invalid synthesis overrides")?),
+        None => None,
+    };
+    Ok(SynthesisOverrides {
+        speaker,
+        noise_scale: file.noise_scale,
+        length_scale: file.length_scale,
+        noise_w: file.noise_w,
+    })
+}
+
+/// The id of the speaker, looking up names using the model.
+/// An unknown name is reported as an error starting with `context`.
+pub(crate) fn resolve_speaker(
+    speaker: SpeakerOverride,
+    model: &dyn SonataModel,
+    context: &str,
+) -> SonataResult<i64> {
+    match speaker {
+        SpeakerOverride::Id(sid) => Ok(sid),
+        SpeakerOverride::Name(name) => match model.speaker_name_to_id(&name)? {
+            Some(sid) => Ok(sid),
+            None => Err(SonataError::OperationError(format!(
+                "{}: no speaker was found with the name `{}`",
+                context, name
+            ))),
+        },
+    }
+}
+
+/// Parse a speaker names file, e.g. `{"0": "alice", "3": "bob"}`
+fn parse_speaker_names(contents: &str) -> SonataResult<HashMap<i64, String>> {
+    let invalid = |reason: String| {
+        SonataError::OperationError(format!("Invalid speaker names file: {}", reason))
+    };
+    let names: HashMap<String, String> =
+        serde_json::from_str(contents).map_err(|e| invalid(e.to_string()))?;
+    names
+        .into_iter()
+        .map(|(sid, name)| match sid.trim().parse() {
+            Ok(sid) => Ok((sid, name)),
+            Err(_) => Err(invalid(format!("`{}` is not a speaker id", sid))),
+        })
+        .collect()
+}
+
+/// Read the speaker names file at `path`, mapping speaker ids to names
+pub(crate) fn load_speaker_names(path: &Path) -> SonataResult<HashMap<i64, String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => parse_speaker_names(&contents),
+        Err(e) => Err(SonataError::FailedToLoadResource(format!(
+            "Failed to read speaker names from `{}`: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_overrides_file() {
+        let file = parse_overrides_file(r#"{"speaker": "alice", "length_scale": 1.5}"#).unwrap();
+        assert_eq!(
+            file,
+            OverridesFile {
+                speaker: Some(SpeakerOverride::Name("alice".to_string())),
+                length_scale: Some(1.5),
+                ..Default::default()
+            }
+        );
+        let file = parse_overrides_file(r#"{"speaker": 3}"#).unwrap();
+        assert_eq!(file.speaker, Some(SpeakerOverride::Id(3)));
+    }
+
+    #[test]
+    fn test_parse_overrides_file_rejects_unknown_keys() {
+        assert!(parse_overrides_file(r#"{"lenght_scale": 1.5}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_speaker_names() {
+        let names = parse_speaker_names(r#"{"0": "alice", "12": "bob"}"#).unwrap();
+        assert_eq!(
+            names,
+            HashMap::from([(0, "alice".to_string()), (12, "bob".to_string())])
+        );
+        assert!(parse_speaker_names(r#"{"first": "alice"}"#).is_err());
+        assert!(parse_speaker_names(r#"["alice"]"#).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::sentences::{ends_with_abbreviation, CLOSING_PUNCTUATION};
+use std::collections::HashMap;
+
+/// Silence (in milliseconds) inserted after a piece of text, based on the punctuation ending it.
+///
+/// The text is split after each punctuation mark in the map that is followed by whitespace,
+/// and each piece is synthesized separately. The default pauses are longer after the end of
+/// a sentence than after a comma.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PunctuationPauses {
+    pauses_ms: HashMap<char, u32>,
+}
+
+impl Default for PunctuationPauses {
+    fn default() -> Self {
+        Self::new(HashMap::from([
+            ('.', 400),
+            ('!', 400),
+            ('?', 400),
+            ('…', 500),
+            (';', 250),
+            (':', 250),
+            (',', 150),
+        ]))
+    }
+}
+
+impl PunctuationPauses {
+    pub fn new(pauses_ms: HashMap<char, u32>) -> Self {
+        Self { pauses_ms }
+    }
+    /// The pause after `punctuation`, if it is in the map
+    pub fn pause_ms(&self, punctuation: char) -> Option<u32> {
+        self.pauses_ms.get(&punctuation).copied()
+    }
+    pub fn pauses_ms(&self) -> &HashMap<char, u32> {
+        &self.pauses_ms
+    }
+    /// Split `text` into pieces, each with the pause that follows it.
+    /// Consecutive marks (e.g. `?!`) end a single piece with the longest of their pauses.
+    pub(crate) fn split(&self, text: &str) -> Vec<(String, Option<u32>)> {
+        let mut pieces = Vec::new();
+        let mut current = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            current.push(c);
+            let Some(mut pause_ms) = self.pause_ms(c) else {
+                continue;
+            };
+            while let Some(&next) = chars.peek() {
+                if let Some(next_pause_ms) = self.pause_ms(next) {
+                    pause_ms = pause_ms.max(next_pause_ms);
+                } else if !CLOSING_PUNCTUATION.contains(&next) {
+                    break;
+                }
+                current.push(next);
+                chars.next();
+            }
+            let at_boundary = chars.peek().is_none_or(|next| next.is_whitespace());
+            if at_boundary && !(c == '.' && ends_with_abbreviation(&current)) {
+                push_piece(&mut pieces, &mut current, Some(pause_ms));
+            }
+        }
+        push_piece(&mut pieces, &mut current, None);
+        pieces
+    }
+}
+
+fn push_piece(pieces: &mut Vec<(String, Option<u32>)>, current: &mut String, pause_ms: Option<u32>) {
+    let piece = current.trim();
+    if !piece.is_empty() {
+        pieces.push((piece.to_string(), pause_ms));
+    }
+    current.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_period_pause_is_longer_than_comma_pause() {
+        let pauses = PunctuationPauses::default();
+        assert!(pauses.pause_ms('.').unwrap() > pauses.pause_ms(',').unwrap());
+    }
+
+    #[test]
+    fn test_split_at_punctuation() {
+        let pauses = PunctuationPauses::default();
+        assert_eq!(
+            pauses.split("Well, Dr. Smith paid 3.5 dollars. Really?! Yes"),
+            vec![
+                ("Well,".to_string(), Some(150)),
+                ("Dr. Smith paid 3.5 dollars.".to_string(), Some(400)),
+                ("Really?!".to_string(), Some(400)),
+                ("Yes".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_pauses() {
+        let pauses = PunctuationPauses::new(HashMap::from([(',', 50)]));
+        assert_eq!(
+            pauses.split("One, two. Three"),
+            vec![
+                ("One,".to_string(), Some(50)),
+                ("two. Three".to_string(), None),
+            ]
+        );
+    }
+}
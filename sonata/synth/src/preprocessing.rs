@@ -0,0 +1,409 @@
+use crate::sentences::{split_sentences, CLOSING_PUNCTUATION, SENTENCE_TERMINATORS};
+use std::borrow::Cow;
+use unicode_normalization::UnicodeNormalization as _;
+
+/// How much markdown syntax to remove from the input text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MarkdownStripping {
+    /// Leave the text unchanged
+    #[default]
+    Off,
+    /// Remove emphasis, inline code, links, images, headings, quotes and list markers
+    Basic,
+    /// Also remove code blocks, HTML tags, bare URLs, footnote references and table syntax
+    Aggressive,
+}
+
+/// Unicode normalization form to convert the input text to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnicodeNormalization {
+    #[default]
+    Off,
+    /// Canonical composition, e.g. `e` followed by a combining acute accent becomes `é`
+    Nfc,
+    /// Compatibility composition. Also folds ligatures, full-width forms and the like
+    /// into their plain equivalents
+    Nfkc,
+}
+
+/// How to clean up the end of each sentence, which sets the intonation the model ends it with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingPunctuation {
+    #[default]
+    Off,
+    /// Remove the characters after the last word or punctuation mark that aren't spoken,
+    /// such as emoji, quotes, brackets and dangling commas or dashes
+    Strip,
+    /// Also end the sentences that have no final punctuation mark with a period, so that
+    /// they get the falling intonation of a statement. `?` and `!` are kept as they are.
+    Ensure,
+}
+
+/// Cleanup applied to the input text before normalization. Everything is off by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextPreprocessing {
+    /// Applied before all other steps
+    pub unicode_normalization: UnicodeNormalization,
+    pub strip_markdown: MarkdownStripping,
+    /// Merge runs of spaces and tabs into a single space, and drop blank lines
+    pub collapse_whitespace: bool,
+    /// Applied after all other steps. Sentences are rejoined with a single space, so the
+    /// lines of the text are kept but not the spacing between sentences.
+    pub trailing_punctuation: TrailingPunctuation,
+}
+
+impl TextPreprocessing {
+    pub fn is_enabled(&self) -> bool {
+        self.unicode_normalization != UnicodeNormalization::Off
+            || self.strip_markdown != MarkdownStripping::Off
+            || self.collapse_whitespace
+            || self.trailing_punctuation != TrailingPunctuation::Off
+    }
+    pub fn apply(&self, text: &str) -> String {
+        let text = match self.unicode_normalization {
+            UnicodeNormalization::Off => Cow::Borrowed(text),
+            UnicodeNormalization::Nfc => Cow::Owned(text.nfc().collect()),
+            UnicodeNormalization::Nfkc => Cow::Owned(text.nfkc().collect()),
+        };
+        let text = match self.strip_markdown {
+            MarkdownStripping::Off => text.into_owned(),
+            level => markdown::strip(&text, level == MarkdownStripping::Aggressive),
+        };
+        let text = if self.collapse_whitespace {
+            collapse_whitespace(&text)
+        } else {
+            text
+        };
+        match self.trailing_punctuation {
+            TrailingPunctuation::Off => text,
+            mode => clean_sentence_endings(&text, mode == TrailingPunctuation::Ensure),
+        }
+    }
+}
+
+fn clean_sentence_endings(text: &str, ensure_punctuation: bool) -> String {
+    let lines = text.lines().map(|line| {
+        split_sentences(line)
+            .iter()
+            .filter_map(|sentence| clean_sentence_ending(sentence, ensure_punctuation))
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+    lines.collect::<Vec<_>>().join("\n")
+}
+
+/// `None` if nothing but stray characters is left of the sentence
+fn clean_sentence_ending(sentence: &str, ensure_punctuation: bool) -> Option<String> {
+    let mut sentence = trim_stray_ending(sentence).to_string();
+    if sentence.is_empty() {
+        return None;
+    }
+    if ensure_punctuation && !sentence.trim_end_matches(CLOSING_PUNCTUATION).ends_with(SENTENCE_TERMINATORS) {
+        sentence.push('.');
+    }
+    Some(sentence)
+}
+
+/// Remove the stray characters at the end of the sentence, except for closing quotes and
+/// brackets after its terminator, as in `"Stop!"` or `(Really?)`
+fn trim_stray_ending(sentence: &str) -> &str {
+    let mut end = sentence.len();
+    for (i, c) in sentence.char_indices().rev() {
+        if !is_stray_trailing_char(c) {
+            break;
+        }
+        let closes_sentence = CLOSING_PUNCTUATION.contains(&c)
+            && sentence[..i].trim_end_matches(CLOSING_PUNCTUATION).ends_with(SENTENCE_TERMINATORS);
+        if closes_sentence {
+            break;
+        }
+        end = i;
+    }
+    &sentence[..end]
+}
+
+/// Characters that aren't spoken at the end of a sentence. Symbols that are read out,
+/// such as `%` or `$`, aren't stray.
+fn is_stray_trailing_char(c: char) -> bool {
+    matches!(
+        c,
+        '"' | '\'' | '`' | '«' | '»' | '“' | '”' | '‘' | '’' | '„'
+            | '(' | ')' | '[' | ']' | '{' | '}' | '<' | '>'
+            | ',' | ':' | ';' | '-' | '–' | '—' | '*' | '_' | '~' | '|'
+            // Zero width joiner and variation selectors, which are part of emoji
+            | '\u{200D}' | '\u{FE00}'..='\u{FE0F}'
+            // Dingbats, pictographs and other emoji
+            | '\u{2190}'..='\u{21FF}' | '\u{2300}'..='\u{27BF}' | '\u{2B00}'..='\u{2BFF}'
+            | '\u{1F000}'..='\u{1FAFF}'
+    ) || c.is_whitespace()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let lines = text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty());
+    lines.collect::<Vec<_>>().join("\n")
+}
+
+mod markdown {
+    const EMPHASIS_MARKERS: &[&str] = &["**", "__", "~~", "*", "_", "`"];
+
+    pub(super) fn strip(text: &str, aggressive: bool) -> String {
+        let mut lines = Vec::new();
+        let mut in_code_block = false;
+        for line in text.lines() {
+            let trimmed = line.trim_start();
+            if aggressive {
+                if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                    in_code_block = !in_code_block;
+                    continue;
+                }
+                if in_code_block || is_horizontal_rule(trimmed) || is_table_separator(trimmed) {
+                    continue;
+                }
+            }
+            let mut line = strip_block_markers(trimmed).to_string();
+            if aggressive {
+                line = strip_html_tags(&line);
+                line = strip_footnote_references(&line);
+                line = strip_urls(&line);
+                line = line.trim_matches('|').replace('|', ",");
+            }
+            line = strip_links(&line);
+            for marker in EMPHASIS_MARKERS {
+                line = strip_delimited(&line, marker);
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    /// Remove heading, block quote and list item markers at the start of a line
+    fn strip_block_markers(mut line: &str) -> &str {
+        loop {
+            let stripped = line.trim_start();
+            let hashes = stripped.len() - stripped.trim_start_matches('#').len();
+            let next = if (1..=6).contains(&hashes) && stripped[hashes..].starts_with(' ') {
+                &stripped[hashes..]
+            } else if let Some(rest) = stripped.strip_prefix('>') {
+                rest
+            } else if let Some(rest) = ["- ", "* ", "+ "]
+                .iter()
+                .find_map(|marker| stripped.strip_prefix(marker))
+            {
+                rest
+            } else {
+                let digits = stripped.len() - stripped.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                match stripped[digits..].strip_prefix(". ") {
+                    Some(rest) if digits > 0 => rest,
+                    _ => return stripped,
+                }
+            };
+            line = next;
+        }
+    }
+
+    /// Replace `[text](url)` with `text` and `![alt](url)` with `alt`
+    fn strip_links(line: &str) -> String {
+        let mut output = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(open) = rest.find('[') {
+            let Some(close) = rest[open..].find("](").map(|i| open + i) else {
+                break;
+            };
+            let Some(end) = rest[close..].find(')').map(|i| close + i) else {
+                break;
+            };
+            let prefix = &rest[..open];
+            output.push_str(prefix.strip_suffix('!').unwrap_or(prefix));
+            output.push_str(&rest[open + 1..close]);
+            rest = &rest[end + 1..];
+        }
+        output.push_str(rest);
+        output
+    }
+
+    /// Remove pairs of `marker` that wrap text, e.g. `**bold**`, leaving single markers
+    /// and markers inside words (such as `snake_case`) untouched.
+    fn strip_delimited(line: &str, marker: &str) -> String {
+        let is_boundary = |c: Option<char>| c.is_none_or(|c| !c.is_alphanumeric());
+        let mut output = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(open) = rest.find(marker) {
+            let inner_start = open + marker.len();
+            let opens = is_boundary(rest[..open].chars().last())
+                && rest[inner_start..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| !c.is_whitespace());
+            let close = rest[inner_start..]
+                .find(marker)
+                .map(|i| inner_start + i)
+                .filter(|&close| {
+                    close > inner_start
+                        && !rest[..close].ends_with(char::is_whitespace)
+                        && is_boundary(rest[close + marker.len()..].chars().next())
+                });
+            match close {
+                Some(close) if opens => {
+                    output.push_str(&rest[..open]);
+                    output.push_str(&rest[inner_start..close]);
+                    rest = &rest[close + marker.len()..];
+                }
+                _ => {
+                    output.push_str(&rest[..inner_start]);
+                    rest = &rest[inner_start..];
+                }
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+
+    fn strip_html_tags(line: &str) -> String {
+        let mut output = String::with_capacity(line.len());
+        let mut in_tag = false;
+        for c in line.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' if in_tag => in_tag = false,
+                _ if !in_tag => output.push(c),
+                _ => {}
+            }
+        }
+        output
+    }
+
+    fn strip_footnote_references(line: &str) -> String {
+        let mut output = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(open) = rest.find("[^") {
+            let Some(close) = rest[open..].find(']').map(|i| open + i) else {
+                break;
+            };
+            output.push_str(&rest[..open]);
+            rest = &rest[close + 1..];
+        }
+        output.push_str(rest);
+        output
+    }
+
+    fn strip_urls(line: &str) -> String {
+        line.split(' ')
+            .filter(|word| {
+                !(word.starts_with("http://")
+                    || word.starts_with("https://")
+                    || word.starts_with("www."))
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn is_horizontal_rule(line: &str) -> bool {
+        let line = line.trim_end();
+        line.len() >= 3
+            && ['-', '*', '_']
+                .iter()
+                .any(|c| line.chars().all(|x| x == *c || x == ' '))
+    }
+
+    fn is_table_separator(line: &str) -> bool {
+        line.contains('-')
+            && line
+                .trim_end()
+                .chars()
+                .all(|c| matches!(c, '|' | '-' | ':' | ' '))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic() -> TextPreprocessing {
+        TextPreprocessing {
+            strip_markdown: MarkdownStripping::Basic,
+            collapse_whitespace: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let text = "**Hello**   [text](url)";
+        assert!(!TextPreprocessing::default().is_enabled());
+        assert_eq!(TextPreprocessing::default().apply(text), text);
+    }
+
+    #[test]
+    fn test_strip_basic_markdown() {
+        let preprocessing = basic();
+        assert_eq!(preprocessing.apply("**Hello**"), "Hello");
+        assert_eq!(preprocessing.apply("[text](https://example.com)"), "text");
+        assert_eq!(
+            preprocessing.apply("# Title\n> - *an* `inline` ![logo](a.png) item\n1. snake_case 2 * 3"),
+            "Title\nan inline logo item\nsnake_case 2 * 3"
+        );
+    }
+
+    #[test]
+    fn test_strip_aggressive_markdown() {
+        let preprocessing = TextPreprocessing {
+            strip_markdown: MarkdownStripping::Aggressive,
+            collapse_whitespace: true,
+            ..Default::default()
+        };
+        let text = "Intro<br> see https://example.com[^1]\n```\nlet x = 1;\n```\n---\n| a | b |\n|---|---|\nDone";
+        assert_eq!(preprocessing.apply(text), "Intro see\na , b\nDone");
+    }
+
+    #[test]
+    fn test_collapse_whitespace() {
+        let preprocessing = TextPreprocessing {
+            collapse_whitespace: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            preprocessing.apply("Hello \t  world\n\n\n  again  "),
+            "Hello world\nagain"
+        );
+    }
+
+    #[test]
+    fn test_trailing_punctuation() {
+        let ensure = TextPreprocessing {
+            trailing_punctuation: TrailingPunctuation::Ensure,
+            ..Default::default()
+        };
+        assert!(ensure.is_enabled());
+        assert_eq!(ensure.apply("Hello 😀"), "Hello.");
+        assert_eq!(ensure.apply("Hello."), "Hello.");
+        assert_eq!(ensure.apply("Really?"), "Really?");
+        assert_eq!(ensure.apply("\"Stop!\" Then, 👋🏽\nIt costs 5%"), "\"Stop!\" Then.\nIt costs 5%.");
+        assert_eq!(ensure.apply("She said \"Go.\" (Quietly.) 👋"), "She said \"Go.\" (Quietly.)");
+        assert_eq!(ensure.apply("👍 Done. 🎉"), "👍 Done.");
+        let strip = TextPreprocessing {
+            trailing_punctuation: TrailingPunctuation::Strip,
+            ..Default::default()
+        };
+        assert_eq!(strip.apply("Hello 😀"), "Hello");
+        assert_eq!(strip.apply("(Really?) ❤️"), "(Really?)");
+    }
+
+    #[test]
+    fn test_unicode_normalization() {
+        let text = "Cafe\u{301} ﬁne ＡＢ";
+        let nfc = TextPreprocessing {
+            unicode_normalization: UnicodeNormalization::Nfc,
+            ..Default::default()
+        };
+        assert!(nfc.is_enabled());
+        assert_eq!(nfc.apply(text), "Café ﬁne ＡＢ");
+        let nfkc = TextPreprocessing {
+            unicode_normalization: UnicodeNormalization::Nfkc,
+            ..Default::default()
+        };
+        assert_eq!(nfkc.apply(text), "Café fine AB");
+    }
+}
@@ -0,0 +1,61 @@
+use flume::Sender;
+
+pub(crate) use sonata_core::{ends_with_abbreviation, CLOSING_PUNCTUATION, SENTENCE_TERMINATORS};
+
+/// [`sonata_core::split_sentences`], as owned strings
+pub(crate) fn split_sentences(text: &str) -> Vec<String> {
+    Vec::from_iter(sonata_core::split_sentences(text).into_iter().map(str::to_string))
+}
+
+/// A segment that is about to be synthesized, as passed to the sentence callback. See
+/// [`crate::SonataSpeechSynthesizer::set_sentence_callback`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentenceEvent {
+    /// Position of the segment in the input text, or of the sentence for clips of single
+    /// sentences
+    pub index: usize,
+    /// The text the phonemes were made from
+    pub text: String,
+    /// The phonemes of the segment, as given to the model
+    pub phonemes: String,
+}
+
+/// Delivers sentence events to a callback running on its own thread,
+/// so that a slow callback never holds up synthesis.
+#[derive(Clone)]
+pub(crate) struct SentenceNotifier(Sender<SentenceEvent>);
+
+impl SentenceNotifier {
+    /// The dispatch thread exits once every clone of the notifier is dropped
+    pub(crate) fn new(mut callback: impl FnMut(SentenceEvent) + Send + 'static) -> Self {
+        let (tx, rx) = flume::unbounded::<SentenceEvent>();
+        std::thread::Builder::new()
+            .name("sonata-sentence-callback".to_string())
+            .spawn(move || rx.iter().for_each(&mut callback))
+            .expect("Failed to spawn the sentence callback thread");
+        Self(tx)
+    }
+    pub(crate) fn notify(&self, event: SentenceEvent) {
+        self.0.send(event).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentence_notifier_delivers_events_in_order() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let notifier = SentenceNotifier::new(move |event| tx.send(event.index).unwrap());
+        for index in 0..3 {
+            notifier.notify(SentenceEvent {
+                index,
+                text: String::new(),
+                phonemes: String::new(),
+            });
+        }
+        drop(notifier);
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{AudioOutputConfig, RealtimeSpeechStream, SonataSpeechSynthesizer, StreamingConfig};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use sonata_core::{AudioSamples, SampleConverter, SonataError, SonataResult};
+use std::io::Cursor;
+
+/// How the audio of each `audio` event of a [`SseStream`] is encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SseAudioFormat {
+    /// Interleaved 16-bit little-endian PCM, without a header. The smallest frames, for
+    /// clients that feed the samples to the Web Audio API themselves.
+    #[default]
+    Pcm16,
+    /// A complete wave file per chunk, which `AudioContext.decodeAudioData` and `<audio>`
+    /// elements can decode on their own
+    Wav,
+}
+
+impl SseAudioFormat {
+    /// The name the `format` event gives the format
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pcm16 => "pcm_s16le",
+            Self::Wav => "wav",
+        }
+    }
+}
+
+/// A speech stream as server-sent events, each one a frame ready to be written to the
+/// response body. See [`SonataSpeechSynthesizer::synthesize_sse`].
+///
+/// The stream sends, in order:
+/// - one `format` event, whose data is a JSON object with the `sample_rate` and `channels`
+///   of the audio, and the `encoding` of the audio events (see [`SseAudioFormat::name`]),
+///   e.g. `{"sample_rate":22050,"channels":1,"encoding":"pcm_s16le"}`
+/// - an `audio` event per chunk, whose data is the base64 encoded (with padding) audio of
+///   the chunk. Each chunk holds whole frames, so it decodes without the ones before it.
+/// - an `error` event, whose data is the message, for every chunk that failed
+/// - one `end` event, with empty data, once all the speech was sent
+///
+/// For example:
+///
+/// ```text
+/// event: format
+/// data: {"sample_rate":22050,"channels":1,"encoding":"pcm_s16le"}
+///
+/// event: audio
+/// data: AAABAAIA...
+///
+/// event: end
+/// data:
+///
+/// ```
+pub struct SseStream {
+    stream: RealtimeSpeechStream,
+    format: SseAudioFormat,
+    sample_converter: SampleConverter,
+    sample_rate: usize,
+    num_channels: usize,
+    state: SseState,
+}
+
+enum SseState {
+    Start,
+    Audio,
+    Finished,
+}
+
+impl SseStream {
+    pub(crate) fn new(
+        stream: RealtimeSpeechStream,
+        format: SseAudioFormat,
+        sample_converter: Option<SampleConverter>,
+    ) -> Self {
+        let config_handle = stream.config_handle();
+        Self {
+            sample_rate: config_handle.output_sample_rate(),
+            num_channels: config_handle.output_num_channels(),
+            stream,
+            format,
+            // Normalizing the peak of every chunk would change the level from chunk to chunk
+            sample_converter: sample_converter.unwrap_or_else(|| SampleConverter::rounding(false)),
+            state: SseState::Start,
+        }
+    }
+    fn format_frame(&self) -> String {
+        let format = serde_json::json!({
+            "sample_rate": self.sample_rate,
+            "channels": self.num_channels,
+            "encoding": self.format.name(),
+        });
+        frame("format", &format.to_string())
+    }
+    fn audio_frame(&self, samples: &AudioSamples) -> SonataResult<String> {
+        let samples = self.sample_converter.convert(samples.as_slice())?;
+        let bytes = match self.format {
+            SseAudioFormat::Pcm16 => Vec::from_iter(samples.iter().flat_map(|i| i.to_le_bytes())),
+            SseAudioFormat::Wav => {
+                let mut buf = Cursor::new(Vec::new());
+                audio_ops::write_wave_samples_to_buffer(
+                    &mut buf,
+                    samples.iter(),
+                    self.sample_rate as u32,
+                    self.num_channels as u32,
+                    2,
+                )?;
+                buf.into_inner()
+            }
+        };
+        Ok(frame("audio", &BASE64.encode(bytes)))
+    }
+}
+
+impl Iterator for SseStream {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.state {
+            SseState::Start => {
+                self.state = SseState::Audio;
+                Some(self.format_frame())
+            }
+            SseState::Audio => match self.stream.next() {
+                Some(result) => {
+                    let frame = result.and_then(|samples| {
+                        let frame = self.audio_frame(&samples);
+                        self.stream.recycle(samples);
+                        frame
+                    });
+                    Some(frame.unwrap_or_else(|e| error_frame(&e)))
+                }
+                None => {
+                    self.state = SseState::Finished;
+                    Some(frame("end", ""))
+                }
+            },
+            SseState::Finished => None,
+        }
+    }
+}
+
+/// An event with single-line `data`
+fn frame(event: &str, data: &str) -> String {
+    format!("event: {}\ndata: {}\n\n", event, data)
+}
+
+fn error_frame(error: &SonataError) -> String {
+    // A line break would end the data field
+    let message = error.to_string().replace(['\r', '\n'], " ");
+    frame("error", &message)
+}
+
+impl SonataSpeechSynthesizer {
+    /// Stream the speech for `text` as server-sent events, for browsers to play with an
+    /// `EventSource` instead of a WebSocket. See [`SseStream`] for the events.
+    ///
+    /// The samples are converted with the synthesizer's [`Self::sample_converter`], or at a
+    /// fixed scale (see [`SampleConverter::rounding`]) if it has none, so that the chunks of
+    /// a stream keep the same level.
+    pub fn synthesize_sse(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        streaming_config: StreamingConfig,
+        format: SseAudioFormat,
+    ) -> SonataResult<SseStream> {
+        let stream = self.synthesize_streamed_with_config(text, output_config, streaming_config)?;
+        Ok(SseStream::new(stream, format, self.sample_converter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StreamConfigHandle;
+
+    /// A 16 kHz mono stream of the given chunks
+    fn stream_of(chunks: Vec<SonataResult<AudioSamples>>) -> RealtimeSpeechStream {
+        let (tx, rx) = flume::unbounded();
+        for chunk in chunks {
+            tx.send(chunk).unwrap();
+        }
+        RealtimeSpeechStream {
+            receiver: rx,
+            buffer_pool: None,
+            config_handle: StreamConfigHandle::new(None, None, 16000, 1),
+            counters: Default::default(),
+            chunk_padding: 0,
+            sample_converter: SampleConverter::rounding(false),
+        }
+    }
+
+    /// The data of each event of `frames`, as `(event, data)`
+    fn parse(frames: &str) -> Vec<(&str, &str)> {
+        frames
+            .split_terminator("\n\n")
+            .map(|event| {
+                let (name, data) = event.split_once('\n').unwrap();
+                (
+                    name.strip_prefix("event: ").unwrap(),
+                    data.strip_prefix("data: ").unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_audio_frames_round_trip() {
+        let samples: AudioSamples = vec![0.0, 0.5, -0.5, 0.999].into();
+        // Converted at a fixed scale, not normalized to the peak of the chunk
+        let expected = vec![0, 16384, -16384, 32734];
+
+        let mut sse = SseStream::new(stream_of(Vec::new()), SseAudioFormat::Pcm16, None);
+        let frames = sse.format_frame() + &sse.audio_frame(&samples).unwrap();
+        let events = parse(&frames);
+        assert_eq!(events[0].0, "format");
+        let format: serde_json::Value = serde_json::from_str(events[0].1).unwrap();
+        assert_eq!(format["encoding"], "pcm_s16le");
+        assert_eq!(format["sample_rate"], 16000);
+        let pcm = BASE64.decode(events[1].1).unwrap();
+        let decoded: Vec<i16> = pcm
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        assert_eq!(decoded, expected);
+
+        sse.format = SseAudioFormat::Wav;
+        let frame = sse.audio_frame(&samples).unwrap();
+        let wav = BASE64.decode(parse(&frame)[0].1).unwrap();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(wav[wav.len() - pcm.len()..], pcm[..]);
+    }
+
+    #[test]
+    fn test_events_are_sent_in_order() {
+        let stream = stream_of(vec![
+            Ok(vec![0.1; 160].into()),
+            Err(SonataError::OperationError("Failed\nchunk".to_string())),
+            Ok(vec![0.2; 160].into()),
+        ]);
+        let frames: String = SseStream::new(stream, SseAudioFormat::Pcm16, None).collect();
+        let events = parse(&frames);
+        let names: Vec<_> = events.iter().map(|(event, _)| *event).collect();
+        assert_eq!(names, ["format", "audio", "error", "audio", "end"]);
+        assert!(!events[2].1.contains('\n'));
+        assert_eq!(events[4].1, "");
+    }
+}
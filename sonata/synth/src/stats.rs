@@ -0,0 +1,187 @@
+use sonata_core::Audio;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Counters of all the synthesis in the process, e.g. to export as metrics of a service.
+/// Taken with [`stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SynthesisStats {
+    /// Number of sentences synthesized by a model, including the sentences of streams
+    pub num_syntheses: u64,
+    /// Seconds of audio produced, after the output config is applied
+    pub audio_seconds: f64,
+    /// Milliseconds spent running models, for the paths that measure it
+    /// (streams don't, since their inference is spread over their chunks)
+    pub inference_ms: f64,
+    /// Lookups reported with [`record_cache_lookup`] that found the audio in a cache
+    pub cache_hits: u64,
+    /// Lookups reported with [`record_cache_lookup`] that didn't
+    pub cache_misses: u64,
+}
+
+impl SynthesisStats {
+    /// The share of cache lookups that were hits, or `None` if there were none
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+}
+
+struct Counters {
+    num_syntheses: AtomicU64,
+    audio_us: AtomicU64,
+    inference_us: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+static COUNTERS: Counters = Counters {
+    num_syntheses: AtomicU64::new(0),
+    audio_us: AtomicU64::new(0),
+    inference_us: AtomicU64::new(0),
+    cache_hits: AtomicU64::new(0),
+    cache_misses: AtomicU64::new(0),
+};
+
+/// A snapshot of the counters. Each counter is read on its own, so a snapshot taken during
+/// synthesis may count a sentence in one counter but not yet in another.
+pub fn stats() -> SynthesisStats {
+    SynthesisStats {
+        num_syntheses: COUNTERS.num_syntheses.load(Ordering::Relaxed),
+        audio_seconds: COUNTERS.audio_us.load(Ordering::Relaxed) as f64 / 1e6,
+        inference_ms: COUNTERS.inference_us.load(Ordering::Relaxed) as f64 / 1e3,
+        cache_hits: COUNTERS.cache_hits.load(Ordering::Relaxed),
+        cache_misses: COUNTERS.cache_misses.load(Ordering::Relaxed),
+    }
+}
+
+/// Count a lookup of a cache of synthesized audio, e.g. one keyed by
+/// [`SonataSpeechSynthesizer::input_hash`](crate::SonataSpeechSynthesizer::input_hash).
+/// Sonata doesn't cache audio itself, so the hit rate only covers caches that report here.
+pub fn record_cache_lookup(hit: bool) {
+    let counter = match hit {
+        true => &COUNTERS.cache_hits,
+        false => &COUNTERS.cache_misses,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count a sentence synthesized in one go
+pub(crate) fn record_synthesis(audio: &Audio) {
+    COUNTERS.num_syntheses.fetch_add(1, Ordering::Relaxed);
+    record_audio(audio.duration_ms());
+    let inference_ms = audio.inference_ms().unwrap_or_default();
+    COUNTERS
+        .inference_us
+        .fetch_add((inference_ms as f64 * 1e3) as u64, Ordering::Relaxed);
+}
+
+/// Count a sentence of a stream, whose audio is counted chunk by chunk
+pub(crate) fn record_streamed_synthesis() {
+    COUNTERS.num_syntheses.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_audio(duration_ms: f32) {
+    COUNTERS
+        .audio_us
+        .fetch_add((duration_ms as f64 * 1e3) as u64, Ordering::Relaxed);
+}
+
+/// Diagnostics of the chunk scheduling of a [`RealtimeSpeechStream`](crate::RealtimeSpeechStream),
+/// e.g. to tune its [`StreamingConfig`](crate::StreamingConfig) for latency.
+/// Taken with [`RealtimeSpeechStream::stats`](crate::RealtimeSpeechStream::stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// Number of mel frames the model is asked for per chunk of the current sentence.
+    /// It starts at [`StreamingConfig::chunk_size`](crate::StreamingConfig::chunk_size)
+    /// and grows with the number of chunks synthesized. Incremental streams don't use it.
+    pub chunk_size: usize,
+    /// Number of mel frames each chunk is padded with
+    pub chunk_padding: usize,
+    /// Chunks synthesized so far, including the silence of pauses
+    pub produced_chunks: u64,
+    /// Chunks taken from the stream so far
+    pub consumed_chunks: u64,
+    /// Frames (at the output sample rate) synthesized but not taken from the stream yet,
+    /// i.e. how far synthesis is ahead of playback
+    pub buffered_frames: u64,
+}
+
+/// Shared by a stream and its worker
+#[derive(Default)]
+pub(crate) struct StreamCounters {
+    chunk_size: AtomicUsize,
+    produced_chunks: AtomicU64,
+    produced_samples: AtomicU64,
+    consumed_chunks: AtomicU64,
+    consumed_samples: AtomicU64,
+}
+
+impl StreamCounters {
+    pub(crate) fn set_chunk_size(&self, chunk_size: usize) {
+        self.chunk_size.store(chunk_size, Ordering::Relaxed);
+    }
+    pub(crate) fn record_produced(&self, num_samples: usize) {
+        self.produced_chunks.fetch_add(1, Ordering::Relaxed);
+        self.produced_samples.fetch_add(num_samples as u64, Ordering::Relaxed);
+    }
+    pub(crate) fn record_consumed(&self, num_samples: usize) {
+        self.consumed_chunks.fetch_add(1, Ordering::Relaxed);
+        self.consumed_samples.fetch_add(num_samples as u64, Ordering::Relaxed);
+    }
+    pub(crate) fn snapshot(&self, chunk_padding: usize, num_channels: usize) -> StreamStats {
+        // Consumed first, so that a chunk produced in between isn't consumed before it's produced
+        let consumed_chunks = self.consumed_chunks.load(Ordering::Relaxed);
+        let consumed_samples = self.consumed_samples.load(Ordering::Relaxed);
+        let buffered_samples = self
+            .produced_samples
+            .load(Ordering::Relaxed)
+            .saturating_sub(consumed_samples);
+        StreamStats {
+            chunk_size: self.chunk_size.load(Ordering::Relaxed),
+            chunk_padding,
+            produced_chunks: self.produced_chunks.load(Ordering::Relaxed),
+            consumed_chunks,
+            buffered_frames: buffered_samples / num_channels.max(1) as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_count_syntheses_and_audio() {
+        let before = stats();
+        record_synthesis(&Audio::new(vec![0.0; 8000].into(), 16000, Some(40.0)));
+        record_cache_lookup(true);
+        record_cache_lookup(false);
+        let after = stats();
+        // Other tests synthesize concurrently, so the counters only give lower bounds
+        assert!(after.num_syntheses > before.num_syntheses);
+        assert!(after.audio_seconds - before.audio_seconds >= 0.5 - 1e-6);
+        assert!(after.inference_ms - before.inference_ms >= 40.0 - 1e-3);
+        assert!(after.cache_hits > before.cache_hits && after.cache_misses > before.cache_misses);
+        assert!(after.cache_hit_rate().is_some());
+        assert_eq!(SynthesisStats::default().cache_hit_rate(), None);
+    }
+
+    #[test]
+    fn test_stream_counters_track_the_buffered_frames() {
+        let counters = StreamCounters::default();
+        counters.set_chunk_size(72);
+        counters.record_produced(400);
+        counters.record_produced(600);
+        counters.record_consumed(400);
+        assert_eq!(
+            counters.snapshot(3, 2),
+            StreamStats {
+                chunk_size: 72,
+                chunk_padding: 3,
+                produced_chunks: 2,
+                consumed_chunks: 1,
+                buffered_frames: 300,
+            }
+        );
+    }
+}
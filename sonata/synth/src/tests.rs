@@ -1,6 +1,11 @@
 mod dev_utils;
 
-use sonata_synth::SonataResult;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use sonata_synth::{
+    Audio, AudioOutputConfig, CALIBRATION_PHRASE, MarkdownStripping, NoiseTensor, NumberReading, ParallelSynthesisOptions, PunctuationPauses, SonataModel, SonataResult, SonataSpeechSynthesizer,
+    SseAudioFormat, StreamingConfig, SynthesisOverrides, TextPreprocessing, TrailingPunctuation, UnicodeNormalization,
+};
 
 #[test]
 fn test_lazy_stream() -> SonataResult<()> {
@@ -20,9 +25,632 @@ fn test_parallel_stream() -> SonataResult<()> {
     dev_utils::iterate_stream(stream)
 }
 
+#[test]
+fn test_parallel_stream_with_sentence_info() -> SonataResult<()> {
+    let (synth, _, output_config) = dev_utils::gen_params("std");
+    let options = ParallelSynthesisOptions {
+        include_sentence_info: true,
+        ..Default::default()
+    };
+    let stream = synth.synthesize_parallel_with_options(
+        "One sentence. And another one!".to_string(),
+        output_config,
+        options,
+    )?;
+    let sentences: Vec<_> = stream
+        .map(|result| result.map(|audio| audio.sentence.unwrap()))
+        .collect::<SonataResult<_>>()?;
+    assert_eq!(sentences.len(), 2);
+    assert_eq!(sentences[1].index, 1);
+    assert_eq!(sentences[1].text, "And another one!");
+    Ok(())
+}
+
+#[test]
+fn test_synthesize_from_sentences_keeps_the_given_units() -> SonataResult<()> {
+    let (synth, _, output_config) = dev_utils::gen_params("std");
+    let sentences = vec![
+        "Dr. Smith arrived. He sat down.".to_string(),
+        "Sect. 4".to_string(),
+    ];
+    let stream = synth.synthesize_from_sentences(sentences.clone(), output_config, Default::default())?;
+    let infos: Vec<_> = stream
+        .map(|result| result.map(|audio| audio.sentence.unwrap()))
+        .collect::<SonataResult<_>>()?;
+    assert_eq!(infos.len(), 2);
+    assert_eq!(infos[0].text, sentences[0]);
+    assert_eq!(infos[1].index, 1);
+    Ok(())
+}
+
+#[test]
+fn test_streams_know_the_number_of_clips_left() -> SonataResult<()> {
+    let (synth, _, output_config) = dev_utils::gen_params("std");
+    let text = "One sentence. And another one!".to_string();
+    let mut stream = synth.synthesize_lazy(text.clone(), output_config.clone())?;
+    assert_eq!(stream.len(), 2);
+    assert!(!stream.next().unwrap()?.is_empty());
+    assert_eq!(stream.len(), 1);
+    let options = ParallelSynthesisOptions {
+        include_sentence_info: true,
+        ..Default::default()
+    };
+    let stream = synth.synthesize_parallel_with_options(text, output_config, options)?;
+    assert_eq!(stream.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_synchronous_parallel_stream() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("std");
+    let options = ParallelSynthesisOptions {
+        synchronous: true,
+        ..Default::default()
+    };
+    let stream = synth
+        .synthesize_parallel_with_options(text, output_config, options)?
+        .map(|ar| ar.map(|a| a.samples));
+    dev_utils::iterate_stream(stream)
+}
+
+#[test]
+fn test_parallel_stream_with_max_concurrency() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("std");
+    let options = ParallelSynthesisOptions {
+        max_concurrency: Some(1),
+        ..Default::default()
+    };
+    let stream = synth
+        .synthesize_parallel_with_options(text, output_config, options)?
+        .map(|ar| ar.map(|a| a.samples));
+    dev_utils::iterate_stream(stream)
+}
+
+#[test]
+fn test_synthesis_on_caller_thread_pool() -> SonataResult<()> {
+    let thread_pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let (synth, text, output_config) = dev_utils::gen_params("std");
+    let options = ParallelSynthesisOptions {
+        thread_pool: Some(&thread_pool),
+        ..Default::default()
+    };
+    let stream = synth
+        .synthesize_parallel_with_options(text, output_config, options)?
+        .map(|ar| ar.map(|a| a.samples));
+    dev_utils::iterate_stream(stream)?;
+    let (synth, text, output_config) = dev_utils::gen_params("rt");
+    let streaming_config = StreamingConfig {
+        thread_pool: Some(&thread_pool),
+        ..Default::default()
+    };
+    let stream = synth.synthesize_streamed_with_config(text, output_config, streaming_config)?;
+    drop(thread_pool);
+    dev_utils::iterate_stream(stream)
+}
+
+#[test]
+fn test_sentence_callback() -> SonataResult<()> {
+    let (synth, _, output_config) = dev_utils::gen_params("std");
+    let (tx, rx) = std::sync::mpsc::channel();
+    let synth = SonataSpeechSynthesizer::builder(synth.clone_model())
+        .with_sentence_callback(move |event| tx.send(event).unwrap())
+        .build()?;
+    let text = "One sentence. And another one!".to_string();
+    let stream = synth.synthesize_lazy(text.clone(), output_config)?;
+    stream.collect::<SonataResult<Vec<_>>>()?;
+    // The events follow the segments of the phonemizer
+    let segments = synth.phonemize_text(&text)?.to_vec();
+    drop(synth);
+    let events: Vec<_> = rx.iter().collect();
+    assert_eq!(events.len(), segments.len());
+    assert_eq!(events[1].index, 1);
+    assert_eq!(events[1].text, text);
+    assert_eq!(events[1].phonemes, segments[1]);
+    Ok(())
+}
+
+#[test]
+fn test_conflicting_overrides_are_rejected() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let path = std::env::temp_dir().join("sonata-test-overrides.json");
+    std::fs::write(&path, r#"{"speaker": 5, "length_scale": 1.5}"#).unwrap();
+    let result = SonataSpeechSynthesizer::builder(synth.clone_model())
+        .with_overrides(&path)
+        .build();
+    std::fs::remove_file(&path).unwrap();
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_speaker_names_from_file() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let path = std::env::temp_dir().join("sonata-test-speaker-names.json");
+    std::fs::write(&path, r#"{"0": "narrator"}"#).unwrap();
+    let result = SonataSpeechSynthesizer::builder(synth.clone_model())
+        .with_speaker_names(&path)
+        .build();
+    std::fs::remove_file(&path).unwrap();
+    let synth = result?;
+    assert_eq!(synth.speaker_name_to_id("narrator")?, Some(0));
+    assert_eq!(synth.speaker_id_to_name(&0)?, Some("narrator".to_string()));
+    Ok(())
+}
+
+#[test]
+fn test_infer_raw_returns_the_decoder_output() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let phonemes = synth.phonemize_text("Hello")?.to_vec().remove(0);
+    let outputs = synth.infer_raw(phonemes)?;
+    assert!(!outputs.is_empty());
+    assert!(!outputs[0].data.is_empty());
+    assert_eq!(outputs[0].data.len(), outputs[0].shape.iter().product::<usize>());
+    Ok(())
+}
+
+#[test]
+fn test_input_hash_changes_with_every_input() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("std");
+    let hash = synth.input_hash(&text, output_config.as_ref())?;
+    assert_eq!(hash, synth.input_hash(&text, output_config.as_ref())?);
+    assert_ne!(hash, synth.input_hash("Another text", output_config.as_ref())?);
+    let louder = AudioOutputConfig {
+        volume: Some(80),
+        ..output_config.clone().unwrap()
+    };
+    assert_ne!(hash, synth.input_hash(&text, Some(&louder))?);
+    let de_essed = AudioOutputConfig {
+        de_esser_threshold_db: Some(-30.0),
+        ..output_config.clone().unwrap()
+    };
+    assert_ne!(hash, synth.input_hash(&text, Some(&de_essed))?);
+    synth.set_punctuation_pauses(Some(PunctuationPauses::default()));
+    assert_ne!(hash, synth.input_hash(&text, output_config.as_ref())?);
+    synth.set_punctuation_pauses(None);
+    synth.set_sentence_callback(Some(Box::new(|_| {})));
+    assert_eq!(hash, synth.input_hash(&text, output_config.as_ref())?);
+    synth.set_sentence_callback(None);
+    synth.set_sample_converter(Some(Box::new(|samples| vec![0; samples.len()])));
+    assert_ne!(hash, synth.input_hash(&text, output_config.as_ref())?);
+    synth.set_sample_converter(None);
+    let (rt_synth, _, _) = dev_utils::gen_params("rt");
+    assert_ne!(hash, rt_synth.input_hash(&text, output_config.as_ref())?);
+    let model = dev_utils::load_unshared_voice();
+    let unshared_synth = SonataSpeechSynthesizer::new(model.clone())?;
+    assert_eq!(hash, unshared_synth.input_hash(&text, output_config.as_ref())?);
+    let seeded_synth = |seed| SonataSpeechSynthesizer::builder(model.clone()).with_seed(seed).build();
+    let seeded_hash = seeded_synth(7)?.input_hash(&text, output_config.as_ref())?;
+    assert_ne!(hash, seeded_hash);
+    assert_eq!(seeded_hash, seeded_synth(7)?.input_hash(&text, output_config.as_ref())?);
+    assert_ne!(seeded_hash, seeded_synth(8)?.input_hash(&text, output_config.as_ref())?);
+    model.apply_synthesis_overrides(&SynthesisOverrides::stable())?;
+    assert_ne!(hash, unshared_synth.input_hash(&text, output_config.as_ref())?);
+    Ok(())
+}
+
+#[test]
+fn test_manifest_rows_fail_independently() -> SonataResult<()> {
+    let synth = SonataSpeechSynthesizer::new(dev_utils::load_unshared_voice())?;
+    let dir = std::env::temp_dir().join("sonata-test-manifest");
+    let manifest_path = std::env::temp_dir().join("sonata-test-manifest.csv");
+    std::fs::write(
+        &manifest_path,
+        "id,text,speaker,length_scale\na,Hello there.,,1.2\nb,Unknown speaker.,nobody,\nc,Goodbye.,,\n",
+    )
+    .unwrap();
+    let result = synth.synthesize_manifest(&manifest_path, &dir, None);
+    std::fs::remove_file(&manifest_path).unwrap();
+    let report = result?;
+    let written: Vec<&str> = report.written.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(written, ["a", "c"]);
+    assert!(report.written.iter().all(|(_, path)| path.exists()));
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, "b");
+    std::fs::remove_dir_all(&dir).ok();
+    Ok(())
+}
+
+#[test]
+fn test_manifest_within_memory_budget() -> SonataResult<()> {
+    let synth = SonataSpeechSynthesizer::new(dev_utils::load_unshared_voice())?;
+    let dir = std::env::temp_dir().join("sonata-test-manifest-budget");
+    let manifest_path = std::env::temp_dir().join("sonata-test-manifest-budget.jsonl");
+    std::fs::write(
+        &manifest_path,
+        "{\"id\": \"a\", \"text\": \"Hello there.\"}\n{\"id\": \"b\", \"text\": \"Goodbye.\"}\n",
+    )
+    .unwrap();
+    // Every row is larger than the budget, so they run one at a time
+    let result = synth.synthesize_manifest_with_memory_budget(&manifest_path, &dir, None, Some(1));
+    std::fs::remove_file(&manifest_path).unwrap();
+    let report = result?;
+    assert_eq!(report.written.len(), 2);
+    let largest = report
+        .written
+        .iter()
+        .map(|(_, path)| std::fs::metadata(path).unwrap().len() as usize)
+        .max()
+        .unwrap();
+    // 6 buffered bytes per sample of one row at a time, against 2 bytes per sample of the file
+    assert!(report.peak_buffered_bytes > largest * 2 && report.peak_buffered_bytes < largest * 3);
+    std::fs::remove_dir_all(&dir).ok();
+    Ok(())
+}
+
+#[test]
+fn test_period_pause_is_longer_than_comma_pause() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    synth.set_punctuation_pauses(Some(PunctuationPauses::default()));
+    let trailing_silence = |text: &str| -> SonataResult<usize> {
+        let first = synth.synthesize_lazy(text.to_string(), None)?.next().unwrap()?;
+        Ok(first.samples.as_slice().iter().rev().take_while(|s| **s == 0.0).count())
+    };
+    assert!(trailing_silence("One. Two")? > trailing_silence("One, two")?);
+    Ok(())
+}
+
 #[test]
 fn test_realtime_stream() -> SonataResult<()> {
     let (synth, text, output_config) = dev_utils::gen_params("rt");
     let stream = synth.synthesize_streamed(text, output_config, 72, 3)?;
     dev_utils::iterate_stream(stream)
 }
+
+#[test]
+fn test_realtime_stream_low_memory() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("rt");
+    let streaming_config = StreamingConfig {
+        chunk_size: 72,
+        chunk_padding: 3,
+        low_memory: true,
+        ..Default::default()
+    };
+    let stream = synth.synthesize_streamed_with_config(text, output_config, streaming_config)?;
+    dev_utils::iterate_recycling_stream(stream)
+}
+
+#[test]
+fn test_realtime_stream_fade_in() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("rt");
+    let streaming_config = StreamingConfig {
+        fade_in_ms: Some(200),
+        ..Default::default()
+    };
+    let mut stream = synth.synthesize_streamed_with_config(text, output_config, streaming_config)?;
+    let first_chunk = stream.next().unwrap()?;
+    assert_eq!(first_chunk.as_slice()[0], 0.0);
+    Ok(())
+}
+
+#[test]
+fn test_realtime_stream_stats() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("rt");
+    let streaming_config = StreamingConfig {
+        chunk_size: 40,
+        chunk_padding: 2,
+        ..Default::default()
+    };
+    let mut stream = synth.synthesize_streamed_with_config(text, output_config, streaming_config)?;
+    let first_chunk = stream.next().unwrap()?;
+    stream.recycle(first_chunk);
+    let stats = stream.stats();
+    assert_eq!(stats.chunk_padding, 2);
+    assert!(stats.chunk_size >= 40);
+    assert_eq!(stats.consumed_chunks, 1);
+    assert!(stats.produced_chunks >= stats.consumed_chunks);
+    while let Some(chunk) = stream.next() {
+        stream.recycle(chunk?);
+    }
+    let stats = stream.stats();
+    assert_eq!(stats.produced_chunks, stats.consumed_chunks);
+    assert_eq!(stats.buffered_frames, 0);
+    Ok(())
+}
+
+#[test]
+fn test_global_stats_count_the_cli_and_grpc_paths() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("std");
+    // Other tests synthesize concurrently, so the counters only give lower bounds
+    let before = sonata_synth::stats();
+    let clips = synth
+        .synthesize_lazy(text.clone(), output_config.clone())?
+        .collect::<SonataResult<Vec<_>>>()?;
+    let lazy_seconds: f32 = clips.iter().map(|clip| clip.duration_ms() / 1000.0).sum();
+    let after_lazy = sonata_synth::stats();
+    assert!(after_lazy.num_syntheses - before.num_syntheses >= clips.len() as u64);
+    assert!(after_lazy.audio_seconds - before.audio_seconds >= lazy_seconds as f64 - 1e-3);
+    assert!(after_lazy.inference_ms > before.inference_ms);
+
+    let filename = std::env::temp_dir().join("sonata_test_global_stats.wav");
+    synth.synthesize_to_file(&filename, text.clone(), output_config.clone())?;
+    std::fs::remove_file(filename).ok();
+    let after_file = sonata_synth::stats();
+    assert!(after_file.num_syntheses - after_lazy.num_syntheses >= clips.len() as u64);
+    assert!(after_file.audio_seconds - after_lazy.audio_seconds >= lazy_seconds as f64 - 1e-3);
+
+    let (synth, text, output_config) = dev_utils::gen_params("rt");
+    let mut streamed_samples = 0;
+    for chunk in synth.synthesize_streamed(text, output_config.clone(), 55, 3)? {
+        streamed_samples += chunk?.len();
+    }
+    let samples_per_second = synth.output_sample_rate(output_config.as_ref())? as f64;
+    let after_stream = sonata_synth::stats();
+    assert!(after_stream.num_syntheses > after_file.num_syntheses);
+    assert!(after_stream.audio_seconds - after_file.audio_seconds >= streamed_samples as f64 / samples_per_second - 1e-3);
+    Ok(())
+}
+
+#[test]
+fn test_incremental_stream() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("rt");
+    let streaming_config = StreamingConfig {
+        incremental: true,
+        ..Default::default()
+    };
+    let stream = synth.synthesize_streamed_with_config(text, output_config, streaming_config)?;
+    let mut num_samples = 0;
+    for chunk in stream {
+        num_samples += chunk?.len();
+    }
+    assert!(num_samples > 0);
+    // The streaming voice outputs its phoneme durations
+    assert!(synth.supports_incremental_synthesis());
+    Ok(())
+}
+
+#[test]
+fn test_sse_stream_starts_with_the_format() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("std");
+    let sample_rate = synth.output_sample_rate(output_config.as_ref())?;
+    let frames: Vec<String> = synth
+        .synthesize_sse(text, output_config, StreamingConfig::default(), SseAudioFormat::Wav)?
+        .collect();
+    assert!(frames[0].starts_with("event: format\n"));
+    assert!(frames[0].contains(&format!("\"sample_rate\":{}", sample_rate)));
+    assert!(frames[1..frames.len() - 1].iter().all(|frame| frame.starts_with("event: audio\ndata: ")));
+    assert_eq!(frames.last().unwrap(), "event: end\ndata: \n\n");
+    Ok(())
+}
+
+#[test]
+fn test_sentences_to_files() -> SonataResult<()> {
+    let (synth, _, output_config) = dev_utils::gen_params("std");
+    let output_dir = std::env::temp_dir().join("sonata_test_sentences_to_files");
+    let files = synth.synthesize_sentences_to_files(
+        &output_dir,
+        "First sentence. Second sentence! Third one?".to_string(),
+        output_config,
+    )?;
+    let names: Vec<_> = files
+        .iter()
+        .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, vec!["001.wav", "002.wav", "003.wav"]);
+    assert_eq!(files[1].1, "Second sentence!");
+    assert!(files.iter().all(|(path, _)| path.exists()));
+    std::fs::remove_dir_all(output_dir).ok();
+    Ok(())
+}
+
+#[test]
+fn test_streamed_to_file() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("rt");
+    let filename = std::env::temp_dir().join("sonata_test_streamed_to_file.wav");
+    synth.synthesize_streamed_to_file(&filename, text, output_config, StreamingConfig::default())?;
+    let bytes = std::fs::read(&filename).unwrap();
+    let data_size = u32::from_le_bytes(bytes[40..44].try_into().unwrap());
+    assert_eq!(data_size as usize, bytes.len() - 44);
+    std::fs::remove_file(filename).ok();
+    Ok(())
+}
+
+#[test]
+fn test_custom_sample_converter_writes_files() -> SonataResult<()> {
+    let (synth, text, output_config) = dev_utils::gen_params("std");
+    let synth = SonataSpeechSynthesizer::builder(synth.clone_model())
+        .with_sample_converter(|samples| vec![0; samples.len()])
+        .build()?;
+    let filename = std::env::temp_dir().join("sonata_test_custom_sample_converter.wav");
+    synth.synthesize_to_file(&filename, text, output_config)?;
+    let bytes = std::fs::read(&filename).unwrap();
+    assert!(bytes.len() > 44);
+    assert!(bytes[44..].iter().all(|byte| *byte == 0));
+    std::fs::remove_file(filename).ok();
+    Ok(())
+}
+
+#[test]
+fn test_injected_rng_is_deterministic() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let build = || {
+        SonataSpeechSynthesizer::builder(synth.clone_model())
+            .with_rng(Box::new(StdRng::seed_from_u64(42)))
+            .build()
+    };
+    assert_eq!(build()?.generate_noise(256), build()?.generate_noise(256));
+    Ok(())
+}
+
+#[test]
+fn test_markdown_is_stripped_before_phonemization() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    synth.set_text_preprocessing(TextPreprocessing {
+        strip_markdown: MarkdownStripping::Basic,
+        collapse_whitespace: true,
+        ..Default::default()
+    });
+    assert_eq!(
+        synth.phonemize_text("**Hello**   [world](https://example.com)")?.to_string(),
+        synth.phonemize_text("Hello world")?.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_trailing_punctuation_sets_the_sentence_ending() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    synth.set_text_preprocessing(TextPreprocessing {
+        trailing_punctuation: TrailingPunctuation::Ensure,
+        ..Default::default()
+    });
+    // The phonemes carry the final punctuation that the model's intonation follows
+    assert_eq!(
+        synth.phonemize_text("Hello 😀")?.to_string(),
+        synth.phonemize_text("Hello.")?.to_string()
+    );
+    let question = synth.phonemize_text("Really?")?.to_string();
+    assert!(question.trim_end().ends_with('?'));
+    assert_ne!(question, synth.phonemize_text("Really.")?.to_string());
+    Ok(())
+}
+
+#[test]
+fn test_unicode_normalization_and_unsupported_scripts() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let synth = SonataSpeechSynthesizer::builder(synth.clone_model())
+        .with_text_preprocessing(TextPreprocessing {
+            unicode_normalization: UnicodeNormalization::Nfc,
+            ..Default::default()
+        })
+        .build()?;
+    assert_eq!(
+        synth.phonemize_text("Cafe\u{301} 你好世界 menu")?.to_string(),
+        synth.phonemize_text("Café menu")?.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_digits_and_cardinal_readings_differ() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let cardinal = synth.phonemize_text("Room 101")?.to_string();
+    let digits = synth.phonemize_text("Room digits{101}")?.to_string();
+    assert_ne!(cardinal, digits);
+    assert_eq!(digits, synth.phonemize_text("Room one oh one")?.to_string());
+    let digits_synth = SonataSpeechSynthesizer::builder(synth.clone_model())
+        .with_number_reading(NumberReading::Digits)
+        .build()?;
+    assert_eq!(digits_synth.phonemize_text("Room 101")?.to_string(), digits);
+    assert_eq!(digits_synth.phonemize_text("Room cardinal{101}")?.to_string(), cardinal);
+    Ok(())
+}
+
+#[test]
+fn test_calibrated_gain_reaches_the_loudness_target() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let target_db = -26.0;
+    synth.calibrate_gain(target_db)?;
+    assert!(synth.calibrated_gain_db().is_some());
+    let audio = synth.synthesize_parallel(CALIBRATION_PHRASE.to_string(), None)?;
+    let samples: Vec<f32> = audio
+        .collect::<SonataResult<Vec<_>>>()?
+        .into_iter()
+        .flat_map(|audio| audio.into_vec())
+        .collect();
+    let loudness_db = Audio::new(samples.into(), synth.native_sample_rate()?, None)
+        .loudness_db()
+        .unwrap();
+    assert!((loudness_db - target_db).abs() < 1.0, "{} dBFS", loudness_db);
+    synth.set_calibrated_gain_db(None);
+    assert_eq!(synth.calibrated_gain_db(), None);
+    Ok(())
+}
+
+#[test]
+fn test_default_output_config() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let text = "Hello world";
+    let total_len = |config| -> SonataResult<usize> {
+        synth
+            .synthesize_lazy(text.to_string(), config)?
+            .map(|result| result.map(|audio| audio.len()))
+            .sum()
+    };
+    let with_silence = AudioOutputConfig {
+        appended_silence_ms: Some(500),
+        ..Default::default()
+    };
+    let explicit = total_len(Some(with_silence.clone()))?;
+    synth.set_default_output_config(Some(with_silence));
+    assert_eq!(total_len(None)?, explicit);
+    assert!(total_len(Some(AudioOutputConfig::default()))? < explicit);
+    Ok(())
+}
+
+#[test]
+fn test_output_sample_rate() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let native_sample_rate = synth.native_sample_rate()?;
+    assert_eq!(synth.output_sample_rate(None)?, native_sample_rate);
+    let output_config = AudioOutputConfig {
+        sample_rate: Some(48000),
+        ..Default::default()
+    };
+    assert_eq!(synth.output_sample_rate(Some(&output_config))?, 48000);
+    let native = synth
+        .synthesize_lazy("Hello".to_string(), None)?
+        .next()
+        .unwrap()?;
+    let resampled = synth
+        .synthesize_lazy("Hello".to_string(), Some(output_config))?
+        .next()
+        .unwrap()?;
+    assert_eq!(resampled.info.sample_rate, 48000);
+    assert!((resampled.duration_ms() - native.duration_ms()).abs() < 1.0);
+    Ok(())
+}
+
+#[test]
+fn test_synthesize_with_overridden_durations() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("rt");
+    let mut sentences = synth.predict_durations("Hello there.")?;
+    assert_eq!(sentences.len(), 1);
+    assert_eq!(sentences[0].symbols.len(), sentences[0].durations_ms.len());
+    assert_eq!(sentences[0].symbols.first().map(String::as_str), Some("^"));
+    let predicted = synth.synthesize_with_durations(&sentences, None)?;
+    for duration_ms in sentences[0].durations_ms.iter_mut() {
+        *duration_ms *= 2.0;
+    }
+    let slowed = synth.synthesize_with_durations(&sentences, None)?;
+    assert!(slowed.len() > predicted.len() * 3 / 2);
+    sentences[0].durations_ms.pop();
+    assert!(synth.synthesize_with_durations(&sentences, None).is_err());
+    let (std_synth, _, _) = dev_utils::gen_params("std");
+    assert!(std_synth.predict_durations("Hello there.").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_cost_estimate_grows_with_text() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let short = synth.cost_estimate("Hello there.")?;
+    let long = synth.cost_estimate("Hello there. How are you doing today?")?;
+    assert!(short.num_phonemes > 0 && short.num_frames > 0);
+    assert!(long.num_phonemes > short.num_phonemes);
+    assert!(long.num_frames > short.num_frames);
+    Ok(())
+}
+
+#[test]
+fn test_noise_injection_requires_noise_inputs() -> SonataResult<()> {
+    let (synth, _, _) = dev_utils::gen_params("std");
+    let phonemes = synth.phonemize_text("Hello there.")?.to_vec().remove(0);
+    assert!(synth.noise_shapes(&phonemes)?.is_empty());
+    let noise = [NoiseTensor {
+        name: "noise".to_string(),
+        shape: vec![1, 2, 4],
+        data: synth.generate_noise(8),
+    }];
+    assert!(synth.synthesize_with_noise(&phonemes, &noise, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_style_embedding_requires_a_style_input() -> SonataResult<()> {
+    for model in ["std", "rt"] {
+        let (synth, _, _) = dev_utils::gen_params(model);
+        assert!(!synth.has_style_input());
+        assert!(synth.synthesize_with_style("Hello there.", &[0.0; 256], None).is_err());
+    }
+    Ok(())
+}
@@ -1,3 +1,8 @@
+use rand::{Rng, RngCore};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::sync::{Condvar, Mutex};
+
 #[allow(dead_code)]
 pub fn param_to_percent(value: f32, min: f32, max: f32) -> u8 {
     ((value - min) / (max - min) * 100.0f32).round() as u8
@@ -6,3 +11,210 @@ pub fn param_to_percent(value: f32, min: f32, max: f32) -> u8 {
 pub fn percent_to_param(value: u8, min: f32, max: f32) -> f32 {
     (value as f32 / 100.0f32) * (max - min) + min
 }
+
+/// Fill `out` with samples from the standard normal distribution (Box-Muller transform)
+pub fn fill_standard_normal(rng: &mut dyn RngCore, out: &mut [f32]) {
+    for pair in out.chunks_mut(2) {
+        // Map to (0, 1] so that the logarithm is finite
+        let u1 = 1.0 - rng.gen::<f32>();
+        let u2 = rng.gen::<f32>();
+        let radius = (-2.0 * u1.ln()).sqrt();
+        let (sin, cos) = (std::f32::consts::TAU * u2).sin_cos();
+        pair[0] = radius * cos;
+        if let Some(second) = pair.get_mut(1) {
+            *second = radius * sin;
+        }
+    }
+}
+
+/// Run `op` on the given pool, or on the current thread if there is none
+pub fn install_on<R: Send>(thread_pool: Option<&ThreadPool>, op: impl FnOnce() -> R + Send) -> R {
+    match thread_pool {
+        Some(thread_pool) => thread_pool.install(op),
+        None => op(),
+    }
+}
+
+/// Map `items` in parallel on the current pool, or in order on the calling thread if
+/// `synchronous`. With `max_workers`, at most that many items are mapped at once: that many
+/// jobs take the next item from a shared queue until it is empty, so no job of the pool is
+/// blocked waiting for its turn. The results are in the order of `items` either way.
+pub fn map_items<T: Send, R: Send>(
+    items: Vec<T>,
+    synchronous: bool,
+    max_workers: Option<usize>,
+    op: impl Fn(T) -> R + Send + Sync,
+) -> Vec<R> {
+    let max_workers = match (synchronous, max_workers) {
+        (true, _) => return items.into_iter().map(op).collect(),
+        (false, Some(max_workers)) => max_workers.clamp(1, items.len().max(1)),
+        (false, None) => return items.into_par_iter().map(op).collect(),
+    };
+    let len = items.len();
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new(Vec::with_capacity(len));
+    rayon::scope(|scope| {
+        for _ in 0..max_workers {
+            scope.spawn(|_| loop {
+                // Take the lock in its own statement, so it is released before mapping
+                let next = queue.lock().unwrap().next();
+                let Some((index, item)) = next else {
+                    break;
+                };
+                let result = op(item);
+                results.lock().unwrap().push((index, result));
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Limits the total size of the buffers held by concurrent tasks, and measures its peak
+pub struct MemoryBudget {
+    limit: Option<usize>,
+    /// The size reserved now and the most that was reserved at once
+    reserved: Mutex<(usize, usize)>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    /// A budget of `limit` bytes, or one that only measures without a limit
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            reserved: Mutex::new((0, 0)),
+            released: Condvar::new(),
+        }
+    }
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+    /// Block until `size` bytes fit in the rest of the budget. A reservation larger than the
+    /// whole budget waits until nothing else is reserved, and then runs alone.
+    /// It is released when the guard is dropped.
+    pub fn reserve(&self, size: usize) -> MemoryReservation<'_> {
+        let mut reserved = self
+            .released
+            .wait_while(self.reserved.lock().unwrap(), |(reserved, _)| {
+                *reserved > 0 && self.limit.is_some_and(|limit| *reserved + size > limit)
+            })
+            .unwrap();
+        reserved.0 += size;
+        reserved.1 = reserved.1.max(reserved.0);
+        MemoryReservation { budget: self, size }
+    }
+    /// The most that was reserved at once
+    pub fn peak(&self) -> usize {
+        self.reserved.lock().unwrap().1
+    }
+}
+
+pub struct MemoryReservation<'a> {
+    budget: &'a MemoryBudget,
+    size: usize,
+}
+
+impl MemoryReservation<'_> {
+    /// Replace the reserved size, e.g. with the actual size of a buffer once it's known.
+    /// This never blocks, so the total may go over the budget.
+    pub fn resize(&mut self, size: usize) {
+        let mut reserved = self.budget.reserved.lock().unwrap();
+        reserved.0 = reserved.0 - self.size + size;
+        reserved.1 = reserved.1.max(reserved.0);
+        self.size = size;
+        self.budget.released.notify_all();
+    }
+}
+
+impl Drop for MemoryReservation<'_> {
+    fn drop(&mut self) {
+        self.budget.reserved.lock().unwrap().0 -= self.size;
+        self.budget.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_standard_normal_is_deterministic_for_seeded_rng() {
+        let mut a = vec![0f32; 1001];
+        let mut b = vec![0f32; 1001];
+        fill_standard_normal(&mut StdRng::seed_from_u64(7), &mut a);
+        fill_standard_normal(&mut StdRng::seed_from_u64(7), &mut b);
+        assert_eq!(a, b);
+        let mean = a.iter().sum::<f32>() / a.len() as f32;
+        let variance = a.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / a.len() as f32;
+        assert!(mean.abs() < 0.1);
+        assert!((variance - 1.0).abs() < 0.15);
+    }
+
+    #[test]
+    fn test_map_items_limits_the_workers() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(8).build().unwrap();
+        let doubled = pool.install(|| {
+            map_items(Vec::from_iter(0..16), false, Some(2), |i| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                running.fetch_sub(1, Ordering::SeqCst);
+                i * 2
+            })
+        });
+        assert_eq!(doubled, Vec::from_iter((0..16).map(|i| i * 2)));
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_map_items_doesnt_block_a_single_thread_pool() {
+        // A worker that waited for a permit held by a job queued behind it would never finish
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let results = pool.install(|| map_items(Vec::from_iter(0..8), false, Some(3), |i| i + 1));
+        assert_eq!(results, Vec::from_iter(1..9));
+    }
+
+    #[test]
+    fn test_memory_budget_limits_reserved_size() {
+        let budget = MemoryBudget::new(Some(100));
+        let reserved = AtomicUsize::new(0);
+        let max_reserved = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for size in [40, 40, 40, 150, 40] {
+                let (budget, reserved, max_reserved) = (&budget, &reserved, &max_reserved);
+                scope.spawn(move || {
+                    let _reservation = budget.reserve(size);
+                    let now = reserved.fetch_add(size, Ordering::SeqCst) + size;
+                    max_reserved.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    reserved.fetch_sub(size, Ordering::SeqCst);
+                });
+            }
+        });
+        // The oversized reservation runs alone, and the others two at a time
+        assert_eq!(max_reserved.load(Ordering::SeqCst), 150);
+        assert_eq!(budget.peak(), 150);
+        let mut reservation = budget.reserve(10);
+        reservation.resize(300);
+        assert_eq!(budget.peak(), 300);
+        drop(reservation);
+        let unlimited = MemoryBudget::new(None);
+        let (_a, _b) = (unlimited.reserve(500), unlimited.reserve(500));
+        assert_eq!(unlimited.peak(), 1000);
+    }
+
+    #[test]
+    fn test_synchronous_map_runs_on_the_calling_thread() {
+        let caller = std::thread::current().id();
+        let threads = map_items(vec![1, 2, 3], true, None, |_| std::thread::current().id());
+        assert!(threads.iter().all(|thread| *thread == caller));
+        assert_eq!(map_items(vec![1, 2, 3], false, None, |i| i * 2), [2, 4, 6]);
+    }
+}